@@ -0,0 +1,155 @@
+//! Compares the accumulation of the renderer, where each thread fills
+//! tiles of its own, with the one used before, where every pixel was
+//! sent through an mpsc channel:
+//!
+//! ```sh
+//! cargo run --release --example accumulation_bench -- 3840x2160 8
+//! ```
+//!
+//! The arguments are the image size (1920x1080 by default) and the
+//! number of threads (all the cores by default). Renders are seeded so
+//! that both accumulations give the same raw image, which is checked.
+//! Deep zooms (rendered with perturbation) are not supported.
+
+use std::{
+    array, env,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use fractal_rndr::{
+    complexx::Complexx,
+    mat::Mat2D,
+    render_raw_image,
+    rendering::{mix_seed, thread_pool, View},
+    FrameParams, F, FX,
+};
+use rayon::prelude::*;
+
+const RUNS: usize = 3;
+
+/// The render loop before the tiles: pixels are sampled in no
+/// particular order by `par_bridge` and sent through a channel, and
+/// the image is assembled once they all are.
+fn render_through_channel(params: &FrameParams, sampling_points: &[(F, F)]) -> Mat2D<F> {
+    const LANES: usize = size_of::<FX>() / size_of::<F>();
+
+    let view = View::new(params);
+    let filter = params.sampling.filter();
+    let (img_width, img_height) = (params.img_width, params.img_height);
+
+    let (tx, rx) = mpsc::channel();
+    let sample_pixels = move || {
+        (0..img_height)
+            .flat_map(|j| (0..img_width).map(move |i| (i, j)))
+            .par_bridge()
+            .for_each_with((tx, fastrand::Rng::new()), |(s, rng), (i, j)| {
+                let (offset_x, offset_y) = if params.sampling.random_offsets {
+                    if let Some(seed) = params.seed {
+                        *rng =
+                            fastrand::Rng::with_seed(mix_seed(seed, ((j as u64) << 32) | i as u64));
+                    }
+                    #[cfg(feature = "force_f32")]
+                    let v = (rng.f32(), rng.f32());
+                    #[cfg(not(feature = "force_f32"))]
+                    let v = (rng.f64(), rng.f64());
+                    v
+                } else {
+                    (0., 0.)
+                };
+                let points = sampling_points
+                    .iter()
+                    .map(|&(dx, dy)| filter.map_point(dx, dy, offset_x, offset_y))
+                    .collect::<Vec<_>>();
+
+                let (sum, weights) = points
+                    .chunks(LANES)
+                    .flat_map(|d| {
+                        let l = d.len();
+                        let points: [(F, F); LANES] = array::from_fn(|k| {
+                            let (dx, dy, _) = d[k % l];
+                            view.pixel_to_complex(i as F + 0.5 + dx, j as F + 0.5 + dy)
+                        });
+                        let re = FX::from(points.map(|(re, _)| re));
+                        let im = FX::from(points.map(|(_, im)| im));
+                        let samples = params.fractal.sample(
+                            Complexx { re, im },
+                            params.max_iter,
+                            params.value,
+                            false,
+                        );
+                        d.iter()
+                            .enumerate()
+                            .map(move |(k, &(_, _, weight))| (weight * samples.value[k], weight))
+                    })
+                    .fold((0., 0.), |(sum, weights), (v, w)| (sum + v, weights + w));
+
+                s.send(((i, j), if weights != 0. { sum / weights } else { 0. }))
+                    .unwrap();
+            });
+    };
+    match thread_pool(params.threads) {
+        Some(pool) => pool.install(sample_pixels),
+        None => sample_pixels(),
+    }
+
+    let mut raw_image = Mat2D::filled_with(0., img_width as usize, img_height as usize);
+    for ((i, j), value) in rx {
+        raw_image[(i as usize, j as usize)] = value;
+    }
+    raw_image
+}
+
+fn time(mut render: impl FnMut() -> Mat2D<F>) -> (Duration, Mat2D<F>) {
+    let mut durations = Vec::new();
+    let mut raw_image = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        raw_image = Some(render());
+        durations.push(start.elapsed());
+    }
+    (
+        durations.iter().sum::<Duration>() / RUNS as u32,
+        raw_image.unwrap(),
+    )
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (img_width, img_height) = args
+        .next()
+        .map(|size| {
+            let (w, h) = size.split_once('x').expect("expected <width>x<height>");
+            (w.parse().unwrap(), h.parse().unwrap())
+        })
+        .unwrap_or((1920, 1080));
+    let threads = args.next().map(|threads| threads.parse().unwrap());
+
+    let params = FrameParams {
+        img_width,
+        img_height,
+        max_iter: 200,
+        seed: Some(0),
+        threads,
+        ..Default::default()
+    };
+    let sampling_points = params.sampling.generate_sampling_points();
+
+    let (tiles, tiled) = time(|| render_raw_image(&params, &sampling_points, None, None).0);
+    let (channel, through_channel) = time(|| render_through_channel(&params, &sampling_points));
+    assert_eq!(
+        tiled, through_channel,
+        "the accumulations give different images"
+    );
+
+    println!(
+        "{}x{}, {} threads, mean of {} runs",
+        img_width,
+        img_height,
+        thread_pool(threads).map_or_else(rayon::current_num_threads, |pool| pool
+            .current_num_threads()),
+        RUNS
+    );
+    println!("tiles:   {:.3} s", tiles.as_secs_f64());
+    println!("channel: {:.3} s", channel.as_secs_f64());
+}
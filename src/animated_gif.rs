@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use color_quant::NeuQuant;
+use gif::{Encoder, Frame, Repeat};
+use image::RgbImage;
+
+use crate::{
+    error::{ErrorKind, Result},
+    F,
+};
+
+/// Writes the frames of an animation to a single looping gif.
+///
+/// By default the palette is computed from the first frame and
+/// shared by all the frames, which keeps files small as the colors
+/// of an animation come from the same gradient. Frames can have
+/// their own palette instead, which is more accurate but larger.
+pub struct GifWriter<W: Write> {
+    encoder: Encoder<W>,
+    /// In hundredths of a second, the unit of gif delays.
+    delay: u16,
+    width: u16,
+    height: u16,
+    /// `None` when each frame gets its own palette.
+    palette: Option<NeuQuant>,
+}
+
+impl GifWriter<BufWriter<fs::File>> {
+    /// Creates the gif file, its size and palette come from the
+    /// first frame.
+    pub fn create(
+        path: &Path,
+        first_frame: &RgbImage,
+        fps: F,
+        local_palettes: bool,
+    ) -> Result<Self> {
+        let file = fs::File::create(path).map_err(|e| ErrorKind::EncodeGif(e.to_string()))?;
+        GifWriter::new(BufWriter::new(file), first_frame, fps, local_palettes)
+    }
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Quality of the palettes, from 1 (best and slowest) to 30.
+    const QUANTIZATION_SPEED: i32 = 10;
+
+    pub fn new(writer: W, first_frame: &RgbImage, fps: F, local_palettes: bool) -> Result<Self> {
+        let too_large = || ErrorKind::EncodeGif("frames are larger than 65535x65535".to_string());
+        let width = u16::try_from(first_frame.width()).map_err(|_| too_large())?;
+        let height = u16::try_from(first_frame.height()).map_err(|_| too_large())?;
+
+        let palette = (!local_palettes)
+            .then(|| NeuQuant::new(Self::QUANTIZATION_SPEED, 256, &rgba_pixels(first_frame)));
+        let global_palette = palette
+            .as_ref()
+            .map(|palette| palette.color_map_rgb())
+            .unwrap_or_default();
+
+        let mut encoder = Encoder::new(writer, width, height, &global_palette)
+            .map_err(|e| ErrorKind::EncodeGif(e.to_string()))?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| ErrorKind::EncodeGif(e.to_string()))?;
+
+        Ok(GifWriter {
+            encoder,
+            // Most viewers don't go below 2 hundredths.
+            delay: ((100. / fps).round() as u16).max(2),
+            width,
+            height,
+            palette,
+        })
+    }
+
+    /// Adds a frame, of the same size as the first one.
+    pub fn add_frame(&mut self, image: &RgbImage) -> Result<()> {
+        let mut frame = match &self.palette {
+            Some(palette) => {
+                let indices = image
+                    .pixels()
+                    .map(|p| palette.index_of(&[p[0], p[1], p[2], 255]) as u8)
+                    .collect::<Vec<_>>();
+                Frame::from_indexed_pixels(self.width, self.height, indices, None)
+            }
+            None => Frame::from_rgb_speed(
+                self.width,
+                self.height,
+                image.as_raw(),
+                Self::QUANTIZATION_SPEED,
+            ),
+        };
+        frame.delay = self.delay;
+
+        self.encoder
+            .write_frame(&frame)
+            .map_err(|e| ErrorKind::EncodeGif(e.to_string()))
+    }
+
+    /// Writes the end of the gif.
+    pub fn finish(self) -> Result<()> {
+        self.encoder
+            .into_inner()
+            .and_then(|mut writer| writer.flush())
+            .map_err(|e| ErrorKind::EncodeGif(e.to_string()))
+    }
+}
+
+fn rgba_pixels(image: &RgbImage) -> Vec<u8> {
+    image
+        .pixels()
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use gif::DecodeOptions;
+    use image::Rgb;
+
+    use super::*;
+
+    /// Frames of an 8x6 animation.
+    fn frames(count: u8) -> Vec<RgbImage> {
+        (0..count)
+            .map(|i| RgbImage::from_fn(8, 6, |x, y| Rgb([x as u8 * 30, y as u8 * 40, i * 60])))
+            .collect()
+    }
+
+    struct Decoded {
+        frame_count: usize,
+        delays: Vec<u16>,
+        repeat: Repeat,
+        /// Number of colors of the global palette.
+        global_palette_size: usize,
+        has_local_palettes: Vec<bool>,
+    }
+
+    fn encode_and_decode(frames: &[RgbImage], fps: F, local_palettes: bool) -> Decoded {
+        let mut bytes = Vec::new();
+        let mut writer = GifWriter::new(&mut bytes, &frames[0], fps, local_palettes).unwrap();
+        for frame in frames {
+            writer.add_frame(frame).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut decoder = DecodeOptions::new().read_info(bytes.as_slice()).unwrap();
+        let mut delays = Vec::new();
+        let mut has_local_palettes = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (8, 6));
+            delays.push(frame.delay);
+            has_local_palettes.push(frame.palette.is_some());
+        }
+
+        Decoded {
+            frame_count: delays.len(),
+            delays,
+            repeat: decoder.repeat(),
+            global_palette_size: decoder.global_palette().map_or(0, |p| p.len() / 3),
+            has_local_palettes,
+        }
+    }
+
+    #[test]
+    fn frames_share_the_global_palette() {
+        let decoded = encode_and_decode(&frames(3), 25., false);
+        assert_eq!(decoded.frame_count, 3);
+        // 4 hundredths at 25 fps.
+        assert_eq!(decoded.delays, vec![4; 3]);
+        assert_eq!(decoded.repeat, Repeat::Infinite);
+        assert_eq!(decoded.global_palette_size, 256);
+        assert_eq!(decoded.has_local_palettes, vec![false; 3]);
+    }
+
+    #[test]
+    fn frames_can_have_their_own_palette() {
+        let decoded = encode_and_decode(&frames(2), 10., true);
+        assert_eq!(decoded.frame_count, 2);
+        assert_eq!(decoded.delays, vec![10; 2]);
+        assert_eq!(decoded.repeat, Repeat::Infinite);
+        // The gif crate still writes the smallest global palette, of
+        // 2 colors.
+        assert!(decoded.global_palette_size <= 2);
+        assert_eq!(decoded.has_local_palettes, vec![true; 2]);
+    }
+
+    #[test]
+    fn delays_are_at_least_two_hundredths() {
+        // 100 fps would be 1 hundredth.
+        assert_eq!(
+            encode_and_decode(&frames(2), 100., false).delays,
+            vec![2; 2]
+        );
+        assert_eq!(encode_and_decode(&frames(1), 240., false).delays, vec![2]);
+        // 100 / 30 rounds to 3.
+        assert_eq!(encode_and_decode(&frames(1), 30., false).delays, vec![3]);
+    }
+}
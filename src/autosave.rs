@@ -0,0 +1,263 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ErrorKind, Result},
+    mat::Mat2D,
+    params::FrameParams,
+    values_file::{fnv1a, read_values_file, write_values_file},
+    F,
+};
+
+/// First line of autosave files.
+const MAGIC: &[u8] = b"fractal_rndr autosave\n";
+
+/// Samples accumulated in a gui session, written periodically so
+/// that they can be restored after a crash.
+#[derive(Debug, Clone)]
+pub struct Autosave {
+    pub params: FrameParams,
+    pub samples_per_pixel: F,
+    pub raw_image: Mat2D<F>,
+}
+
+/// Header of autosave files (see [`write_values_file`]).
+#[derive(Serialize, Deserialize)]
+struct Header {
+    raw_params_hash: u64,
+    samples_per_pixel: F,
+    width: usize,
+    height: usize,
+    params: FrameParams,
+}
+
+/// Autosave file of a session: next to the param file, or in the
+/// temporary directory when the params don't come from a file.
+pub fn autosave_path(param_file_path: Option<&Path>) -> PathBuf {
+    match param_file_path.and_then(|path| Some((path.parent()?, path.file_name()?))) {
+        Some((dir, file_name)) => {
+            let mut file_name = file_name.to_owned();
+            file_name.push(".autosave");
+            dir.join(file_name)
+        }
+        None => std::env::temp_dir().join("fractal_rndr.autosave"),
+    }
+}
+
+/// Hashes (FNV-1a of their RON representation) the parameters that
+/// change the raw image, an autosave can only be restored when they
+/// match the current ones.
+pub fn raw_params_hash(params: &FrameParams) -> Result<u64> {
+    let raw_params = (
+        params.img_width,
+        params.img_height,
+        params.zoom,
+        params.center_x,
+        params.center_y,
+        params.rotate,
+        params.aspect,
+        params.fractal.clone(),
+        params.max_iter,
+        params.value,
+        params.seed,
+        params.supersample,
+        params.sampling.filter(),
+        params.sampling.pattern,
+    );
+    let params_str = ron::to_string(&raw_params).map_err(ErrorKind::EncodeParameterFile)?;
+
+    Ok(fnv1a(params_str.bytes()))
+}
+
+/// Whether samples rendered with the given parameters can be
+/// accumulated together.
+pub fn raw_params_match(a: &FrameParams, b: &FrameParams) -> bool {
+    matches!((raw_params_hash(a), raw_params_hash(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Writes the autosave to a temporary file first and then renames
+/// it, so that a crash while writing doesn't leave a truncated
+/// autosave.
+pub fn write_autosave(autosave: &Autosave, path: &Path) -> Result<()> {
+    let header = Header {
+        raw_params_hash: raw_params_hash(&autosave.params)?,
+        samples_per_pixel: autosave.samples_per_pixel,
+        width: autosave.raw_image.width,
+        height: autosave.raw_image.height,
+        params: autosave.params.clone(),
+    };
+
+    let mut tmp_path = OsString::from(path);
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    write_values_file(
+        &tmp_path,
+        MAGIC,
+        &header,
+        &autosave.raw_image.vec,
+        ErrorKind::WriteAutosave,
+    )?;
+    fs::rename(&tmp_path, path).map_err(ErrorKind::WriteAutosave)
+}
+
+/// Removes the autosave file, if any.
+pub fn remove_autosave(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(ErrorKind::WriteAutosave(e)),
+        _ => Ok(()),
+    }
+}
+
+pub fn read_autosave(path: &Path) -> Result<Autosave> {
+    let invalid =
+        |reason: &str| ErrorKind::DecodeAutosave(path.display().to_string(), reason.into());
+
+    let (header, values) = read_values_file(
+        path,
+        MAGIC,
+        |header: &Header| header.width * header.height,
+        ErrorKind::ReadAutosave,
+        invalid,
+    )?;
+    if raw_params_hash(&header.params)? != header.raw_params_hash {
+        return Err(invalid("the parameters don't match their hash"));
+    }
+
+    Ok(Autosave {
+        params: header.params,
+        samples_per_pixel: header.samples_per_pixel,
+        raw_image: Mat2D {
+            width: header.width,
+            height: header.height,
+            vec: values,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    use crate::sampling::{ReconstructionFilter, Sampling, SamplingPattern};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("fractal_rndr_test_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn autosave_paths() {
+        assert_eq!(
+            autosave_path(Some(Path::new("dir/params.ron"))),
+            Path::new("dir/params.ron.autosave")
+        );
+        assert_eq!(
+            autosave_path(None),
+            env::temp_dir().join("fractal_rndr.autosave")
+        );
+    }
+
+    #[test]
+    fn autosave_round_trips() {
+        let autosave = Autosave {
+            params: FrameParams::default(),
+            samples_per_pixel: 96.,
+            raw_image: Mat2D {
+                vec: (0..24).map(|k| k as F * 1.25 - 3.).collect(),
+                width: 6,
+                height: 4,
+            },
+        };
+        let path = temp_path("round_trip.autosave");
+        write_autosave(&autosave, &path).unwrap();
+        let read = read_autosave(&path);
+        remove_autosave(&path).unwrap();
+        // Removing a missing autosave is not an error.
+        remove_autosave(&path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(read.samples_per_pixel, 96.);
+        assert_eq!(read.raw_image, autosave.raw_image);
+        assert!(raw_params_match(&read.params, &autosave.params));
+    }
+
+    #[test]
+    fn other_files_are_not_autosaves() {
+        let path = temp_path("not_an.autosave");
+        fs::write(&path, b"fractal_rndr band\n").unwrap();
+        let read = read_autosave(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(read, Err(ErrorKind::DecodeAutosave(_, _))));
+    }
+
+    #[test]
+    fn only_raw_params_must_match() {
+        let params = FrameParams::default();
+        let recolored = FrameParams {
+            gradient_offset: Some(0.3),
+            gradient_repeat: Some(2.),
+            threads: Some(1),
+            ..params.clone()
+        };
+        assert!(raw_params_match(&params, &recolored));
+
+        let moved = FrameParams {
+            center_x: params.center_x + 0.1,
+            ..params.clone()
+        };
+        let reseeded = FrameParams {
+            seed: Some(1),
+            ..params.clone()
+        };
+        let stretched = FrameParams {
+            aspect: Some(2.),
+            ..params.clone()
+        };
+        let supersampled = FrameParams {
+            supersample: Some(2),
+            ..params.clone()
+        };
+        let refiltered = FrameParams {
+            sampling: Sampling {
+                filter: Some(ReconstructionFilter::Gaussian { radius: 1. }),
+                ..params.sampling
+            },
+            ..params.clone()
+        };
+        let repatterned = FrameParams {
+            sampling: Sampling {
+                pattern: SamplingPattern::Halton,
+                ..params.sampling
+            },
+            ..params.clone()
+        };
+        for changed in [
+            moved,
+            reseeded,
+            stretched,
+            supersampled,
+            refiltered,
+            repatterned,
+        ] {
+            assert!(!raw_params_match(&params, &changed), "{changed:?}");
+        }
+
+        // The default filter is the same whether it is given or not.
+        let default_filter = FrameParams {
+            sampling: Sampling {
+                filter: Some(ReconstructionFilter::DEFAULT),
+                ..params.sampling
+            },
+            ..params.clone()
+        };
+        assert!(raw_params_match(&params, &default_filter));
+    }
+}
@@ -0,0 +1,288 @@
+use std::{ops::Range, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ErrorKind, Result},
+    mat::Mat2D,
+    params::FrameParams,
+    values_file::{fnv1a, read_values_file, write_values_file},
+    F,
+};
+
+/// First line of band files.
+const MAGIC: &[u8] = b"fractal_rndr band\n";
+
+/// Horizontal band of the raw image of a frame, rendered on its own
+/// so that a frame can be split across several machines.
+#[derive(Debug, Clone)]
+pub struct Band {
+    /// Index of the band, from 1 to `count`.
+    pub index: usize,
+    pub count: usize,
+    pub params: FrameParams,
+    pub samples_per_pixel: F,
+    /// Raw values of the rows of the band.
+    pub raw_image: Mat2D<F>,
+}
+
+/// Header of band files (see [`write_values_file`]).
+#[derive(Serialize, Deserialize)]
+struct Header {
+    index: usize,
+    count: usize,
+    params_hash: u64,
+    samples_per_pixel: F,
+    width: usize,
+    height: usize,
+    params: FrameParams,
+}
+
+/// Rows of the band of the given index (from 1 to `count`).
+pub fn band_rows(index: usize, count: usize, img_height: u32) -> Range<u32> {
+    let row = |i: usize| (i as u64 * img_height as u64 / count as u64) as u32;
+    row(index - 1)..row(index)
+}
+
+/// Hashes the parameters (FNV-1a of their RON representation) so
+/// that bands rendered with different parameters are not merged.
+/// The number of threads is ignored as it is specific to each
+/// machine.
+pub fn params_hash(params: &FrameParams) -> Result<u64> {
+    let params = FrameParams {
+        threads: None,
+        ..params.clone()
+    };
+    let params_str = ron::to_string(&params).map_err(ErrorKind::EncodeParameterFile)?;
+
+    Ok(fnv1a(params_str.bytes()))
+}
+
+pub fn write_band(band: &Band, path: &Path) -> Result<()> {
+    let header = Header {
+        index: band.index,
+        count: band.count,
+        params_hash: params_hash(&band.params)?,
+        samples_per_pixel: band.samples_per_pixel,
+        width: band.raw_image.width,
+        height: band.raw_image.height,
+        params: band.params.clone(),
+    };
+    write_values_file(
+        path,
+        MAGIC,
+        &header,
+        &band.raw_image.vec,
+        ErrorKind::WriteBand,
+    )
+}
+
+pub fn read_band(path: &Path) -> Result<Band> {
+    let invalid = |reason: &str| ErrorKind::DecodeBand(path.display().to_string(), reason.into());
+
+    let (header, values) = read_values_file(
+        path,
+        MAGIC,
+        |header: &Header| header.width * header.height,
+        ErrorKind::ReadBand,
+        invalid,
+    )?;
+    if params_hash(&header.params)? != header.params_hash {
+        return Err(invalid("the parameters don't match their hash"));
+    }
+
+    Ok(Band {
+        index: header.index,
+        count: header.count,
+        params: header.params,
+        samples_per_pixel: header.samples_per_pixel,
+        raw_image: Mat2D {
+            width: header.width,
+            height: header.height,
+            vec: values,
+        },
+    })
+}
+
+/// Assembles the raw image of a frame from all of its bands, also
+/// returning the parameters and the average number of samples per
+/// pixel.
+pub fn merge_bands(bands: &[Band]) -> Result<(FrameParams, Mat2D<F>, F)> {
+    let first = bands
+        .first()
+        .ok_or_else(|| ErrorKind::MergeBands("no band given".to_string()))?;
+    let FrameParams {
+        img_width,
+        img_height,
+        ..
+    } = first.params;
+    let count = first.count;
+    let hash = params_hash(&first.params)?;
+
+    let mut raw_image = Mat2D::filled_with(0., img_width as usize, img_height as usize);
+    let mut merged = vec![false; count];
+    let mut sample_count = 0.;
+
+    for band in bands {
+        if band.count != count {
+            return Err(ErrorKind::MergeBands(format!(
+                "band {} is one of {} bands but band {} is one of {}",
+                band.index, band.count, first.index, count
+            )));
+        }
+        if !(1..=count).contains(&band.index) {
+            return Err(ErrorKind::MergeBands(format!(
+                "band index {} is out of range 1-{}",
+                band.index, count
+            )));
+        }
+        if params_hash(&band.params)? != hash {
+            return Err(ErrorKind::MergeBands(format!(
+                "band {} was rendered with different parameters than band {}",
+                band.index, first.index
+            )));
+        }
+        if merged[band.index - 1] {
+            return Err(ErrorKind::MergeBands(format!(
+                "band {} is given twice",
+                band.index
+            )));
+        }
+
+        let rows = band_rows(band.index, count, img_height);
+        if band.raw_image.width != img_width as usize || band.raw_image.height != rows.len() {
+            return Err(ErrorKind::MergeBands(format!(
+                "band {} is {}x{} instead of {}x{}",
+                band.index,
+                band.raw_image.width,
+                band.raw_image.height,
+                img_width,
+                rows.len()
+            )));
+        }
+
+        let start = rows.start as usize * raw_image.width;
+        raw_image.vec[start..start + band.raw_image.vec.len()].copy_from_slice(&band.raw_image.vec);
+        merged[band.index - 1] = true;
+        sample_count += band.samples_per_pixel * band.raw_image.vec.len() as F;
+    }
+
+    let missing = (1..=count)
+        .filter(|&i| !merged[i - 1])
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(ErrorKind::MergeBands(format!(
+            "missing band(s) {}",
+            missing.join(", ")
+        )));
+    }
+
+    let samples_per_pixel = sample_count / raw_image.vec.len() as F;
+    Ok((first.params.clone(), raw_image, samples_per_pixel))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    /// The bands of a 5x7 frame whose raw values are their index in
+    /// the image.
+    fn bands(count: usize) -> Vec<Band> {
+        let params = FrameParams {
+            img_width: 5,
+            img_height: 7,
+            ..Default::default()
+        };
+        (1..=count)
+            .map(|index| {
+                let rows = band_rows(index, count, params.img_height);
+                let width = params.img_width as usize;
+                Band {
+                    index,
+                    count,
+                    params: params.clone(),
+                    samples_per_pixel: index as F,
+                    raw_image: Mat2D {
+                        vec: (rows.start as usize * width..rows.end as usize * width)
+                            .map(|k| k as F)
+                            .collect(),
+                        width,
+                        height: rows.len(),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bands_cover_the_rows() {
+        for count in [1, 2, 3, 7, 10] {
+            let rows = (1..=count)
+                .map(|index| band_rows(index, count, 7))
+                .collect::<Vec<_>>();
+            assert_eq!(rows[0].start, 0);
+            assert_eq!(rows[count - 1].end, 7);
+            assert!(rows.windows(2).all(|w| w[0].end == w[1].start));
+        }
+    }
+
+    #[test]
+    fn band_round_trips() {
+        let band = bands(3).remove(1);
+        let path = env::temp_dir().join(format!("fractal_rndr_test_{}.band", std::process::id()));
+        write_band(&band, &path).unwrap();
+        let read = read_band(&path);
+        fs::remove_file(&path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!((read.index, read.count), (2, 3));
+        assert_eq!(read.samples_per_pixel, 2.);
+        assert_eq!(read.raw_image, band.raw_image);
+        assert_eq!(
+            params_hash(&read.params).unwrap(),
+            params_hash(&band.params).unwrap()
+        );
+    }
+
+    #[test]
+    fn merged_bands_give_the_frame() {
+        let mut bands = bands(3);
+        bands.reverse();
+
+        let (_, raw_image, samples_per_pixel) = merge_bands(&bands).unwrap();
+        assert_eq!(raw_image.vec, (0..35).map(|k| k as F).collect::<Vec<_>>());
+        // Bands of 2, 2 and 3 rows.
+        assert_eq!(samples_per_pixel, (2. * 1. + 2. * 2. + 3. * 3.) / 7.);
+    }
+
+    #[test]
+    fn merging_checks_the_bands() {
+        let all = bands(3);
+
+        let merge_error = |bands: &[Band]| match merge_bands(bands) {
+            Err(ErrorKind::MergeBands(message)) => message,
+            _ => panic!("the bands were merged"),
+        };
+        assert_eq!(merge_error(&[]), "no band given");
+        assert_eq!(merge_error(&all[..2]), "missing band(s) 3");
+        assert_eq!(
+            merge_error(&[all[0].clone(), all[0].clone()]),
+            "band 1 is given twice"
+        );
+
+        let mut other_params = all[1].clone();
+        other_params.params.zoom *= 2.;
+        assert_eq!(
+            merge_error(&[all[0].clone(), other_params]),
+            "band 2 was rendered with different parameters than band 1"
+        );
+
+        // The number of threads doesn't matter.
+        let mut other_threads = all.clone();
+        other_threads[2].params.threads = Some(3);
+        assert!(merge_bands(&other_threads).is_ok());
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ErrorKind, Result},
+    fractal::Fractal,
+    params::FrameParams,
+    F,
+};
+
+/// Location saved from the gui.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub center_x: F,
+    pub center_y: F,
+    pub zoom: F,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<F>,
+    pub fractal: Fractal,
+}
+
+impl Bookmark {
+    pub fn new(params: &FrameParams) -> Self {
+        Bookmark {
+            center_x: params.center_x,
+            center_y: params.center_y,
+            zoom: params.zoom,
+            rotate: params.rotate,
+            fractal: params.fractal.clone(),
+        }
+    }
+
+    /// Moves the view of `params` to the bookmarked location.
+    pub fn apply(&self, params: &mut FrameParams) {
+        params.center_x = self.center_x;
+        params.center_y = self.center_y;
+        params.zoom = self.zoom;
+        params.rotate = self.rotate;
+        params.fractal = self.fractal.clone();
+    }
+}
+
+/// Bookmarks file of a param file, a JSON sidecar next to it.
+pub fn bookmarks_path(param_file_path: &Path) -> PathBuf {
+    let mut file_name = param_file_path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".bookmarks.json");
+    param_file_path.with_file_name(file_name)
+}
+
+/// Reads a bookmarks file, a missing file having no bookmarks.
+pub fn load_bookmarks(path: &Path) -> Result<Vec<Bookmark>> {
+    match fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).map_err(ErrorKind::DecodeBookmarks),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(ErrorKind::ReadBookmarks(e)),
+    }
+}
+
+pub fn save_bookmarks(path: &Path, bookmarks: &[Bookmark]) -> Result<()> {
+    fs::write(
+        path,
+        serde_json::to_string_pretty(bookmarks).map_err(ErrorKind::EncodeJsonParameterFile)?,
+    )
+    .map_err(ErrorKind::WriteBookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn bookmarks_path_is_a_sidecar() {
+        assert_eq!(
+            bookmarks_path(Path::new("dir/params.ron")),
+            Path::new("dir/params.ron.bookmarks.json")
+        );
+    }
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let params = FrameParams {
+            center_x: -0.75,
+            center_y: 0.1,
+            zoom: 0.002,
+            rotate: Some(30.),
+            fractal: Fractal::SdrgeParam {
+                a_re: 0.5,
+                a_im: -0.25,
+            },
+            ..Default::default()
+        };
+        let bookmarks = [
+            Bookmark::new(&FrameParams::default()),
+            Bookmark::new(&params),
+        ];
+
+        let path = env::temp_dir().join(format!(
+            "fractal_rndr_test_{}.bookmarks.json",
+            std::process::id()
+        ));
+        save_bookmarks(&path, &bookmarks).unwrap();
+        let read = load_bookmarks(&path);
+        fs::remove_file(&path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(read.len(), 2);
+        let mut applied = FrameParams::default();
+        read[1].apply(&mut applied);
+        assert_eq!(
+            (
+                applied.center_x,
+                applied.center_y,
+                applied.zoom,
+                applied.rotate
+            ),
+            (-0.75, 0.1, 0.002, Some(30.))
+        );
+        assert_eq!(applied.fractal, params.fractal);
+        assert_eq!(read[0].rotate, None);
+
+        // A missing file has no bookmarks.
+        assert!(load_bookmarks(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    params::FrameParams,
+    rendering::render_raw_image,
+    sampling::{Sampling, SamplingLevel},
+    F,
+};
+
+/// Speed of the machine, measured by rendering a small Mandelbrot
+/// set for a moment, from which the gui picks defaults suited to it.
+/// It only holds for the version and the CPU it was measured with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub version: String,
+    pub cpu: String,
+    /// Samples of the calibration render taken per second.
+    pub samples_per_sec: F,
+}
+
+/// Time the calibration render is repeated for.
+const CALIBRATION_TIME: Duration = Duration::from_secs(2);
+const CALIBRATION_SIZE: u32 = 64;
+const CALIBRATION_MAX_ITER: u32 = 100;
+
+/// Resolutions of the preview to pick from. The preview is rendered
+/// on the ui thread, it should take at most `PREVIEW_TIME` at the
+/// calibration's number of iterations.
+const PREVIEW_SIZES: &[u32] = &[128, 192, 256, 384, 512];
+const PREVIEW_TIME: F = 0.05;
+
+/// Sampling levels to suggest, the highest one whose render is
+/// expected to take at most `SUGGESTED_RENDER_TIME` is suggested.
+const SUGGESTED_LEVELS: &[SamplingLevel] = &[
+    SamplingLevel::Exploration,
+    SamplingLevel::Low,
+    SamplingLevel::Medium,
+    SamplingLevel::High,
+    SamplingLevel::Ultra,
+    SamplingLevel::Extreme,
+];
+pub const SUGGESTED_RENDER_TIME: F = 30.;
+
+/// Memory budgets of the caches by minimum number of samples per
+/// second, machines rendering faster tending to have more memory.
+const CACHE_BUDGETS: &[(F, usize)] = &[(0., 32 << 20), (2e7, 64 << 20), (1e8, 128 << 20)];
+
+impl Calibration {
+    /// Calibration of the current version and CPU.
+    pub fn new(samples_per_sec: F) -> Self {
+        Calibration {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            cpu: cpu_brand(),
+            samples_per_sec,
+        }
+    }
+
+    /// Whether it was measured with the current version and CPU,
+    /// otherwise it has to be measured again.
+    pub fn is_current(&self) -> bool {
+        self.version == env!("CARGO_PKG_VERSION") && self.cpu == cpu_brand()
+    }
+
+    /// Seconds taken per iteration of a sample, the constant of the
+    /// estimated duration model.
+    fn secs_per_iteration(&self) -> F {
+        1. / (self.samples_per_sec * CALIBRATION_MAX_ITER as F)
+    }
+
+    /// Expected duration of the render of `params`, taking every
+    /// sample to cost `max_iter` iterations. It overestimates renders
+    /// with few points in the set, which stop early, and doesn't count
+    /// the additional samples of adaptive sampling.
+    pub fn expected_duration(&self, params: &FrameParams) -> Duration {
+        let factor = params.supersample.unwrap_or(1).max(1) as F;
+        let samples = params.img_width as F
+            * params.img_height as F
+            * factor
+            * factor
+            * params.sampling.sample_count() as F;
+        let secs = samples * params.max_iter as F * self.secs_per_iteration();
+        #[allow(clippy::unnecessary_cast)]
+        Duration::try_from_secs_f64(secs as f64).unwrap_or(Duration::MAX)
+    }
+
+    /// Largest resolution of the preview rendering in time. Previews
+    /// are rendered at the exploration level, as the calibration.
+    pub fn preview_size(&self) -> u32 {
+        let sample_count = calibration_params().sampling.sample_count();
+        let samples = |size: u32| (size * size) as F * sample_count as F;
+        PREVIEW_SIZES
+            .iter()
+            .copied()
+            .rev()
+            .find(|&size| samples(size) / self.samples_per_sec <= PREVIEW_TIME)
+            .unwrap_or(PREVIEW_SIZES[0])
+    }
+
+    /// Highest sampling level rendering `params` in a reasonable
+    /// time.
+    pub fn suggested_level(&self, params: &FrameParams) -> SamplingLevel {
+        SUGGESTED_LEVELS
+            .iter()
+            .copied()
+            .rev()
+            .find(|&level| {
+                let params = FrameParams {
+                    sampling: Sampling {
+                        level,
+                        ..params.sampling
+                    },
+                    ..params.clone()
+                };
+                self.expected_duration(&params).as_secs_f64() as F <= SUGGESTED_RENDER_TIME
+            })
+            .unwrap_or(SUGGESTED_LEVELS[0])
+    }
+
+    /// Memory budget of the caches, in bytes.
+    pub fn cache_budget(&self) -> usize {
+        CACHE_BUDGETS
+            .iter()
+            .rev()
+            .find(|&&(min, _)| self.samples_per_sec >= min)
+            .map_or(CACHE_BUDGETS[0].1, |&(_, budget)| budget)
+    }
+}
+
+/// Brand string of the CPU, falling back to its architecture where
+/// it can't be read.
+pub fn cpu_brand() -> String {
+    #[cfg(target_os = "linux")]
+    if let Some(brand) = std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|info| {
+            info.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                matches!(key.trim(), "model name" | "Model" | "Hardware")
+                    .then(|| value.trim().to_string())
+            })
+        })
+    {
+        return brand;
+    }
+
+    #[cfg(target_os = "macos")]
+    if let Some(brand) = std::process::Command::new("sysctl")
+        .args(["-n", "machdep.cpu.brand_string"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|brand| brand.trim().to_string())
+        .filter(|brand| !brand.is_empty())
+    {
+        return brand;
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Ok(brand) = std::env::var("PROCESSOR_IDENTIFIER") {
+        return brand;
+    }
+
+    std::env::consts::ARCH.to_string()
+}
+
+fn calibration_params() -> FrameParams {
+    let default = FrameParams::default();
+    FrameParams {
+        img_width: CALIBRATION_SIZE,
+        img_height: CALIBRATION_SIZE,
+        max_iter: CALIBRATION_MAX_ITER,
+        auto_max_iter: None,
+        sampling: Sampling {
+            level: SamplingLevel::Exploration,
+            ..default.sampling
+        },
+        supersample: None,
+        seed: Some(0),
+        threads: None,
+        ..default
+    }
+}
+
+/// Renders a small Mandelbrot set for a couple of seconds to measure
+/// the speed of the machine, `None` is returned when cancelled.
+pub fn calibrate(cancel: &AtomicBool) -> Option<Calibration> {
+    let params = calibration_params();
+    let sampling_points = params.sampling.generate_sampling_points();
+    let samples_per_render =
+        (params.img_width * params.img_height) as usize * sampling_points.len();
+
+    // The first render starts the thread pool, it isn't counted.
+    render_raw_image(&params, &sampling_points, None, Some(cancel));
+
+    let start = Instant::now();
+    let mut samples = 0;
+    while start.elapsed() < CALIBRATION_TIME {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        render_raw_image(&params, &sampling_points, None, Some(cancel));
+        samples += samples_per_render;
+    }
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(Calibration::new(
+        samples as F / start.elapsed().as_secs_f64() as F,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration_is_only_current_for_this_version_and_cpu() {
+        let calibration = Calibration::new(1e7);
+        assert!(calibration.is_current());
+        assert!(!Calibration {
+            version: "0.0.0-other".to_string(),
+            ..calibration.clone()
+        }
+        .is_current());
+        assert!(!Calibration {
+            cpu: "Other CPU".to_string(),
+            ..calibration
+        }
+        .is_current());
+    }
+
+    #[test]
+    fn faster_machines_get_better_defaults() {
+        let slow = Calibration::new(1e6);
+        let fast = Calibration::new(1e9);
+        let params = FrameParams::default();
+
+        assert_eq!(slow.preview_size(), PREVIEW_SIZES[0]);
+        assert_eq!(fast.preview_size(), *PREVIEW_SIZES.last().unwrap());
+        assert!(slow.expected_duration(&params) > fast.expected_duration(&params));
+        assert_eq!(slow.suggested_level(&params), SamplingLevel::Exploration);
+        assert_eq!(fast.suggested_level(&params), SamplingLevel::Extreme);
+        assert!(slow.cache_budget() < fast.cache_budget());
+    }
+
+    #[test]
+    fn expected_duration_scales_with_the_samples_and_iterations() {
+        let calibration = Calibration::new(1e6);
+        let params = FrameParams {
+            img_width: 100,
+            img_height: 100,
+            max_iter: CALIBRATION_MAX_ITER,
+            sampling: Sampling {
+                level: SamplingLevel::Custom(10),
+                ..FrameParams::default().sampling
+            },
+            supersample: None,
+            ..FrameParams::default()
+        };
+        let secs = |params: &FrameParams| calibration.expected_duration(params).as_secs_f64();
+
+        assert!((secs(&params) - 0.1).abs() < 1e-6);
+        assert!(
+            (secs(&FrameParams {
+                max_iter: 2 * CALIBRATION_MAX_ITER,
+                supersample: Some(2),
+                ..params.clone()
+            }) - 0.8)
+                .abs()
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn cancelled_calibration_returns_nothing() {
+        assert!(calibrate(&AtomicBool::new(true)).is_none());
+    }
+}
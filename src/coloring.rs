@@ -1,52 +1,567 @@
-use cumulative_histogram::{compute_histogram, cumulate_histogram, get_histogram_value};
-use image::{Rgb, RgbImage};
-use serde::{Deserialize, Serialize};
+use std::{array, f64::consts::PI, fmt};
 
-use crate::{mat::Mat2D, params::FrameParams, F};
+use cumulative_histogram::{
+    compute_histogram, cumulate_histogram, get_histogram_value, histogram_quantile,
+};
+use image::{imageops::FilterType, Rgb, Rgb32FImage, RgbImage};
+use serde::{
+    de::{self, value::SeqAccessDeserializer, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 
-pub fn color_raw_image(params: &FrameParams, mut raw_image: Mat2D<F>) -> RgbImage {
+use crate::{
+    error::ErrorKind,
+    fractal::{decode_newton_value, ValueKind},
+    gradient_file::parse_hex_color,
+    mat::Mat2D,
+    params::FrameParams,
+    rendering::RawChannels,
+    F,
+};
+
+pub fn color_raw_image(params: &FrameParams, raw_image: Mat2D<F>) -> RgbImage {
+    color_raw_channels(params, raw_image, None)
+}
+
+/// Same as [`color_raw_image`] with the channels used by
+/// [`ColoringMode::TwoChannel`], which are colored as zeros when
+/// missing.
+pub fn color_raw_channels(
+    params: &FrameParams,
+    raw_image: Mat2D<F>,
+    raw_channels: Option<&RawChannels>,
+) -> RgbImage {
+    color_raw_region(params, raw_image, raw_channels, (0, 0))
+}
+
+/// Same as [`color_raw_channels`] for a region of a larger image
+/// whose top left pixel is at `origin` in it, so that the dithering
+/// noise continues across the regions.
+pub fn color_raw_region(
+    params: &FrameParams,
+    mut raw_image: Mat2D<F>,
+    raw_channels: Option<&RawChannels>,
+    (x0, y0): (usize, usize),
+) -> RgbImage {
+    sanitize_raw_image(&mut raw_image);
+
+    // The band is computed on raw values so that it does not depend
+    // on the coloring mode.
+    let band_weights = params
+        .boundary_band
+        .map(|band| band.compute_weights(&raw_image));
+    let shades = match params.coloring_mode {
+        ColoringMode::Roots { darkening } => Some(Mat2D {
+            vec: raw_image
+                .vec
+                .iter()
+                .map(|&v| (-darkening * decode_newton_value(v, params.max_iter).1).exp())
+                .collect(),
+            ..raw_image
+        }),
+        _ => None,
+    };
+    let interior = params.interior_mode.as_ref().map(|interior_mode| {
+        let interior = interior_mask(params, &raw_image);
+        (interior_mode, interior)
+    });
+    let raw_image = map_distance_estimates(params, raw_image);
+    let two_channel_colors = match params.coloring_mode {
+        ColoringMode::TwoChannel {
+            value,
+            hue,
+            saturation,
+            map,
+        } => {
+            let value = normalize_channel(value, &raw_image, raw_channels);
+            let hue = normalize_channel(hue, &raw_image, raw_channels);
+            let hue_offset = params.gradient_offset.unwrap_or(0.);
+            Some(Mat2D {
+                vec: value
+                    .vec
+                    .iter()
+                    .zip(&hue.vec)
+                    .map(|(&v, &h)| {
+                        let v = map.apply(v);
+                        let v = params
+                            .tone_curve
+                            .map_or(v, |tone_curve| tone_curve.apply(v));
+                        hsv_to_rgb((h + hue_offset).rem_euclid(1.), saturation, v)
+                    })
+                    .collect(),
+                width: value.width,
+                height: value.height,
+            })
+        }
+        _ => None,
+    };
+    let mut values = normalize_raw_image(params, raw_image);
+    // The values of roots are not tones, they pick the color of
+    // each root.
+    if let (Some(tone_curve), false) = (
+        params.tone_curve,
+        matches!(params.coloring_mode, ColoringMode::Roots { .. }),
+    ) {
+        for value in &mut values.vec {
+            *value = tone_curve.apply(*value);
+        }
+    }
+    let gradient = params.gradient.stops();
+
+    RgbImage::from_fn(params.img_width, params.img_height, |i, j| {
+        let (i, j) = (i as usize, j as usize);
+        match &interior {
+            Some((interior_mode, interior)) if interior[(i, j)] => {
+                quantize_color(params, interior_mode.color(params, i, j), x0 + i, y0 + j)
+            }
+            _ => {
+                let band_weight = band_weights.as_ref().map_or(0., |w| w[(i, j)]);
+                let shade = shades.as_ref().map_or(1., |s| s[(i, j)]);
+                let color = match &two_channel_colors {
+                    Some(colors) => colors[(i, j)],
+                    None => gradient_pixel_color(params, gradient, values[(i, j)]),
+                };
+                pixel_color(params, color, band_weight, shade, x0 + i, y0 + j)
+            }
+        }
+    })
+}
+
+/// Finds the interior pixels, the ones whose samples all reached
+/// `max_iter` without escaping. Only escape time values and distance
+/// estimates (which are zero inside) tell them apart.
+fn interior_mask(params: &FrameParams, raw_image: &Mat2D<F>) -> Mat2D<bool> {
+    // Averaging the samples of a pixel doesn't give exactly
+    // `max_iter` back.
+    const TOLERANCE: F = 1e-3;
+
+    let max_iter = params.max_iter as F;
+    let is_interior = |v: F| match (params.coloring_mode, params.value) {
+        (ColoringMode::Roots { .. }, _) => {
+            decode_newton_value(v, params.max_iter).1 >= max_iter - TOLERANCE
+        }
+        (_, ValueKind::DistanceEstimate) => v <= 0.,
+        _ => v >= max_iter - TOLERANCE,
+    };
+
+    Mat2D {
+        vec: raw_image.vec.iter().map(|&v| is_interior(v)).collect(),
+        width: raw_image.width,
+        height: raw_image.height,
+    }
+}
+
+/// Stores the normalized raw values (the values that are mapped to
+/// the gradient) in a float image, repeated over the three
+/// channels.
+pub fn raw_image_to_rgb32f(params: &FrameParams, mut raw_image: Mat2D<F>) -> Rgb32FImage {
+    sanitize_raw_image(&mut raw_image);
+    let values = normalize_raw_image(params, map_distance_estimates(params, raw_image));
+
+    Rgb32FImage::from_fn(
+        params.img_width,
+        params.img_height,
+        #[allow(clippy::unnecessary_cast)]
+        |i, j| Rgb([values[(i as usize, j as usize)] as f32; 3]),
+    )
+}
+
+/// Distance estimates span many orders of magnitude and shrink with
+/// the zoom, so they are colored as the log of the distance in
+/// pixels, which keeps the boundary as crisp at any zoom. Other
+/// values are left as they are.
+pub fn map_distance_estimates(params: &FrameParams, mut raw_image: Mat2D<F>) -> Mat2D<F> {
+    if params.value == ValueKind::DistanceEstimate {
+        let pixel_size = params.zoom / params.img_width as F;
+        raw_image
+            .vec
+            .iter_mut()
+            .for_each(|v| *v = (*v / pixel_size).ln_1p());
+    }
+    raw_image
+}
+
+/// Minimum and maximum finite raw values, the ones used by
+/// [`Extremum::Auto`].
+pub fn raw_extrema(raw_image: &Mat2D<F>) -> (F, F) {
+    let finite = raw_image.vec.iter().copied().filter(|v| v.is_finite());
+    let max = finite.clone().fold(0., F::max);
+    let min = finite.fold(max, F::min);
+    (min, max)
+}
+
+/// Same as [`raw_extrema`] for a frame rendered in parts (stripes or
+/// poster tiles), the values being mapped as they are when colored
+/// so that the extrema are in the same units as them.
+pub fn parts_raw_extrema(
+    params: &FrameParams,
+    parts: impl IntoIterator<Item = Mat2D<F>>,
+) -> (F, F) {
+    let (min, max) = parts
+        .into_iter()
+        .fold((F::MAX, 0.), |(min_v, max_v), raw_image| {
+            let raw_image = map_distance_estimates(params, raw_image);
+            let finite = raw_image.vec.iter().copied().filter(|v| v.is_finite());
+            (
+                finite.clone().fold(min_v, F::min),
+                finite.fold(max_v, F::max),
+            )
+        });
+    (min.min(max), max)
+}
+
+/// Number of NaN or infinite raw values, which some fractals give
+/// for extreme parameters (e.g. `MandelbrotCustomExp` with `exp`
+/// close to 0).
+pub fn count_non_finite(raw_image: &Mat2D<F>) -> usize {
+    raw_image.vec.iter().filter(|v| !v.is_finite()).count()
+}
+
+/// Replaces the non-finite raw values so that they can be colored:
+/// positive infinities by the maximum finite value, NaNs and negative
+/// infinities by the minimum one. Returns the number of values
+/// replaced.
+pub fn sanitize_raw_image(raw_image: &mut Mat2D<F>) -> usize {
+    let count = count_non_finite(raw_image);
+    if count > 0 {
+        let (min, max) = raw_extrema(raw_image);
+        for v in raw_image.vec.iter_mut().filter(|v| !v.is_finite()) {
+            *v = if *v == F::INFINITY { max } else { min };
+        }
+    }
+    count
+}
+
+/// Percentiles of the raw values given by [`RenderStats`].
+pub const STATS_PERCENTILES: [F; 6] = [1., 5., 25., 75., 95., 99.];
+/// Number of bars of the histogram of [`RenderStats`].
+pub const STATS_HISTOGRAM_BARS: usize = 48;
+
+/// Distribution of the raw values of a render, to choose the
+/// extrema and `max_iter`.
+#[derive(Debug, Clone)]
+pub struct RenderStats {
+    pub min: F,
+    pub max: F,
+    pub mean: F,
+    pub median: F,
+    /// Values of [`STATS_PERCENTILES`].
+    pub percentiles: [F; STATS_PERCENTILES.len()],
+    /// Fraction of the pixels which reached `max_iter`.
+    pub interior_fraction: F,
+    /// Number of values in each of [`STATS_HISTOGRAM_BARS`] bars of
+    /// equal width between `min` and `max`.
+    pub histogram: Vec<u32>,
+    /// Number of NaN or infinite values, which are left out of the
+    /// other statistics.
+    pub non_finite_count: usize,
+}
+
+/// Computes the statistics of the raw values, percentiles being read
+/// from their cumulative histogram, precise to a millionth of the
+/// range of the values.
+pub fn analyze_raw_image(params: &FrameParams, raw_image: &Mat2D<F>) -> RenderStats {
+    let (min, max) = raw_extrema(raw_image);
+    let range = if max > min { max - min } else { 1. };
+    let pixel_count = raw_image.vec.len().max(1) as F;
+    let finite = raw_image
+        .vec
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect::<Vec<_>>();
+    let non_finite_count = raw_image.vec.len() - finite.len();
+
+    let normalized = finite.iter().map(|v| (v - min) / range).collect::<Vec<_>>();
+    let histogram = compute_histogram(&normalized);
+    let bars = histogram
+        .chunks(histogram.len().div_ceil(STATS_HISTOGRAM_BARS))
+        .map(|bar| bar.iter().sum())
+        .collect();
+    let cumulative_histogram = cumulate_histogram(histogram);
+    let percentile = |p: F| min + range * histogram_quantile(p / 100., &cumulative_histogram);
+
+    let interior_count = interior_mask(params, raw_image)
+        .vec
+        .iter()
+        .filter(|&&interior| interior)
+        .count();
+
+    RenderStats {
+        min,
+        max,
+        mean: finite.iter().sum::<F>() / finite.len().max(1) as F,
+        median: percentile(50.),
+        percentiles: STATS_PERCENTILES.map(percentile),
+        interior_fraction: interior_count as F / pixel_count,
+        histogram: bars,
+        non_finite_count,
+    }
+}
+
+/// Maps a channel of the raw image to [0,1] for
+/// [`ColoringMode::TwoChannel`]: values and norms span the range of
+/// the image while arguments wrap around.
+fn normalize_channel(
+    channel: ChannelSelect,
+    raw_image: &Mat2D<F>,
+    raw_channels: Option<&RawChannels>,
+) -> Mat2D<F> {
+    let min_max_norm = |values: Vec<F>| {
+        let values = Mat2D {
+            vec: values,
+            width: raw_image.width,
+            height: raw_image.height,
+        };
+        let (min, max) = raw_extrema(&values);
+        let range = if max > min { max - min } else { 1. };
+        Mat2D {
+            vec: values
+                .vec
+                .iter()
+                .map(|v| if v.is_finite() { (v - min) / range } else { 0. })
+                .collect(),
+            ..values
+        }
+    };
+    let channel_values = |f: fn(&[F; 3]) -> F| match raw_channels {
+        Some(raw_channels) => raw_channels.vec.iter().map(f).collect(),
+        None => vec![0.; raw_image.vec.len()],
+    };
+
+    match channel {
+        ChannelSelect::Value => min_max_norm(raw_image.vec.clone()),
+        ChannelSelect::FinalNorm => min_max_norm(channel_values(|&[norm, _, _]| norm)),
+        ChannelSelect::FinalArg => Mat2D {
+            vec: channel_values(|&[_, cos, sin]| (sin.atan2(cos) / (2. * PI as F)).rem_euclid(1.)),
+            width: raw_image.width,
+            height: raw_image.height,
+        },
+    }
+}
+
+/// Converts a color from HSV (all in range [0,1]) to RGB in range
+/// [0,255].
+fn hsv_to_rgb(h: F, s: F, v: F) -> [F; 3] {
+    let (s, v) = (s.clamp(0., 1.), v.clamp(0., 1.));
+    let h = 6. * h;
+    let c = v * s;
+    let x = c * (1. - (h.rem_euclid(2.) - 1.).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    let m = v - c;
+    [r, g, b].map(|channel| 255. * (channel + m))
+}
+
+/// Maps raw values to [0,1] using the coloring mode (normalization
+/// and value mapping).
+fn normalize_raw_image(params: &FrameParams, mut raw_image: Mat2D<F>) -> Mat2D<F> {
     let &FrameParams {
         img_width,
         img_height,
         ..
     } = params;
 
-    let mut output_image = RgbImage::new(img_width, img_height);
+    let mut values = Mat2D::filled_with(0., img_width as usize, img_height as usize);
 
-    let max_v = raw_image.vec.iter().copied().fold(0., F::max);
-    let min_v = raw_image.vec.iter().copied().fold(max_v, F::min);
+    let (min_v, max_v) = raw_extrema(&raw_image);
 
     match params.coloring_mode {
+        ColoringMode::Roots { .. } => {
+            let root_count = params.fractal.root_count();
+            for (value, &raw_value) in values.vec.iter_mut().zip(&raw_image.vec) {
+                let (root, _) = decode_newton_value(raw_value, params.max_iter);
+                *value = (root as F + 0.5) / root_count as F;
+            }
+        }
         ColoringMode::MinMaxNorm { min, max, map } => {
-            let min = min.unwrap_custom_or(min_v);
-            let max = max.unwrap_custom_or(max_v);
+            let min = min.resolve(&raw_image.vec, min_v);
+            let max = max.resolve(&raw_image.vec, max_v);
 
             for j in 0..img_height as usize {
                 for i in 0..img_width as usize {
                     let value = raw_image[(i, j)];
 
-                    let t = map.apply((value - min) / (max - min));
-
-                    output_image.put_pixel(i as u32, j as u32, color_mapping(t, &params.gradient));
+                    values[(i, j)] = map.apply((value - min) / (max - min));
                 }
             }
         }
-        ColoringMode::CumulativeHistogram { map } => {
-            raw_image.vec.iter_mut().for_each(|v| *v /= max_v);
-            let cumulative_histogram = cumulate_histogram(compute_histogram(&raw_image.vec));
-            for j in 0..img_height as usize {
-                for i in 0..img_width as usize {
-                    let value = raw_image[(i, j)];
+        // Only used for raw outputs, the colors come from the channels.
+        ColoringMode::TwoChannel { map, .. } => {
+            for (value, &raw_value) in values.vec.iter_mut().zip(&raw_image.vec) {
+                *value = map.apply((raw_value - min_v) / (max_v - min_v));
+            }
+        }
+        ColoringMode::CumulativeHistogram {
+            map,
+            exclude_interior,
+        } => {
+            let interior =
+                (exclude_interior == Some(true)).then(|| interior_mask(params, &raw_image));
+            let is_interior = |k: usize| interior.as_ref().is_some_and(|mask| mask.vec[k]);
 
-                    let t = map.apply(get_histogram_value(value, &cumulative_histogram));
+            let exterior_max = raw_image
+                .vec
+                .iter()
+                .enumerate()
+                .filter(|&(k, _)| !is_interior(k))
+                .fold(0., |max, (_, &v)| F::max(max, v));
+            if exterior_max > 0. {
+                raw_image.vec.iter_mut().for_each(|v| *v /= exterior_max);
+            }
+            let exterior = raw_image
+                .vec
+                .iter()
+                .enumerate()
+                .filter(|&(k, _)| !is_interior(k))
+                .map(|(_, &v)| v)
+                .collect::<Vec<_>>();
+            let cumulative_histogram = cumulate_histogram(compute_histogram(&exterior));
 
-                    output_image.put_pixel(i as u32, j as u32, color_mapping(t, &params.gradient));
-                }
+            for (k, (value, &raw_value)) in values.vec.iter_mut().zip(&raw_image.vec).enumerate() {
+                *value = if is_interior(k) {
+                    map.apply(1.)
+                } else {
+                    map.apply(get_histogram_value(raw_value, &cumulative_histogram))
+                };
             }
         }
     };
 
-    output_image
+    values
+}
+
+/// Scales the colored image for saving. Filtering is done on
+/// linear-light values so that colors are averaged correctly.
+pub fn scale_image(image: &RgbImage, scale: F) -> RgbImage {
+    #[inline]
+    fn srgb_to_linear(c: u8) -> f32 {
+        let c = c as f32 / 255.;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+    #[inline]
+    fn linear_to_srgb(c: f32) -> u8 {
+        let c = c.clamp(0., 1.);
+        let c = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1. / 2.4) - 0.055
+        };
+        (c * 255.).round() as u8
+    }
+
+    let linear = Rgb32FImage::from_fn(image.width(), image.height(), |i, j| {
+        Rgb(image.get_pixel(i, j).0.map(srgb_to_linear))
+    });
+    let scaled = scale_linear_image(&linear, scale);
+
+    RgbImage::from_fn(scaled.width(), scaled.height(), |i, j| {
+        Rgb(scaled.get_pixel(i, j).0.map(linear_to_srgb))
+    })
+}
+
+/// Scales a float image whose values are already linear.
+pub fn scale_linear_image(image: &Rgb32FImage, scale: F) -> Rgb32FImage {
+    let width = ((image.width() as F * scale).round() as u32).max(1);
+    let height = ((image.height() as F * scale).round() as u32).max(1);
+
+    image::imageops::resize(image, width, height, FilterType::Lanczos3)
+}
+
+/// Color of `gradient` (the resolved gradient of `params`) for the
+/// normalized value `t`.
+#[inline]
+fn gradient_pixel_color(params: &FrameParams, gradient: &[(F, [u8; 3])], t: F) -> [F; 3] {
+    match params.gradient_repeat {
+        Some(repeat) => {
+            let t = (t * repeat + params.gradient_offset.unwrap_or(0.)).rem_euclid(1.);
+            cyclic_gradient_color(t, gradient)
+        }
+        None => gradient_color(apply_gradient_offset(t, params.gradient_offset), gradient),
+    }
+}
+
+/// Computes the color of pixel (i, j) from its base `color`,
+/// darkened by `shade` and blending in the boundary band color by
+/// `band_weight`.
+#[inline]
+fn pixel_color(
+    params: &FrameParams,
+    color: [F; 3],
+    band_weight: F,
+    shade: F,
+    i: usize,
+    j: usize,
+) -> Rgb<u8> {
+    let mut color = color.map(|c| c * shade);
+    if let Some(BoundaryBand {
+        color: band_color, ..
+    }) = params.boundary_band
+    {
+        for (c, band_c) in color.iter_mut().zip(band_color) {
+            *c += (band_c as F - *c) * band_weight;
+        }
+    }
+
+    quantize_color(params, color, i, j)
+}
+
+/// Quantizes a continuous color to 8 bits, dithering it when enabled.
+#[inline]
+fn quantize_color(params: &FrameParams, color: [F; 3], i: usize, j: usize) -> Rgb<u8> {
+    if params.dither == Some(true) {
+        dither(color, i, j)
+    } else {
+        Rgb(color.map(|c| c.clamp(0., 255.) as u8))
+    }
+}
+
+/// Shifts `t` by the gradient offset, wrapping around so that the
+/// gradient is cycled through.
+#[inline]
+fn apply_gradient_offset(t: F, offset: Option<F>) -> F {
+    if let Some(offset) = offset {
+        (t + offset).rem_euclid(1.)
+    } else {
+        t
+    }
+}
+
+/// Quantizes a continuous color to 8 bits after adding a noise
+/// offset in [0,1) to each channel, which hides banding in slowly
+/// varying regions. The noise only depends on the pixel position so
+/// renders stay reproducible.
+fn dither(color: [F; 3], i: usize, j: usize) -> Rgb<u8> {
+    const DITHER_SEED: u32 = 0x9e3779b9;
+
+    #[inline]
+    fn hash_noise(i: u32, j: u32, c: u32) -> F {
+        let mut h = i.wrapping_mul(0x8da6b343)
+            ^ j.wrapping_mul(0xd8163841)
+            ^ c.wrapping_mul(0xcb1ab31f)
+            ^ DITHER_SEED;
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846ca68b);
+        h ^= h >> 16;
+        h as F / (u32::MAX as F + 1.)
+    }
+
+    Rgb(array::from_fn(|c| {
+        let noise = hash_noise(i as u32, j as u32, c as u32);
+        (color[c] + noise).floor().clamp(0., 255.) as u8
+    }))
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -60,7 +575,52 @@ pub enum ColoringMode {
     },
     CumulativeHistogram {
         map: MapValue,
+        /// Builds the histogram from the exterior pixels only, the
+        /// interior ones (which can make up most of the histogram)
+        /// taking the end of the gradient.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exclude_interior: Option<bool>,
     },
+    /// Gives each root of a Newton fractal its own color from the
+    /// gradient, darkened by the number of iterations needed to
+    /// converge.
+    Roots { darkening: F },
+    /// Composes two channels of the raw image in HSV, one giving the
+    /// brightness and the other the hue (shifted by the gradient
+    /// offset). The gradient is not used.
+    TwoChannel {
+        value: ChannelSelect,
+        hue: ChannelSelect,
+        /// In range [0,1].
+        saturation: F,
+        /// Applied to the brightness.
+        map: MapValue,
+    },
+}
+
+impl ColoringMode {
+    /// Whether the channels of the last points of the orbits have
+    /// to be rendered (see [`crate::rendering::RawChannels`]).
+    pub fn needs_channels(&self) -> bool {
+        matches!(
+            self,
+            ColoringMode::TwoChannel { value, hue, .. }
+                if *value != ChannelSelect::Value || *hue != ChannelSelect::Value
+        )
+    }
+}
+
+/// Channel of the raw image used by [`ColoringMode::TwoChannel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelSelect {
+    /// Value of the samples, the one used by the other coloring
+    /// modes.
+    Value,
+    /// Norm of the last point of the orbits.
+    FinalNorm,
+    /// Argument of the last point of the orbits, which wraps around
+    /// like hues.
+    FinalArg,
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
@@ -68,6 +628,9 @@ pub enum Extremum {
     #[default]
     Auto,
     Custom(F),
+    /// Percentile of the raw values, in range [0,100], so that a few
+    /// outlying pixels don't take up the whole range.
+    Percentile(F),
 }
 
 impl Extremum {
@@ -75,6 +638,20 @@ impl Extremum {
         matches!(self, Extremum::Auto)
     }
 
+    pub fn is_percentile(&self) -> bool {
+        matches!(self, Extremum::Percentile(_))
+    }
+
+    /// Value of the extremum for the raw values `values`, `auto`
+    /// being the one of [`Extremum::Auto`].
+    pub fn resolve(self, values: &[F], auto: F) -> F {
+        match self {
+            Extremum::Auto => auto,
+            Extremum::Custom(x) => x,
+            Extremum::Percentile(p) => percentile(values, p),
+        }
+    }
+
     pub fn unwrap_custom_or(self, default: F) -> F {
         if let Extremum::Custom(x) = self {
             x
@@ -84,14 +661,101 @@ impl Extremum {
     }
 }
 
+/// Value below which `p` percent of the finite `values` are, found
+/// by selection on a copy of the values instead of sorting them.
+pub fn percentile(values: &[F], p: F) -> F {
+    let mut values = values
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return 0.;
+    }
+    let i = (p.clamp(0., 100.) / 100. * (values.len() - 1) as F).round() as usize;
+    *values.select_nth_unstable_by(i, F::total_cmp).1
+}
+
+/// Highlights the pixels whose value is within `width_iters` of the
+/// maximum value of the image.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BoundaryBand {
+    pub width_iters: u32,
+    pub color: [u8; 3],
+    /// How much `color` covers the gradient at the maximum value
+    /// (in range [0,1]), it fades out linearly across the band.
+    pub blend: F,
+}
+
+impl BoundaryBand {
+    /// Computes how much the band color covers each pixel.
+    pub fn compute_weights(&self, raw_image: &Mat2D<F>) -> Mat2D<F> {
+        let (_, max_v) = raw_extrema(raw_image);
+        let width = self.width_iters.max(1) as F;
+
+        Mat2D {
+            vec: raw_image
+                .vec
+                .iter()
+                .map(|&v| self.blend.clamp(0., 1.) * (1. - (max_v - v) / width).max(0.))
+                .collect(),
+            ..*raw_image
+        }
+    }
+}
+
+/// Colors the interior pixels (the ones which never escaped), which
+/// otherwise all get the color of the maximum value and look flat.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum InteriorMode {
+    Flat([u8; 3]),
+    /// Maps the distance from the center of the view through a
+    /// secondary gradient, 0 at the center and 1 at the corners.
+    RadialGradient {
+        #[serde(deserialize_with = "deserialize_gradient")]
+        gradient: Vec<(F, [u8; 3])>,
+    },
+}
+
+impl InteriorMode {
+    /// Continuous color of the interior pixel (i, j).
+    fn color(&self, params: &FrameParams, i: usize, j: usize) -> [F; 3] {
+        match self {
+            InteriorMode::Flat(color) => color.map(|c| c as F),
+            InteriorMode::RadialGradient { gradient } => {
+                let (half_w, half_h) = (params.img_width as F / 2., params.img_height as F / 2.);
+                let (dx, dy) = (i as F + 0.5 - half_w, j as F + 0.5 - half_h);
+                let t = (dx * dx + dy * dy).sqrt() / (half_w * half_w + half_h * half_h).sqrt();
+                gradient_color(t, gradient)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum MapValue {
     Linear,
     Squared,
     Powf(F),
+    /// `ln(1 + k*t) / ln(1 + k)`
+    Log(F),
+    Sqrt,
+    /// `0.5 - 0.5 * cos(freq * PI as F * t)`
+    Sine(F),
 }
 
 impl MapValue {
+    /// Same map with the given parameter, maps without one are
+    /// returned as they are.
+    pub fn with_parameter(self, parameter: F) -> MapValue {
+        match self {
+            MapValue::Powf(_) => MapValue::Powf(parameter),
+            MapValue::Log(_) => MapValue::Log(parameter),
+            MapValue::Sine(_) => MapValue::Sine(parameter),
+            map => map,
+        }
+    }
+
     #[inline]
     pub fn apply(&self, t: F) -> F {
         match self {
@@ -105,17 +769,295 @@ impl MapValue {
                     0.
                 }
             }
+            MapValue::Log(k) => {
+                let t = (1. + k * t).ln() / (1. + k).ln();
+                if t.is_normal() {
+                    t
+                } else {
+                    0.
+                }
+            }
+            MapValue::Sqrt => {
+                let t = t.sqrt();
+                if t.is_normal() {
+                    t
+                } else {
+                    0.
+                }
+            }
+            MapValue::Sine(freq) => {
+                let t = 0.5 - 0.5 * (freq * PI as F * t).cos();
+                if t.is_finite() {
+                    t
+                } else {
+                    0.
+                }
+            }
         }
     }
 }
 
+/// Final adjustment of the normalized values, applied after the map
+/// of the coloring mode and before looking up the gradient. Values
+/// are clamped to [0,1] and parameters out of their range are
+/// clamped too, so the curve never gives NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ToneCurve {
+    /// `t^(1/gamma)`, values above 1 brighten the dark tones.
+    pub gamma: F,
+    /// Added to the values, in range [-1,1].
+    pub brightness: F,
+    /// Scales the values around 0.5, 1 leaves them as they are.
+    pub contrast: F,
+    /// Maps the values with `ln(1 + 100t) / ln(101)` first, which
+    /// spreads the low values.
+    pub log: bool,
+}
+
+impl Default for ToneCurve {
+    fn default() -> Self {
+        ToneCurve {
+            gamma: 1.,
+            brightness: 0.,
+            contrast: 1.,
+            log: false,
+        }
+    }
+}
+
+impl ToneCurve {
+    pub const GAMMA_RANGE: (F, F) = (0.1, 10.);
+    pub const CONTRAST_RANGE: (F, F) = (0., 10.);
+
+    #[inline]
+    pub fn apply(&self, t: F) -> F {
+        const LOG_STRENGTH: F = 100.;
+
+        let clamp_param = |v: F, (min, max): (F, F), default: F| {
+            if v.is_finite() {
+                v.clamp(min, max)
+            } else {
+                default
+            }
+        };
+        let gamma = clamp_param(self.gamma, Self::GAMMA_RANGE, 1.);
+        let contrast = clamp_param(self.contrast, Self::CONTRAST_RANGE, 1.);
+        let brightness = clamp_param(self.brightness, (-1., 1.), 0.);
+
+        // Infinities are clamped like the other saturated values.
+        let mut t = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+        if self.log {
+            t = (1. + LOG_STRENGTH * t).ln() / (1. + LOG_STRENGTH).ln();
+        }
+        t = t.powf(1. / gamma);
+        t = (t - 0.5) * contrast + 0.5 + brightness;
+
+        t.clamp(0., 1.)
+    }
+}
+
+/// Deserializes gradient stops, accepting colors either as
+/// `(r, g, b)` tuples or as hex strings (`"#e6e6f0"` or `"#eef"`).
+pub fn deserialize_gradient<'de, D>(deserializer: D) -> Result<Vec<(F, [u8; 3])>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct StopColor([u8; 3]);
+
+    impl<'de> Deserialize<'de> for StopColor {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct StopColorVisitor;
+
+            impl<'de> Visitor<'de> for StopColorVisitor {
+                type Value = StopColor;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "an (r, g, b) tuple or a hex color string")
+                }
+
+                fn visit_str<E: de::Error>(self, v: &str) -> Result<StopColor, E> {
+                    parse_hex_color(v).map(StopColor).map_err(E::custom)
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<StopColor, A::Error> {
+                    let mut color = [0; 3];
+                    for (k, c) in color.iter_mut().enumerate() {
+                        *c = seq
+                            .next_element()?
+                            .ok_or_else(|| de::Error::invalid_length(k, &self))?;
+                    }
+                    if seq.next_element::<de::IgnoredAny>()?.is_some() {
+                        return Err(de::Error::invalid_length(4, &self));
+                    }
+                    Ok(StopColor(color))
+                }
+            }
+
+            deserializer.deserialize_any(StopColorVisitor)
+        }
+    }
+
+    Ok(Vec::<(F, StopColor)>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(t, StopColor(color))| (t, color))
+        .collect())
+}
+
+/// Fixes up hand-edited gradients: positions are clamped into
+/// [0,1], stops are sorted by position (keeping file order for
+/// equal positions) and stops sharing the position of a previous
+/// stop are dropped. Returns a warning for each fix.
+pub fn sanitize_gradient(gradient: &mut Vec<(F, [u8; 3])>) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for (i, (t, _)) in gradient.iter_mut().enumerate() {
+        if !(0. ..=1.).contains(t) {
+            let clamped = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+            warnings.push(format!(
+                "gradient stop {} has position {} outside [0,1], clamped to {}",
+                i, t, clamped
+            ));
+            *t = clamped;
+        }
+    }
+
+    if !gradient.is_sorted_by(|a, b| a.0 <= b.0) {
+        warnings.push("gradient stops are not sorted by position, sorting them".to_string());
+        gradient.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    let mut i = 1;
+    while i < gradient.len() {
+        if gradient[i].0 == gradient[i - 1].0 {
+            warnings.push(format!(
+                "gradient stop {} has the same position as the previous one, removed",
+                i
+            ));
+            gradient.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    warnings
+}
+
+/// Checks the gradient once sanitized (see [`sanitize_gradient`]):
+/// it needs at least one stop, with positions within [0,1] in
+/// increasing order.
+pub fn validate_gradient(gradient: &[(F, [u8; 3])]) -> crate::error::Result<()> {
+    let reason = if gradient.is_empty() {
+        "it has no stops"
+    } else if gradient.iter().any(|(t, _)| !(0. ..=1.).contains(t)) {
+        "stop positions must be within [0,1]"
+    } else if !gradient.is_sorted_by(|a, b| a.0 <= b.0) {
+        "stops must be sorted by position"
+    } else {
+        return Ok(());
+    };
+
+    Err(ErrorKind::InvalidGradient(reason.to_string()))
+}
+
+/// Gradient of the parameters: either the name of a built-in
+/// gradient (see [`BUILTIN_GRADIENTS`]) or a list of stops. Names
+/// are written as strings and stops as a list in parameter files.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum GradientSpec {
+    Named(String),
+    Custom(Vec<(F, [u8; 3])>),
+}
+
+impl Default for GradientSpec {
+    fn default() -> Self {
+        GradientSpec::Named("default".to_string())
+    }
+}
+
+impl GradientSpec {
+    /// Stops of the gradient, looking up named gradients. Unknown
+    /// names are rejected when decoding and by [`Self::validate`],
+    /// they fall back to the default gradient here.
+    pub fn stops(&self) -> &[(F, [u8; 3])] {
+        match self {
+            GradientSpec::Named(name) => builtin_gradient(name).unwrap_or(DEFAULT_GRADIENT),
+            GradientSpec::Custom(stops) => stops,
+        }
+    }
+
+    /// Name of the gradient if it is a built-in one.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            GradientSpec::Named(name) => Some(name),
+            GradientSpec::Custom(_) => None,
+        }
+    }
+
+    pub fn validate(&self) -> crate::error::Result<()> {
+        match self {
+            GradientSpec::Named(name) => match builtin_gradient(name) {
+                Some(_) => Ok(()),
+                None => Err(ErrorKind::InvalidGradient(unknown_gradient_message(name))),
+            },
+            GradientSpec::Custom(stops) => validate_gradient(stops),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for GradientSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct GradientSpecVisitor;
+
+        impl<'de> Visitor<'de> for GradientSpecVisitor {
+            type Value = GradientSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "the name of a built-in gradient or a list of stops")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<GradientSpec, E> {
+                match builtin_gradient(v) {
+                    Some(_) => Ok(GradientSpec::Named(v.to_string())),
+                    None => Err(E::custom(unknown_gradient_message(v))),
+                }
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<GradientSpec, A::Error> {
+                deserialize_gradient(SeqAccessDeserializer::new(seq)).map(GradientSpec::Custom)
+            }
+        }
+
+        deserializer.deserialize_any(GradientSpecVisitor)
+    }
+}
+
+fn unknown_gradient_message(name: &str) -> String {
+    let names = BUILTIN_GRADIENTS
+        .iter()
+        .map(|&(name, _)| name)
+        .collect::<Vec<_>>();
+    format!(
+        "unknown gradient \"{}\", available gradients are: {}",
+        name,
+        names.join(", ")
+    )
+}
+
+/// Stops of the built-in gradient called `name`.
+pub fn builtin_gradient(name: &str) -> Option<&'static [(F, [u8; 3])]> {
+    BUILTIN_GRADIENTS
+        .iter()
+        .find(|&&(builtin, _)| builtin == name)
+        .map(|&(_, stops)| stops)
+}
+
 pub const DEFAULT_GRADIENT: &[(F, [u8; 3])] = &[
     (0.0, [230, 230, 240]),
     (0.3, [230, 180, 180]),
     (0.5, [60, 60, 90]),
     (1.0, [220, 210, 220]),
 ];
-#[allow(dead_code)]
 pub const OLD_DEFAULT_GRADIENT: &[(F, [u8; 3])] = &[
     (0., [20, 8, 30]),
     (0.1, [160, 30, 200]),
@@ -127,14 +1069,124 @@ pub const OLD_DEFAULT_GRADIENT: &[(F, [u8; 3])] = &[
     (1., [20, 2, 10]),
 ];
 
+type GradientStops = &'static [(F, [u8; 3])];
+
+/// Built-in gradients, selected by name in parameter files and from
+/// the gui.
+pub const BUILTIN_GRADIENTS: &[(&str, GradientStops)] = &[
+    ("default", DEFAULT_GRADIENT),
+    ("old-default", OLD_DEFAULT_GRADIENT),
+    ("viridis", VIRIDIS),
+    ("magma", MAGMA),
+    ("inferno", INFERNO),
+    ("turbo", TURBO),
+    ("grayscale", GRAYSCALE),
+    ("classic-ultra-fractal", CLASSIC_ULTRA_FRACTAL),
+];
+
+pub const GRAYSCALE: &[(F, [u8; 3])] = &[(0., [0, 0, 0]), (1., [255, 255, 255])];
+/// The default gradient of Ultra Fractal, its ends are the same
+/// color so that it can be repeated.
+pub const CLASSIC_ULTRA_FRACTAL: &[(F, [u8; 3])] = &[
+    (0., [0, 7, 100]),
+    (0.16, [32, 107, 203]),
+    (0.42, [237, 255, 255]),
+    (0.6425, [255, 170, 0]),
+    (0.8575, [0, 2, 0]),
+    (1., [0, 7, 100]),
+];
+
+// The following gradients are 16 evenly spaced samples of the
+// matplotlib colormaps of the same name.
+pub const VIRIDIS: &[(F, [u8; 3])] = &[
+    (0., [68, 1, 84]),
+    (0.067, [72, 26, 108]),
+    (0.133, [71, 47, 125]),
+    (0.2, [65, 68, 135]),
+    (0.267, [57, 86, 140]),
+    (0.333, [49, 104, 142]),
+    (0.4, [42, 120, 142]),
+    (0.467, [35, 136, 142]),
+    (0.533, [31, 152, 139]),
+    (0.6, [34, 168, 132]),
+    (0.667, [53, 183, 121]),
+    (0.733, [84, 197, 104]),
+    (0.8, [122, 209, 81]),
+    (0.867, [165, 219, 54]),
+    (0.933, [210, 226, 27]),
+    (1., [253, 231, 37]),
+];
+pub const MAGMA: &[(F, [u8; 3])] = &[
+    (0., [0, 0, 4]),
+    (0.067, [11, 9, 36]),
+    (0.133, [32, 17, 75]),
+    (0.2, [59, 15, 112]),
+    (0.267, [87, 21, 126]),
+    (0.333, [114, 31, 129]),
+    (0.4, [140, 41, 129]),
+    (0.467, [168, 50, 125]),
+    (0.533, [196, 60, 117]),
+    (0.6, [222, 73, 104]),
+    (0.667, [241, 96, 93]),
+    (0.733, [250, 127, 94]),
+    (0.8, [254, 159, 109]),
+    (0.867, [254, 191, 132]),
+    (0.933, [253, 222, 160]),
+    (1., [252, 253, 191]),
+];
+pub const INFERNO: &[(F, [u8; 3])] = &[
+    (0., [0, 0, 4]),
+    (0.067, [12, 8, 38]),
+    (0.133, [36, 12, 79]),
+    (0.2, [66, 10, 104]),
+    (0.267, [93, 18, 110]),
+    (0.333, [120, 28, 109]),
+    (0.4, [147, 38, 103]),
+    (0.467, [174, 48, 92]),
+    (0.533, [199, 62, 76]),
+    (0.6, [221, 81, 58]),
+    (0.667, [237, 105, 37]),
+    (0.733, [248, 133, 15]),
+    (0.8, [252, 165, 10]),
+    (0.867, [250, 198, 45]),
+    (0.933, [242, 230, 97]),
+    (1., [252, 255, 164]),
+];
+pub const TURBO: &[(F, [u8; 3])] = &[
+    (0., [48, 18, 59]),
+    (0.067, [65, 67, 167]),
+    (0.133, [71, 113, 233]),
+    (0.2, [62, 155, 254]),
+    (0.267, [27, 199, 225]),
+    (0.333, [26, 228, 182]),
+    (0.4, [70, 248, 132]),
+    (0.467, [127, 254, 83]),
+    (0.533, [177, 249, 54]),
+    (0.6, [216, 227, 53]),
+    (0.667, [246, 194, 58]),
+    (0.733, [254, 155, 45]),
+    (0.8, [245, 105, 24]),
+    (0.867, [219, 58, 7]),
+    (0.933, [177, 25, 1]),
+    (1., [122, 4, 3]),
+];
+
 pub fn color_mapping(t: F, gradient: &[(F, [u8; 3])]) -> Rgb<u8> {
+    Rgb(gradient_color(t, gradient).map(|c| c.clamp(0., 255.) as u8))
+}
+
+/// Continuous (not yet quantized) color of the gradient at `t`.
+/// The gradient must not be empty (see [`validate_gradient`]), a NaN
+/// `t` gives the first color.
+pub fn gradient_color(t: F, gradient: &[(F, [u8; 3])]) -> [F; 3] {
     let first = gradient[0];
     let last = gradient.last().unwrap();
 
-    if t < first.0 {
-        Rgb(first.1)
-    } else if t > last.0 {
-        Rgb(last.1)
+    // Single stop gradients end here as well.
+    if t.is_nan() || t <= first.0 {
+        first.1.map(|c| c as F)
+    } else if t >= last.0 {
+        last.1.map(|c| c as F)
     } else {
         let i = gradient
             .binary_search_by(|&(value, _)| value.total_cmp(&t))
@@ -144,11 +1196,32 @@ pub fn color_mapping(t: F, gradient: &[(F, [u8; 3])]) -> Rgb<u8> {
         let ratio = (t - gradient[i].0) / (gradient[i + 1].0 - gradient[i].0);
         let [r1, g1, b1] = gradient[i].1;
         let [r2, g2, b2] = gradient[i + 1].1;
-        let r = (r1 as F * (1. - ratio) + r2 as F * ratio).clamp(0., 255.) as u8;
-        let g = (g1 as F * (1. - ratio) + g2 as F * ratio).clamp(0., 255.) as u8;
-        let b = (b1 as F * (1. - ratio) + b2 as F * ratio).clamp(0., 255.) as u8;
+        let r = r1 as F * (1. - ratio) + r2 as F * ratio;
+        let g = g1 as F * (1. - ratio) + g2 as F * ratio;
+        let b = b1 as F * (1. - ratio) + b2 as F * ratio;
+
+        [r, g, b]
+    }
+}
+
+/// Color of the gradient at `t` (in range [0,1)) when it is cycled
+/// through: the stops are squeezed to leave room for a last segment
+/// going back to the first color, as long as the average segment.
+fn cyclic_gradient_color(t: F, gradient: &[(F, [u8; 3])]) -> [F; 3] {
+    let (first, last) = (gradient[0], *gradient.last().unwrap());
+    if gradient.len() < 2 || last.0 <= first.0 {
+        return first.1.map(|c| c as F);
+    }
 
-        Rgb([r, g, b])
+    let wrap = (last.0 - first.0) / (gradient.len() - 1) as F;
+    let span = last.0 - first.0 + wrap;
+    let position = first.0 + t * span;
+
+    if position <= last.0 {
+        gradient_color(position, gradient)
+    } else {
+        let ratio = (position - last.0) / wrap;
+        [0, 1, 2].map(|k| last.1[k] as F * (1. - ratio) + first.1[k] as F * ratio)
     }
 }
 
@@ -157,8 +1230,13 @@ pub mod cumulative_histogram {
 
     const HISTOGRAM_SIZE: usize = 1000000;
 
+    /// Index of a normalized value, values out of range (0, 1) going
+    /// to the first or last index and NaNs to the first.
     fn map_f_to_histogram_index(value: F) -> usize {
-        ((value * (HISTOGRAM_SIZE - 1) as F) as usize).min(HISTOGRAM_SIZE - 1)
+        if value.is_nan() {
+            return 0;
+        }
+        ((value.clamp(0., 1.) * (HISTOGRAM_SIZE - 1) as F) as usize).min(HISTOGRAM_SIZE - 1)
     }
 
     /// Compute an histogram from normalized values in range
@@ -180,16 +1258,455 @@ pub mod cumulative_histogram {
         let mut cumulative = vec![0.; HISTOGRAM_SIZE];
         let mut cumulative_sum = 0.;
         for (i, &count) in histogram.iter().enumerate() {
-            cumulative_sum += count as F / total as F;
+            cumulative_sum += count as F / total.max(1) as F;
             cumulative[i] = cumulative_sum;
         }
 
         cumulative
     }
 
+    /// Normalized value (in range (0, 1)) below which the fraction
+    /// `q` of the values of the cumulative histogram lie.
+    pub fn histogram_quantile(q: F, cumulative_histogram: &[F]) -> F {
+        let index = cumulative_histogram
+            .partition_point(|&c| c < q)
+            .min(HISTOGRAM_SIZE - 1);
+        index as F / (HISTOGRAM_SIZE - 1) as F
+    }
+
     /// Get the cumulative histogram value from a normalized value
     /// in range (0, 1).
     pub fn get_histogram_value(value: F, cumulative_histogram: &[F]) -> F {
         cumulative_histogram[map_f_to_histogram_index(value)]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parameters of a one row image whose raw values are mapped
+    /// linearly from [0,100].
+    fn row_params(width: u32) -> FrameParams {
+        FrameParams {
+            img_width: width,
+            img_height: 1,
+            max_iter: 1000,
+            ..Default::default()
+        }
+    }
+
+    /// One row of values increasing linearly from 0 to 100.
+    fn ramp(width: usize) -> Mat2D<F> {
+        Mat2D {
+            vec: (0..width)
+                .map(|i| 100. * i as F / (width - 1) as F)
+                .collect(),
+            width,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn scaled_image_dimensions() {
+        let image = RgbImage::new(101, 50);
+        assert_eq!(scale_image(&image, 0.5).dimensions(), (51, 25));
+        assert_eq!(scale_image(&image, 0.25).dimensions(), (25, 13));
+        assert_eq!(scale_image(&image, 0.001).dimensions(), (1, 1));
+        assert_eq!(scale_image(&image, 2.).dimensions(), (202, 100));
+    }
+
+    #[test]
+    fn downscaling_a_solid_color_is_lossless() {
+        let color = Rgb([200, 30, 117]);
+        let image = RgbImage::from_pixel(64, 48, color);
+        let scaled = scale_image(&image, 0.5);
+        assert!(scaled.pixels().all(|&p| p == color));
+    }
+
+    #[test]
+    fn downscaling_averages_in_linear_light() {
+        // A black and white checkerboard averages to 50% linear light,
+        // which is about 188 in srgb (not 128).
+        let image = RgbImage::from_fn(64, 64, |i, j| {
+            if (i + j) % 2 == 0 {
+                Rgb([255; 3])
+            } else {
+                Rgb([0; 3])
+            }
+        });
+        let scaled = scale_image(&image, 0.5);
+        let center = scaled.get_pixel(16, 16).0[0];
+        assert!((180..=196).contains(&center), "{center}");
+    }
+
+    #[test]
+    fn dithering_keeps_the_average_of_a_ramp() {
+        const WIDTH: usize = 1024;
+        const HEIGHT: usize = 64;
+
+        let column_averages = (0..WIDTH)
+            .map(|i| {
+                let value = 255. * i as F / WIDTH as F;
+                let sum: F = (0..HEIGHT)
+                    .map(|j| dither([value; 3], i, j).0[0] as F)
+                    .sum();
+                (value, sum / HEIGHT as F)
+            })
+            .collect::<Vec<_>>();
+
+        for &(value, average) in &column_averages {
+            assert!(
+                (average - value).abs() < 0.25,
+                "{value} dithered to {average}"
+            );
+        }
+
+        // Without dithering there would be at most 256 levels.
+        let mut levels = column_averages
+            .iter()
+            .map(|&(_, average)| (average * HEIGHT as F).round() as u32)
+            .collect::<Vec<_>>();
+        levels.dedup();
+        assert!(levels.len() > 256, "{} levels", levels.len());
+    }
+
+    #[test]
+    fn dithering_is_reproducible() {
+        let color = [12.3, 45.6, 250.9];
+        for (i, j) in [(0, 0), (5, 7), (1000, 3)] {
+            assert_eq!(dither(color, i, j), dither(color, i, j));
+        }
+        assert_eq!(dither([255.; 3], 3, 4), Rgb([255; 3]));
+        assert_eq!(dither([0.; 3], 3, 4), Rgb([0; 3]));
+    }
+
+    #[test]
+    fn sanitize_gradient_fixes_stops() {
+        let mut gradient = vec![
+            (0.5, [1, 1, 1]),
+            (-0.2, [0, 0, 0]),
+            (0.5, [2, 2, 2]),
+            (1.5, [3, 3, 3]),
+        ];
+        let warnings = sanitize_gradient(&mut gradient);
+
+        assert_eq!(
+            gradient,
+            vec![(0., [0, 0, 0]), (0.5, [1, 1, 1]), (1., [3, 3, 3])]
+        );
+        // Two clamped positions, the sort and the duplicate.
+        assert_eq!(warnings.len(), 4, "{warnings:?}");
+        assert!(validate_gradient(&gradient).is_ok());
+    }
+
+    #[test]
+    fn sanitize_gradient_leaves_valid_gradients_alone() {
+        let mut gradient = vec![(0., [0, 0, 0]), (0.3, [1, 2, 3]), (1., [255; 3])];
+        let original = gradient.clone();
+        assert!(sanitize_gradient(&mut gradient).is_empty());
+        assert_eq!(gradient, original);
+    }
+
+    #[test]
+    fn validate_gradient_rejects_invalid_stops() {
+        assert!(validate_gradient(&[]).is_err());
+        assert!(validate_gradient(&[(1.2, [0; 3])]).is_err());
+        assert!(validate_gradient(&[(0.8, [0; 3]), (0.2, [0; 3])]).is_err());
+        assert!(validate_gradient(&[(0.5, [0; 3])]).is_ok());
+    }
+
+    #[test]
+    fn gradient_stops_accept_hex_colors() {
+        let spec: GradientSpec =
+            ron::from_str(r##"[(0.0, "#ff8000"), (0.5, "0af"), (1.0, (1, 2, 3))]"##).unwrap();
+        assert_eq!(
+            spec,
+            GradientSpec::Custom(vec![
+                (0., [255, 128, 0]),
+                (0.5, [0, 170, 255]),
+                (1., [1, 2, 3])
+            ])
+        );
+
+        for invalid in [
+            r##"[(0.0, "#ff80")]"##,
+            r##"[(0.0, "#gg0000")]"##,
+            r#"[(0.0, "")]"#,
+        ] {
+            assert!(ron::from_str::<GradientSpec>(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn boundary_band_fades_out_across_its_width() {
+        let band = BoundaryBand {
+            width_iters: 10,
+            color: [255, 0, 0],
+            blend: 0.8,
+        };
+        let weights = band.compute_weights(&ramp(101));
+
+        assert_eq!(weights.vec[100], 0.8);
+        assert!((weights.vec[95] - 0.4).abs() < 1e-9);
+        assert!(weights.vec[..=90].iter().all(|&w| w == 0.));
+        assert!(weights.vec[90..].is_sorted());
+    }
+
+    #[test]
+    fn boundary_band_only_colors_the_band() {
+        let mut params = row_params(101);
+        let plain = color_raw_image(&params, ramp(101));
+        params.boundary_band = Some(BoundaryBand {
+            width_iters: 10,
+            color: [255, 0, 0],
+            blend: 1.,
+        });
+        let banded = color_raw_image(&params, ramp(101));
+
+        assert_eq!(banded.get_pixel(100, 0), &Rgb([255, 0, 0]));
+        for i in 0..=90 {
+            assert_eq!(banded.get_pixel(i, 0), plain.get_pixel(i, 0));
+        }
+        for i in 91..100 {
+            assert_ne!(banded.get_pixel(i, 0), plain.get_pixel(i, 0));
+        }
+    }
+
+    #[test]
+    fn percentiles() {
+        // 0, 1, ..., 100 shuffled.
+        let values = (0..=100).map(|k| (k * 37 % 101) as F).collect::<Vec<_>>();
+        assert_eq!(percentile(&values, 0.), 0.);
+        assert_eq!(percentile(&values, 5.), 5.);
+        assert_eq!(percentile(&values, 50.), 50.);
+        assert_eq!(percentile(&values, 99.), 99.);
+        assert_eq!(percentile(&values, 100.), 100.);
+        // Out of range percentiles are clamped.
+        assert_eq!(percentile(&values, -10.), 0.);
+        assert_eq!(percentile(&values, 150.), 100.);
+    }
+
+    #[test]
+    fn percentiles_ignore_non_finite_values() {
+        let values = [F::NAN, 1., F::INFINITY, 3., 2., F::NEG_INFINITY];
+        assert_eq!(percentile(&values, 0.), 1.);
+        assert_eq!(percentile(&values, 100.), 3.);
+        assert_eq!(percentile(&[F::NAN], 50.), 0.);
+        assert_eq!(percentile(&[], 50.), 0.);
+    }
+
+    #[test]
+    fn percentile_extrema_clip_outliers() {
+        let mut values = vec![1.; 1000];
+        values[0] = 1e9;
+        assert_eq!(Extremum::Percentile(99.).resolve(&values, 1e9), 1.);
+        assert_eq!(Extremum::Auto.resolve(&values, 1e9), 1e9);
+        assert_eq!(Extremum::Custom(4.).resolve(&values, 1e9), 4.);
+    }
+
+    /// Half of the pixels at `max_iter` (interior), the other half a
+    /// ramp below it.
+    fn half_interior(params: &FrameParams) -> Mat2D<F> {
+        let width = params.img_width as usize;
+        let max_iter = params.max_iter as F;
+        Mat2D {
+            vec: (0..width)
+                .map(|i| {
+                    if i % 2 == 0 {
+                        max_iter
+                    } else {
+                        max_iter * 0.8 * i as F / width as F
+                    }
+                })
+                .collect(),
+            width,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn histogram_coloring_can_exclude_the_interior() {
+        let histogram = |exclude_interior| {
+            let params = FrameParams {
+                max_iter: 100,
+                coloring_mode: ColoringMode::CumulativeHistogram {
+                    map: MapValue::Linear,
+                    exclude_interior,
+                },
+                ..row_params(200)
+            };
+            let values = normalize_raw_image(&params, half_interior(&params));
+            let ramp = values.vec.iter().skip(1).step_by(2).copied();
+            let (min, max) = ramp.fold((F::MAX, F::MIN), |(min, max), v| (min.min(v), max.max(v)));
+            (values, min, max)
+        };
+
+        // The interior takes the top half of the gradient.
+        let (_, min, max) = histogram(None);
+        assert!(max < 0.55, "{min}..{max}");
+
+        let (values, min, max) = histogram(Some(true));
+        assert!(min < 0.02 && max > 0.98, "{min}..{max}");
+        assert!(values.vec.iter().step_by(2).all(|&v| v == 1.));
+    }
+
+    #[test]
+    fn sanitize_replaces_non_finite_values_by_the_extrema() {
+        let mut raw_image = ramp(5);
+        raw_image.vec.extend([F::NAN, F::INFINITY, F::NEG_INFINITY]);
+        raw_image.width = 8;
+
+        assert_eq!(sanitize_raw_image(&mut raw_image), 3);
+        assert_eq!(&raw_image.vec[5..], &[0., 100., 0.]);
+        assert_eq!(sanitize_raw_image(&mut raw_image), 0);
+    }
+
+    #[test]
+    fn non_finite_values_are_colored_as_the_extrema() {
+        for coloring_mode in [
+            ColoringMode::MinMaxNorm {
+                min: Extremum::Auto,
+                max: Extremum::Auto,
+                map: MapValue::Linear,
+            },
+            ColoringMode::CumulativeHistogram {
+                map: MapValue::Linear,
+                exclude_interior: None,
+            },
+        ] {
+            let params = FrameParams {
+                coloring_mode,
+                ..row_params(8)
+            };
+            let mut raw_image = ramp(5);
+            raw_image.vec.extend([F::NAN, F::INFINITY, F::NEG_INFINITY]);
+            raw_image.width = 8;
+
+            let image = color_raw_image(&params, raw_image);
+            let (min, max) = (*image.get_pixel(0, 0), *image.get_pixel(4, 0));
+            assert_ne!(min, max, "{coloring_mode:?}");
+            assert_eq!(*image.get_pixel(5, 0), min, "{coloring_mode:?}");
+            assert_eq!(*image.get_pixel(6, 0), max, "{coloring_mode:?}");
+            assert_eq!(*image.get_pixel(7, 0), min, "{coloring_mode:?}");
+        }
+    }
+
+    #[test]
+    fn default_tone_curve_is_the_identity() {
+        let tone_curve = ToneCurve::default();
+        for t in [0., 0.1, 0.5, 0.75, 1.] {
+            assert!((tone_curve.apply(t) - t).abs() < 4. * F::EPSILON, "{t}");
+        }
+    }
+
+    #[test]
+    fn tone_curve_clamps_values_and_parameters() {
+        let tone_curve = ToneCurve::default();
+        assert_eq!(tone_curve.apply(-3.), 0.);
+        assert_eq!(tone_curve.apply(7.), 1.);
+        assert_eq!(tone_curve.apply(F::NAN), 0.);
+        assert_eq!(tone_curve.apply(F::INFINITY), 1.);
+        assert_eq!(tone_curve.apply(F::NEG_INFINITY), 0.);
+
+        // Gamma and contrast are clamped to their ranges, and
+        // non-finite parameters fall back to the identity.
+        let clamped = ToneCurve {
+            gamma: 1000.,
+            contrast: -5.,
+            ..Default::default()
+        };
+        let expected = ToneCurve {
+            gamma: ToneCurve::GAMMA_RANGE.1,
+            contrast: ToneCurve::CONTRAST_RANGE.0,
+            ..Default::default()
+        };
+        let non_finite = ToneCurve {
+            gamma: F::NAN,
+            brightness: F::INFINITY,
+            contrast: F::NEG_INFINITY,
+            log: false,
+        };
+        for t in [0., 0.2, 0.5, 0.9, 1.] {
+            assert_eq!(clamped.apply(t), expected.apply(t));
+            assert!((non_finite.apply(t) - t).abs() < 4. * F::EPSILON, "{t}");
+        }
+    }
+
+    #[test]
+    fn tone_curve_stays_in_range() {
+        for gamma in [0., 0.1, 0.5, 2., 10., 50.] {
+            for brightness in [-2., -0.5, 0., 0.5, 2.] {
+                for contrast in [0., 0.5, 3., 20.] {
+                    for log in [false, true] {
+                        let tone_curve = ToneCurve {
+                            gamma,
+                            brightness,
+                            contrast,
+                            log,
+                        };
+                        for k in 0..=20 {
+                            let v = tone_curve.apply(k as F / 20.);
+                            assert!((0. ..=1.).contains(&v), "{tone_curve:?} gives {v}");
+                        }
+                    }
+                }
+            }
+        }
+
+        // The log map keeps the ends in place.
+        let log = ToneCurve {
+            log: true,
+            ..Default::default()
+        };
+        assert_eq!(log.apply(0.), 0.);
+        assert!((log.apply(1.) - 1.).abs() < 1e-12);
+        assert!(log.apply(0.1) > 0.5);
+    }
+
+    #[test]
+    fn striped_distance_estimates_are_colored_like_the_single_render() {
+        use crate::rendering::{render_raw_image, render_raw_image_rows};
+
+        let params = FrameParams {
+            img_width: 40,
+            img_height: 30,
+            max_iter: 100,
+            value: ValueKind::DistanceEstimate,
+            coloring_mode: ColoringMode::MinMaxNorm {
+                min: Extremum::Auto,
+                max: Extremum::Auto,
+                map: MapValue::Linear,
+            },
+            seed: Some(3),
+            ..Default::default()
+        };
+        let sampling_points = params.sampling.generate_sampling_points();
+        let (single, _) = render_raw_image(&params, &sampling_points, None, None);
+        let stripes = [0..7, 7..19, 19..30]
+            .map(|rows| render_raw_image_rows(&params, &sampling_points, rows, None, None).0);
+
+        // The stripes are colored with the extrema of the whole frame
+        // as the cli does.
+        let (min, max) = parts_raw_extrema(&params, stripes.clone());
+        assert_eq!(
+            (min, max),
+            raw_extrema(&map_distance_estimates(&params, single.clone()))
+        );
+        let mut striped = Vec::new();
+        for stripe in stripes {
+            let stripe_params = FrameParams {
+                img_height: stripe.height as u32,
+                coloring_mode: ColoringMode::MinMaxNorm {
+                    min: Extremum::Custom(min),
+                    max: Extremum::Custom(max),
+                    map: MapValue::Linear,
+                },
+                ..params.clone()
+            };
+            striped.extend(color_raw_image(&stripe_params, stripe).into_raw());
+        }
+
+        assert_eq!(striped, color_raw_image(&params, single).into_raw());
+    }
+}
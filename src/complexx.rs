@@ -1,5 +1,7 @@
 use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
 
+use wide::CmpEq;
+
 use crate::{F, FX};
 
 /// A simd complex type. It holds 4 complex numbers and performs
@@ -44,13 +46,6 @@ impl Complexx {
             im: r * theta.sin(),
         }
     }
-    #[inline]
-    pub fn from_polar_splat(r: F, theta: F) -> Complexx {
-        Complexx {
-            re: FX::splat(r * theta.cos()),
-            im: FX::splat(r * theta.sin()),
-        }
-    }
 
     #[inline]
     pub fn norm_sqr(&self) -> FX {
@@ -75,30 +70,112 @@ impl Complexx {
         }
     }
 
+    /// `z^n` by squaring, the result starting from the first power
+    /// of `z` it needs rather than from 1 as multiplying by 1 isn't
+    /// exact (`z^2` is the same as `z * z`).
     pub fn powu(&self, n: usize) -> Complexx {
         if n == 0 {
             return Complexx::splat(1.0, 0.0);
         }
 
-        let mut result = Complexx::splat(1.0, 0.0);
+        let mut result = None;
         let mut base = *self;
 
         let mut exp = n;
-        while exp > 0 {
+        loop {
             if exp % 2 == 1 {
-                result = result * base;
+                result = Some(result.map_or(base, |result| result * base));
             }
-            base = base * base;
             exp /= 2;
+            if exp == 0 {
+                break;
+            }
+            base = base * base;
         }
 
-        result
+        result.unwrap()
     }
 
+    /// Modulus of `z^exp` for `z = 0` and negative exponents, larger
+    /// than any bailout. Moduli of powers are capped to it so that
+    /// they stay finite (including with `force_f32`).
+    pub const POW_MAX_NORM: F = 1e15;
+
+    /// `z^exp` in polar form, with the branch cut on the negative
+    /// real axis whatever the sign of a zero imaginary part. `0^exp`
+    /// is 0 for positive exponents, 1 for `exp = 0` and
+    /// [`Complexx::POW_MAX_NORM`] for negative ones, where the polar
+    /// form would give NaN.
     #[inline]
     pub fn powf(&self, exp: F) -> Complexx {
-        let (r, theta) = self.to_polar();
-        Complexx::from_polar(r.powf(exp), theta * exp)
+        let zero = FX::splat(0.);
+        let zero_mask = self.norm_sqr().cmp_eq(zero);
+
+        // `-0 + 0 = +0`, otherwise points of the negative real axis
+        // get either -π or π depending on how they were computed and
+        // the seam of fractional exponents moves between frames.
+        let theta = (self.im + zero).atan2(self.re);
+        let r = self.norm().powf(exp).min(FX::splat(Self::POW_MAX_NORM));
+        let power = Complexx::from_polar(r, theta * exp);
+
+        if !zero_mask.any() {
+            return power;
+        }
+        let zero_power = if exp > 0. {
+            0.
+        } else if exp == 0. {
+            1.
+        } else {
+            Self::POW_MAX_NORM
+        };
+        Complexx {
+            re: zero_mask.blend(FX::splat(zero_power), power.re),
+            im: zero_mask.blend(zero, power.im),
+        }
+    }
+
+    /// `e^z = e^re * (cos(im) + i sin(im))`
+    #[inline]
+    pub fn exp(&self) -> Complexx {
+        let r = self.re.exp();
+        let (sin, cos) = self.im.sin_cos();
+        Complexx {
+            re: r * cos,
+            im: r * sin,
+        }
+    }
+
+    /// `sin(z) = sin(re) cosh(im) + i cos(re) sinh(im)`
+    #[inline]
+    pub fn sin(&self) -> Complexx {
+        let (sin, cos) = self.re.sin_cos();
+        let (e, e_inv) = (self.im.exp(), (-self.im).exp());
+        let half = FX::splat(0.5);
+        Complexx {
+            re: sin * (e + e_inv) * half,
+            im: cos * (e - e_inv) * half,
+        }
+    }
+
+    /// `cos(z) = cos(re) cosh(im) - i sin(re) sinh(im)`
+    #[inline]
+    pub fn cos(&self) -> Complexx {
+        let (sin, cos) = self.re.sin_cos();
+        let (e, e_inv) = (self.im.exp(), (-self.im).exp());
+        let half = FX::splat(0.5);
+        Complexx {
+            re: cos * (e + e_inv) * half,
+            im: -sin * (e - e_inv) * half,
+        }
+    }
+
+    /// Principal logarithm, `ln|z| + i arg(z)`.
+    #[inline]
+    pub fn ln(&self) -> Complexx {
+        Complexx {
+            re: self.norm_sqr().ln() * FX::splat(0.5),
+            im: self.arg(),
+        }
     }
 
     // #[inline]
@@ -225,3 +302,104 @@ impl Div for Complexx {
         self * rhs.conjugate() / rhs_norm
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::array;
+
+    use super::*;
+
+    /// Lanes taken from `values`, repeated to fill the vector.
+    fn lanes(values: &[(F, F)]) -> Complexx {
+        let re: [F; size_of::<FX>() / size_of::<F>()] =
+            array::from_fn(|k| values[k % values.len()].0);
+        let im = array::from_fn(|k| values[k % values.len()].1);
+        Complexx {
+            re: FX::from(re),
+            im: FX::from(im),
+        }
+    }
+
+    fn values(z: Complexx) -> Vec<(F, F)> {
+        z.re.to_array().into_iter().zip(z.im.to_array()).collect()
+    }
+
+    #[test]
+    fn powf_is_finite_at_and_near_zero() {
+        // The smallest normal numbers of `F` and their square root.
+        let (tiny, small) = (F::MIN_POSITIVE, F::MIN_POSITIVE.sqrt());
+        let z = lanes(&[(0., 0.), (-0., -0.), (small, -small), (-tiny, tiny)]);
+
+        for exp in [-2., 0.5, 2.5] {
+            for (k, (re, im)) in values(z.powf(exp)).into_iter().enumerate() {
+                assert!(re.is_finite() && im.is_finite(), "{exp}: lane {k}");
+                let norm = (re * re + im * im).sqrt();
+                assert!(norm <= Complexx::POW_MAX_NORM, "{exp}: lane {k}");
+                if exp > 0. {
+                    assert!(norm < F::EPSILON, "{exp}: lane {k}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn powf_of_zero() {
+        let z = lanes(&[(0., 0.), (-0., 0.), (0., -0.), (-0., -0.)]);
+        for (exp, expected) in [
+            (2.5, 0.),
+            (0.5, 0.),
+            (0., 1.),
+            (-0.5, Complexx::POW_MAX_NORM),
+            (-2., Complexx::POW_MAX_NORM),
+        ] {
+            for (re, im) in values(z.powf(exp)) {
+                assert_eq!((re, im), (expected, 0.), "{exp}");
+            }
+        }
+    }
+
+    #[test]
+    fn powf_has_a_fixed_branch_cut() {
+        // Both signs of zero give the argument π on the negative real
+        // axis.
+        let z = lanes(&[(-4., 0.), (-4., -0.)]);
+        for exp in [0.5, 2.5, -2.] {
+            let powers = values(z.powf(exp));
+            assert_eq!(powers[0], powers[1], "{exp}");
+
+            let (sin, cos) = (exp * std::f64::consts::PI as F).sin_cos();
+            let r = (4. as F).powf(exp);
+            let (re, im) = powers[0];
+            let tolerance = 64. * F::EPSILON * r;
+            assert!((re - r * cos).abs() < tolerance && (im - r * sin).abs() < tolerance);
+        }
+    }
+
+    #[test]
+    fn powf_agrees_with_integer_powers() {
+        let z = lanes(&[(0.3, -1.2), (-2., 0.5), (1.5, 1.5), (-0.1, -0.7)]);
+        let square = z * z;
+        let norm_sqr = square.norm_sqr();
+        let inverse_square = Complexx {
+            re: square.re / norm_sqr,
+            im: -square.im / norm_sqr,
+        };
+
+        for (power, expected) in [(z.powf(2.), square), (z.powf(-2.), inverse_square)] {
+            for ((re, im), (expected_re, expected_im)) in
+                values(power).into_iter().zip(values(expected))
+            {
+                // Relative to the magnitude of the powers (about 1).
+                let tolerance = 64. * F::EPSILON * expected_re.hypot(expected_im).max(1.);
+                assert!(
+                    (re - expected_re).abs() < tolerance,
+                    "{re} != {expected_re}"
+                );
+                assert!(
+                    (im - expected_im).abs() < tolerance,
+                    "{im} != {expected_im}"
+                );
+            }
+        }
+    }
+}
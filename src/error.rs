@@ -1,28 +1,120 @@
-use std::{fmt::Debug, io};
+use std::{any::Any, fmt::Debug, io};
 
+use image::ImageFormat;
 use ron::de::SpannedError;
 
 pub type Result<T> = std::result::Result<T, ErrorKind>;
 
 pub enum ErrorKind {
     MissingCliArg,
+    InvalidCliArg(String),
     ReadParameterFile(io::Error),
     WriteParameterFile(io::Error),
     DecodeParameterFile(SpannedError),
     EncodeParameterFile(ron::Error),
+    DecodeJsonParameterFile(serde_json::Error),
+    EncodeJsonParameterFile(serde_json::Error),
+    ReadGradientFile(io::Error),
+    DecodeGradientFile(String),
+    InvalidGradient(String),
     SaveImage(image::ImageError),
-    StartGui,
+    SaveRawImage(exr::error::Error),
+    EncodePng(png::EncodingError),
+    ReadImage(io::Error),
+    DecodePng(png::DecodingError),
+    MissingEmbeddedParams,
+    StripedRendering(String),
+    PosterRendering(String),
+    WritePosterManifest(io::Error),
+    ZoomAnimation(String),
+    RenderBand(String),
+    ReadBand(io::Error),
+    WriteBand(io::Error),
+    /// Path of the band file and reason.
+    DecodeBand(String, String),
+    MergeBands(String),
+    StartGui(String),
+    MissingDisplay,
+    AnimationInGui,
+    FrameOnlyOption(String),
+    WriteCrashLog(io::Error),
+    ReadManifest(io::Error),
+    WriteManifest(io::Error),
+    DecodeManifest(SpannedError),
+    /// Path of the frame values file and reason.
+    DecodeFrameValues(String, String),
+    /// Number of frames which don't match the manifest.
+    VerifyFrames(usize),
+    FrameHashing(String),
+    Bench(String),
+    EncodeGif(String),
+    ReadAutosave(io::Error),
+    WriteAutosave(io::Error),
+    /// Path of the autosave file and reason.
+    DecodeAutosave(String, String),
+    FfmpegNotFound,
+    Ffmpeg(String),
+    MissingConfigDir,
+    InvalidPresetName(String),
+    ReadPreset(io::Error),
+    WritePreset(io::Error),
+    /// Path of the preset file and reason.
+    DecodePreset(String, String),
+    Resume(String),
+    InvalidParams(String),
+    Watch(String),
+    ReadBookmarks(io::Error),
+    WriteBookmarks(io::Error),
+    DecodeBookmarks(serde_json::Error),
+    ReadWorkspace(io::Error),
+    WriteWorkspace(io::Error),
+    /// Path of the workspace file and reason.
+    DecodeWorkspace(String, String),
+    ReadSettings(io::Error),
+    WriteSettings(io::Error),
+    DecodeSettings(SpannedError),
+    Batch(String),
+    /// Number of parameter files that failed to render.
+    BatchFailures(usize),
+    Region(String),
+    /// Message of the panic.
+    RenderPanicked(String),
+    Interrupted,
+    Sweep(String),
+    Diff(String),
+    /// Problems found in the steps of the animated values.
+    AnimationSteps(Vec<String>),
+    /// Extension of the output path.
+    UnsupportedImageFormat(String),
 }
 
+/// Text of a panic payload, which is a string unless the panic was
+/// raised with [`std::panic::panic_any`].
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.to_owned()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Printed when the gui can't be started.
+const GUI_HINT: &str = "Use '--no-gui' to render without a window. Without a working OpenGL driver, installing Mesa (or setting LIBGL_ALWAYS_SOFTWARE=1 to use its software renderer) or trying '--gui-backend wgpu' may help.";
+
 impl Debug for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ErrorKind::MissingCliArg => {
                 writeln!(
                     f,
-                    "Parameter file and output image paths are required when using '--no-gui'"
+                    "Parameter file and output image paths (or '--stdin' and '--stdout') are required when using '--no-gui'"
                 )
             }
+            ErrorKind::InvalidCliArg(arg) => {
+                writeln!(f, "Invalid value for '--{}'", arg)
+            }
             ErrorKind::ReadParameterFile(e) => {
                 writeln!(f, "Failed to read parameter file: {}", e)
             }
@@ -35,11 +127,233 @@ impl Debug for ErrorKind {
             ErrorKind::EncodeParameterFile(e) => {
                 writeln!(f, "Failed to encode parameter file: {}", e)
             }
+            ErrorKind::DecodeJsonParameterFile(e) => {
+                writeln!(f, "Failed to decode parameter file: {}", e)
+            }
+            ErrorKind::EncodeJsonParameterFile(e) => {
+                writeln!(f, "Failed to encode parameter file: {}", e)
+            }
+            ErrorKind::ReadGradientFile(e) => {
+                writeln!(f, "Failed to read gradient file: {}", e)
+            }
+            ErrorKind::DecodeGradientFile(e) => {
+                writeln!(f, "Failed to decode gradient file: {}", e)
+            }
+            ErrorKind::InvalidGradient(reason) => {
+                writeln!(f, "Invalid gradient: {}", reason)
+            }
             ErrorKind::SaveImage(e) => {
                 writeln!(f, "Failed to save image: {}", e)
             }
-            ErrorKind::StartGui => {
-                writeln!(f, "Failed to start gui")
+            ErrorKind::SaveRawImage(e) => {
+                writeln!(f, "Failed to save raw image: {}", e)
+            }
+            ErrorKind::EncodePng(e) => {
+                writeln!(f, "Failed to save image: {}", e)
+            }
+            ErrorKind::ReadImage(e) => {
+                writeln!(f, "Failed to read image: {}", e)
+            }
+            ErrorKind::DecodePng(e) => {
+                writeln!(f, "Failed to decode png image: {}", e)
+            }
+            ErrorKind::MissingEmbeddedParams => {
+                writeln!(f, "No parameters are embedded in this image")
+            }
+            ErrorKind::StripedRendering(feature) => {
+                writeln!(f, "Striped rendering does not support {}", feature)
+            }
+            ErrorKind::PosterRendering(feature) => {
+                writeln!(f, "Poster rendering does not support {}", feature)
+            }
+            ErrorKind::WritePosterManifest(e) => {
+                writeln!(f, "Failed to write poster manifest: {}", e)
+            }
+            ErrorKind::ZoomAnimation(reason) => {
+                writeln!(f, "Cannot export a zoom animation: {}", reason)
+            }
+            ErrorKind::RenderBand(reason) => {
+                writeln!(f, "Cannot render a band: {}", reason)
+            }
+            ErrorKind::ReadBand(e) => {
+                writeln!(f, "Failed to read band file: {}", e)
+            }
+            ErrorKind::WriteBand(e) => {
+                writeln!(f, "Failed to write band file: {}", e)
+            }
+            ErrorKind::DecodeBand(path, reason) => {
+                writeln!(f, "Failed to decode band file '{}': {}", path, reason)
+            }
+            ErrorKind::MergeBands(reason) => {
+                writeln!(f, "Cannot merge bands: {}", reason)
+            }
+            ErrorKind::StartGui(e) => {
+                writeln!(f, "Failed to start gui: {}", e)?;
+                writeln!(f, "{}", GUI_HINT)
+            }
+            ErrorKind::MissingDisplay => {
+                writeln!(
+                    f,
+                    "Failed to start gui: no display found (neither DISPLAY nor WAYLAND_DISPLAY is set)"
+                )?;
+                writeln!(f, "Use '--no-gui' to render without a window.")
+            }
+            ErrorKind::AnimationInGui => {
+                writeln!(
+                    f,
+                    "Animations can't be opened in the gui, use '--no-gui' to render them"
+                )
+            }
+            ErrorKind::FrameOnlyOption(option) => {
+                writeln!(
+                    f,
+                    "'--{}' only applies to frames, not to animations",
+                    option
+                )
+            }
+            ErrorKind::WriteCrashLog(e) => {
+                writeln!(f, "Failed to write crash log: {}", e)
+            }
+            ErrorKind::ReadManifest(e) => {
+                writeln!(f, "Failed to read frame manifest: {}", e)
+            }
+            ErrorKind::WriteManifest(e) => {
+                writeln!(f, "Failed to write frame manifest: {}", e)
+            }
+            ErrorKind::DecodeManifest(e) => {
+                writeln!(f, "Failed to decode frame manifest: {}", e)
+            }
+            ErrorKind::DecodeFrameValues(path, reason) => {
+                writeln!(
+                    f,
+                    "Failed to decode frame values file '{}': {}",
+                    path, reason
+                )
+            }
+            ErrorKind::VerifyFrames(count) => {
+                writeln!(f, "{} frame(s) don't match the manifest", count)
+            }
+            ErrorKind::FrameHashing(feature) => {
+                writeln!(f, "Frame hashing does not support {}", feature)
+            }
+            ErrorKind::Bench(reason) => {
+                writeln!(f, "Cannot run the benchmark: {}", reason)
+            }
+            ErrorKind::EncodeGif(e) => {
+                writeln!(f, "Failed to encode gif: {}", e)
+            }
+            ErrorKind::ReadAutosave(e) => {
+                writeln!(f, "Failed to read autosave file: {}", e)
+            }
+            ErrorKind::WriteAutosave(e) => {
+                writeln!(f, "Failed to write autosave file: {}", e)
+            }
+            ErrorKind::DecodeAutosave(path, reason) => {
+                writeln!(f, "Failed to decode autosave file '{}': {}", path, reason)
+            }
+            ErrorKind::FfmpegNotFound => {
+                writeln!(
+                    f,
+                    "ffmpeg is required to render videos but was not found, install it or render the frames as images"
+                )
+            }
+            ErrorKind::Ffmpeg(e) => {
+                writeln!(f, "Failed to encode video with ffmpeg: {}", e)
+            }
+            ErrorKind::MissingConfigDir => {
+                writeln!(
+                    f,
+                    "No config directory found (neither XDG_CONFIG_HOME, HOME nor APPDATA is set)"
+                )
+            }
+            ErrorKind::InvalidPresetName(name) => {
+                writeln!(f, "Invalid preset name '{}'", name)
+            }
+            ErrorKind::ReadPreset(e) => {
+                writeln!(f, "Failed to read user presets: {}", e)
+            }
+            ErrorKind::WritePreset(e) => {
+                writeln!(f, "Failed to write preset: {}", e)
+            }
+            ErrorKind::DecodePreset(path, reason) => {
+                writeln!(f, "Skipped preset '{}': {}", path, reason)
+            }
+            ErrorKind::Resume(reason) => {
+                writeln!(f, "Cannot resume the animation: {}", reason)
+            }
+            ErrorKind::InvalidParams(reason) => {
+                writeln!(f, "Invalid parameters: {}", reason)
+            }
+            ErrorKind::Watch(reason) => {
+                writeln!(f, "Cannot watch the parameter file: {}", reason)
+            }
+            ErrorKind::ReadBookmarks(e) => {
+                writeln!(f, "Failed to read bookmarks: {}", e)
+            }
+            ErrorKind::WriteBookmarks(e) => {
+                writeln!(f, "Failed to write bookmarks: {}", e)
+            }
+            ErrorKind::DecodeBookmarks(e) => {
+                writeln!(f, "Failed to decode bookmarks: {}", e)
+            }
+            ErrorKind::ReadWorkspace(e) => {
+                writeln!(f, "Failed to read workspace file: {}", e)
+            }
+            ErrorKind::WriteWorkspace(e) => {
+                writeln!(f, "Failed to write workspace file: {}", e)
+            }
+            ErrorKind::DecodeWorkspace(path, reason) => {
+                writeln!(f, "Failed to decode workspace file '{}': {}", path, reason)
+            }
+            ErrorKind::ReadSettings(e) => {
+                writeln!(f, "Failed to read settings: {}", e)
+            }
+            ErrorKind::WriteSettings(e) => {
+                writeln!(f, "Failed to write settings: {}", e)
+            }
+            ErrorKind::DecodeSettings(e) => {
+                writeln!(f, "Failed to decode settings: {}", e)
+            }
+            ErrorKind::Batch(reason) => {
+                writeln!(f, "Cannot render the batch: {}", reason)
+            }
+            ErrorKind::BatchFailures(count) => {
+                writeln!(f, "{} parameter file(s) failed to render", count)
+            }
+            ErrorKind::Region(reason) => {
+                writeln!(f, "Cannot render the region: {}", reason)
+            }
+            ErrorKind::RenderPanicked(message) => {
+                writeln!(f, "The render thread panicked: {}", message)
+            }
+            ErrorKind::Interrupted => {
+                writeln!(f, "The render was interrupted before its first pass ended")
+            }
+            ErrorKind::Sweep(reason) => {
+                writeln!(f, "Cannot render the sweep: {}", reason)
+            }
+            ErrorKind::Diff(reason) => {
+                writeln!(f, "Cannot compare the images: {}", reason)
+            }
+            ErrorKind::AnimationSteps(problems) => {
+                writeln!(f, "Invalid animation steps:")?;
+                for problem in problems {
+                    writeln!(f, "  {}", problem)?;
+                }
+                Ok(())
+            }
+            ErrorKind::UnsupportedImageFormat(extension) => {
+                let supported = ImageFormat::all()
+                    .filter(|format| format.writing_enabled())
+                    .flat_map(|format| format.extensions_str())
+                    .copied()
+                    .collect::<Vec<_>>();
+                writeln!(
+                    f,
+                    "Unsupported output image format '{}', the supported extensions are {} (and gif and mp4 for animations)",
+                    extension,
+                    supported.join(", ")
+                )
             }
         }
     }
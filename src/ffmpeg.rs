@@ -0,0 +1,98 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use image::RgbImage;
+
+use crate::{
+    error::{ErrorKind, Result},
+    F,
+};
+
+/// Encoding arguments used when none are given: H.264 with a pixel
+/// format most players support, which needs even dimensions.
+const DEFAULT_ARGS: &[&str] = &[
+    "-c:v",
+    "libx264",
+    "-pix_fmt",
+    "yuv420p",
+    "-vf",
+    "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+];
+
+/// Encodes the frames of an animation to a video by writing them as
+/// raw rgb to the standard input of ffmpeg.
+pub struct FfmpegWriter {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+}
+
+impl FfmpegWriter {
+    /// Starts ffmpeg, the size of the video comes from the first
+    /// frame. `args` replace the default encoding arguments.
+    pub fn spawn(path: &Path, first_frame: &RgbImage, fps: F, args: Option<&str>) -> Result<Self> {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .arg("-s")
+            .arg(format!("{}x{}", first_frame.width(), first_frame.height()))
+            .arg("-r")
+            .arg(fps.to_string())
+            .args(["-i", "-"]);
+        match args {
+            Some(args) => command.args(args.split_whitespace()),
+            None => command.args(DEFAULT_ARGS),
+        };
+        command
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null());
+
+        let mut child = command.spawn().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => ErrorKind::FfmpegNotFound,
+            _ => ErrorKind::Ffmpeg(e.to_string()),
+        })?;
+        let stdin = BufWriter::new(child.stdin.take().unwrap());
+
+        Ok(FfmpegWriter { child, stdin })
+    }
+
+    /// Adds a frame, of the same size as the first one.
+    pub fn add_frame(&mut self, image: &RgbImage) -> Result<()> {
+        // A broken pipe means that ffmpeg stopped, its own error is
+        // more useful.
+        if let Err(e) = self.stdin.write_all(image.as_raw()) {
+            return Err(wait(&mut self.child)
+                .err()
+                .unwrap_or(ErrorKind::Ffmpeg(e.to_string())));
+        }
+        Ok(())
+    }
+
+    /// Closes the input of ffmpeg and waits for it to write the end
+    /// of the video.
+    pub fn finish(self) -> Result<()> {
+        let FfmpegWriter {
+            mut child,
+            mut stdin,
+        } = self;
+        stdin
+            .flush()
+            .map_err(|e| ErrorKind::Ffmpeg(e.to_string()))?;
+        drop(stdin);
+        wait(&mut child)
+    }
+}
+
+fn wait(child: &mut Child) -> Result<()> {
+    match child.wait() {
+        Ok(status) if !status.success() => {
+            Err(ErrorKind::Ffmpeg(format!("ffmpeg exited with {}", status)))
+        }
+        Ok(_) => Ok(()),
+        Err(e) => Err(ErrorKind::Ffmpeg(e.to_string())),
+    }
+}
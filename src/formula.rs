@@ -0,0 +1,572 @@
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{complexx::Complexx, F, FX};
+
+/// Iteration of [`crate::fractal::Fractal::Custom`] typed as text,
+/// such as `z = z^2 + c` or `z = sin(z) + c*z`. The orbit starts at
+/// `z = 0` and `c` is the point sampled.
+///
+/// The grammar has the variables `z` and `c`, real constants
+/// (`1.5`), imaginary ones (`0.5i`, `i`), the operators `+ - * / ^`
+/// and the functions `sin`, `exp`, `conj` and `abs` (the modulus).
+/// The `z =` prefix is optional.
+#[derive(Clone)]
+pub struct Formula {
+    source: String,
+    expr: Expr,
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Formula, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            depth: 0,
+        };
+        let expr = parser.formula()?;
+
+        Ok(Formula {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Next point of the orbits `z` of the points `c`.
+    #[inline]
+    pub fn step(&self, z: Complexx, c: Complexx) -> Complexx {
+        self.expr.eval(z, c)
+    }
+}
+
+impl Default for Formula {
+    fn default() -> Self {
+        Formula::parse("z^2 + c").unwrap()
+    }
+}
+
+impl PartialEq for Formula {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Debug for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+impl Serialize for Formula {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Formula {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        Formula::parse(&source).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sin,
+    Exp,
+    Conj,
+    Abs,
+}
+
+impl Function {
+    const ALL: [(&'static str, Function); 4] = [
+        ("sin", Function::Sin),
+        ("exp", Function::Exp),
+        ("conj", Function::Conj),
+        ("abs", Function::Abs),
+    ];
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Z,
+    C,
+    /// Complex constant (re, im).
+    Const(F, F),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    /// Power with a constant integer exponent, computed by
+    /// multiplications.
+    PowInt(Box<Expr>, i32),
+    /// Power with a constant real exponent.
+    PowReal(Box<Expr>, F),
+    /// Power with any exponent, `e^(b ln(a))`.
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, z: Complexx, c: Complexx) -> Complexx {
+        match self {
+            Expr::Z => z,
+            Expr::C => c,
+            &Expr::Const(re, im) => Complexx::splat(re, im),
+            Expr::Neg(a) => -a.eval(z, c),
+            Expr::Add(a, b) => a.eval(z, c) + b.eval(z, c),
+            Expr::Sub(a, b) => a.eval(z, c) - b.eval(z, c),
+            Expr::Mul(a, b) => a.eval(z, c) * b.eval(z, c),
+            Expr::Div(a, b) => a.eval(z, c) * reciprocal(b.eval(z, c)),
+            &Expr::PowInt(ref a, n) => {
+                let p = a.eval(z, c).powu(n.unsigned_abs() as usize);
+                if n < 0 {
+                    reciprocal(p)
+                } else {
+                    p
+                }
+            }
+            &Expr::PowReal(ref a, exp) => a.eval(z, c).powf(exp),
+            Expr::Pow(a, b) => (b.eval(z, c) * a.eval(z, c).ln()).exp(),
+            &Expr::Call(function, ref a) => {
+                let a = a.eval(z, c);
+                match function {
+                    Function::Sin => a.sin(),
+                    Function::Exp => a.exp(),
+                    Function::Conj => a.conjugate(),
+                    Function::Abs => Complexx {
+                        re: a.norm(),
+                        im: FX::splat(0.),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Value of constant expressions, used to pick how powers are
+    /// computed.
+    fn constant(&self) -> Option<(F, F)> {
+        match self {
+            &Expr::Const(re, im) => Some((re, im)),
+            Expr::Neg(a) => a.constant().map(|(re, im)| (-re, -im)),
+            _ => None,
+        }
+    }
+}
+
+/// `1/z = conj(z) / |z|²`
+#[inline]
+fn reciprocal(z: Complexx) -> Complexx {
+    z.conjugate() / z.norm_sqr()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(F),
+    Imaginary(F),
+    Ident(String),
+    Symbol(char),
+}
+
+/// Splits the formula into tokens along with their position (in
+/// characters, from 1) for the error messages.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+
+    let mut k = 0;
+    while k < chars.len() {
+        let start = k;
+        match chars[k] {
+            c if c.is_whitespace() => k += 1,
+            c if c.is_ascii_digit() || c == '.' => {
+                while k < chars.len() && (chars[k].is_ascii_digit() || chars[k] == '.') {
+                    k += 1;
+                }
+                let text = chars[start..k].iter().collect::<String>();
+                let value = text
+                    .parse::<F>()
+                    .map_err(|_| format!("invalid number \"{}\" at {}", text, start + 1))?;
+                // `0.5i` is an imaginary constant.
+                let is_imaginary = chars.get(k) == Some(&'i')
+                    && !chars.get(k + 1).is_some_and(|c| c.is_alphanumeric());
+                if is_imaginary {
+                    k += 1;
+                    tokens.push((start + 1, Token::Imaginary(value)));
+                } else {
+                    tokens.push((start + 1, Token::Number(value)));
+                }
+            }
+            c if c.is_alphabetic() => {
+                while k < chars.len() && chars[k].is_alphanumeric() {
+                    k += 1;
+                }
+                let name = chars[start..k].iter().collect::<String>();
+                tokens.push((start + 1, Token::Ident(name.to_lowercase())));
+            }
+            c @ ('+' | '-' | '*' | '/' | '^' | '(' | ')' | '=') => {
+                k += 1;
+                tokens.push((start + 1, Token::Symbol(c)));
+            }
+            c => return Err(format!("unexpected '{}' at {}", c, start + 1)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Maximum depth of the expression tree and of the recursion of the
+/// parser, so that deeply nested formulas are rejected instead of
+/// overflowing the stack when they are parsed or evaluated.
+const MAX_DEPTH: usize = 100;
+
+/// Expression along with the depth of its tree.
+type Node = (Expr, usize);
+
+/// Recursive descent parser, from the lowest precedence (sums) to the
+/// highest (powers, which are right associative).
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    position: usize,
+    /// Number of nested expressions being parsed.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.position += 1;
+        self.tokens.get(self.position - 1).map(|(_, token)| token)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// Describes where the current token is, for error messages.
+    fn location(&self) -> String {
+        match self.tokens.get(self.position) {
+            Some((at, _)) => format!("at {}", at),
+            None => "at the end".to_string(),
+        }
+    }
+
+    fn too_deep(&self) -> String {
+        format!(
+            "the formula is nested too deeply {} (at most {} levels)",
+            self.location(),
+            MAX_DEPTH
+        )
+    }
+
+    /// Checks the depth of a new node of the expression tree.
+    fn node(&self, expr: Expr, depth: usize) -> Result<Node, String> {
+        if depth > MAX_DEPTH {
+            Err(self.too_deep())
+        } else {
+            Ok((expr, depth))
+        }
+    }
+
+    /// Parses a nested expression (in parentheses, after a sign or
+    /// as an exponent), every recursion of the parser going through
+    /// here.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Node, String>,
+    ) -> Result<Node, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+        self.depth += 1;
+        let node = parse(self);
+        self.depth -= 1;
+        node
+    }
+
+    fn formula(&mut self) -> Result<Expr, String> {
+        if self.tokens.get(1).map(|(_, token)| token) == Some(&Token::Symbol('=')) {
+            if self.peek() != Some(&Token::Ident("z".to_string())) {
+                return Err("only z can be assigned".to_string());
+            }
+            self.position += 2;
+        }
+
+        let (expr, _) = self.sum()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(_) => Err(format!(
+                "unexpected {} {}",
+                self.describe(),
+                self.location()
+            )),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let (mut expr, mut depth) = self.product()?;
+        loop {
+            let operation: fn(Box<Expr>, Box<Expr>) -> Expr = if self.eat('+') {
+                Expr::Add
+            } else if self.eat('-') {
+                Expr::Sub
+            } else {
+                return Ok((expr, depth));
+            };
+            let (rhs, rhs_depth) = self.product()?;
+            (expr, depth) = self.node(
+                operation(Box::new(expr), Box::new(rhs)),
+                1 + depth.max(rhs_depth),
+            )?;
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let (mut expr, mut depth) = self.unary()?;
+        loop {
+            let operation: fn(Box<Expr>, Box<Expr>) -> Expr = if self.eat('*') {
+                Expr::Mul
+            } else if self.eat('/') {
+                Expr::Div
+            } else {
+                return Ok((expr, depth));
+            };
+            let (rhs, rhs_depth) = self.unary()?;
+            (expr, depth) = self.node(
+                operation(Box::new(expr), Box::new(rhs)),
+                1 + depth.max(rhs_depth),
+            )?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat('-') {
+            let (expr, depth) = self.nested(Self::unary)?;
+            self.node(Expr::Neg(Box::new(expr)), depth + 1)
+        } else if self.eat('+') {
+            self.nested(Self::unary)
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, String> {
+        let (base, base_depth) = self.atom()?;
+        if !self.eat('^') {
+            return Ok((base, base_depth));
+        }
+
+        let (exponent, exponent_depth) = self.nested(Self::unary)?;
+        match exponent.constant() {
+            Some((exp, 0.)) if exp.fract() == 0. && exp.abs() <= 64. => {
+                self.node(Expr::PowInt(Box::new(base), exp as i32), base_depth + 1)
+            }
+            Some((exp, 0.)) => self.node(Expr::PowReal(Box::new(base), exp), base_depth + 1),
+            _ => self.node(
+                Expr::Pow(Box::new(base), Box::new(exponent)),
+                1 + base_depth.max(exponent_depth),
+            ),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let location = self.location();
+        match self.next().cloned() {
+            Some(Token::Number(value)) => Ok((Expr::Const(value, 0.), 1)),
+            Some(Token::Imaginary(value)) => Ok((Expr::Const(0., value), 1)),
+            Some(Token::Symbol('(')) => {
+                let node = self.nested(Self::sum)?;
+                if self.eat(')') {
+                    Ok(node)
+                } else {
+                    Err(format!("expected ')' {}", self.location()))
+                }
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "z" => Ok((Expr::Z, 1)),
+                "c" => Ok((Expr::C, 1)),
+                "i" => Ok((Expr::Const(0., 1.), 1)),
+                _ => {
+                    let Some(&(_, function)) = Function::ALL.iter().find(|(n, _)| *n == name)
+                    else {
+                        return Err(format!(
+                            "unknown name \"{}\" {}, the variables are z and c and the functions are {}",
+                            name,
+                            location,
+                            Function::ALL.map(|(n, _)| n).join(", ")
+                        ));
+                    };
+                    if !self.eat('(') {
+                        return Err(format!("expected '(' after {} {}", name, self.location()));
+                    }
+                    let (argument, depth) = self.nested(Self::sum)?;
+                    if !self.eat(')') {
+                        return Err(format!("expected ')' {}", self.location()));
+                    }
+                    self.node(Expr::Call(function, Box::new(argument)), depth + 1)
+                }
+            },
+            Some(Token::Symbol(c)) => Err(format!("unexpected '{}' {}", c, location)),
+            None => Err("unexpected end of the formula".to_string()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(Token::Number(v)) => format!("number {}", v),
+            Some(Token::Imaginary(v)) => format!("number {}i", v),
+            Some(Token::Ident(name)) => format!("\"{}\"", name),
+            Some(Token::Symbol(c)) => format!("'{}'", c),
+            None => "end".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value of the formula at `z` and `c`.
+    fn eval(source: &str, z: (F, F), c: (F, F)) -> (F, F) {
+        let formula = Formula::parse(source).unwrap();
+        let w = formula.step(Complexx::splat(z.0, z.1), Complexx::splat(c.0, c.1));
+        (w.re.to_array()[0], w.im.to_array()[0])
+    }
+
+    fn assert_close(a: (F, F), b: (F, F)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn squares_iterate_like_the_mandelbrot() {
+        for source in ["z^2 + c", "z*z + c", "z = z^2 + c"] {
+            let formula = Formula::parse(source).unwrap();
+            for (re, im) in [(-0.75, 0.1), (0.3, 0.5), (-1.9, 0.), (0.26, 0.)] {
+                let c = Complexx::splat(re, im);
+                let (mut z, mut w) = (Complexx::zeros(), Complexx::zeros());
+                for _ in 0..50 {
+                    // Same step as `Fractal::Mandelbrot`.
+                    z = z * z + c;
+                    w = formula.step(w, c);
+                    assert_eq!(z.re.to_array(), w.re.to_array(), "{source}");
+                    assert_eq!(z.im.to_array(), w.im.to_array(), "{source}");
+                    if z.norm_sqr().to_array()[0] > 4. {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn operators_have_their_precedence() {
+        let z = (1., 1.);
+        // `-z^2` is `-(z^2)`, `z^2 = 2i`.
+        assert_close(eval("-z^2", z, (0., 0.)), (0., -2.));
+        assert_close(eval("(-z)^2", z, (0., 0.)), (0., 2.));
+        assert_close(eval("2^-1", z, (0., 0.)), (0.5, 0.));
+        assert_close(eval("2^3^2", z, (0., 0.)), (512., 0.));
+        assert_close(eval("2*3^2", z, (0., 0.)), (18., 0.));
+        assert_close(eval("1 - 2 - 3", z, (0., 0.)), (-4., 0.));
+        assert_close(eval("8 / 4 / 2", z, (0., 0.)), (1., 0.));
+        assert_close(eval("1 + 2*3", z, (0., 0.)), (7., 0.));
+        assert_close(eval("--z", z, (0., 0.)), z);
+        assert_close(eval("+z - c", z, (0.5, 2.)), (0.5, -1.));
+    }
+
+    #[test]
+    fn imaginary_literals_and_assignment() {
+        assert_close(eval("i", (0., 0.), (0., 0.)), (0., 1.));
+        assert_close(eval("0.5i", (0., 0.), (0., 0.)), (0., 0.5));
+        assert_close(eval("2 + 3i", (0., 0.), (0., 0.)), (2., 3.));
+        assert_close(eval("i*i", (0., 0.), (0., 0.)), (-1., 0.));
+        assert_close(eval("z*i + c", (1., 2.), (0.5, 0.)), (-1.5, 1.));
+
+        let z = (0.3, -0.2);
+        let c = (0.1, 0.7);
+        assert_close(eval("z = z^3 + c*z", z, c), eval("z^3 + c*z", z, c));
+        assert_close(eval("Z = SIN(Z) + C", z, c), eval("sin(z) + c", z, c));
+    }
+
+    #[test]
+    fn powers_are_computed_by_their_exponent() {
+        let expr = |source| Formula::parse(source).unwrap().expr;
+
+        assert!(matches!(expr("z^2"), Expr::PowInt(_, 2)));
+        assert!(matches!(expr("z^-3"), Expr::PowInt(_, -3)));
+        assert!(matches!(expr("z^(64)"), Expr::PowInt(_, 64)));
+        assert!(matches!(expr("z^2.5"), Expr::PowReal(_, exp) if exp == 2.5));
+        assert!(matches!(expr("z^-0.5"), Expr::PowReal(_, exp) if exp == -0.5));
+        assert!(matches!(expr("z^65"), Expr::PowReal(_, exp) if exp == 65.));
+        assert!(matches!(expr("z^i"), Expr::Pow(..)));
+        assert!(matches!(expr("z^c"), Expr::Pow(..)));
+        assert!(matches!(expr("z^(1 + 1)"), Expr::Pow(..)));
+
+        // The three give the same powers.
+        let z = (0.6, 0.8);
+        assert_close(eval("z^3", z, (3., 0.)), eval("z^c", z, (3., 0.)));
+        assert_close(eval("z^3.0", z, (0., 0.)), eval("z^(1 + 2)", z, (0., 0.)));
+        assert_close(eval("z^-2", z, (0., 0.)), eval("1 / (z*z)", z, (0., 0.)));
+    }
+
+    #[test]
+    fn errors_say_what_is_wrong() {
+        let error = |source| Formula::parse(source).err().unwrap();
+
+        assert!(
+            error("z^2 + x").starts_with("unknown name \"x\" at 7, the variables are z and c"),
+            "{}",
+            error("z^2 + x")
+        );
+        assert_eq!(error("sin z"), "expected '(' after sin at 5");
+        assert_eq!(error("z ="), "unexpected end of the formula");
+        assert_eq!(error("c = z"), "only z can be assigned");
+        assert_eq!(error("z^2 + c)"), "unexpected ')' at 8");
+        assert_eq!(error("z # c"), "unexpected '#' at 3");
+    }
+
+    #[test]
+    fn nested_formulas_parse() {
+        let nested = format!("{}z{}^2 + c", "(".repeat(40), ")".repeat(40));
+        assert!(Formula::parse(&nested).is_ok());
+        assert!(Formula::parse(&format!("{}z", "-".repeat(40))).is_ok());
+        assert!(Formula::parse(&format!("z{}", "+c".repeat(40))).is_ok());
+    }
+
+    #[test]
+    fn deeply_nested_formulas_are_rejected() {
+        for source in [
+            format!("{}z{}", "(".repeat(10_000), ")".repeat(10_000)),
+            "(".repeat(100_000),
+            format!("{}z", "-".repeat(100_000)),
+            format!("{}z", "+".repeat(100_000)),
+            format!("z{}", "+c".repeat(10_000)),
+            format!("z{}", "*c".repeat(10_000)),
+            format!("z{}", "^z".repeat(10_000)),
+            format!("{}z{}", "sin(".repeat(10_000), ")".repeat(10_000)),
+        ] {
+            let error = Formula::parse(&source).err().unwrap();
+            assert!(
+                error.starts_with("the formula is nested too deeply"),
+                "{error}"
+            );
+        }
+    }
+}
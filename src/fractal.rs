@@ -1,9 +1,15 @@
+use std::array;
+
 use serde::{Deserialize, Serialize};
-use wide::CmpLe;
+use wide::{CmpGt, CmpLe, CmpLt};
+
+use crate::{complexx::Complexx, formula::Formula, F, FX};
 
-use crate::{complexx::Complexx, F, FX};
+/// Number of coefficients of the Newton polynomial (so its maximum
+/// degree is `NEWTON_COEFFICIENT_COUNT - 1`).
+pub const NEWTON_COEFFICIENT_COUNT: usize = 6;
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Fractal {
     Mandelbrot,
     MandelbrotCustomExp {
@@ -54,27 +60,339 @@ pub enum Fractal {
         gamma: (F, F),
     },
 
+    /// Newton-Raphson iteration of the polynomial whose (re, im)
+    /// coefficients are given by increasing degree. Samples encode
+    /// the root a point converges to (see [`decode_newton_value`]).
+    Newton {
+        coefficients: [(F, F); NEWTON_COEFFICIENT_COUNT],
+    },
+
+    /// `z = sin(z) + c`, whose orbits are bounded along the real axis
+    /// and escape through the imaginary one.
+    SinMandelbrot,
+    /// `z = cos(z) + c`
+    CosMandelbrot,
+    /// `z = c * exp(z)`
+    ExpMandelbrot,
+
+    /// 2d slice of the Mandelbox: each step folds both components
+    /// into [-1,1] (box fold), scales up the points inside the unit
+    /// circle (ball fold, up to the factor `1/fold²` reached inside
+    /// the radius `fold`), then `z = scale * z + c`.
+    Mandelbox {
+        scale: F,
+        fold: F,
+    },
+
+    /// Iteration typed as a formula, see [`Formula`].
+    Custom {
+        formula: Formula,
+    },
+
     MoireTest,
 }
 
 #[cfg(feature = "force_f32")]
-type Out = [F; 8];
+pub type Out = [F; 8];
 #[cfg(not(feature = "force_f32"))]
-type Out = [F; 4];
+pub type Out = [F; 4];
+
+/// Value of the samples of escape time fractals. Newton fractals
+/// always use their own values (see [`decode_newton_value`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ValueKind {
+    /// Number of iterations before escaping.
+    #[default]
+    EscapeTime,
+    /// Average of `0.5 + 0.5 * sin(frequency * arg(z))` over the
+    /// orbit, in range [0,1], which gives striped exteriors.
+    StripeAverage { frequency: F },
+    /// Estimated distance to the boundary of the set, `|z| ln|z| /
+    /// |dz|` where `dz` is the derivative of the orbit, zero inside
+    /// the set. Only some fractals support it (see
+    /// [`Fractal::supports_distance_estimate`]).
+    DistanceEstimate,
+}
+
+impl ValueKind {
+    /// Range of the values, the maximum being reached by the points
+    /// which don't escape for escape times.
+    pub fn range(&self, max_iter: u32) -> (F, F) {
+        match self {
+            ValueKind::EscapeTime => (0., max_iter as F),
+            ValueKind::StripeAverage { .. } => (0., 1.),
+            // Colored as the log of the distance in pixels (see
+            // `coloring::map_distance_estimates`).
+            ValueKind::DistanceEstimate => (0., 8.),
+        }
+    }
+}
+
+/// Outputs of the lanes of a sample.
+#[derive(Debug, Clone, Copy)]
+pub struct Samples {
+    /// Value of the samples, see [`ValueKind`].
+    pub value: Out,
+    /// Norm of the last point of the orbits, the first one outside
+    /// of the bailout for the points which escaped. Only computed
+    /// when requested, zero otherwise.
+    pub final_norm: Out,
+    /// Argument of the same point, in range [-pi,pi].
+    pub final_arg: Out,
+}
+
+impl Samples {
+    /// `final_z` is `None` when the last points were not tracked.
+    #[inline]
+    pub fn new(value: FX, final_z: Option<Complexx>) -> Self {
+        let (final_norm, final_arg) = match final_z {
+            Some(z) => (z.norm_sqr().sqrt().to_array(), z.arg().to_array()),
+            None => (Out::default(), Out::default()),
+        };
+
+        Samples {
+            value: value.to_array(),
+            final_norm,
+            final_arg,
+        }
+    }
+}
+
+/// Accumulates the value of the samples along their orbits, lanes
+/// stop accumulating when they escape. Each [`ValueKind`] has its
+/// own implementation so that escape times don't pay for the others.
+pub trait Orbit {
+    /// Whether the orbit keeps the point at which each lane escaped
+    /// (see [`FinalZOrbit`]).
+    const TRACKS_FINAL_Z: bool = false;
+
+    fn new(value: ValueKind, bailout: F) -> Self;
+    /// Adds the new point `z` of the lanes which were undiverged.
+    fn add(&mut self, undiverged_mask: FX, z: Complexx);
+    fn finish(self) -> FX;
+
+    /// Finishes the orbit, also returning the last point of each
+    /// lane: `z`, the last point iterated, unless the orbit tracks
+    /// the points at which the lanes escaped.
+    #[inline]
+    fn finish_with(self, z: Complexx) -> (FX, Complexx)
+    where
+        Self: Sized,
+    {
+        (self.finish(), z)
+    }
+}
+
+/// Wraps another orbit to also keep the point at which each lane
+/// escaped, the last points iterated are meaningless for the lanes
+/// which escaped before the others.
+pub struct FinalZOrbit<O> {
+    orbit: O,
+    z: Complexx,
+}
+
+impl<O: Orbit> Orbit for FinalZOrbit<O> {
+    const TRACKS_FINAL_Z: bool = true;
+
+    #[inline]
+    fn new(value: ValueKind, bailout: F) -> Self {
+        FinalZOrbit {
+            orbit: O::new(value, bailout),
+            z: Complexx::zeros(),
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, undiverged_mask: FX, z: Complexx) {
+        self.orbit.add(undiverged_mask, z);
+        self.z = Complexx {
+            re: undiverged_mask.blend(z.re, self.z.re),
+            im: undiverged_mask.blend(z.im, self.z.im),
+        };
+    }
+
+    #[inline]
+    fn finish(self) -> FX {
+        self.orbit.finish()
+    }
+
+    #[inline]
+    fn finish_with(self, _z: Complexx) -> (FX, Complexx) {
+        (self.orbit.finish(), self.z)
+    }
+}
+
+pub struct EscapeTimeOrbit {
+    iter: FX,
+}
+
+impl Orbit for EscapeTimeOrbit {
+    #[inline]
+    fn new(_value: ValueKind, _bailout: F) -> Self {
+        EscapeTimeOrbit {
+            iter: FX::splat(0.),
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, undiverged_mask: FX, _z: Complexx) {
+        self.iter += undiverged_mask.blend(FX::splat(1.), FX::splat(0.));
+    }
+
+    #[inline]
+    fn finish(self) -> FX {
+        self.iter
+    }
+}
+
+pub struct StripeAverageOrbit {
+    frequency: F,
+    bailout: F,
+    iter: FX,
+    sum: FX,
+    /// Last addend of `sum`, removed to get the average of the
+    /// previous iteration.
+    last: FX,
+    /// Squared norm of the point which escaped (or of the last one).
+    escaped_norm_sqr: FX,
+}
+
+impl Orbit for StripeAverageOrbit {
+    #[inline]
+    fn new(value: ValueKind, bailout: F) -> Self {
+        let frequency = match value {
+            ValueKind::StripeAverage { frequency } => frequency,
+            _ => 1.,
+        };
+
+        StripeAverageOrbit {
+            frequency,
+            bailout,
+            iter: FX::splat(0.),
+            sum: FX::splat(0.),
+            last: FX::splat(0.),
+            escaped_norm_sqr: FX::splat(0.),
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, undiverged_mask: FX, z: Complexx) {
+        let zero = FX::splat(0.);
+
+        let addend = 0.5 + 0.5 * (self.frequency * z.arg()).sin();
+        self.iter += undiverged_mask.blend(FX::splat(1.), zero);
+        self.sum += undiverged_mask.blend(addend, zero);
+        self.last = undiverged_mask.blend(addend, self.last);
+        self.escaped_norm_sqr = undiverged_mask.blend(z.norm_sqr(), self.escaped_norm_sqr);
+    }
+
+    #[inline]
+    fn finish(self) -> FX {
+        let one = FX::splat(1.);
+        let average = self.sum / self.iter.max(one);
+        let previous_average = (self.sum - self.last) / (self.iter - one).max(one);
+        // Fractional part of the smooth iteration count, used to
+        // blend the last two averages so that the stripes don't band
+        // at each iteration.
+        let fraction = (one - (self.escaped_norm_sqr.ln() / self.bailout.ln()).log2())
+            .max(FX::splat(0.))
+            .min(one);
+
+        previous_average + fraction * (average - previous_average)
+    }
+}
 
 impl Fractal {
-    pub fn sample(&self, c: Complexx, max_iter: u32) -> Out {
+    /// Samples the lanes of `c`, the last points of the orbits are
+    /// only computed when `final_z` is set.
+    pub fn sample(&self, c: Complexx, max_iter: u32, value: ValueKind, final_z: bool) -> Samples {
+        match (value, final_z) {
+            (ValueKind::EscapeTime, false) => {
+                self.sample_orbits::<EscapeTimeOrbit>(c, max_iter, value)
+            }
+            (ValueKind::EscapeTime, true) => {
+                self.sample_orbits::<FinalZOrbit<EscapeTimeOrbit>>(c, max_iter, value)
+            }
+            (ValueKind::StripeAverage { .. }, false) => {
+                self.sample_orbits::<StripeAverageOrbit>(c, max_iter, value)
+            }
+            (ValueKind::StripeAverage { .. }, true) => {
+                self.sample_orbits::<FinalZOrbit<StripeAverageOrbit>>(c, max_iter, value)
+            }
+            (ValueKind::DistanceEstimate, _) => self.sample_distance_estimate(c, max_iter, final_z),
+        }
+    }
+
+    /// Whether the derivative of the orbits is known, which is
+    /// needed by [`ValueKind::DistanceEstimate`].
+    pub fn supports_distance_estimate(&self) -> bool {
+        matches!(
+            self,
+            Fractal::Mandelbrot | Fractal::MandelbrotCustomExp { .. }
+        )
+    }
+
+    /// Iterates the orbits along with their derivative `dz`. The
+    /// fractal must support distance estimates, the other ones
+    /// sample as zeros.
+    fn sample_distance_estimate(&self, c: Complexx, max_iter: u32, final_z: bool) -> Samples {
+        // The estimate is only accurate far from the set.
+        const BAILOUT: F = 1e10;
+        let bailout_mask = FX::splat(BAILOUT);
+        let one = Complexx::splat(1., 0.);
+
+        let step = |z: Complexx, dz: Complexx| match self {
+            Fractal::Mandelbrot => (z * z + c, z * dz * 2. + one),
+            &Fractal::MandelbrotCustomExp { exp } => {
+                (z.powf(exp) + c, z.powf(exp - 1.) * dz * exp + one)
+            }
+            _ => (Complexx::zeros(), Complexx::zeros()),
+        };
+
+        let mut z = Complexx::zeros();
+        let mut dz = Complexx::zeros();
+        // Points and derivatives at which the lanes escaped, the
+        // following ones overflow.
+        let mut escaped_z = z;
+        let mut escaped_dz = dz;
+        for _ in 0..max_iter {
+            let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
+            if !undiverged_mask.any() {
+                break;
+            }
+
+            (z, dz) = step(z, dz);
+
+            escaped_z = Complexx {
+                re: undiverged_mask.blend(z.re, escaped_z.re),
+                im: undiverged_mask.blend(z.im, escaped_z.im),
+            };
+            escaped_dz = Complexx {
+                re: undiverged_mask.blend(dz.re, escaped_dz.re),
+                im: undiverged_mask.blend(dz.im, escaped_dz.im),
+            };
+        }
+
+        let norm = escaped_z.norm();
+        let escaped_mask = escaped_z.norm_sqr().cmp_gt(bailout_mask);
+        let distance = escaped_mask.blend(norm * norm.ln() / escaped_dz.norm(), FX::splat(0.));
+
+        Samples::new(distance, final_z.then_some(escaped_z))
+    }
+
+    #[inline]
+    fn sample_orbits<O: Orbit>(&self, c: Complexx, max_iter: u32, value: ValueKind) -> Samples {
         let one = FX::splat(1.0);
         let zero = FX::splat(0.0);
 
-        let (iter, _last_z) = match self {
+        let (iter, last_z) = match self {
             Fractal::Mandelbrot => {
                 const BAILOUT: F = 4.;
                 let bailout_mask = FX::splat(BAILOUT);
 
                 let mut z = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -83,10 +401,10 @@ impl Fractal {
 
                     z = z * z + c;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z);
                 }
 
-                (iter, z)
+                orbit.finish_with(z)
             }
             &Fractal::MandelbrotCustomExp { exp } => {
                 const BAILOUT: F = 4.;
@@ -94,7 +412,7 @@ impl Fractal {
 
                 let mut z = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -103,10 +421,10 @@ impl Fractal {
 
                     z = z.powf(exp) + c;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z);
                 }
 
-                (iter, z)
+                orbit.finish_with(z)
             }
             Fractal::Sdrge => {
                 const BAILOUT: F = 4.;
@@ -115,7 +433,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -126,10 +444,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z1;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             &Fractal::SdrgeCustomExp { exp } => {
                 const BAILOUT: F = 4.;
@@ -138,7 +456,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -149,10 +467,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z1;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             &Fractal::SdrgeCustomIntExp { exp } => {
                 const BAILOUT: F = 4.;
@@ -161,7 +479,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -172,10 +490,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z1;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             &Fractal::SdrgeParam { a_re, a_im } => {
                 const BAILOUT: F = 4.;
@@ -186,7 +504,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -197,10 +515,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z1;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             Fractal::Sdrage => {
                 const BAILOUT: F = 4.;
@@ -209,7 +527,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -220,10 +538,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z1;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             Fractal::Tdrge => {
                 const BAILOUT: F = 4.;
@@ -233,7 +551,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z2.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -244,10 +562,10 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z2;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z2);
                 }
 
-                (iter, z2)
+                orbit.finish_with(z2)
             }
             Fractal::NthDrge(n) => {
                 const BAILOUT: F = 4.;
@@ -256,7 +574,7 @@ impl Fractal {
                 let n = *n;
                 let mut z = vec![Complexx::zeros(); n];
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z[n - 1].norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -272,10 +590,10 @@ impl Fractal {
                     }
                     z[n - 1] = new_z;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z[n - 1]);
                 }
 
-                (iter, z[n - 1])
+                orbit.finish_with(z[n - 1])
             }
             Fractal::ThirdDegreeRecPairs => {
                 const BAILOUT: F = 4.;
@@ -285,7 +603,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z2.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -297,10 +615,10 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z2;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z2);
                 }
 
-                (iter, z2)
+                orbit.finish_with(z2)
             }
             Fractal::SecondDegreeThirtySevenBlend => {
                 const BAILOUT: F = 4.;
@@ -309,7 +627,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for i in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -326,10 +644,10 @@ impl Fractal {
                         z1 = new_z1;
                     }
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             &Fractal::ComplexLogisticMapLike { a_re: re, a_im: im } => {
                 const BAILOUT: F = 50.;
@@ -338,7 +656,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -349,10 +667,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z1;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
 
             Fractal::Vshqwj => {
@@ -363,7 +681,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z2.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -374,10 +692,10 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z2;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z2);
                 }
 
-                (iter, z2)
+                orbit.finish_with(z2)
             }
             &Fractal::Wmriho { a_re, a_im } => {
                 const BAILOUT: F = 10.;
@@ -387,7 +705,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::splat(a_re, a_im);
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z2.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -404,10 +722,10 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z2;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z2);
                 }
 
-                (iter, z2)
+                orbit.finish_with(z2)
             }
             &Fractal::Iigdzh { a_re, a_im } => {
                 const BAILOUT: F = 10.;
@@ -417,7 +735,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::splat(a_re, a_im);
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z2.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -433,10 +751,10 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z2;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z2);
                 }
 
-                (iter, z2)
+                orbit.finish_with(z2)
             }
             Fractal::Fxdicq => {
                 const BAILOUT: F = 10.;
@@ -446,7 +764,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z2.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -462,10 +780,10 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z2;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z2);
                 }
 
-                (iter, z2)
+                orbit.finish_with(z2)
             }
             Fractal::Mjygzr => {
                 const BAILOUT: F = 5.;
@@ -474,7 +792,7 @@ impl Fractal {
                 let mut z0 = Complexx::zeros();
                 let mut z1 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -485,10 +803,10 @@ impl Fractal {
                     z0 = z1;
                     z1 = new_z;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
                 }
 
-                (iter, z1)
+                orbit.finish_with(z1)
             }
             Fractal::Sfwypc { alpha, beta, gamma } => {
                 const BAILOUT: F = 100.;
@@ -502,7 +820,7 @@ impl Fractal {
                 let mut z1 = Complexx::zeros();
                 let mut z2 = Complexx::zeros();
 
-                let mut iter = FX::splat(0.);
+                let mut orbit = O::new(value, BAILOUT);
                 for _ in 0..max_iter {
                     let undiverged_mask = z1.norm_sqr().cmp_le(bailout_mask);
                     if !undiverged_mask.any() {
@@ -514,10 +832,156 @@ impl Fractal {
                     z1 = z2;
                     z2 = new_z;
 
-                    iter += undiverged_mask.blend(one, zero);
+                    orbit.add(undiverged_mask, z1);
+                }
+
+                orbit.finish_with(z1)
+            }
+
+            Fractal::Newton { coefficients } => {
+                const CONVERGENCE_EPSILON: F = 1e-6;
+                let epsilon_sqr_mask = FX::splat(CONVERGENCE_EPSILON * CONVERGENCE_EPSILON);
+
+                let roots = newton::roots(coefficients);
+                let degree = roots.len();
+
+                let p = coefficients[..=degree]
+                    .iter()
+                    .map(|&(re, im)| Complexx::splat(re, im))
+                    .collect::<Vec<_>>();
+                let dp = (1..=degree).map(|k| p[k] * k as F).collect::<Vec<_>>();
+                let eval = |coefficients: &[Complexx], z: Complexx| {
+                    coefficients
+                        .iter()
+                        .rev()
+                        .fold(Complexx::zeros(), |acc, &a| acc * z + a)
+                };
+
+                let mut z = c;
+
+                let mut iter = FX::splat(0.);
+                for _ in 0..if degree > 0 { max_iter } else { 0 } {
+                    let dp_z = eval(&dp, z);
+                    let dz = eval(&p, z) * dp_z.conjugate() / dp_z.norm_sqr();
+
+                    let unconverged_mask = dz.norm_sqr().cmp_gt(epsilon_sqr_mask);
+                    if !unconverged_mask.any() {
+                        break;
+                    }
+
+                    z = Complexx {
+                        re: unconverged_mask.blend(z.re - dz.re, z.re),
+                        im: unconverged_mask.blend(z.im - dz.im, z.im),
+                    };
+
+                    iter += unconverged_mask.blend(one, zero);
                 }
 
-                (iter, z1)
+                let (re, im) = (z.re.to_array(), z.im.to_array());
+                let root_index = FX::from(array::from_fn(|k| {
+                    newton::nearest_root(&roots, (re[k], im[k])) as F
+                }));
+
+                (root_index * (max_iter as F + 1.) + iter, z)
+            }
+
+            Fractal::SinMandelbrot | Fractal::CosMandelbrot | Fractal::ExpMandelbrot => {
+                // The orbits go to infinity in a few iterations once
+                // they grow, so a large bailout barely costs anything
+                // and keeps the escape times smooth. Overflowing lanes
+                // (exp of a huge value) are masked out by then.
+                const BAILOUT: F = 2500.;
+                let bailout_mask = FX::splat(BAILOUT);
+
+                let step = |z: Complexx| match self {
+                    Fractal::SinMandelbrot => z.sin() + c,
+                    Fractal::CosMandelbrot => z.cos() + c,
+                    _ => c * z.exp(),
+                };
+
+                let mut z = Complexx::zeros();
+
+                let mut orbit = O::new(value, BAILOUT);
+                for _ in 0..max_iter {
+                    let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
+                    if !undiverged_mask.any() {
+                        break;
+                    }
+
+                    z = step(z);
+
+                    orbit.add(undiverged_mask, z);
+                }
+
+                orbit.finish_with(z)
+            }
+            &Fractal::Mandelbox { scale, fold } => {
+                // Folded orbits can stray quite far from the origin
+                // and still come back.
+                const BAILOUT: F = 1024.;
+                let bailout_mask = FX::splat(BAILOUT);
+
+                let one = FX::splat(1.);
+                let two = FX::splat(2.);
+                // A zero radius would scale the origin infinitely.
+                let min_radius_sqr = FX::splat((fold * fold).max(1e-6));
+
+                let mut z = Complexx::zeros();
+
+                let mut orbit = O::new(value, BAILOUT);
+                for _ in 0..max_iter {
+                    let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
+                    if !undiverged_mask.any() {
+                        break;
+                    }
+
+                    // Box fold: reflects the components beyond 1 and -1.
+                    let box_fold = |x: FX| {
+                        x.cmp_gt(one)
+                            .blend(two - x, x.cmp_lt(-one).blend(-two - x, x))
+                    };
+                    z = Complexx {
+                        re: box_fold(z.re),
+                        im: box_fold(z.im),
+                    };
+
+                    // Ball fold: inverts the points inside the unit
+                    // circle, the ones inside the fold radius being
+                    // scaled linearly instead.
+                    let norm_sqr = z.norm_sqr();
+                    let factor = norm_sqr
+                        .cmp_lt(one)
+                        .blend(one / norm_sqr.max(min_radius_sqr), one);
+                    z = z * factor;
+
+                    z = z * scale + c;
+
+                    orbit.add(undiverged_mask, z);
+                }
+
+                orbit.finish_with(z)
+            }
+            Fractal::Custom { formula } => {
+                // Formulas such as `sin(z) + c` stay bounded for longer
+                // than polynomials before escaping.
+                const BAILOUT: F = 16.;
+                let bailout_mask = FX::splat(BAILOUT);
+
+                let mut z = Complexx::zeros();
+
+                let mut orbit = O::new(value, BAILOUT);
+                for _ in 0..max_iter {
+                    let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
+                    if !undiverged_mask.any() {
+                        break;
+                    }
+
+                    z = formula.step(z, c);
+
+                    orbit.add(undiverged_mask, z);
+                }
+
+                orbit.finish_with(z)
             }
 
             Fractal::MoireTest => {
@@ -526,10 +990,618 @@ impl Fractal {
             }
         };
 
-        // let s = _last_z.norm_sqr().ln().ln();
+        // let s = last_z.norm_sqr().ln().ln();
         // (iter + one - s.min(20. * one)).to_array()
         // (iter + one - s).to_array()
 
-        iter.to_array()
+        Samples::new(iter, O::TRACKS_FINAL_Z.then_some(last_z))
+    }
+
+    /// Every fractal with the parameters it gets when selected in
+    /// the gui, to be updated when a fractal is added.
+    pub fn all_default() -> Vec<Fractal> {
+        // z^3 - 1
+        let mut newton_coefficients = [(0., 0.); NEWTON_COEFFICIENT_COUNT];
+        newton_coefficients[0] = (-1., 0.);
+        newton_coefficients[3] = (1., 0.);
+
+        vec![
+            Fractal::Mandelbrot,
+            Fractal::MandelbrotCustomExp { exp: 2. },
+            Fractal::Sdrge,
+            Fractal::SdrgeCustomIntExp { exp: 2 },
+            Fractal::SdrgeCustomExp { exp: 2. },
+            Fractal::SdrgeParam { a_re: 1., a_im: 0. },
+            Fractal::Sdrage,
+            Fractal::Tdrge,
+            Fractal::NthDrge(4),
+            Fractal::ThirdDegreeRecPairs,
+            Fractal::SecondDegreeThirtySevenBlend,
+            Fractal::ComplexLogisticMapLike { a_re: 1., a_im: 0. },
+            Fractal::Vshqwj,
+            Fractal::Wmriho { a_re: 0., a_im: 0. },
+            Fractal::Iigdzh { a_re: 0., a_im: 0. },
+            Fractal::Fxdicq,
+            Fractal::Mjygzr,
+            Fractal::Sfwypc {
+                alpha: (0., 0.),
+                beta: (0., 0.),
+                gamma: (0., 0.),
+            },
+            Fractal::Newton {
+                coefficients: newton_coefficients,
+            },
+            Fractal::SinMandelbrot,
+            Fractal::CosMandelbrot,
+            Fractal::ExpMandelbrot,
+            Fractal::Mandelbox {
+                scale: 2.,
+                fold: 0.5,
+            },
+            Fractal::Custom {
+                formula: Formula::default(),
+            },
+            Fractal::MoireTest,
+        ]
+    }
+
+    /// View (center_x, center_y, zoom) and max_iter that show the
+    /// fractal as a whole, used when it is selected in the gui and
+    /// when a parameter file leaves the view out.
+    pub fn default_view(&self) -> (F, F, F, u32) {
+        match self {
+            Fractal::Mandelbrot => (-0.75, 0., 6., 1000),
+            Fractal::MandelbrotCustomExp { .. } => (0., 0., 5., 1000),
+            Fractal::Sdrge => (0., 0., 5., 2000),
+            Fractal::SdrgeCustomIntExp { .. } => (0., 0., 5., 2000),
+            Fractal::SdrgeCustomExp { .. } => (0., 0., 5., 2000),
+            Fractal::SdrgeParam { .. } => (0., 0., 5., 2000),
+            Fractal::Sdrage => (0., 0., 5., 8000),
+            Fractal::Tdrge => (0., 0., 5., 2000),
+            Fractal::NthDrge(_) => (0., 0., 5., 2000),
+            Fractal::ThirdDegreeRecPairs => (0., 0., 5., 1500),
+            Fractal::SecondDegreeThirtySevenBlend => (0., 0., 5., 1500),
+            Fractal::ComplexLogisticMapLike { .. } => (0.4, 0., 4., 2500),
+            Fractal::Vshqwj => (0., 0., 5., 1000),
+            Fractal::Wmriho { .. } => (0., 0., 5., 1000),
+            Fractal::Iigdzh { .. } => (0., 0., 5., 600),
+            Fractal::Fxdicq => (0., 0., 5., 1000),
+            Fractal::Mjygzr => (0., 0., 2.5, 1000),
+            Fractal::Sfwypc { .. } => (0., 0., 8., 1500),
+            Fractal::Newton { .. } => (0., 0., 6., 50),
+            Fractal::SinMandelbrot => (0., 0., 12., 200),
+            Fractal::CosMandelbrot => (0., 0., 12., 200),
+            Fractal::ExpMandelbrot => (-1., 0., 8., 200),
+            Fractal::Mandelbox { .. } => (0., 0., 30., 100),
+            Fractal::Custom { .. } => (-0.5, 0., 5., 500),
+            Fractal::MoireTest => (0., 0., 5., 10),
+        }
+    }
+
+    /// `max_iter` suited to the zoom: the recommended one down to the
+    /// default zoom of the fractal, then growing with the square of
+    /// the number of decades zoomed in beyond it so that deep zooms
+    /// don't get blank interiors.
+    pub fn auto_max_iter(&self, zoom: F) -> u32 {
+        /// Growth of `max_iter` per squared decade, relative to the
+        /// recommended one.
+        const GROWTH: F = 0.1;
+        const LIMIT: F = 1_000_000.;
+
+        let (_, _, default_zoom, max_iter) = self.default_view();
+        let decades = (default_zoom / zoom).log10().max(0.);
+        (max_iter as F * (1. + GROWTH * decades * decades))
+            .round()
+            .min(LIMIT) as u32
+    }
+
+    /// Settings that work well as a starting point for each
+    /// fractal (its default view). Bailouts are fixed per fractal so
+    /// they are not part of the recommendations.
+    pub fn recommended_settings(&self) -> RecommendedSettings {
+        let (center_x, center_y, zoom, max_iter) = self.default_view();
+
+        RecommendedSettings {
+            max_iter,
+            center_x,
+            center_y,
+            zoom,
+        }
+    }
+
+    /// Constants of the fractal along with how they are edited in
+    /// the GUI.
+    pub fn parameters(&mut self) -> Vec<FractalParameter<'_>> {
+        const NEWTON_COEFFICIENT_NAMES: [&str; NEWTON_COEFFICIENT_COUNT] =
+            ["z^0", "z^1", "z^2", "z^3", "z^4", "z^5"];
+
+        match self {
+            Fractal::MandelbrotCustomExp { exp } => vec![FractalParameter::Real {
+                name: "exp",
+                value: exp,
+                range: ParameterRange {
+                    min: 0.001,
+                    max: 20.,
+                    speed: 0.001,
+                    logarithmic: true,
+                    decimals: 4,
+                },
+            }],
+            Fractal::SdrgeCustomIntExp { exp } => vec![FractalParameter::Integer {
+                name: "exp",
+                value: exp,
+                min: 1,
+                max: 10,
+            }],
+            Fractal::SdrgeCustomExp { exp } => vec![FractalParameter::Real {
+                name: "exp",
+                value: exp,
+                range: ParameterRange {
+                    min: 1.,
+                    max: 10.,
+                    speed: 0.001,
+                    logarithmic: false,
+                    decimals: 4,
+                },
+            }],
+            // Negative scales give a different family of shapes.
+            Fractal::Mandelbox { scale, fold } => vec![
+                FractalParameter::Real {
+                    name: "scale",
+                    value: scale,
+                    range: ParameterRange {
+                        min: -4.,
+                        max: 4.,
+                        speed: 0.001,
+                        logarithmic: false,
+                        decimals: 4,
+                    },
+                },
+                FractalParameter::Real {
+                    name: "fold",
+                    value: fold,
+                    range: ParameterRange {
+                        min: 0.,
+                        max: 1.,
+                        speed: 0.001,
+                        logarithmic: false,
+                        decimals: 4,
+                    },
+                },
+            ],
+            // Interesting shapes appear in narrow ranges of `a`.
+            Fractal::SdrgeParam { a_re, a_im } => vec![FractalParameter::Complex {
+                name: "a",
+                re: a_re,
+                im: a_im,
+                range: ParameterRange {
+                    min: -2.,
+                    max: 2.,
+                    speed: 0.00001,
+                    logarithmic: false,
+                    decimals: 6,
+                },
+                plane: true,
+            }],
+            Fractal::NthDrge(n) => vec![FractalParameter::Integer {
+                name: "n",
+                value: n,
+                min: 2,
+                max: 20,
+            }],
+            Fractal::ComplexLogisticMapLike { a_re, a_im } => vec![FractalParameter::Complex {
+                name: "a",
+                re: a_re,
+                im: a_im,
+                range: ParameterRange {
+                    min: -4.,
+                    max: 4.,
+                    speed: 0.001,
+                    logarithmic: false,
+                    decimals: 4,
+                },
+                plane: true,
+            }],
+            Fractal::Wmriho { a_re, a_im } | Fractal::Iigdzh { a_re, a_im } => {
+                vec![FractalParameter::Complex {
+                    name: "a",
+                    re: a_re,
+                    im: a_im,
+                    range: ParameterRange {
+                        min: -2.,
+                        max: 2.,
+                        speed: 0.0001,
+                        logarithmic: false,
+                        decimals: 5,
+                    },
+                    plane: true,
+                }]
+            }
+            Fractal::Sfwypc { alpha, beta, gamma } => {
+                [("alpha", alpha), ("beta", beta), ("gamma", gamma)]
+                    .into_iter()
+                    .map(|(name, (re, im))| FractalParameter::Complex {
+                        name,
+                        re,
+                        im,
+                        range: ParameterRange {
+                            min: -5.,
+                            max: 5.,
+                            speed: 0.005,
+                            logarithmic: false,
+                            decimals: 4,
+                        },
+                        plane: false,
+                    })
+                    .collect()
+            }
+            Fractal::Newton { coefficients } => NEWTON_COEFFICIENT_NAMES
+                .into_iter()
+                .zip(coefficients.iter_mut())
+                .map(|(name, (re, im))| FractalParameter::Complex {
+                    name,
+                    re,
+                    im,
+                    range: ParameterRange {
+                        min: -10.,
+                        max: 10.,
+                        speed: 0.01,
+                        logarithmic: false,
+                        decimals: 3,
+                    },
+                    plane: false,
+                })
+                .collect(),
+            Fractal::Mandelbrot
+            | Fractal::Sdrge
+            | Fractal::Sdrage
+            | Fractal::Tdrge
+            | Fractal::ThirdDegreeRecPairs
+            | Fractal::SecondDegreeThirtySevenBlend
+            | Fractal::Vshqwj
+            | Fractal::Fxdicq
+            | Fractal::Mjygzr
+            | Fractal::SinMandelbrot
+            | Fractal::CosMandelbrot
+            | Fractal::ExpMandelbrot
+            | Fractal::Custom { .. }
+            | Fractal::MoireTest => Vec::new(),
+        }
+    }
+
+    /// Whether the values of the samples pack the index of a root
+    /// with the number of iterations (see [`decode_newton_value`]),
+    /// in which case they can't be averaged: the mean of values of
+    /// different roots decodes to another root.
+    pub fn packs_values(&self) -> bool {
+        matches!(self, Fractal::Newton { .. })
+    }
+
+    /// Number of distinct values the coloring has to tell apart:
+    /// the number of roots for Newton fractals, 1 otherwise.
+    pub fn root_count(&self) -> usize {
+        match self {
+            Fractal::Newton { coefficients } => newton::roots(coefficients).len().max(1),
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecommendedSettings {
+    pub max_iter: u32,
+    pub center_x: F,
+    pub center_y: F,
+    pub zoom: F,
+}
+
+impl RecommendedSettings {
+    /// Ratio above which a value is considered far from the
+    /// recommended one.
+    pub const DEVIATION_RATIO: F = 4.;
+
+    /// Describes how far `max_iter` is from the recommended value,
+    /// `None` when it is close enough.
+    pub fn max_iter_deviation(&self, max_iter: u32) -> Option<&'static str> {
+        deviation(
+            max_iter as F,
+            self.max_iter as F,
+            ("much lower", "much higher"),
+        )
+    }
+
+    /// Same as [`RecommendedSettings::max_iter_deviation`] for the
+    /// zoom.
+    pub fn zoom_deviation(&self, zoom: F) -> Option<&'static str> {
+        deviation(zoom, self.zoom, ("much deeper", "much wider"))
+    }
+}
+
+/// Editable constant of a fractal, see [`Fractal::parameters`].
+pub enum FractalParameter<'a> {
+    Real {
+        name: &'static str,
+        value: &'a mut F,
+        range: ParameterRange,
+    },
+    Integer {
+        name: &'static str,
+        value: &'a mut usize,
+        min: usize,
+        max: usize,
+    },
+    /// Complex constant, `plane` tells whether it can also be
+    /// dragged on a small plane.
+    Complex {
+        name: &'static str,
+        re: &'a mut F,
+        im: &'a mut F,
+        range: ParameterRange,
+        plane: bool,
+    },
+}
+
+/// Range and precision of a real constant (or of both parts of a
+/// complex one). Values outside of the range can still be typed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterRange {
+    pub min: F,
+    pub max: F,
+    /// Change of the value per dragged pixel.
+    pub speed: F,
+    /// Whether a slider with a logarithmic scale is used instead of
+    /// a drag value.
+    pub logarithmic: bool,
+    pub decimals: usize,
+}
+
+fn deviation(
+    value: F,
+    recommended: F,
+    (lower, higher): (&'static str, &'static str),
+) -> Option<&'static str> {
+    let ratio = RecommendedSettings::DEVIATION_RATIO;
+    if value * ratio < recommended {
+        Some(lower)
+    } else if value > recommended * ratio {
+        Some(higher)
+    } else {
+        None
+    }
+}
+
+/// Splits a raw Newton value into the index of the root and the
+/// number of iterations it took to converge.
+pub fn decode_newton_value(value: F, max_iter: u32) -> (usize, F) {
+    let block = max_iter as F + 1.;
+    let root = (value / block).floor().max(0.);
+    (root as usize, value - root * block)
+}
+
+mod newton {
+    use std::cell::RefCell;
+
+    use super::NEWTON_COEFFICIENT_COUNT;
+    use crate::F;
+
+    type C = (F, F);
+
+    #[inline]
+    fn mul(a: C, b: C) -> C {
+        (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+    }
+    #[inline]
+    fn div(a: C, b: C) -> C {
+        let d = b.0 * b.0 + b.1 * b.1;
+        ((a.0 * b.0 + a.1 * b.1) / d, (a.1 * b.0 - a.0 * b.1) / d)
+    }
+    #[inline]
+    fn sub(a: C, b: C) -> C {
+        (a.0 - b.0, a.1 - b.1)
+    }
+    #[inline]
+    fn norm_sqr(a: C) -> F {
+        a.0 * a.0 + a.1 * a.1
+    }
+
+    thread_local! {
+        /// Roots of the last polynomial, as they are needed for every
+        /// sample.
+        static ROOTS: RefCell<Option<([C; NEWTON_COEFFICIENT_COUNT], Vec<C>)>> =
+            const { RefCell::new(None) };
+    }
+
+    /// Roots of the polynomial (there are as many as its degree).
+    pub fn roots(coefficients: &[C; NEWTON_COEFFICIENT_COUNT]) -> Vec<C> {
+        ROOTS.with_borrow_mut(|cache| match cache {
+            Some((cached, roots)) if cached == coefficients => roots.clone(),
+            _ => {
+                let roots = durand_kerner(coefficients);
+                *cache = Some((*coefficients, roots.clone()));
+                roots
+            }
+        })
+    }
+
+    /// Finds all the roots at once with the Durand-Kerner method.
+    fn durand_kerner(coefficients: &[C]) -> Vec<C> {
+        const MAX_ITER: usize = 500;
+        const EPSILON: F = 1e-24;
+
+        let Some(degree) = coefficients
+            .iter()
+            .rposition(|&c| c != (0., 0.))
+            .filter(|&d| d > 0)
+        else {
+            return Vec::new();
+        };
+
+        let leading = coefficients[degree];
+        let monic = coefficients[..=degree]
+            .iter()
+            .map(|&c| div(c, leading))
+            .collect::<Vec<_>>();
+        let eval = |z: C| {
+            monic.iter().rev().fold((0., 0.), |acc, &a| {
+                let m = mul(acc, z);
+                (m.0 + a.0, m.1 + a.1)
+            })
+        };
+
+        // Standard starting points: powers of a complex number that is
+        // neither real nor a root of unity.
+        let mut roots = Vec::with_capacity(degree);
+        let mut z = (1., 0.);
+        for _ in 0..degree {
+            roots.push(z);
+            z = mul(z, (0.4, 0.9));
+        }
+
+        for _ in 0..MAX_ITER {
+            let mut max_delta: F = 0.;
+            for i in 0..degree {
+                let denominator = (0..degree)
+                    .filter(|&j| j != i)
+                    .fold((1., 0.), |acc, j| mul(acc, sub(roots[i], roots[j])));
+                let delta = div(eval(roots[i]), denominator);
+                roots[i] = sub(roots[i], delta);
+                max_delta = max_delta.max(norm_sqr(delta));
+            }
+            if max_delta < EPSILON {
+                break;
+            }
+        }
+
+        roots
+    }
+
+    pub fn nearest_root(roots: &[C], z: C) -> usize {
+        roots
+            .iter()
+            .enumerate()
+            .min_by(|(_, &a), (_, &b)| norm_sqr(sub(a, z)).total_cmp(&norm_sqr(sub(b, z))))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        params::FrameParams,
+        rendering::render_raw_image,
+        sampling::{Sampling, SamplingLevel, SamplingPattern},
+    };
+
+    /// Index of the variant, the match makes sure that the test below
+    /// is updated when a fractal is added.
+    fn variant_index(fractal: &Fractal) -> usize {
+        match fractal {
+            Fractal::Mandelbrot => 0,
+            Fractal::MandelbrotCustomExp { .. } => 1,
+            Fractal::Sdrge => 2,
+            Fractal::SdrgeCustomIntExp { .. } => 3,
+            Fractal::SdrgeCustomExp { .. } => 4,
+            Fractal::SdrgeParam { .. } => 5,
+            Fractal::Sdrage => 6,
+            Fractal::Tdrge => 7,
+            Fractal::NthDrge(_) => 8,
+            Fractal::ThirdDegreeRecPairs => 9,
+            Fractal::SecondDegreeThirtySevenBlend => 10,
+            Fractal::ComplexLogisticMapLike { .. } => 11,
+            Fractal::Vshqwj => 12,
+            Fractal::Wmriho { .. } => 13,
+            Fractal::Iigdzh { .. } => 14,
+            Fractal::Fxdicq => 15,
+            Fractal::Mjygzr => 16,
+            Fractal::Sfwypc { .. } => 17,
+            Fractal::Newton { .. } => 18,
+            Fractal::SinMandelbrot => 19,
+            Fractal::CosMandelbrot => 20,
+            Fractal::ExpMandelbrot => 21,
+            Fractal::Mandelbox { .. } => 22,
+            Fractal::Custom { .. } => 23,
+            Fractal::MoireTest => 24,
+        }
+    }
+
+    #[test]
+    fn all_default_lists_every_fractal_once() {
+        let mut indices = Fractal::all_default()
+            .iter()
+            .map(variant_index)
+            .collect::<Vec<_>>();
+        indices.sort();
+        assert_eq!(indices, (0..=24).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn recommended_settings_are_usable() {
+        for fractal in Fractal::all_default() {
+            let settings = fractal.recommended_settings();
+            assert!(settings.max_iter > 0, "{fractal:?}");
+            assert!(
+                settings.zoom > 0. && settings.zoom.is_finite(),
+                "{fractal:?}"
+            );
+            assert!(settings.center_x.is_finite() && settings.center_y.is_finite());
+
+            assert_eq!(settings.max_iter_deviation(settings.max_iter), None);
+            assert_eq!(settings.zoom_deviation(settings.zoom), None);
+            assert_eq!(fractal.auto_max_iter(settings.zoom), settings.max_iter);
+        }
+    }
+
+    #[test]
+    fn recommended_views_show_the_fractal() {
+        for fractal in Fractal::all_default() {
+            let settings = fractal.recommended_settings();
+            let params = FrameParams {
+                img_width: 16,
+                img_height: 12,
+                center_x: settings.center_x,
+                center_y: settings.center_y,
+                zoom: settings.zoom,
+                fractal: fractal.clone(),
+                // Enough to tell the regions apart while keeping the
+                // test fast.
+                max_iter: settings.max_iter.min(100),
+                sampling: Sampling {
+                    level: SamplingLevel::Raw,
+                    random_offsets: false,
+                    filter: None,
+                    pattern: SamplingPattern::UniformGrid,
+                },
+                ..Default::default()
+            };
+
+            let (raw_image, _) = render_raw_image(
+                &params,
+                &params.sampling.generate_sampling_points(),
+                None,
+                None,
+            );
+            let first = raw_image.vec[0];
+            assert!(
+                raw_image.vec.iter().any(|&v| v != first),
+                "{fractal:?} renders a flat image"
+            );
+        }
+    }
+
+    #[test]
+    fn deviations() {
+        let settings = RecommendedSettings {
+            max_iter: 1000,
+            center_x: 0.,
+            center_y: 0.,
+            zoom: 5.,
+        };
+        assert_eq!(settings.max_iter_deviation(100), Some("much lower"));
+        assert_eq!(settings.max_iter_deviation(300), None);
+        assert_eq!(settings.max_iter_deviation(5000), Some("much higher"));
+        assert_eq!(settings.zoom_deviation(1.), Some("much deeper"));
+        assert_eq!(settings.zoom_deviation(30.), Some("much wider"));
     }
 }
@@ -0,0 +1,250 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ErrorKind, Result},
+    mat::Mat2D,
+    values_file::{fnv1a, read_values_file, write_values_file},
+    F,
+};
+
+/// First line of frame values files.
+const VALUES_MAGIC: &[u8] = b"fractal_rndr frame values\n";
+
+/// Hashes of the raw images of the frames of a render, to check
+/// that another render (after a change or on another machine) gives
+/// the same frames.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameManifest {
+    pub frames: Vec<FrameHash>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameHash {
+    /// Index of the frame, 0 for a single frame.
+    pub frame: usize,
+    pub hash: u64,
+    /// Hashes of each row, to locate the first difference.
+    pub row_hashes: Vec<u64>,
+}
+
+impl FrameHash {
+    pub fn new(frame: usize, raw_image: &Mat2D<F>) -> Self {
+        FrameHash {
+            frame,
+            hash: hash_values(&raw_image.vec),
+            row_hashes: raw_image
+                .vec
+                .chunks(raw_image.width.max(1))
+                .map(hash_values)
+                .collect(),
+        }
+    }
+
+    /// First row that differs between the two frames, `None` when
+    /// they are the same.
+    pub fn first_different_row(&self, other: &FrameHash) -> Option<usize> {
+        if self.hash == other.hash && self.row_hashes.len() == other.row_hashes.len() {
+            return None;
+        }
+
+        Some(
+            self.row_hashes
+                .iter()
+                .zip(&other.row_hashes)
+                .position(|(a, b)| a != b)
+                .unwrap_or(self.row_hashes.len().min(other.row_hashes.len())),
+        )
+    }
+}
+
+/// FNV-1a of the values as little-endian f64, so that the hash
+/// doesn't depend on the machine (nor on the `force_f32` feature
+/// for values that fit in an f32).
+fn hash_values(values: &[F]) -> u64 {
+    fnv1a(values.iter().flat_map(|&v| {
+        #[allow(clippy::unnecessary_cast)]
+        (v as f64).to_le_bytes()
+    }))
+}
+
+/// Header of frame values files (see [`write_values_file`]).
+#[derive(Serialize, Deserialize)]
+struct ValuesHeader {
+    frame: usize,
+    width: usize,
+    height: usize,
+}
+
+/// File written next to the manifest with the raw values of a
+/// frame, to locate the first pixel that differs when verifying it.
+pub fn frame_values_path(manifest_path: &Path, frame: usize) -> PathBuf {
+    let mut path = OsString::from(manifest_path);
+    path.push(format!(".{}.values", frame));
+    PathBuf::from(path)
+}
+
+pub fn write_frame_values(manifest_path: &Path, frame: usize, raw_image: &Mat2D<F>) -> Result<()> {
+    write_values_file(
+        &frame_values_path(manifest_path, frame),
+        VALUES_MAGIC,
+        &ValuesHeader {
+            frame,
+            width: raw_image.width,
+            height: raw_image.height,
+        },
+        &raw_image.vec,
+        ErrorKind::WriteManifest,
+    )
+}
+
+pub fn read_frame_values(manifest_path: &Path, frame: usize) -> Result<Mat2D<F>> {
+    let path = frame_values_path(manifest_path, frame);
+    let invalid =
+        |reason: &str| ErrorKind::DecodeFrameValues(path.display().to_string(), reason.into());
+
+    let (header, values) = read_values_file(
+        &path,
+        VALUES_MAGIC,
+        |header: &ValuesHeader| header.width * header.height,
+        ErrorKind::ReadManifest,
+        invalid,
+    )?;
+    if header.frame != frame {
+        return Err(invalid("the values are the ones of another frame"));
+    }
+
+    Ok(Mat2D {
+        vec: values,
+        width: header.width,
+        height: header.height,
+    })
+}
+
+/// First pixel (in row order) that differs between the two raw
+/// images, `None` when they are the same. Pixels outside of one of
+/// the images differ.
+pub fn first_different_pixel(a: &Mat2D<F>, b: &Mat2D<F>) -> Option<(usize, usize)> {
+    let width = a.width.max(b.width);
+    let height = a.height.max(b.height);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .find(|&index| match (a.get(index), b.get(index)) {
+            (Some(a), Some(b)) => a.to_bits() != b.to_bits(),
+            _ => true,
+        })
+}
+
+pub fn write_manifest(manifest: &FrameManifest, path: &Path) -> Result<()> {
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(manifest, PrettyConfig::default())
+            .map_err(ErrorKind::EncodeParameterFile)?,
+    )
+    .map_err(ErrorKind::WriteManifest)
+}
+
+pub fn read_manifest(path: &Path) -> Result<FrameManifest> {
+    let manifest = fs::read_to_string(path).map_err(ErrorKind::ReadManifest)?;
+    ron::from_str(&manifest).map_err(ErrorKind::DecodeManifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize) -> Mat2D<F> {
+        Mat2D {
+            vec: (0..width * height).map(|k| k as F * 0.5).collect(),
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn hash_is_over_little_endian_f64() {
+        // FNV-1a of the bytes of 1.0 and -2.5 as little-endian f64.
+        assert_eq!(hash_values(&[1., -2.5]), 0x2f20b4ea1c69d79c);
+        assert_eq!(hash_values(&[]), 0xcbf29ce484222325);
+    }
+
+    #[test]
+    fn same_frames_have_no_difference() {
+        let a = FrameHash::new(3, &image(5, 4));
+        let b = FrameHash::new(3, &image(5, 4));
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.row_hashes.len(), 4);
+        assert_eq!(a.first_different_row(&b), None);
+    }
+
+    #[test]
+    fn first_different_row() {
+        let expected = FrameHash::new(0, &image(5, 4));
+
+        let mut changed = image(5, 4);
+        // The smallest change of an `F`, whatever its width.
+        changed[(1, 2)] = F::from_bits(changed[(1, 2)].to_bits() + 1);
+        changed[(4, 3)] = -1.;
+        assert_eq!(
+            expected.first_different_row(&FrameHash::new(0, &changed)),
+            Some(2)
+        );
+
+        // A frame with missing rows differs where the rows end.
+        let shorter = FrameHash::new(0, &image(5, 3));
+        assert_eq!(expected.first_different_row(&shorter), Some(3));
+    }
+
+    #[test]
+    fn first_different_pixel_of_images() {
+        let expected = image(5, 4);
+        assert_eq!(first_different_pixel(&expected, &image(5, 4)), None);
+
+        let mut changed = image(5, 4);
+        changed[(4, 3)] = -1.;
+        changed[(3, 1)] = F::from_bits(changed[(3, 1)].to_bits() + 1);
+        assert_eq!(first_different_pixel(&expected, &changed), Some((3, 1)));
+
+        // Images of different sizes differ where one of them ends.
+        assert_eq!(first_different_pixel(&expected, &image(5, 3)), Some((0, 3)));
+    }
+
+    #[test]
+    fn frame_values_round_trip() {
+        let manifest_path =
+            std::env::temp_dir().join(format!("manifest_values_{}.ron", std::process::id()));
+        write_frame_values(&manifest_path, 4, &image(3, 2)).unwrap();
+        let read = read_frame_values(&manifest_path, 4);
+        let other_frame = read_frame_values(&manifest_path, 5);
+        fs::remove_file(frame_values_path(&manifest_path, 4)).unwrap();
+
+        assert_eq!(read.unwrap(), image(3, 2));
+        assert!(matches!(other_frame, Err(ErrorKind::ReadManifest(_))));
+    }
+
+    #[test]
+    fn manifest_round_trips() {
+        let manifest = FrameManifest {
+            frames: vec![
+                FrameHash::new(0, &image(3, 2)),
+                FrameHash::new(7, &image(3, 2)),
+            ],
+        };
+        let path = std::env::temp_dir().join(format!("manifest_{}.ron", std::process::id()));
+        write_manifest(&manifest, &path).unwrap();
+        let read = read_manifest(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.frames.len(), 2);
+        for (a, b) in manifest.frames.iter().zip(&read.frames) {
+            assert_eq!((a.frame, a.hash), (b.frame, b.hash));
+            assert_eq!(a.first_different_row(b), None);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::{ErrorKind, Result},
+    F,
+};
+
+/// Loads a gradient from a file. The format is guessed from the
+/// extension:
+/// - `.ggr`: GIMP gradient
+/// - `.csv`: one `value,#rrggbb` stop per line
+/// - anything else: a list of hex colors (separated by commas or
+///   whitespace) spread evenly over the gradient
+pub fn load_gradient(path: &Path) -> Result<Vec<(F, [u8; 3])>> {
+    let content = fs::read_to_string(path).map_err(ErrorKind::ReadGradientFile)?;
+
+    let gradient = match path.extension().and_then(|e| e.to_str()) {
+        Some("ggr") => parse_ggr(&content),
+        Some("csv") => parse_csv(&content),
+        _ => parse_hex_list(&content),
+    }
+    .map_err(ErrorKind::DecodeGradientFile)?;
+
+    if gradient.len() < 2 {
+        return Err(ErrorKind::DecodeGradientFile(
+            "a gradient needs at least two stops".to_string(),
+        ));
+    }
+
+    Ok(gradient)
+}
+
+pub fn parse_hex_color(s: &str) -> std::result::Result<[u8; 3], String> {
+    let hex = s.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    let invalid = || format!("invalid hex color '{}'", s.trim());
+
+    // Also keeps the slices below on char boundaries, and rejects
+    // the signs that `from_str_radix` accepts.
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex.to_string(),
+        _ => return Err(invalid()),
+    };
+
+    let channel = |k: usize| u8::from_str_radix(&hex[2 * k..2 * k + 2], 16).map_err(|_| invalid());
+    Ok([channel(0)?, channel(1)?, channel(2)?])
+}
+
+fn parse_hex_list(content: &str) -> std::result::Result<Vec<(F, [u8; 3])>, String> {
+    let colors = content
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(parse_hex_color)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let n = colors.len();
+    Ok(colors
+        .into_iter()
+        .enumerate()
+        .map(|(i, color)| (i as F / (n.max(2) - 1) as F, color))
+        .collect())
+}
+
+fn parse_csv(content: &str) -> std::result::Result<Vec<(F, [u8; 3])>, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (value, color) = line
+                .split_once(',')
+                .ok_or_else(|| format!("expected 'value,#rrggbb', got '{}'", line))?;
+            let value = value
+                .trim()
+                .parse::<F>()
+                .map_err(|_| format!("invalid stop position '{}'", value.trim()))?;
+            Ok((value, parse_hex_color(color)?))
+        })
+        .collect()
+}
+
+/// Parses a GIMP gradient. Each segment contributes its left and
+/// right colors; blending and coloring types are ignored.
+fn parse_ggr(content: &str) -> std::result::Result<Vec<(F, [u8; 3])>, String> {
+    let mut lines = content.lines().map(str::trim);
+
+    if lines.next() != Some("GIMP Gradient") {
+        return Err("missing 'GIMP Gradient' header".to_string());
+    }
+
+    let mut line = lines.next().ok_or("unexpected end of file")?;
+    if line.starts_with("Name:") {
+        line = lines.next().ok_or("unexpected end of file")?;
+    }
+    let segment_count = line
+        .parse::<usize>()
+        .map_err(|_| format!("invalid segment count '{}'", line))?;
+
+    let to_u8 = |v: F| (v * 255.).round().clamp(0., 255.) as u8;
+
+    let mut gradient: Vec<(F, [u8; 3])> = Vec::new();
+    for _ in 0..segment_count {
+        let line = lines.next().ok_or("unexpected end of file")?;
+        let values = line
+            .split_whitespace()
+            .take(11)
+            .map(|v| v.parse::<F>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid segment '{}'", line))?;
+        if values.len() < 11 {
+            return Err(format!("invalid segment '{}'", line));
+        }
+
+        let left = (
+            values[0],
+            [to_u8(values[3]), to_u8(values[4]), to_u8(values[5])],
+        );
+        let right = (
+            values[2],
+            [to_u8(values[7]), to_u8(values[8]), to_u8(values[9])],
+        );
+
+        for stop in [left, right] {
+            if gradient.last() != Some(&stop) {
+                gradient.push(stop);
+            }
+        }
+    }
+
+    Ok(gradient)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_hex_color("#e6e6f0"), Ok([0xe6, 0xe6, 0xf0]));
+        assert_eq!(parse_hex_color(" 0A0b0C "), Ok([10, 11, 12]));
+        assert_eq!(parse_hex_color("#eef"), Ok([0xee, 0xee, 0xff]));
+    }
+
+    #[test]
+    fn invalid_hex_colors() {
+        for s in [
+            "", "#", "##fff", "#ff", "#ffff", "#+1+1+1", "#-1-1-1", "#aéaaa", "#ééé", "#ggg",
+            "#12 456",
+        ] {
+            assert!(parse_hex_color(s).is_err(), "{s:?}");
+        }
+    }
+}
@@ -1,15 +1,24 @@
 use std::{
+    collections::{HashMap, HashSet},
     f64::consts::{PI, TAU},
     fs,
-    path::PathBuf,
+    hash::{DefaultHasher, Hash, Hasher},
+    io, panic,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Condvar, Mutex,
+    },
     thread::{self, JoinHandle},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use eframe::{
     egui::{
         self, color_picker::color_edit_button_srgb, Button, Color32, ColorImage, ComboBox,
-        DragValue, Grid, Image, ProgressBar, ScrollArea, Slider, TextureHandle, Vec2, Vec2b,
+        DragValue, Grid, Image, Key, Modifiers, ProgressBar, Rect, ScrollArea, Sense, Slider,
+        SliderClamping, StrokeKind, TextureHandle, Vec2, Vec2b,
     },
     App, CreationContext, Frame as EFrame,
 };
@@ -18,22 +27,288 @@ use ron::ser::PrettyConfig;
 use serde::Serialize;
 
 use crate::{
-    coloring::{color_raw_image, ColoringMode, Extremum, MapValue},
-    error::{ErrorKind, Result},
-    fractal::Fractal,
+    autosave::{raw_params_match, read_autosave, remove_autosave, write_autosave, Autosave},
+    bookmarks::{bookmarks_path, load_bookmarks, save_bookmarks, Bookmark},
+    calibration::{calibrate, Calibration, SUGGESTED_RENDER_TIME},
+    coloring::{
+        analyze_raw_image, color_raw_channels, gradient_color, map_distance_estimates, raw_extrema,
+        sanitize_gradient, scale_image, BoundaryBand, ChannelSelect, ColoringMode, Extremum,
+        GradientSpec, InteriorMode, MapValue, RenderStats, ToneCurve, BUILTIN_GRADIENTS, MAGMA,
+        STATS_PERCENTILES,
+    },
+    error::{panic_message, ErrorKind, Result},
+    formula::Formula,
+    fractal::{
+        Fractal, FractalParameter, ParameterRange, RecommendedSettings, ValueKind,
+        NEWTON_COEFFICIENT_COUNT,
+    },
+    gradient_file::load_gradient,
     mat::Mat2D,
-    params::{FrameParams, ParamsKind},
-    presets::PRESETS,
-    progress::Progress,
-    rendering::render_raw_image,
-    sampling::{Sampling, SamplingLevel},
+    params::{AnimationParams, DevOptions, FrameParams, ParamsFormat, ParamsKind},
+    png_metadata,
+    presets::{
+        check_preset_name, decode_preset, load_user_presets, save_user_preset, user_presets_dir,
+        UserPresets, PRESETS,
+    },
+    progress::{format_remaining_time, Progress},
+    provenance::{Provenance, Source},
+    rendering::{
+        accumulate_samples, mix_seed, progress_total, render_raw_image_channels, RawChannels, View,
+    },
+    sampling::{ReconstructionFilter, Sampling, SamplingLevel, SamplingPattern},
+    settings::{load_settings, save_settings, settings_path},
+    workspace::{
+        read_workspace, read_workspace_or_default, write_workspace, ImportMode, Workspace,
+    },
     F,
 };
 
 pub const WINDOW_SIZE: Vec2 = Vec2 { x: 1000., y: 540. };
-const DEFAULT_ZOOM: F = 5.;
 
-type RenderInfo = Option<(JoinHandle<(Mat2D<F>, Duration)>, Progress)>;
+/// Handle of the render thread, its progress, the flag used to
+/// cancel it, the time it was started at and the samples taken
+/// before it.
+type RunningRender = (
+    JoinHandle<(Mat2D<F>, Option<RawChannels>, F, Duration)>,
+    Progress,
+    Arc<AtomicBool>,
+    Instant,
+    SamplesSnapshot,
+);
+type RenderInfo = Option<RunningRender>;
+
+/// Accumulated samples when a render is started, restored when the
+/// render is cancelled or fails so that the partial pass leaves
+/// them as they were.
+#[derive(Debug, Clone)]
+struct SamplesSnapshot {
+    raw_image: Option<Mat2D<F>>,
+    raw_channels: Option<RawChannels>,
+    samples_per_pixel: F,
+}
+
+impl SamplesSnapshot {
+    fn take(
+        raw_image: &Option<Mat2D<F>>,
+        raw_channels: &Option<RawChannels>,
+        samples_per_pixel: F,
+    ) -> Self {
+        SamplesSnapshot {
+            raw_image: raw_image.clone(),
+            raw_channels: raw_channels.clone(),
+            samples_per_pixel,
+        }
+    }
+
+    fn restore(
+        self,
+        raw_image: &mut Option<Mat2D<F>>,
+        raw_channels: &mut Option<RawChannels>,
+        samples_per_pixel: &mut F,
+    ) {
+        *raw_image = self.raw_image;
+        *raw_channels = self.raw_channels;
+        *samples_per_pixel = self.samples_per_pixel;
+    }
+}
+
+/// Colored previews of the last coloring states, so that going back
+/// to one of them (undo, A/B comparisons) doesn't color the preview
+/// again. The raw preview image is kept as well since it only
+/// changes with breaking changes.
+struct PreviewCache {
+    /// Raw preview image with its channels and the key of the
+    /// params it was rendered with (see [`preview_raw_key`]).
+    raw: Option<(u64, Mat2D<F>, Option<RawChannels>)>,
+    /// Colored previews by key of their params, the least recently
+    /// used first.
+    colored: Vec<(u64, ColorImage)>,
+    /// Number of colored previews kept, set from the memory budget
+    /// of the calibration.
+    capacity: usize,
+    hits: usize,
+    misses: usize,
+}
+
+impl Default for PreviewCache {
+    fn default() -> Self {
+        PreviewCache {
+            raw: None,
+            colored: Vec::new(),
+            capacity: Self::DEFAULT_CAPACITY,
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl PreviewCache {
+    const DEFAULT_CAPACITY: usize = 12;
+    const CAPACITY_RANGE: (usize, usize) = (4, 64);
+
+    /// Keeps as many colored previews as fit in `budget` bytes.
+    fn set_budget(&mut self, budget: usize, preview_size: u32) {
+        let preview_bytes = (preview_size * preview_size) as usize * size_of::<Color32>();
+        let (min, max) = Self::CAPACITY_RANGE;
+        self.capacity = (budget / preview_bytes).clamp(min, max);
+
+        let excess = self.colored.len().saturating_sub(self.capacity);
+        self.colored.drain(..excess);
+    }
+
+    fn get(&mut self, key: u64) -> Option<ColorImage> {
+        match self.colored.iter().position(|(k, _)| *k == key) {
+            Some(i) => {
+                self.hits += 1;
+                let entry = self.colored.remove(i);
+                let image = entry.1.clone();
+                self.colored.push(entry);
+                Some(image)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: u64, image: ColorImage) {
+        if self.colored.len() >= self.capacity {
+            self.colored.remove(0);
+        }
+        self.colored.push((key, image));
+    }
+
+    fn clear(&mut self) {
+        self.raw = None;
+        self.colored.clear();
+    }
+}
+
+/// Label of a value formatted as ron, formatted again only when the
+/// value changes instead of on every frame.
+struct RonLabel<T> {
+    value: Option<T>,
+    label: String,
+}
+
+impl<T> Default for RonLabel<T> {
+    fn default() -> Self {
+        RonLabel {
+            value: None,
+            label: String::new(),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + Serialize> RonLabel<T> {
+    fn get(&mut self, value: &T) -> &str {
+        if self.value.as_ref() != Some(value) {
+            self.label = Gui::format_label_ron(value);
+            self.value = Some(value.clone());
+        }
+        &self.label
+    }
+}
+
+#[derive(Default)]
+struct Labels {
+    fractal: RonLabel<Fractal>,
+    sampling_level: RonLabel<SamplingLevel>,
+    sampling_pattern: RonLabel<SamplingPattern>,
+}
+
+/// Preset of the "load preset" menu.
+struct Preset {
+    name: String,
+    params: FrameParams,
+    /// Whether the preset was saved by the user.
+    user: bool,
+    /// Key of its thumbnail (see [`params_key`]).
+    thumbnail_key: u64,
+}
+
+/// Bookmark of the bookmarks section.
+struct BookmarkEntry {
+    bookmark: Bookmark,
+    label: String,
+    /// Key of its thumbnail (see [`params_key`]).
+    thumbnail_key: u64,
+}
+
+/// Bookmarks of the param file.
+struct Bookmarks {
+    /// Param file the bookmarks belong to, they are read again when
+    /// it changes.
+    param_file_path: Option<PathBuf>,
+    /// File the bookmarks are saved to (the workspace file with
+    /// `--workspace`), `None` when they are only kept for the session
+    /// (without a param file, or when the file couldn't be read so
+    /// that it isn't overwritten).
+    path: Option<PathBuf>,
+    entries: Vec<BookmarkEntry>,
+}
+
+/// Latest request of the pilot worker (the generation of the
+/// preview and its params) and whether the worker was dropped.
+type PilotRequest = Arc<(Mutex<(Option<(u64, FrameParams)>, bool)>, Condvar)>;
+
+/// Renders the previews of the pilot mode on a background thread.
+/// Only the latest request is kept: requests made while a preview
+/// is rendered replace each other.
+struct PilotWorker {
+    request: PilotRequest,
+    receiver: Receiver<(u64, ColorImage)>,
+}
+
+impl PilotWorker {
+    fn new(ctx: egui::Context) -> Self {
+        let request: PilotRequest = Arc::new((Mutex::new((None, false)), Condvar::new()));
+        let (sender, receiver) = mpsc::channel();
+
+        let request_clone = request.clone();
+        thread::spawn(move || loop {
+            let (lock, condvar) = &*request_clone;
+            let (generation, params) = {
+                let mut pending = condvar
+                    .wait_while(lock.lock().unwrap(), |(request, closed)| {
+                        request.is_none() && !*closed
+                    })
+                    .unwrap();
+                match pending.0.take() {
+                    Some(request) if !pending.1 => request,
+                    _ => break,
+                }
+            };
+
+            if sender.send((generation, render_colored(&params))).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        });
+
+        PilotWorker { request, receiver }
+    }
+
+    fn request(&self, generation: u64, params: FrameParams) {
+        let (lock, condvar) = &*self.request;
+        lock.lock().unwrap().0 = Some((generation, params));
+        condvar.notify_one();
+    }
+
+    /// Latest preview rendered since the last call.
+    fn latest(&self) -> Option<(u64, ColorImage)> {
+        self.receiver.try_iter().last()
+    }
+}
+
+impl Drop for PilotWorker {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.request;
+        lock.lock().unwrap().1 = true;
+        condvar.notify_one();
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ParamsChanges {
@@ -70,38 +345,140 @@ pub struct Gui {
     last_saved_params: FrameParams,
 
     params_changes: ParamsChanges,
+    provenance: Provenance,
 
     param_file_path: Option<PathBuf>,
     output_image_path: Option<PathBuf>,
     path_selection_handle: Option<JoinHandle<(u8, Option<PathBuf>)>>,
 
     preview_texture: TextureHandle,
+    preview_cache: PreviewCache,
+    /// Last full preview shown, moved to `previous_preview` when the
+    /// params change.
+    current_preview: Option<ColorImage>,
+    /// Preview from before the last change of the params, shown
+    /// instead of the preview while "compare" is held, empty until
+    /// the params change.
+    previous_preview: TextureHandle,
+    comparing: bool,
+
+    /// Formula of [`Fractal::Custom`] as typed, which is only applied
+    /// once it parses.
+    formula_text: String,
+    formula_error: Option<String>,
 
     raw_image: Option<Mat2D<F>>,
-    samples_per_pixel: usize,
+    /// Channels of `raw_image`, only rendered when the coloring
+    /// mode needs them.
+    raw_channels: Option<RawChannels>,
+    /// Minimum and maximum values of `raw_image`.
+    raw_extrema: Option<(F, F)>,
+    /// Distribution of the values of `raw_image`.
+    render_stats: Option<RenderStats>,
+    /// Average number of samples per pixel of the internal image,
+    /// fractional with adaptive sampling.
+    samples_per_pixel: F,
     should_save_image: bool,
 
     render_info: RenderInfo,
+    /// Settings replaced by the last "tune", restored by "undo".
+    tune_undo: Option<RecommendedSettings>,
 
     message: Option<(String, Instant)>,
+
+    labels: Labels,
+    /// Duration of the last pass of the ui.
+    ui_time: Duration,
+
+    /// Path of the autosave file and time between two autosaves,
+    /// `None` when autosaving is disabled.
+    autosave: Option<(PathBuf, Duration)>,
+    autosave_handle: Option<JoinHandle<Result<()>>>,
+    last_autosave: Instant,
+    /// Samples per pixel of the last autosave, to only write it
+    /// again when samples were added.
+    autosaved_samples: F,
+    /// Set by breaking changes: the autosave no longer matches the
+    /// params and is removed.
+    autosave_stale: bool,
+    /// Autosave of a previous session, waiting for the user to
+    /// restore or discard it.
+    autosave_offer: Option<Autosave>,
+
+    /// Built-in and user presets, loaded when the "load preset"
+    /// menu is first opened.
+    presets: Option<Vec<Preset>>,
+    /// Thumbnails of the presets and bookmarks by key of their
+    /// params, rendered in the background.
+    thumbnails: HashMap<u64, TextureHandle>,
+    /// Keys of the thumbnails being rendered.
+    pending_thumbnails: HashSet<u64>,
+    thumbnail_receivers: Vec<Receiver<(u64, ColorImage)>>,
+    /// Name the current params are saved as by "save as preset".
+    preset_name: String,
+    /// Loaded when the bookmarks section is first opened.
+    bookmarks: Option<Bookmarks>,
+    /// Workspace file given with `--workspace`, which holds the
+    /// bookmarks and user presets instead of the files next to the
+    /// param file and the config directory.
+    workspace_path: Option<PathBuf>,
+    import_mode: ImportMode,
+
+    /// Rectangle being drawn on the preview to zoom into.
+    box_zoom_selection: Option<Rect>,
+    /// Stop of the gradient whose color is edited below the
+    /// gradient bar.
+    selected_gradient_stop: usize,
+    /// Whether changing the image width or height changes the other
+    /// to keep the image ratio.
+    lock_aspect: bool,
+
+    /// Renders low resolution previews while dragging, `None` when
+    /// the pilot mode is off.
+    pilot: Option<PilotWorker>,
+    /// Time of the last change previewed by the pilot mode, the full
+    /// preview is rendered once the input stops.
+    pilot_last_change: Option<Instant>,
+    /// Generations of the last preview requested and of the one
+    /// shown, pilot previews older than the one shown are dropped.
+    preview_generation: u64,
+    shown_generation: u64,
+
+    /// Resolution of the full preview, picked by the calibration.
+    preview_size: u32,
+    /// Speed of the machine from the settings, measured again when
+    /// the version or the CPU changed.
+    calibration: Option<Calibration>,
+    /// Calibration running in the background and the flag used to
+    /// cancel it.
+    calibration_handle: Option<(JoinHandle<Option<Calibration>>, Arc<AtomicBool>)>,
+    /// Whether a calibration should be started once no render is
+    /// running.
+    calibration_pending: bool,
 }
 
 impl Gui {
     pub const PREVIEW_SIZE: u32 = 256;
+    pub const THUMBNAIL_SIZE: u32 = 64;
+    pub const PILOT_SIZE: u32 = 128;
 
     pub fn new(
         cc: &CreationContext,
         frame_params: FrameParams,
+        provenance: Provenance,
         param_file_path: Option<PathBuf>,
         output_image_path: Option<PathBuf>,
+        autosave: Option<(PathBuf, Duration)>,
+        workspace_path: Option<PathBuf>,
     ) -> Self {
         egui_extras::install_image_loaders(&cc.egui_ctx);
 
-        Gui {
+        let mut gui = Gui {
             last_saved_params: frame_params.clone(),
             params: frame_params,
 
             params_changes: ParamsChanges::NonBreakingChanges,
+            provenance,
 
             param_file_path,
             output_image_path,
@@ -112,620 +489,2014 @@ impl Gui {
                 ColorImage::filled([0, 0], Color32::TRANSPARENT),
                 Default::default(),
             ),
+            preview_cache: PreviewCache::default(),
+            current_preview: None,
+            previous_preview: cc.egui_ctx.load_texture(
+                "previous_preview_image",
+                ColorImage::filled([0, 0], Color32::TRANSPARENT),
+                Default::default(),
+            ),
+            comparing: false,
+
+            formula_text: String::new(),
+            formula_error: None,
 
             raw_image: None,
-            samples_per_pixel: 0,
+            raw_channels: None,
+            raw_extrema: None,
+            render_stats: None,
+            samples_per_pixel: 0.,
             should_save_image: false,
 
             render_info: None,
+            tune_undo: None,
 
             message: None,
-        }
+
+            labels: Labels::default(),
+            ui_time: Duration::ZERO,
+
+            autosave,
+            autosave_handle: None,
+            last_autosave: Instant::now(),
+            autosaved_samples: 0.,
+            autosave_stale: false,
+            autosave_offer: None,
+
+            presets: None,
+            thumbnails: HashMap::new(),
+            pending_thumbnails: HashSet::new(),
+            thumbnail_receivers: Vec::new(),
+            preset_name: String::new(),
+            bookmarks: None,
+            workspace_path,
+            import_mode: ImportMode::Merge,
+
+            box_zoom_selection: None,
+            selected_gradient_stop: 0,
+            lock_aspect: false,
+
+            pilot: None,
+            pilot_last_change: None,
+            preview_generation: 0,
+            shown_generation: 0,
+
+            preview_size: Gui::PREVIEW_SIZE,
+            calibration: None,
+            calibration_handle: None,
+            calibration_pending: false,
+        };
+        gui.find_autosave();
+        gui.load_calibration();
+        gui
     }
 }
 
 impl App for Gui {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut EFrame) {
+        let ui_start = Instant::now();
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            const SLIDER_END_POS: f32 = 350.;
             ui.spacing_mut().slider_width = 150.;
 
             ui.columns_const(|[c1, c2]| {
                 // First column
 
-                c1.heading("Settings");
+                c1.horizontal(|ui| {
+                    ui.heading("Settings");
+                    ui.menu_button("?", |ui| {
+                        Grid::new("shortcuts grid").show(ui, |ui| {
+                            for (keys, action) in SHORTCUTS {
+                                ui.code(*keys);
+                                ui.label(*action);
+                                ui.end_row();
+                            }
+                        });
+                    })
+                    .response
+                    .on_hover_text("keyboard shortcuts");
+                });
                 c1.separator();
 
                 egui::ScrollArea::vertical()
                     .auto_shrink(Vec2b::new(false, true))
                     .show(c1, |ui| {
-                        egui::CollapsingHeader::new("Fractal")
-                            .default_open(true)
-                            .show(ui, |ui| {
-                                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.label("fractal:");
-
-                                        let inner_res = ComboBox::from_id_salt("fractal")
-                                            .selected_text(Self::format_label_ron(
-                                                self.params.fractal,
-                                            ))
-                                            .show_ui(ui, |ui| self.show_combobox_fractal(ui));
+                        self.fractal_section(ui);
+                        self.controls_section(ui);
+                        self.coloring_section(ui);
+                        self.gradient_section(ui);
+                        self.save_section(ui);
+                        self.bookmarks_section(ui);
+                        self.render_section(ui);
+                        self.statistics_section(ui);
+                        self.provenance_section(ui);
 
-                                        inner_res
-                                            .response
-                                            .on_hover_text("select the fractal to render");
-
-                                        if inner_res.inner.unwrap_or(false) {
-                                            // Reset view
-                                            self.params.center_x = 0.;
-                                            self.params.center_y = 0.;
-                                            self.params.zoom = DEFAULT_ZOOM;
-
-                                            self.params_changes.set_breaking();
-                                        }
-                                    });
+                        ui.add_space(16.);
+                    });
 
-                                    if self.show_fractal_parameters(ui) {
-                                        self.params_changes.set_breaking();
-                                    }
+                // Second column
 
-                                    ui.horizontal(|ui| {
-                                        let label_width = ui.label("max_iter:").rect.width();
-                                        ui.spacing_mut().slider_width =
-                                            SLIDER_END_POS - label_width;
-                                        let prev_max_iter = self.params.max_iter;
-                                        let res = ui.add(
-                                            Slider::new(&mut self.params.max_iter, 10..=200000)
-                                                .logarithmic(true),
-                                        );
-                                        if res.changed() {
-                                            self.params_changes.set_breaking();
-
-                                            // Avoid leaving max slider at a low value when
-                                            // max_iter is increased.
-                                            if prev_max_iter < self.params.max_iter {
-                                                if let ColoringMode::MinMaxNorm {
-                                                    max: Extremum::Custom(max),
-                                                    ..
-                                                } = &mut self.params.coloring_mode
-                                                {
-                                                    *max = self.params.max_iter as F;
-                                                }
-                                            }
-                                        }
-                                    });
-                                });
-                            });
+                self.preview_section(c2);
+            });
+        });
 
-                        egui::CollapsingHeader::new("Controls")
-                            .default_open(true)
-                            .show(ui, |ui| {
-                                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
-                                    const N_DECIMALS: usize = 8;
+        self.ui_time = ui_start.elapsed();
 
-                                    ui.scope(|ui| {
-                                        ui.horizontal(|ui| {
-                                            let label_width = ui.label("zoom:").rect.width();
-                                            ui.spacing_mut().slider_width =
-                                                SLIDER_END_POS - label_width;
-                                            let res = ui.add(
-                                                Slider::new(
-                                                    &mut self.params.zoom,
-                                                    0.000000000001..=50.,
-                                                )
-                                                .logarithmic(true)
-                                                .min_decimals(N_DECIMALS),
-                                            );
-                                            if res.changed() {
-                                                self.params_changes.set_breaking();
-                                            }
-                                        });
-                                    });
+        self.handle_shortcuts(ctx);
+        self.handle_dropped_files(ctx);
+        self.handle_update(ctx);
+        self.handle_autosave(ctx);
+        self.handle_calibration(ctx);
+        self.receive_thumbnails(ctx);
+    }
 
-                                    let speed = 0.001 * self.params.zoom;
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some((handle, cancel)) = self.calibration_handle.take() {
+            cancel.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+        if let Some(handle) = self.autosave_handle.take() {
+            let _ = handle.join();
+        }
+        // An autosave that wasn't restored nor discarded is kept for
+        // the next session.
+        if let (Some((path, _)), None) = (&self.autosave, &self.autosave_offer) {
+            if let Err(e) = remove_autosave(path) {
+                eprintln!("warning: {}", format!("{:?}", e).trim());
+            }
+        }
+    }
+}
 
-                                    let mut changed = false;
+const SLIDER_END_POS: f32 = 350.;
+/// Time a pass of the ui should stay under with the default params,
+/// shown by the `ui_time` dev option.
+const UI_TIME_BUDGET: Duration = Duration::from_millis(2);
 
-                                    const FIXED_LABEL_WIDTH: f32 = 20.;
+const SHORTCUTS: &[(&str, &str)] = &[
+    ("arrows", "move the view"),
+    ("+ / -", "zoom in/out"),
+    ("R", "sample fractal"),
+    ("S", "save image"),
+    ("Ctrl+S", "save parameter file"),
+];
 
-                                    ui.horizontal(|ui| {
-                                        let label_width = ui.label("re:").rect.width();
-                                        ui.add_space(FIXED_LABEL_WIDTH - label_width);
-                                        let res = ui.add(
-                                            DragValue::new(&mut self.params.center_x)
-                                                .speed(speed)
-                                                .min_decimals(N_DECIMALS),
-                                        );
-                                        changed |= res.changed();
-                                    });
-                                    ui.horizontal(|ui| {
-                                        let label_width = ui.label("im:").rect.width();
-                                        ui.add_space(FIXED_LABEL_WIDTH - label_width);
-                                        let res = ui.add(
-                                            DragValue::new(&mut self.params.center_y)
-                                                .speed(speed)
-                                                .min_decimals(N_DECIMALS),
-                                        );
-                                        changed |= res.changed();
-                                    });
+impl Gui {
+    fn fractal_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Fractal")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("fractal:");
+
+                        let inner_res = ComboBox::from_id_salt("fractal")
+                            .selected_text(self.labels.fractal.get(&self.params.fractal))
+                            .show_ui(ui, |ui| self.show_combobox_fractal(ui));
+
+                        inner_res
+                            .response
+                            .on_hover_text("select the fractal to render");
+
+                        if inner_res.inner.unwrap_or(false) {
+                            let (center_x, center_y, zoom, max_iter) =
+                                self.params.fractal.default_view();
+                            self.params.center_x = center_x;
+                            self.params.center_y = center_y;
+                            self.params.zoom = zoom;
+                            self.params.max_iter = max_iter;
+                            self.tune_undo = None;
+
+                            self.params_changes.set_breaking();
+                        }
 
-                                    ui.horizontal(|ui| {
-                                        ui.label("rotate:");
-                                        let mut rotate = self.params.rotate.unwrap_or(0.);
-
-                                        const FRAC_PI_180: F = PI as F / 180.;
-                                        let res = ui.add(
-                                            DragValue::new(&mut rotate)
-                                                .speed(0.01)
-                                                .range(0. ..=TAU as F)
-                                                .custom_parser(|s| {
-                                                    #[allow(clippy::unnecessary_cast)]
-                                                    s.parse::<F>().ok().map(|degrees| {
-                                                        (degrees.floor() * FRAC_PI_180) as f64
-                                                    })
-                                                })
-                                                .custom_formatter(|rad, _| {
-                                                    let degrees = rad as F * FRAC_PI_180;
-                                                    degrees.floor().to_string()
-                                                }),
-                                        );
-                                        ui.label("deg");
-                                        if res.changed() {
-                                            self.params.rotate =
-                                                if rotate > 0. { Some(rotate) } else { None };
-                                        }
-                                        changed |= res.changed();
-                                    });
+                        let res = ui.button("tune").on_hover_text(
+                            "apply the recommended max_iter and view for this fractal",
+                        );
+                        if res.clicked() {
+                            self.tune();
+                        }
 
-                                    if changed {
-                                        self.params_changes.set_breaking();
-                                    }
-                                });
-                            });
+                        if let Some(previous) = self.tune_undo {
+                            let res = ui
+                                .button("undo")
+                                .on_hover_text("restore the settings before tuning");
+                            if res.clicked() {
+                                self.apply_settings(previous);
+                                self.tune_undo = None;
+                                self.notify("tuning undone");
+                            }
+                        }
+                    });
 
-                        egui::CollapsingHeader::new("Coloring")
-                            .default_open(true)
-                            .show(ui, |ui| {
-                                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.label("coloring mode:");
+                    if self.show_fractal_parameters(ui) {
+                        self.params_changes.set_breaking();
+                    }
 
-                                        ComboBox::from_id_salt("coloring_mode")
-                                            .selected_text(match self.params.coloring_mode {
-                                                ColoringMode::MinMaxNorm { .. } => "MinMaxNorm",
-                                                ColoringMode::CumulativeHistogram { .. } => {
-                                                    "CumulativeHistogram"
-                                                }
-                                            })
-                                            .show_ui(ui, |ui| {
-                                                let selected = matches!(
-                                                    self.params.coloring_mode,
-                                                    ColoringMode::MinMaxNorm { .. }
-                                                );
-                                                if ui
-                                                    .selectable_label(selected, "MinMaxNorm")
-                                                    .clicked()
-                                                    && !selected
-                                                {
-                                                    self.params.coloring_mode =
-                                                        ColoringMode::MinMaxNorm {
-                                                            min: Extremum::Auto,
-                                                            max: Extremum::Auto,
-                                                            map: MapValue::Linear,
-                                                        };
-                                                    self.params_changes.set_non_breaking();
-                                                };
-
-                                                let selected = matches!(
-                                                    self.params.coloring_mode,
-                                                    ColoringMode::CumulativeHistogram { .. }
-                                                );
-                                                if ui
-                                                    .selectable_label(
-                                                        selected,
-                                                        "CumulativeHistogram",
-                                                    )
-                                                    .clicked()
-                                                    && !selected
-                                                {
-                                                    self.params.coloring_mode =
-                                                        ColoringMode::CumulativeHistogram {
-                                                            map: MapValue::Linear,
-                                                        };
-                                                    self.params_changes.set_non_breaking();
-                                                };
-                                            });
-                                    });
+                    ui.horizontal(|ui| {
+                        let label_width = ui.label("max_iter:").rect.width();
+                        ui.spacing_mut().slider_width = SLIDER_END_POS - label_width;
+                        let prev_max_iter = self.params.max_iter;
+                        let auto = self.params.auto_max_iter == Some(true);
+                        let res = ui
+                            .add_enabled(
+                                !auto,
+                                Slider::new(&mut self.params.max_iter, 10..=200000)
+                                    .logarithmic(true),
+                            )
+                            .on_disabled_hover_text("chosen from the zoom");
+                        let mut checked = auto;
+                        let auto_res = ui
+                            .checkbox(&mut checked, "auto")
+                            .on_hover_text("choose max_iter from the zoom");
+                        if auto_res.changed() {
+                            self.params.auto_max_iter = checked.then_some(true);
+                            self.params.apply_auto_max_iter();
+                            if self.params.max_iter != prev_max_iter {
+                                self.params_changes.set_breaking();
+                            }
+                        }
+                        if res.changed() {
+                            self.params_changes.set_breaking();
+
+                            // Avoid leaving max slider at a low value when
+                            // max_iter is increased.
+                            if prev_max_iter < self.params.max_iter
+                                && self.params.value == ValueKind::EscapeTime
+                            {
+                                if let ColoringMode::MinMaxNorm {
+                                    max: Extremum::Custom(max),
+                                    ..
+                                } = &mut self.params.coloring_mode
+                                {
+                                    *max = self.params.max_iter as F;
+                                }
+                            }
+                        }
+                    });
 
-                                    ui.horizontal(|ui| {
-                                        ui.label("map value:");
-
-                                        let (ColoringMode::CumulativeHistogram { map }
-                                        | ColoringMode::MinMaxNorm { map, .. }) =
-                                            &mut self.params.coloring_mode;
-
-                                        ComboBox::from_id_salt("map_value")
-                                            .selected_text(match map {
-                                                MapValue::Linear => "Linear",
-                                                MapValue::Squared => "Squared",
-                                                MapValue::Powf(_) => "Powf",
-                                            })
-                                            .show_ui(ui, |ui| {
-                                                let selected = matches!(map, MapValue::Linear);
-                                                if ui.selectable_label(selected, "Linear").clicked()
-                                                    && !selected
-                                                {
-                                                    *map = MapValue::Linear;
-                                                    self.params_changes.set_non_breaking();
-                                                };
-
-                                                let selected = matches!(map, MapValue::Squared);
-                                                if ui
-                                                    .selectable_label(selected, "Squared")
-                                                    .clicked()
-                                                    && !selected
-                                                {
-                                                    *map = MapValue::Squared;
-                                                    self.params_changes.set_non_breaking();
-                                                };
-
-                                                let selected = matches!(map, MapValue::Powf(_));
-                                                if ui.selectable_label(selected, "Powf").clicked()
-                                                    && !selected
-                                                {
-                                                    *map = MapValue::Powf(1.);
-                                                    self.params_changes.set_non_breaking();
-                                                };
-                                            });
-
-                                        if let MapValue::Powf(exp) = map {
-                                            let res = ui.add(
-                                                Slider::new(exp, 0.01..=20.).logarithmic(true),
-                                            );
-                                            if res.changed() {
-                                                self.params_changes.set_non_breaking();
-                                            }
-                                        }
-                                    });
+                    ui.horizontal(|ui| {
+                        ui.label("value:");
+                        let inner_res = ComboBox::from_id_salt("value_kind")
+                            .selected_text(Self::value_kind_name(self.params.value))
+                            .show_ui(ui, |ui| self.show_combobox_value_kind(ui));
+                        inner_res
+                            .response
+                            .on_hover_text("value computed for each sample");
+                        if inner_res.inner == Some(true) {
+                            // The previous extrema don't fit the new
+                            // range of values.
+                            if let ColoringMode::MinMaxNorm { min, max, .. } =
+                                &mut self.params.coloring_mode
+                            {
+                                *min = Extremum::Auto;
+                                *max = Extremum::Auto;
+                            }
+                            self.params_changes.set_breaking();
+                        }
 
-                                    if let ColoringMode::MinMaxNorm { min, max, .. } =
-                                        &mut self.params.coloring_mode
-                                    {
-                                        const FIXED_LABEL_WIDTH: f32 = 30.;
+                        if let ValueKind::StripeAverage { frequency } = &mut self.params.value {
+                            ui.label("frequency:");
+                            let res =
+                                ui.add(DragValue::new(frequency).range(1. ..=20.).speed(0.05));
+                            if res.changed() {
+                                self.params_changes.set_breaking();
+                            }
+                        }
+                    });
+                });
+            });
+    }
 
-                                        ui.horizontal(|ui| {
-                                            let label_width = ui.label("min:").rect.width();
-                                            ui.add_space(FIXED_LABEL_WIDTH - label_width);
-
-                                            let mut auto = min.is_auto();
-                                            let res = ui.checkbox(&mut auto, "auto");
-                                            if res.changed() {
-                                                *min = if auto {
-                                                    Extremum::Auto
-                                                } else {
-                                                    Extremum::Custom(0.)
-                                                };
-                                                self.params_changes.set_non_breaking();
-                                            }
+    fn controls_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Controls")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    const N_DECIMALS: usize = 8;
+
+                    ui.scope(|ui| {
+                        ui.horizontal(|ui| {
+                            let label_width = ui.label("zoom:").rect.width();
+                            ui.spacing_mut().slider_width = SLIDER_END_POS - label_width;
+                            let res = ui.add(
+                                Slider::new(&mut self.params.zoom, 0.000000000001..=50.)
+                                    .logarithmic(true)
+                                    .min_decimals(N_DECIMALS),
+                            );
+                            if res.changed() {
+                                self.params_changes.set_breaking();
+                            }
+                        });
+                    });
 
-                                            ui.spacing_mut().slider_width = SLIDER_END_POS
-                                                - FIXED_LABEL_WIDTH
-                                                - res.rect.width();
-
-                                            if let Extremum::Custom(min) = min {
-                                                let res = ui.add(
-                                                    Slider::new(
-                                                        min,
-                                                        0. ..=self.params.max_iter as F,
-                                                    )
-                                                    .fixed_decimals(0),
-                                                );
-                                                if res.changed() {
-                                                    self.params_changes.set_non_breaking();
-                                                }
-                                            }
-                                        });
+                    let speed = 0.001 * self.params.zoom;
+
+                    let mut changed = false;
+
+                    const FIXED_LABEL_WIDTH: f32 = 20.;
+
+                    let zoom = self.params.zoom;
+                    for (label, value) in [
+                        ("re:", &mut self.params.center_x),
+                        ("im:", &mut self.params.center_y),
+                    ] {
+                        ui.horizontal(|ui| {
+                            let label_width = ui.label(label).rect.width();
+                            ui.add_space(FIXED_LABEL_WIDTH - label_width);
+                            let current = *value;
+                            let res = ui.add(
+                                DragValue::new(value)
+                                    .speed(speed)
+                                    .custom_formatter(|v, _| format_coordinate(v as F, zoom))
+                                    .custom_parser(|s| {
+                                        #[allow(clippy::unnecessary_cast)]
+                                        parse_coordinate(s, current, zoom).map(|v| v as f64)
+                                    }),
+                            );
+                            changed |= res.changed();
+                        });
+                    }
 
-                                        ui.horizontal(|ui| {
-                                            let label_width = ui.label("max:").rect.width();
-                                            ui.add_space(FIXED_LABEL_WIDTH - label_width);
-
-                                            let mut auto = max.is_auto();
-                                            let res = ui.checkbox(&mut auto, "auto");
-                                            if res.changed() {
-                                                *max = if auto {
-                                                    Extremum::Auto
-                                                } else {
-                                                    Extremum::Custom(self.params.max_iter as F)
-                                                };
-                                                self.params_changes.set_non_breaking();
-                                            }
+                    ui.horizontal(|ui| {
+                        ui.label("rotate:");
+                        let mut rotate = self.params.rotate.unwrap_or(0.);
+
+                        const FRAC_PI_180: F = PI as F / 180.;
+                        let res = ui.add(
+                            DragValue::new(&mut rotate)
+                                .speed(0.01)
+                                .range(0. ..=TAU as F)
+                                .custom_parser(|s| {
+                                    #[allow(clippy::unnecessary_cast)]
+                                    s.parse::<F>()
+                                        .ok()
+                                        .map(|degrees| (degrees * FRAC_PI_180) as f64)
+                                })
+                                .custom_formatter(|rad, _| {
+                                    let degrees = rad as F / FRAC_PI_180;
+                                    format!("{:.1}", degrees)
+                                }),
+                        );
+                        ui.label("deg");
+                        if res.changed() {
+                            self.params.rotate = if rotate > 0. { Some(rotate) } else { None };
+                        }
+                        changed |= res.changed();
+                    });
 
-                                            ui.spacing_mut().slider_width = SLIDER_END_POS
-                                                - FIXED_LABEL_WIDTH
-                                                - res.rect.width();
-
-                                            if let Extremum::Custom(max) = max {
-                                                let res = ui.add(
-                                                    Slider::new(
-                                                        max,
-                                                        0. ..=self.params.max_iter as F,
-                                                    )
-                                                    .fixed_decimals(0),
-                                                );
-                                                if res.changed() {
-                                                    self.params_changes.set_non_breaking();
-                                                }
-                                            }
-                                        });
-                                    }
-                                });
-                            });
+                    if changed {
+                        self.params_changes.set_breaking();
+                    }
+                });
+            });
+    }
 
-                        egui::CollapsingHeader::new("Gradient")
-                            .default_open(false)
-                            .show(ui, |ui| {
-                                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
-                                    if self.show_gradient_ui(ui) {
-                                        self.params_changes.set_non_breaking();
-                                    }
-                                });
+    fn coloring_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Coloring")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("coloring mode:");
+
+                        ComboBox::from_id_salt("coloring_mode")
+                            .selected_text(match self.params.coloring_mode {
+                                ColoringMode::MinMaxNorm { .. } => "MinMaxNorm",
+                                ColoringMode::CumulativeHistogram { .. } => "CumulativeHistogram",
+                                ColoringMode::Roots { .. } => "Roots",
+                                ColoringMode::TwoChannel { .. } => "TwoChannel",
+                            })
+                            .show_ui(ui, |ui| {
+                                let selected = matches!(
+                                    self.params.coloring_mode,
+                                    ColoringMode::MinMaxNorm { .. }
+                                );
+                                if ui.selectable_label(selected, "MinMaxNorm").clicked()
+                                    && !selected
+                                {
+                                    self.params.coloring_mode = ColoringMode::MinMaxNorm {
+                                        min: Extremum::Auto,
+                                        max: Extremum::Auto,
+                                        map: MapValue::Linear,
+                                    };
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(
+                                    self.params.coloring_mode,
+                                    ColoringMode::CumulativeHistogram { .. }
+                                );
+                                if ui
+                                    .selectable_label(selected, "CumulativeHistogram")
+                                    .clicked()
+                                    && !selected
+                                {
+                                    self.params.coloring_mode = ColoringMode::CumulativeHistogram {
+                                        map: MapValue::Linear,
+                                        exclude_interior: None,
+                                    };
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected =
+                                    matches!(self.params.coloring_mode, ColoringMode::Roots { .. });
+                                if ui
+                                    .selectable_label(selected, "Roots")
+                                    .on_hover_text("for Newton fractals")
+                                    .clicked()
+                                    && !selected
+                                {
+                                    self.params.coloring_mode =
+                                        ColoringMode::Roots { darkening: 0.1 };
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(
+                                    self.params.coloring_mode,
+                                    ColoringMode::TwoChannel { .. }
+                                );
+                                if ui
+                                    .selectable_label(selected, "TwoChannel")
+                                    .on_hover_text("brightness and hue from two channels")
+                                    .clicked()
+                                    && !selected
+                                {
+                                    self.params.coloring_mode = ColoringMode::TwoChannel {
+                                        value: ChannelSelect::Value,
+                                        hue: ChannelSelect::FinalArg,
+                                        saturation: 0.8,
+                                        map: MapValue::Linear,
+                                    };
+                                    self.set_coloring_changed();
+                                };
                             });
+                    });
 
-                        egui::CollapsingHeader::new("Save Settings")
-                            .default_open(true)
-                            .show(ui, |ui| {
-                                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
-                                    ui.horizontal(|ui| {
-                                        if self.path_selection_handle.is_none() {
-                                            if ui.button("open parameter file").clicked() {
-                                                self.path_selection_handle =
-                                                    Some(thread::spawn(|| {
-                                                        (0, FileDialog::new().pick_file())
-                                                    }));
+                    if let ColoringMode::TwoChannel {
+                        value,
+                        hue,
+                        saturation,
+                        ..
+                    } = &mut self.params.coloring_mode
+                    {
+                        let mut channels_changed = false;
+                        for (label, channel) in [("brightness:", value), ("hue:", hue)] {
+                            ui.horizontal(|ui| {
+                                ui.label(label);
+                                ComboBox::from_id_salt(label)
+                                    .selected_text(Self::channel_name(*channel))
+                                    .show_ui(ui, |ui| {
+                                        for c in [
+                                            ChannelSelect::Value,
+                                            ChannelSelect::FinalNorm,
+                                            ChannelSelect::FinalArg,
+                                        ] {
+                                            if ui
+                                                .selectable_value(channel, c, Self::channel_name(c))
+                                                .changed()
+                                            {
+                                                channels_changed = true;
                                             }
-                                            if ui.button("set output image").clicked() {
-                                                self.path_selection_handle =
-                                                    Some(thread::spawn(|| {
-                                                        (1, FileDialog::new().save_file())
-                                                    }));
-                                            }
-                                        }
-                                    });
-
-                                    ui.horizontal(|ui| {
-                                        if ui.button("revert unsaved changes").clicked() {
-                                            self.params = self.last_saved_params.clone();
-                                            self.params_changes.set_breaking();
                                         }
-                                        ui.add_enabled_ui(
-                                            self.render_info.is_none()
-                                                && self.param_file_path.is_some(),
-                                            |ui| {
-                                                let res = ui.button("save parameter file");
-                                                let res = if self.param_file_path.is_none() {
-                                                    res.on_disabled_hover_text(
-                                                    "no path was provided for the parameter file",
-                                                )
-                                                } else {
-                                                    res
-                                                };
-
-                                                if res.clicked() {
-                                                    match self.save_parameter_file() {
-                                                        Ok(_) => self.notify("saved"),
-                                                        Err(_) => self.notify(
-                                                            "failed to save parameter file",
-                                                        ),
-                                                    }
-                                                }
-                                            },
-                                        );
-                                        ui.menu_button("load preset", |ui| {
-                                            ScrollArea::vertical()
-                                                .max_width(200.)
-                                                .max_height(100.)
-                                                .show(ui, |ui| {
-                                                    for &(name, cfg_file) in PRESETS {
-                                                        if let ParamsKind::Frame(params) =
-                                                            ron::from_str(cfg_file).unwrap()
-                                                        {
-                                                            if ui.button(name).clicked() {
-                                                                self.params = params;
-                                                                self.params_changes.set_breaking();
-                                                                self.notify(format!(
-                                                                    "loaded {}",
-                                                                    name
-                                                                ));
-                                                                ui.close();
-                                                            };
-                                                        }
-                                                    }
-                                                })
-                                        });
                                     });
-                                });
                             });
+                        }
+                        let saturation_changed = ui
+                            .horizontal(|ui| {
+                                ui.label("saturation:");
+                                ui.add(Slider::new(saturation, 0. ..=1.)).changed()
+                            })
+                            .inner;
+
+                        if channels_changed {
+                            self.set_coloring_changed();
+                        } else if saturation_changed {
+                            self.params_changes.set_non_breaking();
+                        }
+                    }
 
-                        egui::CollapsingHeader::new("Render")
-                            .default_open(true)
-                            .show(ui, |ui| {
-                                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
-                                    ui.horizontal(|ui| {
-                                        ui.label("image width:");
-                                        let res1 = ui.add(
-                                            DragValue::new(&mut self.params.img_width)
-                                                .range(32..=20000)
-                                                .speed(4.),
-                                        );
-                                        ui.label("image height:");
-                                        let res2 = ui.add(
-                                            DragValue::new(&mut self.params.img_height)
-                                                .range(32..=20000)
-                                                .speed(4.),
-                                        );
-
-                                        if res1.changed() || res2.changed() {
-                                            self.params_changes.set_breaking();
-                                        }
-                                    });
+                    if let ColoringMode::CumulativeHistogram {
+                        exclude_interior, ..
+                    } = &mut self.params.coloring_mode
+                    {
+                        let mut exclude = exclude_interior.unwrap_or(false);
+                        let res = ui.checkbox(&mut exclude, "exclude interior").on_hover_text(
+                            "build the histogram from the exterior only, the interior taking the end of the gradient",
+                        );
+                        if res.changed() {
+                            *exclude_interior = exclude.then_some(true);
+                            self.params_changes.set_non_breaking();
+                        }
+                    }
 
-                                    ui.horizontal(|ui| {
-                                        ui.label("current spp:").on_hover_text(
-                                            "number of samples per pixel of the internal image",
-                                        );
-                                        ui.code(format!(" {} ", self.samples_per_pixel));
-                                    });
+                    if let ColoringMode::Roots { darkening } = &mut self.params.coloring_mode {
+                        ui.horizontal(|ui| {
+                            ui.label("darkening:");
+                            let res = ui
+                                .add(Slider::new(darkening, 0. ..=1.))
+                                .on_hover_text("darken points that take longer to converge");
+                            if res.changed() {
+                                self.params_changes.set_non_breaking();
+                            }
+                        });
+                    }
 
-                                    ui.horizontal(|ui| {
-                                        let inner_res = ComboBox::from_id_salt("sampling_level")
-                                            .selected_text(Self::format_label_ron(
-                                                self.params.sampling.level,
-                                            ))
-                                            .show_ui(ui, |ui| {
-                                                self.show_combobox_sampling_level(ui);
-                                            });
-                                        inner_res.response.on_hover_text("sampling level");
-
-                                        let res = ui
-                                            .button(format!(
-                                                "sample fractal (+{} spp)",
-                                                self.params.sampling.sample_count()
-                                            ))
-                                            .on_hover_text("collect new samples");
-                                        if res.clicked() {
-                                            self.render_info = Some(self.render_and_save());
-                                        };
-
-                                        let no_samples = self.samples_per_pixel == 0;
-                                        let no_output_image_path = self.output_image_path.is_none();
-                                        ui.add_enabled_ui(
-                                            !(no_samples || no_output_image_path),
-                                            |ui| {
-                                                let res = {
-                                                    let btn = ui.button("save image");
-
-                                                    if no_output_image_path {
-                                                        btn.on_disabled_hover_text(
-                                                        "no path was provided for the output image",
-                                                    )
-                                                    } else if no_samples {
-                                                        btn.on_disabled_hover_text(
-                                                "sample the fractal before saving the image",
-                                            )
-                                                    } else {
-                                                        btn
-                                                    }
-                                                };
-
-                                                self.should_save_image |= res.clicked();
-                                            },
-                                        );
-                                    });
-                                });
+                    ui.horizontal(|ui| {
+                        let (ColoringMode::CumulativeHistogram { map, .. }
+                        | ColoringMode::MinMaxNorm { map, .. }
+                        | ColoringMode::TwoChannel { map, .. }) = &mut self.params.coloring_mode
+                        else {
+                            return;
+                        };
+
+                        ui.label("map value:");
+
+                        ComboBox::from_id_salt("map_value")
+                            .selected_text(match map {
+                                MapValue::Linear => "Linear",
+                                MapValue::Squared => "Squared",
+                                MapValue::Powf(_) => "Powf",
+                                MapValue::Log(_) => "Log",
+                                MapValue::Sqrt => "Sqrt",
+                                MapValue::Sine(_) => "Sine",
+                            })
+                            .show_ui(ui, |ui| {
+                                let selected = matches!(map, MapValue::Linear);
+                                if ui.selectable_label(selected, "Linear").clicked() && !selected {
+                                    *map = MapValue::Linear;
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(map, MapValue::Squared);
+                                if ui.selectable_label(selected, "Squared").clicked() && !selected {
+                                    *map = MapValue::Squared;
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(map, MapValue::Powf(_));
+                                if ui.selectable_label(selected, "Powf").clicked() && !selected {
+                                    *map = MapValue::Powf(1.);
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(map, MapValue::Log(_));
+                                if ui.selectable_label(selected, "Log").clicked() && !selected {
+                                    *map = MapValue::Log(10.);
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(map, MapValue::Sqrt);
+                                if ui.selectable_label(selected, "Sqrt").clicked() && !selected {
+                                    *map = MapValue::Sqrt;
+                                    self.params_changes.set_non_breaking();
+                                };
+
+                                let selected = matches!(map, MapValue::Sine(_));
+                                if ui.selectable_label(selected, "Sine").clicked() && !selected {
+                                    *map = MapValue::Sine(1.);
+                                    self.params_changes.set_non_breaking();
+                                };
                             });
 
-                        ui.add_space(16.);
+                        if let MapValue::Powf(exp) = map {
+                            let res = ui.add(Slider::new(exp, 0.01..=20.).logarithmic(true));
+                            if res.changed() {
+                                self.params_changes.set_non_breaking();
+                            }
+                        }
+                        if let MapValue::Log(k) = map {
+                            let res =
+                                ui.add(Slider::new(k, 0.01..=1000.).logarithmic(true).text("k"));
+                            if res.changed() {
+                                self.params_changes.set_non_breaking();
+                            }
+                        }
+                        if let MapValue::Sine(freq) = map {
+                            let res = ui
+                                .add(Slider::new(freq, 0.1..=100.).logarithmic(true).text("freq"));
+                            if res.changed() {
+                                self.params_changes.set_non_breaking();
+                            }
+                        }
                     });
 
-                // Second column
+                    if let ColoringMode::MinMaxNorm { min, max, .. } =
+                        &mut self.params.coloring_mode
+                    {
+                        const FIXED_LABEL_WIDTH: f32 = 30.;
+
+                        // Sliders span the values of the current image
+                        // when there is one, raw values are not always
+                        // iteration counts.
+                        let (range_min, range_max) = self
+                            .raw_extrema
+                            .filter(|(min, max)| max > min)
+                            .unwrap_or(self.params.value.range(self.params.max_iter));
+                        let decimals = if range_max - range_min < 10. { 3 } else { 0 };
+                        let measured = |ui: &mut egui::Ui, value: Option<F>| {
+                            value.map_or(0., |value| {
+                                ui.weak(format!("({:.*})", decimals, value))
+                                    .on_hover_text("value of the current image when auto")
+                                    .rect
+                                    .width()
+                                    + ui.spacing().item_spacing.x
+                            })
+                        };
+
+                        ui.horizontal(|ui| {
+                            let label_width = ui.label("min:").rect.width();
+                            ui.add_space(FIXED_LABEL_WIDTH - label_width);
+
+                            let (res, changed) =
+                                extremum_selector(ui, "min_extremum", min, range_min, 0.5);
+                            if changed {
+                                self.params_changes.set_non_breaking();
+                            }
+                            let measured_width = measured(ui, self.raw_extrema.map(|(min, _)| min));
+
+                            ui.spacing_mut().slider_width = SLIDER_END_POS
+                                - FIXED_LABEL_WIDTH
+                                - res.rect.width()
+                                - measured_width;
+
+                            let slider = match min {
+                                Extremum::Auto => None,
+                                Extremum::Custom(min) => Some(
+                                    Slider::new(min, range_min..=range_max)
+                                        .clamping(SliderClamping::Edits)
+                                        .fixed_decimals(decimals),
+                                ),
+                                Extremum::Percentile(p) => {
+                                    Some(Slider::new(p, 0. ..=100.).fixed_decimals(1).suffix("%"))
+                                }
+                            };
+                            if let Some(slider) = slider {
+                                if ui.add(slider).changed() {
+                                    self.params_changes.set_non_breaking();
+                                }
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            let label_width = ui.label("max:").rect.width();
+                            ui.add_space(FIXED_LABEL_WIDTH - label_width);
 
-                const INFO_AREA_HEIGHT: f32 = 48.;
+                            let (res, changed) =
+                                extremum_selector(ui, "max_extremum", max, range_max, 99.5);
+                            if changed {
+                                self.params_changes.set_non_breaking();
+                            }
+                            let measured_width = measured(ui, self.raw_extrema.map(|(_, max)| max));
+
+                            ui.spacing_mut().slider_width = SLIDER_END_POS
+                                - FIXED_LABEL_WIDTH
+                                - res.rect.width()
+                                - measured_width;
+
+                            let slider = match max {
+                                Extremum::Auto => None,
+                                Extremum::Custom(max) => Some(
+                                    Slider::new(max, range_min..=range_max)
+                                        .clamping(SliderClamping::Edits)
+                                        .fixed_decimals(decimals),
+                                ),
+                                Extremum::Percentile(p) => {
+                                    Some(Slider::new(p, 0. ..=100.).fixed_decimals(1).suffix("%"))
+                                }
+                            };
+                            if let Some(slider) = slider {
+                                if ui.add(slider).changed() {
+                                    self.params_changes.set_non_breaking();
+                                }
+                            }
+                        });
+                    }
 
-                c2.heading("Preview");
-                c2.separator();
+                    let mut dither = self.params.dither.unwrap_or(false);
+                    let res = ui
+                        .checkbox(&mut dither, "dither")
+                        .on_hover_text("add noise to colors to avoid banding");
+                    if res.changed() {
+                        self.params.dither = dither.then_some(true);
+                        self.params_changes.set_non_breaking();
+                    }
 
-                let texture_size = self.preview_texture.size_vec2();
-                let d = 0.5 * (c2.available_height() - texture_size.y - INFO_AREA_HEIGHT);
-                c2.add_space(d);
-                c2.add_sized(
-                    texture_size,
-                    Image::from_texture((self.preview_texture.id(), texture_size))
-                        .show_loading_spinner(false)
-                        .maintain_aspect_ratio(true)
-                        .corner_radius(2),
-                );
-                c2.add_space(d);
-
-                c2.with_layout(
-                    egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
-                    |ui| {
-                        if let Some((_, progress)) = &self.render_info {
-                            ui.add(
-                                ProgressBar::new(progress.get_progress())
-                                    .desired_height(4.)
-                                    .desired_width(128.)
-                                    .corner_radius(0.)
-                                    .fill(Color32::WHITE),
+                    let mut band_enabled = self.params.boundary_band.is_some();
+                    let res = ui
+                        .checkbox(&mut band_enabled, "boundary band")
+                        .on_hover_text("blend a color over pixels close to the maximum value");
+                    if res.changed() {
+                        self.params.boundary_band = band_enabled.then_some(BoundaryBand {
+                            width_iters: 10,
+                            color: [255, 255, 255],
+                            blend: 0.5,
+                        });
+                        self.params_changes.set_non_breaking();
+                    }
+                    if let Some(band) = &mut self.params.boundary_band {
+                        ui.horizontal(|ui| {
+                            ui.label("width:");
+                            let res = ui.add(
+                                DragValue::new(&mut band.width_iters)
+                                    .range(1..=self.params.max_iter)
+                                    .suffix(" iters"),
                             );
-                        } else if let Some((text, start)) = self.message.as_mut() {
-                            const MESSAGE_DISPLAY_TIME: Duration = Duration::from_secs(5);
-                            ui.label(text.as_str());
-                            if start.elapsed() > MESSAGE_DISPLAY_TIME {
-                                self.message = None;
+                            let res = res | ui.color_edit_button_srgb(&mut band.color);
+                            if res.changed() {
+                                self.params_changes.set_non_breaking();
                             }
+                        });
+                        let res = ui.add(Slider::new(&mut band.blend, 0. ..=1.).text("blend"));
+                        if res.changed() {
+                            self.params_changes.set_non_breaking();
                         }
-                    },
-                );
-            });
-        });
+                    }
 
-        self.handle_update(ctx);
-    }
-}
+                    ui.horizontal(|ui| {
+                        ui.label("interior:");
+                        let inner_res = ComboBox::from_id_salt("interior_mode")
+                            .selected_text(Self::interior_mode_name(
+                                self.params.interior_mode.as_ref(),
+                            ))
+                            .show_ui(ui, |ui| self.show_combobox_interior_mode(ui));
+                        inner_res
+                            .response
+                            .on_hover_text("color of the pixels which never escaped");
+                        if inner_res.inner == Some(true) {
+                            self.params_changes.set_non_breaking();
+                        }
 
-impl Gui {
-    fn handle_update(&mut self, ctx: &egui::Context) {
-        if self.render_info.is_some() {
-            ctx.request_repaint();
-        }
+                        let changed = match &mut self.params.interior_mode {
+                            Some(InteriorMode::Flat(color)) => {
+                                ui.color_edit_button_srgb(color).changed()
+                            }
+                            Some(InteriorMode::RadialGradient { gradient }) => {
+                                let mut changed = false;
+                                ui.menu_button("gradient", |ui| {
+                                    for &(name, builtin) in BUILTIN_GRADIENTS {
+                                        if ui.button(name).clicked() {
+                                            *gradient = builtin.to_vec();
+                                            changed = true;
+                                            ui.close();
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text(
+                                    "gradient mapped from the center of the view to its corners",
+                                );
+                                changed
+                            }
+                            None => false,
+                        };
+                        if changed {
+                            self.params_changes.set_non_breaking();
+                        }
+                    });
 
-        if self.params_changes.breaking() {
-            // Params relative to fractal and position have
-            // changed: stored raw_image is no longer valid.
-            self.raw_image = None;
-            self.samples_per_pixel = 0;
-        }
+                    ui.horizontal(|ui| {
+                        ui.label("tone curve:").on_hover_text(
+                            "adjust the values after the map, before the gradient",
+                        );
+                        if ui
+                            .add_enabled(self.params.tone_curve.is_some(), Button::new("reset"))
+                            .clicked()
+                        {
+                            self.params.tone_curve = None;
+                            self.params_changes.set_non_breaking();
+                        }
+                    });
+                    let mut tone_curve = self.params.tone_curve.unwrap_or_default();
+                    let mut changed = false;
+                    for (name, value, (min, max), logarithmic) in [
+                        ("gamma:", &mut tone_curve.gamma, ToneCurve::GAMMA_RANGE, true),
+                        ("brightness:", &mut tone_curve.brightness, (-1., 1.), false),
+                        ("contrast:", &mut tone_curve.contrast, ToneCurve::CONTRAST_RANGE, false),
+                    ] {
+                        ui.horizontal(|ui| {
+                            let label_width = ui.label(name).rect.width();
+                            ui.spacing_mut().slider_width = SLIDER_END_POS - label_width;
+                            changed |= ui
+                                .add(Slider::new(value, min..=max).logarithmic(logarithmic))
+                                .changed();
+                        });
+                    }
+                    changed |= ui
+                        .checkbox(&mut tone_curve.log, "log")
+                        .on_hover_text("spread the low values with a logarithm")
+                        .changed();
+                    if changed {
+                        self.params.tone_curve = Some(tone_curve);
+                        self.params_changes.set_non_breaking();
+                    }
+                });
+            });
+    }
+
+    fn gradient_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Gradient")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("offset:");
+                        let mut offset = self.params.gradient_offset.unwrap_or(0.);
+                        let res = ui
+                            .add(Slider::new(&mut offset, 0. ..=1.))
+                            .on_hover_text("shift where the gradient starts");
+                        if res.changed() {
+                            self.params.gradient_offset =
+                                if offset > 0. { Some(offset) } else { None };
+                            self.params_changes.set_non_breaking();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("repeat:");
+                        let mut repeat = self.params.gradient_repeat.unwrap_or(1.);
+                        let res = ui
+                            .add(
+                                Slider::new(&mut repeat, 1. ..=32.)
+                                    .logarithmic(true)
+                                    .clamping(SliderClamping::Never),
+                            )
+                            .on_hover_text(
+                                "cycle through the gradient several times",
+                            );
+                        let mut cyclic = self.params.gradient_repeat.is_some();
+                        let cyclic_res = ui.checkbox(&mut cyclic, "cycle").on_hover_text(
+                            "blend the last color into the first one so that repeating the gradient leaves no seam",
+                        );
+                        if res.changed() || cyclic_res.changed() {
+                            self.params.gradient_repeat =
+                                (res.changed() || cyclic).then_some(repeat.max(0.));
+                            self.params_changes.set_non_breaking();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("gradient:");
+                        let current = self.params.gradient.name().unwrap_or("custom").to_string();
+                        ComboBox::from_id_salt("gradient")
+                            .selected_text(&current)
+                            .show_ui(ui, |ui| {
+                                for &(name, stops) in BUILTIN_GRADIENTS {
+                                    ui.horizontal(|ui| {
+                                        let size = Vec2::new(
+                                            GRADIENT_PREVIEW_WIDTH,
+                                            ui.spacing().interact_size.y,
+                                        );
+                                        let (rect, _) =
+                                            ui.allocate_exact_size(size, Sense::hover());
+                                        paint_gradient(&ui.painter_at(rect), rect, stops);
+                                        if ui.selectable_label(current == name, name).clicked() {
+                                            self.params.gradient =
+                                                GradientSpec::Named(name.to_string());
+                                            self.params.gradient_path = None;
+                                            self.params_changes.set_non_breaking();
+                                            ui.close();
+                                        }
+                                    });
+                                }
+                            })
+                            .response
+                            .on_hover_text(
+                                "built-in gradient, editing its stops makes a custom copy of it",
+                            );
+                    });
+
+                    // The stops are edited on a copy so that built-in
+                    // gradients stay named until they are changed.
+                    let mut gradient = self.params.gradient.stops().to_vec();
+                    let mut changed =
+                        gradient_editor(ui, &mut gradient, &mut self.selected_gradient_stop);
+                    changed |= show_gradient_ui(ui, &mut gradient);
+                    if changed {
+                        self.params.gradient = GradientSpec::Custom(gradient);
+                        // The edited gradient is saved in place of the
+                        // file it was loaded from.
+                        self.params.gradient_path = None;
+                        self.params_changes.set_non_breaking();
+                    }
+                });
+            });
+    }
+
+    fn save_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Save Settings")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("parameter file:");
+                        path_text_edit(ui, &mut self.param_file_path)
+                            .on_hover_text("parameter file to load and save");
+                        ui.add_enabled_ui(self.path_selection_handle.is_none(), |ui| {
+                            if ui.button("browse").clicked() {
+                                self.path_selection_handle =
+                                    Some(thread::spawn(|| (0, FileDialog::new().pick_file())));
+                            }
+                        });
+                        ui.add_enabled_ui(self.param_file_path.is_some(), |ui| {
+                            if ui
+                                .button("load parameter file")
+                                .on_hover_text(
+                                    "replace the current parameters with the ones of the file",
+                                )
+                                .clicked()
+                            {
+                                self.load_parameter_file();
+                            }
+                        });
+                    });
+
+                    ui.horizontal(|ui| {
+                        if self.path_selection_handle.is_none() {
+                            if ui.button("load gradient").clicked() {
+                                self.path_selection_handle =
+                                    Some(thread::spawn(|| (2, FileDialog::new().pick_file())));
+                            }
+                            if ui
+                                .button("export zoom animation")
+                                .on_hover_text(
+                                    "write animation params zooming into the current view",
+                                )
+                                .clicked()
+                            {
+                                self.path_selection_handle =
+                                    Some(thread::spawn(|| (3, FileDialog::new().save_file())));
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(self.path_selection_handle.is_none(), |ui| {
+                            if ui
+                                .button("export workspace")
+                                .on_hover_text(
+                                    "write the bookmarks, the user presets and the samples \
+                                    of the session to a single file",
+                                )
+                                .clicked()
+                            {
+                                self.path_selection_handle =
+                                    Some(thread::spawn(|| (4, FileDialog::new().save_file())));
+                            }
+                            if ui
+                                .button("import workspace")
+                                .on_hover_text("add the bookmarks and presets of a workspace file")
+                                .clicked()
+                            {
+                                self.path_selection_handle =
+                                    Some(thread::spawn(|| (5, FileDialog::new().pick_file())));
+                            }
+                        });
+                        let mut replace = self.import_mode == ImportMode::Replace;
+                        if ui
+                            .checkbox(&mut replace, "replace")
+                            .on_hover_text(
+                                "replace the bookmarks and the presets of the same name \
+                                instead of keeping both",
+                            )
+                            .changed()
+                        {
+                            self.import_mode = if replace {
+                                ImportMode::Replace
+                            } else {
+                                ImportMode::Merge
+                            };
+                        }
+                        if let Some(path) = &self.workspace_path {
+                            ui.weak("(workspace)").on_hover_text(format!(
+                                "bookmarks and presets are saved to {}",
+                                path.display()
+                            ));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui.button("revert unsaved changes").clicked() {
+                            self.params = self.last_saved_params.clone();
+                            self.params_changes.set_breaking();
+                        }
+                        ui.add_enabled_ui(
+                            self.render_info.is_none() && self.param_file_path.is_some(),
+                            |ui| {
+                                let res = ui.button("save parameter file");
+                                let res = if self.param_file_path.is_none() {
+                                    res.on_disabled_hover_text(
+                                        "no path was provided for the parameter file",
+                                    )
+                                } else {
+                                    res
+                                };
+
+                                if res.clicked() {
+                                    match self.save_parameter_file() {
+                                        Ok(_) => self.notify("saved"),
+                                        Err(e) => self.notify(format!("{:?}", e).trim()),
+                                    }
+                                }
+                            },
+                        );
+                        ui.menu_button("load preset", |ui| {
+                            if self.presets.is_none() {
+                                self.load_presets(ui.ctx());
+                            }
+
+                            let mut selected = None;
+                            ScrollArea::vertical()
+                                .max_width(240.)
+                                .max_height(300.)
+                                .show(ui, |ui| {
+                                    for (i, preset) in self.presets.iter().flatten().enumerate() {
+                                        ui.horizontal(|ui| {
+                                            let size = Vec2::splat(Gui::THUMBNAIL_SIZE as f32);
+                                            match self.thumbnails.get(&preset.thumbnail_key) {
+                                                Some(texture) => {
+                                                    ui.add_sized(size, Image::new(texture))
+                                                }
+                                                None => ui.add_sized(size, egui::Spinner::new()),
+                                            };
+                                            let label = if preset.user {
+                                                format!("{} (user)", preset.name)
+                                            } else {
+                                                preset.name.to_owned()
+                                            };
+                                            if ui.button(label).clicked() {
+                                                selected = Some(i);
+                                            }
+                                        });
+                                    }
+                                });
+
+                            if let Some(preset) = selected
+                                .and_then(|i| self.presets.as_ref().map(|presets| &presets[i]))
+                            {
+                                let (name, params) =
+                                    (preset.name.to_owned(), preset.params.clone());
+                                self.load_preset(name, params);
+                                ui.close();
+                            }
+                        });
+                        ui.menu_button("save as preset", |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("name:");
+                                ui.text_edit_singleline(&mut self.preset_name);
+                            });
+                            if ui
+                                .button("save")
+                                .on_hover_text("save the current params to the user presets")
+                                .clicked()
+                            {
+                                match self.save_preset() {
+                                    Ok(path) => {
+                                        self.notify(format!("saved preset to {}", path.display()))
+                                    }
+                                    Err(e) => self.notify(format!("{:?}", e).trim()),
+                                }
+                                ui.close();
+                            }
+                        });
+                    });
+                });
+            });
+    }
+
+    fn bookmarks_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Bookmarks")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    if self
+                        .bookmarks
+                        .as_ref()
+                        .is_none_or(|bookmarks| bookmarks.param_file_path != self.param_file_path)
+                    {
+                        self.load_bookmarks(ui.ctx());
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("add bookmark")
+                            .on_hover_text("bookmark the current location")
+                            .clicked()
+                        {
+                            self.add_bookmark(ui.ctx());
+                        }
+                        if self
+                            .bookmarks
+                            .as_ref()
+                            .is_some_and(|bookmarks| bookmarks.path.is_none())
+                        {
+                            ui.weak("(not saved)")
+                                .on_hover_text("bookmarks are saved next to the parameter file");
+                        }
+                    });
+
+                    let mut selected = None;
+                    let mut removed = None;
+                    ScrollArea::vertical()
+                        .id_salt("bookmarks")
+                        .max_height(300.)
+                        .show(ui, |ui| {
+                            let entries = self.bookmarks.iter().flat_map(|b| &b.entries);
+                            for (i, entry) in entries.enumerate() {
+                                ui.horizontal(|ui| {
+                                    let size = Vec2::splat(Gui::THUMBNAIL_SIZE as f32);
+                                    let res = match self.thumbnails.get(&entry.thumbnail_key) {
+                                        Some(texture) => ui.add_sized(
+                                            size,
+                                            Image::new(texture).sense(Sense::click()),
+                                        ),
+                                        None => ui.add_sized(size, egui::Spinner::new()),
+                                    };
+                                    let bookmark = &entry.bookmark;
+                                    let hover_text = format!(
+                                        "re: {}\nim: {}",
+                                        format_coordinate(bookmark.center_x, bookmark.zoom),
+                                        format_coordinate(bookmark.center_y, bookmark.zoom)
+                                    );
+                                    if res.clicked()
+                                        || ui
+                                            .button(&entry.label)
+                                            .on_hover_text(hover_text)
+                                            .clicked()
+                                    {
+                                        selected = Some(i);
+                                    }
+                                    if ui.small_button("x").on_hover_text("remove").clicked() {
+                                        removed = Some(i);
+                                    }
+                                });
+                            }
+                        });
+
+                    if let Some(bookmarks) = &mut self.bookmarks {
+                        if let Some(i) = selected {
+                            bookmarks.entries[i].bookmark.apply(&mut self.params);
+                            self.params_changes.set_breaking();
+                        }
+                        if let Some(i) = removed {
+                            bookmarks.entries.remove(i);
+                            self.save_bookmarks();
+                        }
+                    }
+                });
+            });
+    }
+
+    fn calibration_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("calibration:");
+            if let Some((_, cancel)) = &self.calibration_handle {
+                ui.spinner();
+                if ui.button("cancel").clicked() {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                return;
+            }
+
+            let label = match &self.calibration {
+                Some(calibration) => {
+                    ui.code(format!(
+                        " {:.1}M samples/s ",
+                        calibration.samples_per_sec / 1e6
+                    ))
+                    .on_hover_text(format!(
+                        "measured for {} on {}, previews are rendered at {}px",
+                        calibration.version, calibration.cpu, self.preview_size
+                    ));
+                    "recalibrate"
+                }
+                None => "calibrate",
+            };
+            let res = ui.add_enabled(!self.calibration_pending, Button::new(label));
+            if res
+                .on_hover_text(
+                    "measure the speed of this machine to pick the preview resolution, \
+                    the suggested sampling level and the estimated render times",
+                )
+                .clicked()
+            {
+                self.calibration_pending = true;
+            }
+        });
+    }
+
+    fn render_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Render")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.render_info.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("output image:");
+                        path_text_edit(ui, &mut self.output_image_path)
+                            .on_hover_text("path the image is saved to");
+                        ui.add_enabled_ui(self.path_selection_handle.is_none(), |ui| {
+                            if ui.button("browse").clicked() {
+                                self.path_selection_handle =
+                                    Some(thread::spawn(|| (1, FileDialog::new().save_file())));
+                            }
+                        });
+                    });
+
+                    ui.horizontal(|ui| {
+                        // The ratio of the view, the image is kept at it
+                        // so that its pixels are square.
+                        let ratio = self
+                            .params
+                            .aspect
+                            .unwrap_or(self.params.img_width as F / self.params.img_height as F);
+                        let size = (self.params.img_width, self.params.img_height);
+                        ui.label("image width:");
+                        let res1 = ui.add(
+                            DragValue::new(&mut self.params.img_width)
+                                .range(32..=20000)
+                                .speed(4.),
+                        );
+                        ui.label("image height:");
+                        let res2 = ui.add(
+                            DragValue::new(&mut self.params.img_height)
+                                .range(32..=20000)
+                                .speed(4.),
+                        );
+                        let lock_res = ui
+                            .checkbox(&mut self.lock_aspect, "lock aspect")
+                            .on_hover_text(
+                                "keep the image ratio at the ratio of the view in the complex plane \
+                                when changing its size, so that circles stay circles",
+                            );
+
+                        if self.lock_aspect {
+                            if res1.changed() || lock_res.changed() {
+                                self.params.img_height = (self.params.img_width as F / ratio)
+                                    .round()
+                                    .clamp(32., 20000.)
+                                    as u32;
+                            } else if res2.changed() {
+                                self.params.img_width = (self.params.img_height as F * ratio)
+                                    .round()
+                                    .clamp(32., 20000.)
+                                    as u32;
+                            }
+                        }
+                        if (self.params.img_width, self.params.img_height) != size {
+                            self.params_changes.set_breaking();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut stretched = self.params.aspect.is_some();
+                        let res = ui.checkbox(&mut stretched, "view aspect:").on_hover_text(
+                            "width to height ratio of the view in the complex plane, \
+                            which stretches the pixels when it differs from the image ratio",
+                        );
+                        if res.changed() {
+                            self.params.aspect = stretched.then_some(
+                                self.params.img_width as F / self.params.img_height as F,
+                            );
+                            self.params_changes.set_breaking();
+                        }
+
+                        if let Some(aspect) = self.params.aspect.as_mut() {
+                            let res = ui.add(DragValue::new(aspect).range(0.01..=100.).speed(0.01));
+                            if res.changed() {
+                                if self.lock_aspect {
+                                    self.params.img_height = (self.params.img_width as F / *aspect)
+                                        .round()
+                                        .clamp(32., 20000.)
+                                        as u32;
+                                }
+                                self.params_changes.set_breaking();
+                            }
+                        }
+                    });
+
+                    let (width, height) = View::new(&self.params).size;
+                    ui.label(format!("view extents: {:.4e} × {:.4e}", width, height))
+                        .on_hover_text("size of the view in the complex plane");
+
+                    ui.horizontal(|ui| {
+                        ui.label("threads:");
+                        let available = thread::available_parallelism().map_or(1, |n| n.get());
+                        let mut threads = self.params.threads.unwrap_or(0);
+                        let res = ui
+                            .add(
+                                DragValue::new(&mut threads)
+                                    .range(0..=available)
+                                    .custom_formatter(|n, _| {
+                                        if n == 0. {
+                                            "all".to_string()
+                                        } else {
+                                            n.to_string()
+                                        }
+                                    }),
+                            )
+                            .on_hover_text("number of threads used to render");
+                        if res.changed() {
+                            self.params.threads = if threads > 0 { Some(threads) } else { None };
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("supersample:");
+                        let mut factor = self.params.supersample.unwrap_or(1);
+                        let res = ui
+                            .add(DragValue::new(&mut factor).range(1..=8).suffix("×"))
+                            .on_hover_text(
+                                "render the image this many times larger and average it down, \
+                                which smooths edges at the cost of rendering factor² times more pixels",
+                            );
+                        if res.changed() {
+                            self.params.supersample = (factor > 1).then_some(factor);
+                            self.params_changes.set_breaking();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut seeded = self.params.seed.is_some();
+                        let res = ui.checkbox(&mut seeded, "seed:").on_hover_text(
+                            "seed the random offsets so that renders are reproducible",
+                        );
+                        if res.changed() {
+                            self.params.seed = seeded.then_some(0);
+                            self.params_changes.set_breaking();
+                        }
+
+                        if let Some(seed) = self.params.seed.as_mut() {
+                            if ui.add(DragValue::new(seed)).changed() {
+                                self.params_changes.set_breaking();
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("filter:");
+                        let mut filter = self.params.sampling.filter();
+                        ComboBox::from_id_salt("reconstruction_filter")
+                            .selected_text(Self::filter_name(filter))
+                            .show_ui(ui, |ui| self.show_combobox_filter(ui, filter.radius()))
+                            .response
+                            .on_hover_text("filter used to combine the samples of a pixel");
+
+                        ui.label("radius:");
+                        let res = ui.add(
+                            DragValue::new(filter.radius_mut())
+                                .range(0.5..=4.)
+                                .speed(0.01),
+                        );
+                        if res.changed() {
+                            self.params.sampling.filter = Some(filter);
+                            self.params_changes.set_breaking();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut pilot = self.pilot.is_some();
+                        let res = ui.checkbox(&mut pilot, "pilot mode").on_hover_text(
+                            "render low resolution previews continuously while dragging",
+                        );
+                        if res.changed() {
+                            self.pilot = pilot.then(|| PilotWorker::new(ui.ctx().clone()));
+                        }
+                    });
+
+                    self.calibration_row(ui);
+
+                    ui.horizontal(|ui| {
+                        ui.label("current spp:")
+                            .on_hover_text("number of samples per pixel of the internal image");
+                        ui.code(format!(
+                            " {} ",
+                            (10. * self.samples_per_pixel).round() / 10.
+                        ));
+                    });
+
+                    ui.horizontal(|ui| {
+                        let inner_res = ComboBox::from_id_salt("sampling_level")
+                            .selected_text(
+                                self.labels.sampling_level.get(&self.params.sampling.level),
+                            )
+                            .show_ui(ui, |ui| {
+                                self.show_combobox_sampling_level(ui);
+                            });
+                        inner_res.response.on_hover_text("sampling level");
+
+                        ComboBox::from_id_salt("sampling_pattern")
+                            .selected_text(
+                                self.labels
+                                    .sampling_pattern
+                                    .get(&self.params.sampling.pattern),
+                            )
+                            .show_ui(ui, |ui| {
+                                self.show_combobox_sampling_pattern(ui);
+                            })
+                            .response
+                            .on_hover_text("distribution of the samples inside a pixel");
+
+                        let res = ui
+                            .button(match self.params.sampling.level {
+                                SamplingLevel::Adaptive { base, max } => {
+                                    format!("sample fractal (+{}-{} spp)", base, max)
+                                }
+                                _ => format!(
+                                    "sample fractal (+{} spp)",
+                                    self.params.sampling.sample_count()
+                                ),
+                            })
+                            .on_hover_text("collect new samples");
+                        if res.clicked() {
+                            self.render_info = Some(self.render_and_save());
+                        };
+
+                        let no_samples = self.samples_per_pixel == 0.;
+                        let no_output_image_path = self.output_image_path.is_none();
+                        ui.add_enabled_ui(!(no_samples || no_output_image_path), |ui| {
+                            let res = {
+                                let btn = ui.button("save image");
+
+                                if no_output_image_path {
+                                    btn.on_disabled_hover_text(
+                                        "no path was provided for the output image",
+                                    )
+                                } else if no_samples {
+                                    btn.on_disabled_hover_text(
+                                        "sample the fractal before saving the image",
+                                    )
+                                } else {
+                                    btn
+                                }
+                            };
+
+                            self.should_save_image |= res.clicked();
+                        });
+
+                        const SAVE_SCALES: &[(F, &str)] =
+                            &[(1., "100%"), (0.5, "50%"), (0.25, "25%")];
+                        let save_scale = self.params.save_scale.unwrap_or(1.);
+                        ComboBox::from_id_salt("save_scale")
+                            .width(60.)
+                            .selected_text(
+                                SAVE_SCALES
+                                    .iter()
+                                    .find(|&&(scale, _)| scale == save_scale)
+                                    .map(|&(_, name)| name.to_string())
+                                    .unwrap_or_else(|| format!("{:.0}%", 100. * save_scale)),
+                            )
+                            .show_ui(ui, |ui| {
+                                for &(scale, name) in SAVE_SCALES {
+                                    if ui.selectable_label(scale == save_scale, name).clicked() {
+                                        self.params.save_scale = (scale != 1.).then_some(scale);
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("scale of the saved image");
+                    });
+                });
+            });
+    }
+
+    fn statistics_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Statistics")
+            .default_open(false)
+            .show(ui, |ui| {
+                let Some(stats) = &self.render_stats else {
+                    ui.label("render to see the distribution of the values");
+                    return;
+                };
+
+                Grid::new("statistics grid").show(ui, |ui| {
+                    let rows = [
+                        ("min".to_string(), stats.min),
+                        ("median".to_string(), stats.median),
+                        ("mean".to_string(), stats.mean),
+                        ("max".to_string(), stats.max),
+                    ]
+                    .into_iter()
+                    .chain(
+                        STATS_PERCENTILES
+                            .iter()
+                            .zip(stats.percentiles)
+                            .map(|(p, v)| (format!("{}%", p), v)),
+                    );
+                    for (name, value) in rows {
+                        ui.label(name);
+                        ui.label(format!("{:.6}", value));
+                        ui.end_row();
+                    }
+                    ui.label("interior");
+                    ui.label(format!("{:.1}%", 100. * stats.interior_fraction))
+                        .on_hover_text("pixels which reached max_iter");
+                    ui.end_row();
+                    if stats.non_finite_count > 0 {
+                        ui.label("non-finite");
+                        ui.colored_label(Color32::RED, stats.non_finite_count.to_string())
+                            .on_hover_text(
+                                "pixels with NaN or infinite values, colored as the nearest extremum",
+                            );
+                        ui.end_row();
+                    }
+                });
+
+                histogram_chart(ui, &stats.histogram)
+                    .on_hover_text(format!("values from {:.6} to {:.6}", stats.min, stats.max));
+            });
+    }
+
+    fn provenance_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Where did this value come from?")
+            .default_open(false)
+            .show(ui, |ui| {
+                Grid::new("provenance grid").show(ui, |ui| {
+                    for (name, value, source) in self.provenance.iter() {
+                        ui.label(name).on_hover_text(value);
+                        ui.label(source.to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn preview_section(&mut self, ui: &mut egui::Ui) {
+        const INFO_AREA_HEIGHT: f32 = 48.;
+
+        ui.horizontal(|ui| {
+            ui.heading("Preview");
+            if let Some(DevOptions {
+                ui_time: Some(true),
+                ..
+            }) = self.params.dev_options
+            {
+                let text = format!("ui: {:.2}ms", self.ui_time.as_secs_f64() * 1000.);
+                if self.ui_time > UI_TIME_BUDGET {
+                    ui.colored_label(Color32::RED, text);
+                } else {
+                    ui.label(text);
+                }
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let res = ui
+                    .add_enabled(
+                        self.previous_preview.size() != [0, 0],
+                        Button::new("compare"),
+                    )
+                    .on_hover_text("hold to show the preview from before the last change");
+                self.comparing = res.is_pointer_button_down_on();
+            });
+        });
+        ui.separator();
+
+        let preview_texture = if self.comparing {
+            &self.previous_preview
+        } else {
+            &self.preview_texture
+        };
+        // Previews are shown at the same size whatever their
+        // resolution, pilot previews being smaller and the
+        // calibration picking the resolution of full ones.
+        let texture_size = preview_texture.size_vec2();
+        let texture_size = match texture_size.max_elem() {
+            0. => texture_size,
+            size => texture_size * (Gui::PREVIEW_SIZE as f32 / size),
+        };
+        let coordinates_height = ui.text_style_height(&egui::TextStyle::Body);
+        let d =
+            0.5 * (ui.available_height() - texture_size.y - coordinates_height - INFO_AREA_HEIGHT);
+        ui.add_space(d);
+        let res = ui.add_sized(
+            texture_size,
+            Image::from_texture((preview_texture.id(), texture_size))
+                .show_loading_spinner(false)
+                .maintain_aspect_ratio(true)
+                .corner_radius(2)
+                .sense(Sense::drag()),
+        );
+        self.box_zoom(ui, &res);
+
+        // Complex coordinate under the pointer, as typed in the center
+        // fields.
+        let coordinates = res.hover_pos().map(|pos| {
+            let (re, im) = self.preview_to_center(res.rect, pos);
+            format!(
+                "re: {}  im: {}",
+                format_coordinate(re, self.params.zoom),
+                format_coordinate(im, self.params.zoom)
+            )
+        });
+        ui.allocate_ui(Vec2::new(ui.available_width(), coordinates_height), |ui| {
+            ui.vertical_centered(|ui| ui.label(coordinates.unwrap_or_default()));
+        });
+        if let Some(DevOptions {
+            preview_cache_stats: Some(true),
+            ..
+        }) = self.params.dev_options
+        {
+            res.on_hover_text(format!(
+                "preview cache: {} hits, {} misses",
+                self.preview_cache.hits, self.preview_cache.misses
+            ));
+        }
+        ui.add_space(d);
+
+        ui.with_layout(
+            egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
+            |ui| {
+                if let Some((_, progress, cancel, start, _)) = &self.render_info {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            ProgressBar::new(progress.get_progress())
+                                .desired_height(4.)
+                                .desired_width(128.)
+                                .corner_radius(0.)
+                                .fill(Color32::WHITE),
+                        );
+                        ui.label(format_remaining_time(progress.remaining_time(*start)));
+                        if ui
+                            .add_enabled(!cancel.load(Ordering::Relaxed), Button::new("cancel"))
+                            .on_hover_text("stop the render and discard its samples")
+                            .clicked()
+                        {
+                            cancel.store(true, Ordering::Relaxed);
+                        }
+                    });
+                } else if let Some(autosave) = &self.autosave_offer {
+                    let text = format!(
+                        "autosave found ({:.1} samples per pixel)",
+                        autosave.samples_per_pixel
+                    );
+                    ui.horizontal(|ui| {
+                        ui.label(text);
+                        if ui
+                            .button("restore")
+                            .on_hover_text("add the samples of the previous session")
+                            .clicked()
+                        {
+                            self.restore_autosave();
+                        }
+                        if ui.button("discard").clicked() {
+                            self.autosave_offer = None;
+                            self.autosave_stale = true;
+                        }
+                    });
+                } else if let Some((text, start)) = self.message.as_mut() {
+                    const MESSAGE_DISPLAY_TIME: Duration = Duration::from_secs(5);
+                    ui.label(text.as_str());
+                    if start.elapsed() > MESSAGE_DISPLAY_TIME {
+                        self.message = None;
+                    }
+                }
+            },
+        );
+    }
+
+    /// Zooms into the rectangle drawn on the preview by dragging the
+    /// mouse, which is shown while dragging.
+    fn box_zoom(&mut self, ui: &egui::Ui, res: &egui::Response) {
+        /// Smaller selections are ignored, they are most likely
+        /// clicks.
+        const MIN_SELECTION_SIZE: f32 = 4.;
+
+        if self.render_info.is_some() {
+            self.box_zoom_selection = None;
+            return;
+        }
+
+        if res.dragged() {
+            let origin = ui.input(|i| i.pointer.press_origin());
+            if let (Some(origin), Some(pos)) = (origin, res.interact_pointer_pos()) {
+                self.box_zoom_selection = Some(Rect::from_two_pos(origin, pos).intersect(res.rect));
+            }
+        }
+        let Some(selection) = self.box_zoom_selection else {
+            return;
+        };
+
+        if !res.drag_stopped() {
+            ui.painter()
+                .rect_stroke(selection, 0., (1., Color32::WHITE), StrokeKind::Inside);
+            return;
+        }
+
+        self.box_zoom_selection = None;
+        if selection.width().min(selection.height()) < MIN_SELECTION_SIZE {
+            return;
+        }
+
+        (self.params.center_x, self.params.center_y) =
+            self.preview_to_center(res.rect, selection.center());
+
+        // The whole selection stays in view.
+        let scale =
+            (selection.width() / res.rect.width()).max(selection.height() / res.rect.height());
+        self.params.zoom *= scale as F;
+
+        self.params_changes.set_breaking();
+    }
+
+    /// Maps a position on the preview to the complex plane (rotation
+    /// included), as `(center_x, center_y)` to center the view on
+    /// it.
+    fn preview_to_center(&self, preview_rect: Rect, pos: egui::Pos2) -> (F, F) {
+        let t = (pos - preview_rect.min) / preview_rect.size();
+        let (dx, dy) = View::new(&self.params).pixel_to_delta(
+            t.x as F * self.params.img_width as F,
+            t.y as F * self.params.img_height as F,
+        );
+        // The imaginary axis of the view points down.
+        (self.params.center_x + dx, self.params.center_y - dy)
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        /// Fraction of the view size the view is moved by.
+        const PAN_FRACTION: F = 0.1;
+        const ZOOM_FACTOR: F = 1.25;
+
+        // Ignore shortcuts while typing in a field or while rendering.
+        if ctx.wants_keyboard_input() || self.render_info.is_some() {
+            return;
+        }
+
+        let (save_parameter_file, pan, zoom, render, save_image) = ctx.input_mut(|i| {
+            let save_parameter_file = i.consume_key(Modifiers::COMMAND, Key::S);
+
+            let mut pressed = |key| i.consume_key(Modifiers::NONE, key);
+            let pan = [
+                (Key::ArrowLeft, (-1., 0.)),
+                (Key::ArrowRight, (1., 0.)),
+                (Key::ArrowUp, (0., -1.)),
+                (Key::ArrowDown, (0., 1.)),
+            ]
+            .into_iter()
+            .filter(|&(key, _)| pressed(key))
+            .fold((0., 0.), |(x, y), (_, (dx, dy))| (x + dx, y + dy));
+            let zoom = if pressed(Key::Plus) || pressed(Key::Equals) {
+                Some(1. / ZOOM_FACTOR)
+            } else if pressed(Key::Minus) {
+                Some(ZOOM_FACTOR)
+            } else {
+                None
+            };
+
+            (
+                save_parameter_file,
+                pan,
+                zoom,
+                pressed(Key::R),
+                pressed(Key::S),
+            )
+        });
+
+        if pan != (0., 0.) {
+            // Pan in screen space, so the rotation of the view has to
+            // be taken into account.
+            let step = PAN_FRACTION * self.params.zoom;
+            let (sin, cos) = self.params.rotate.unwrap_or(0.).sin_cos();
+            let (dx, dy) = (step * pan.0, step * pan.1);
+            self.params.center_x += dx * cos - dy * sin;
+            self.params.center_y -= dx * sin + dy * cos;
+            self.params_changes.set_breaking();
+        }
+        if let Some(factor) = zoom {
+            self.params.zoom *= factor;
+            self.params_changes.set_breaking();
+        }
+        if render {
+            self.render_info = Some(self.render_and_save());
+        }
+        if save_image && self.samples_per_pixel != 0. && self.output_image_path.is_some() {
+            self.should_save_image = true;
+        }
+        if save_parameter_file && self.param_file_path.is_some() {
+            match self.save_parameter_file() {
+                Ok(_) => self.notify("saved"),
+                Err(e) => self.notify(format!("{:?}", e).trim()),
+            }
+        }
+    }
+
+    /// Loads the parameters embedded in png images dropped on the
+    /// window.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let paths = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect::<Vec<_>>()
+        });
+
+        for path in paths {
+            if self.render_info.is_some() {
+                self.notify("wait for the render to finish before opening an image");
+                return;
+            }
+
+            match Self::read_image_params(&path) {
+                Ok((params, metadata)) => {
+                    self.params = params;
+                    self.provenance
+                        .record(&self.params, Source::File(path.to_owned()));
+                    self.params_changes.set_breaking();
+                    self.notify(format!(
+                        "loaded {} ({})",
+                        path.file_name().unwrap_or_default().to_string_lossy(),
+                        metadata.describe()
+                    ));
+                }
+                Err(e) => self.notify(format!("{:?}", e).trim()),
+            }
+        }
+    }
+
+    fn read_image_params(path: &Path) -> Result<(FrameParams, png_metadata::Metadata)> {
+        let bytes = fs::read(path).map_err(ErrorKind::ReadImage)?;
+        if !png_metadata::is_png(&bytes) {
+            return Err(ErrorKind::MissingEmbeddedParams);
+        }
+
+        let metadata = png_metadata::read_metadata(bytes.as_slice())?;
+        match ParamsKind::from_ron(&metadata.params).map_err(ErrorKind::DecodeParameterFile)? {
+            ParamsKind::Frame(params) => Ok((*params, metadata)),
+            ParamsKind::Animation(_) => Err(ErrorKind::MissingEmbeddedParams),
+        }
+    }
+
+    fn load_parameter_file(&mut self) {
+        let Some(path) = self.param_file_path.clone() else {
+            return;
+        };
+
+        match Self::read_parameter_file(&path) {
+            Ok(params) => {
+                self.params = params;
+                self.last_saved_params = self.params.clone();
+                self.provenance
+                    .record(&self.params, Source::File(path.to_owned()));
+                self.params_changes.set_breaking();
+                self.notify(format!(
+                    "loaded {}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+            }
+            Err(e) => self.notify(format!("{:?}", e).trim()),
+        }
+    }
+
+    /// Reads the parameters of a parameter file (or of a rendered
+    /// png image) along with the gradient file it refers to.
+    fn read_parameter_file(path: &Path) -> Result<FrameParams> {
+        let bytes = fs::read(path).map_err(ErrorKind::ReadParameterFile)?;
+        let (params_str, format) = if png_metadata::is_png(&bytes) {
+            (
+                png_metadata::read_metadata(bytes.as_slice())?.params,
+                ParamsFormat::Ron,
+            )
+        } else {
+            let params_str = String::from_utf8(bytes).map_err(|e| {
+                ErrorKind::ReadParameterFile(io::Error::new(io::ErrorKind::InvalidData, e))
+            })?;
+            (params_str, ParamsFormat::from_path(path))
+        };
+
+        let mut params = match ParamsKind::decode(&params_str, format)? {
+            ParamsKind::Frame(params) => *params,
+            ParamsKind::Animation(_) => return Err(ErrorKind::AnimationInGui),
+        };
+
+        if let Some(gradient_path) = params.gradient_path.as_ref() {
+            // Relative gradient paths are relative to the parameter
+            // file.
+            let gradient_path = path
+                .parent()
+                .map_or_else(|| gradient_path.to_owned(), |dir| dir.join(gradient_path));
+            params.gradient = GradientSpec::Custom(load_gradient(&gradient_path)?);
+        }
+        if let GradientSpec::Custom(gradient) = &mut params.gradient {
+            for warning in sanitize_gradient(gradient) {
+                eprintln!("warning: {}", warning);
+            }
+        }
+        params.gradient.validate()?;
+
+        Ok(params)
+    }
+
+    fn handle_update(&mut self, ctx: &egui::Context) {
+        if self.render_info.is_some() {
+            ctx.request_repaint();
+        }
+
+        if self.params.value == ValueKind::DistanceEstimate
+            && !self.params.fractal.supports_distance_estimate()
+        {
+            self.params.value = ValueKind::EscapeTime;
+            self.params_changes.set_breaking();
+            self.notify("distance estimates are not supported by this fractal, using escape time");
+        }
+
+        if self.params_changes.breaking() {
+            // The view may have changed.
+            self.params.apply_auto_max_iter();
+
+            // Params relative to fractal and position have
+            // changed: stored raw_image is no longer valid.
+            self.raw_image = None;
+            self.raw_channels = None;
+            self.raw_extrema = None;
+            self.render_stats = None;
+            self.samples_per_pixel = 0.;
+            self.preview_cache.clear();
+            self.autosave_stale = true;
+            self.autosave_offer = None;
+        }
 
         if self.params_changes.changed() {
-            self.update_preview();
+            self.provenance.record(&self.params, Source::GuiEdit);
+            let interacting = ctx.dragged_id().is_some() || ctx.input(|i| i.pointer.any_down());
+            match &self.pilot {
+                Some(pilot) if interacting => {
+                    self.preview_generation += 1;
+                    pilot.request(self.preview_generation, pilot_params(&self.params));
+                    self.pilot_last_change = Some(Instant::now());
+                }
+                _ => self.update_preview(),
+            }
             self.params_changes.set_none();
         }
+        self.handle_pilot(ctx);
 
         if self
             .render_info
             .as_ref()
-            .is_some_and(|(h, _)| h.is_finished())
+            .is_some_and(|(h, _, _, _, _)| h.is_finished())
         {
-            let (handle, _) = self.render_info.take().unwrap();
-
-            let (new_raw_image, start) = handle.join().unwrap();
+            let (handle, _, cancel, _, snapshot) = self.render_info.take().unwrap();
+
+            match handle.join() {
+                Ok((new_raw_image, new_raw_channels, added_sample_count, start)) => {
+                    if cancel.load(Ordering::Relaxed) {
+                        // The partial render is discarded, the samples
+                        // taken before it are restored.
+                        self.restore_samples(snapshot);
+                        self.notify("render cancelled");
+                    } else {
+                        self.add_samples(new_raw_image, new_raw_channels, added_sample_count);
+                        self.notify(format!("{:.1}s elapsed", start.as_secs_f32()));
+                    }
+                }
+                Err(payload) => {
+                    if let Some(DevOptions {
+                        reraise_panics: Some(true),
+                        ..
+                    }) = self.params.dev_options
+                    {
+                        panic::resume_unwind(payload);
+                    }
 
-            let added_sample_count = self.params.sampling.sample_count();
-            if let Some(raw_image) = self.raw_image.as_mut() {
-                let w1 = self.samples_per_pixel as F;
-                let w2 = added_sample_count as F;
-                for (x, y) in raw_image.enumerate() {
-                    raw_image[(x, y)] =
-                        (w1 * raw_image[(x, y)] + w2 * new_raw_image[(x, y)]) / (w1 + w2);
+                    // Same as a cancelled render, the failed pass is
+                    // discarded.
+                    self.restore_samples(snapshot);
+                    let message = panic_message(payload.as_ref());
+                    match write_crash_log(&message, &self.params) {
+                        Ok(path) => self.notify(format!(
+                            "render failed: {} — please report (crash log: {})",
+                            message,
+                            path.display()
+                        )),
+                        Err(e) => self.notify(format!(
+                            "render failed: {} — please report ({})",
+                            message,
+                            format!("{:?}", e).trim()
+                        )),
+                    }
                 }
-            } else {
-                self.raw_image = Some(new_raw_image);
             }
-            self.samples_per_pixel += added_sample_count;
-
-            self.notify(format!("{:.1}s elapsed", start.as_secs_f32()));
         }
 
         if self.should_save_image {
             if let Some(output_image_path) = self.output_image_path.as_ref() {
                 if let Some(raw_image) = &self.raw_image {
-                    let output_image = color_raw_image(&self.params, raw_image.to_owned());
-
-                    match output_image.save(&output_image_path) {
+                    let output_image = color_raw_channels(
+                        &self.params,
+                        raw_image.to_owned(),
+                        self.raw_channels.as_ref(),
+                    );
+                    let output_image = match self.params.save_scale {
+                        Some(scale) if scale != 1. => scale_image(&output_image, scale),
+                        _ => output_image,
+                    };
+
+                    match png_metadata::save_image(
+                        output_image.into(),
+                        &self.params,
+                        self.samples_per_pixel,
+                        output_image_path,
+                    ) {
                         Ok(_) => self.notify("image saved"),
-                        Err(_) => self.notify("failed to save image"),
+                        Err(e) => self.notify(format!("{:?}", e).trim()),
                     }
                 }
             }
@@ -739,75 +2510,401 @@ impl Gui {
             .is_some_and(|h| h.is_finished())
         {
             let (which, path) = self.path_selection_handle.take().unwrap().join().unwrap();
-            if which == 0 {
-                self.param_file_path = path;
-            } else if which == 1 {
-                self.output_image_path = path;
+            // Closing the dialog keeps the current path.
+            if let (0, Some(path)) = (which, &path) {
+                self.param_file_path = Some(path.to_owned());
+            } else if let (1, Some(path)) = (which, &path) {
+                self.output_image_path = Some(path.to_owned());
+            } else if let (3, Some(path)) = (which, &path) {
+                match self.export_zoom_animation(path) {
+                    Ok(_) => self.notify("zoom animation exported"),
+                    Err(e) => self.notify(format!("{:?}", e).trim()),
+                }
+            } else if let (4, Some(path)) = (which, &path) {
+                match self.export_workspace(path) {
+                    Ok(_) => self.notify("workspace exported"),
+                    Err(e) => self.notify(format!("{:?}", e).trim()),
+                }
+            } else if let (5, Some(path)) = (which, &path) {
+                if let Err(e) = self.import_workspace(ctx, path) {
+                    self.notify(format!("{:?}", e).trim());
+                }
+            } else if let (2, Some(path)) = (which, path) {
+                match load_gradient(&path) {
+                    Ok(mut gradient) => {
+                        for warning in sanitize_gradient(&mut gradient) {
+                            eprintln!("warning: {}", warning);
+                        }
+                        self.params.gradient = GradientSpec::Custom(gradient);
+                        self.params.gradient_path = None;
+                        self.params_changes.set_non_breaking();
+                        self.notify("gradient loaded");
+                    }
+                    Err(e) => self.notify(format!("{:?}", e).trim()),
+                }
+            }
+        }
+    }
+
+    /// Accumulates samples into the raw image and its channels.
+    /// Channels rendered after the samples they were missing from
+    /// are kept as they are.
+    fn add_samples(
+        &mut self,
+        new_raw_image: Mat2D<F>,
+        new_raw_channels: Option<RawChannels>,
+        added_sample_count: F,
+    ) {
+        match self.raw_image.as_mut() {
+            Some(raw_image) => accumulate_samples(
+                &self.params.fractal,
+                raw_image,
+                &mut self.raw_channels,
+                self.samples_per_pixel,
+                &new_raw_image,
+                new_raw_channels,
+                added_sample_count,
+            ),
+            None => {
+                self.raw_image = Some(new_raw_image);
+                if new_raw_channels.is_some() {
+                    self.raw_channels = new_raw_channels;
+                }
             }
         }
+        self.samples_per_pixel += added_sample_count;
+        self.update_raw_stats();
     }
 
-    fn render_and_save(&mut self) -> (JoinHandle<(Mat2D<F>, Duration)>, Progress) {
-        let progress = Progress::new((self.params.img_width * self.params.img_height) as usize);
+    /// Puts back the samples taken before a render.
+    fn restore_samples(&mut self, snapshot: SamplesSnapshot) {
+        snapshot.restore(
+            &mut self.raw_image,
+            &mut self.raw_channels,
+            &mut self.samples_per_pixel,
+        );
+        self.update_raw_stats();
+    }
+
+    fn update_raw_stats(&mut self) {
+        // Extrema of the values as colored.
+        self.raw_extrema = self.raw_image.as_ref().map(|raw_image| {
+            raw_extrema(&map_distance_estimates(&self.params, raw_image.to_owned()))
+        });
+        self.render_stats = self
+            .raw_image
+            .as_ref()
+            .map(|raw_image| analyze_raw_image(&self.params, raw_image));
+    }
+
+    /// Offers to restore the autosave of a previous session when it
+    /// matches the params, stale autosaves are removed.
+    fn find_autosave(&mut self) {
+        let Some((path, _)) = &self.autosave else {
+            return;
+        };
+        if !path.exists() {
+            return;
+        }
+
+        match read_autosave(path) {
+            Ok(autosave) if raw_params_match(&autosave.params, &self.params) => {
+                self.autosave_offer = Some(autosave);
+            }
+            result => {
+                if let Err(e) = result {
+                    eprintln!("warning: {}", format!("{:?}", e).trim());
+                }
+                if let Err(e) = remove_autosave(path) {
+                    eprintln!("warning: {}", format!("{:?}", e).trim());
+                }
+            }
+        }
+    }
+
+    fn restore_autosave(&mut self) {
+        if let Some(autosave) = self.autosave_offer.take() {
+            // Samples rendered since the start of the session are kept.
+            self.add_samples(autosave.raw_image, None, autosave.samples_per_pixel);
+            self.autosaved_samples = self.samples_per_pixel;
+            self.notify("autosave restored");
+        }
+    }
 
-        let params_clone = self.params.clone();
+    /// Writes the accumulated samples on a separate thread every
+    /// autosave interval, when samples were added since the last
+    /// autosave.
+    fn handle_autosave(&mut self, ctx: &egui::Context) {
+        let Some((path, interval)) = self.autosave.clone() else {
+            return;
+        };
+
+        if self
+            .autosave_handle
+            .as_ref()
+            .is_some_and(|h| h.is_finished())
+        {
+            if let Err(e) = self.autosave_handle.take().unwrap().join().unwrap() {
+                self.notify(format!("{:?}", e).trim());
+            }
+        }
+        if self.autosave_handle.is_some() {
+            return;
+        }
+
+        if self.autosave_stale {
+            if let Err(e) = remove_autosave(&path) {
+                self.notify(format!("{:?}", e).trim());
+            }
+            self.autosaved_samples = 0.;
+            self.autosave_stale = false;
+        }
+
+        // A pending autosave must not be overwritten.
+        if self.autosave_offer.is_some() || self.samples_per_pixel == self.autosaved_samples {
+            return;
+        }
+        let Some(raw_image) = &self.raw_image else {
+            return;
+        };
+
+        let remaining = interval.saturating_sub(self.last_autosave.elapsed());
+        if remaining.is_zero() {
+            let autosave = Autosave {
+                params: self.params.clone(),
+                samples_per_pixel: self.samples_per_pixel,
+                raw_image: raw_image.to_owned(),
+            };
+            self.autosave_handle = Some(thread::spawn(move || write_autosave(&autosave, &path)));
+            self.autosaved_samples = self.samples_per_pixel;
+            self.last_autosave = Instant::now();
+        } else {
+            ctx.request_repaint_after(remaining);
+        }
+    }
+
+    /// Reads the calibration from the settings, a calibration is
+    /// started after the first frame when there is no current one.
+    fn load_calibration(&mut self) {
+        match settings_path().and_then(|path| load_settings(&path)) {
+            Ok(settings) => {
+                self.calibration = settings
+                    .calibration
+                    .filter(|calibration| calibration.is_current());
+                self.calibration_pending = self.calibration.is_none();
+            }
+            Err(ErrorKind::MissingConfigDir) => self.calibration_pending = true,
+            Err(e) => {
+                self.notify(format!("{:?}", e).trim());
+                self.calibration_pending = true;
+            }
+        }
+        self.apply_calibration();
+    }
+
+    /// Sets the preview resolution and the cache budget from the
+    /// calibration.
+    fn apply_calibration(&mut self) {
+        let Some(calibration) = &self.calibration else {
+            return;
+        };
+        let preview_size = calibration.preview_size();
+        self.preview_cache
+            .set_budget(calibration.cache_budget(), preview_size);
+        if preview_size != self.preview_size {
+            self.preview_size = preview_size;
+            self.preview_cache.clear();
+            self.params_changes.set_non_breaking();
+        }
+    }
+
+    /// Starts the pending calibration once the window was shown and
+    /// no render is running, and applies it when it ends. Renders
+    /// started meanwhile would skew it, it is cancelled and started
+    /// again after them.
+    fn handle_calibration(&mut self, ctx: &egui::Context) {
+        if self.render_info.is_some() {
+            if let Some((_, cancel)) = &self.calibration_handle {
+                cancel.store(true, Ordering::Relaxed);
+                self.calibration_pending = true;
+            }
+        }
+
+        if self
+            .calibration_handle
+            .as_ref()
+            .is_some_and(|(handle, _)| handle.is_finished())
+        {
+            let (handle, _) = self.calibration_handle.take().unwrap();
+            match handle.join() {
+                Ok(Some(calibration)) => {
+                    self.notify(format!(
+                        "calibrated: {:.1}M samples/s",
+                        calibration.samples_per_sec / 1e6
+                    ));
+                    if let Err(e) = settings_path().and_then(|path| {
+                        let mut settings = load_settings(&path).unwrap_or_default();
+                        settings.calibration = Some(calibration.clone());
+                        save_settings(&path, &settings)
+                    }) {
+                        eprintln!("warning: {}", format!("{:?}", e).trim());
+                    }
+                    self.calibration = Some(calibration);
+                    self.apply_calibration();
+                }
+                Ok(None) if !self.calibration_pending => self.notify("calibration cancelled"),
+                Ok(None) => {}
+                Err(payload) => self.notify(format!(
+                    "calibration failed: {}",
+                    panic_message(payload.as_ref())
+                )),
+            }
+        } else if self.calibration_handle.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        if self.calibration_pending
+            && self.calibration_handle.is_none()
+            && self.render_info.is_none()
+        {
+            if ctx.cumulative_pass_nr() == 0 {
+                // Not before the first frame is shown.
+                ctx.request_repaint();
+            } else {
+                let cancel = Arc::new(AtomicBool::new(false));
+                let cancel_clone = cancel.clone();
+                self.calibration_handle =
+                    Some((thread::spawn(move || calibrate(&cancel_clone)), cancel));
+                self.calibration_pending = false;
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+        }
+    }
+
+    fn render_and_save(&mut self) -> RunningRender {
+        let mut progress =
+            Progress::new(progress_total(&self.params, &(0..self.params.img_height)));
+        if let Some(calibration) = &self.calibration {
+            progress = progress.with_expected_duration(calibration.expected_duration(&self.params));
+        }
+
+        // Passes after the first one derive their seed from the
+        // number of samples already taken, otherwise they would take
+        // the same samples again.
+        let seed = self.params.seed.map(|seed| {
+            if self.samples_per_pixel == 0. {
+                seed
+            } else {
+                mix_seed(seed, self.samples_per_pixel.round() as u64)
+            }
+        });
+        let params_clone = FrameParams {
+            seed,
+            ..self.params.clone()
+        };
         let sampling_points_clone = self.params.sampling.generate_sampling_points();
         let progress_clone = progress.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_clone = cancel.clone();
         (
             thread::spawn(move || {
                 let start = Instant::now();
-                let raw_image =
-                    render_raw_image(&params_clone, &sampling_points_clone, Some(progress_clone));
-                (raw_image, start.elapsed())
+                let (raw_image, raw_channels, samples_per_pixel) = render_raw_image_channels(
+                    &params_clone,
+                    &sampling_points_clone,
+                    Some(progress_clone),
+                    Some(&cancel_clone),
+                );
+                (raw_image, raw_channels, samples_per_pixel, start.elapsed())
             }),
             progress,
+            cancel,
+            Instant::now(),
+            SamplesSnapshot::take(&self.raw_image, &self.raw_channels, self.samples_per_pixel),
         )
     }
 
+    /// Shows the previews rendered by the pilot mode and renders the
+    /// full preview once the input stopped for a moment.
+    fn handle_pilot(&mut self, ctx: &egui::Context) {
+        const DEBOUNCE_TIME: Duration = Duration::from_millis(150);
+
+        if let Some((generation, image)) = self.pilot.as_ref().and_then(PilotWorker::latest) {
+            if generation > self.shown_generation {
+                self.preview_texture.set(image, Default::default());
+                self.shown_generation = generation;
+            }
+        }
+
+        if let Some(last_change) = self.pilot_last_change {
+            let interacting = ctx.dragged_id().is_some() || ctx.input(|i| i.pointer.any_down());
+            if self.pilot.is_some() && (interacting || last_change.elapsed() < DEBOUNCE_TIME) {
+                ctx.request_repaint_after(DEBOUNCE_TIME);
+            } else {
+                self.pilot_last_change = None;
+                self.update_preview();
+            }
+        }
+    }
+
     fn update_preview(&mut self) {
-        let (preview_width, preview_height) = if self.params.img_width > self.params.img_height {
-            (
-                Gui::PREVIEW_SIZE,
-                (self.params.img_height * Gui::PREVIEW_SIZE) / self.params.img_width,
-            )
-        } else {
-            (
-                (self.params.img_width * Gui::PREVIEW_SIZE) / self.params.img_height,
-                Gui::PREVIEW_SIZE,
-            )
-        };
+        // Pilot previews still being rendered are outdated.
+        self.preview_generation += 1;
+        self.shown_generation = self.preview_generation;
 
-        let preview_params = FrameParams {
-            img_width: preview_width,
-            img_height: preview_height,
-            sampling: Sampling {
-                level: crate::sampling::SamplingLevel::Exploration,
-                random_offsets: true,
-            },
-            ..self.params.clone()
-        };
+        let preview_params = preview_params(&self.params, self.preview_size);
 
-        let sampling_points = preview_params.sampling.generate_sampling_points();
+        let colored_key = params_key(&preview_params);
+        if let Some(egui_image) = self.preview_cache.get(colored_key) {
+            self.show_preview(egui_image);
+            return;
+        }
 
-        let raw_image = render_raw_image(&preview_params, &sampling_points, None);
+        let raw_key = preview_raw_key(&preview_params);
+        let (raw_image, raw_channels) = match &self.preview_cache.raw {
+            Some((key, raw_image, raw_channels)) if *key == raw_key => {
+                (raw_image.to_owned(), raw_channels.to_owned())
+            }
+            _ => {
+                let sampling_points = preview_params.sampling.generate_sampling_points();
+                let (raw_image, raw_channels, _) =
+                    render_raw_image_channels(&preview_params, &sampling_points, None, None);
+                self.preview_cache.raw =
+                    Some((raw_key, raw_image.to_owned(), raw_channels.to_owned()));
+                (raw_image, raw_channels)
+            }
+        };
 
-        let output_image = color_raw_image(&preview_params, raw_image);
+        let output_image = color_raw_channels(&preview_params, raw_image, raw_channels.as_ref());
         let egui_image = egui::ColorImage::from_rgb(
             [output_image.width() as _, output_image.height() as _],
             output_image.as_raw(),
         );
-        self.preview_texture.set(egui_image, Default::default());
+        self.preview_cache.insert(colored_key, egui_image.clone());
+        self.show_preview(egui_image);
+    }
+
+    /// Shows a full preview, keeping the one it replaces to compare
+    /// them.
+    fn show_preview(&mut self, egui_image: ColorImage) {
+        self.preview_texture
+            .set(egui_image.clone(), Default::default());
+
+        let Some(previous) = self.current_preview.replace(egui_image) else {
+            return;
+        };
+        if Some(&previous) == self.current_preview.as_ref() {
+            return;
+        }
+        self.previous_preview.set(previous, Default::default());
     }
 
     fn save_parameter_file(&mut self) -> Result<()> {
         if let Some(path) = self.param_file_path.as_ref() {
             fs::write(
                 path,
-                ron::ser::to_string_pretty(
-                    &ParamsKind::Frame(self.params.clone()),
-                    PrettyConfig::default(),
-                )
-                .map_err(ErrorKind::EncodeParameterFile)?,
+                ParamsKind::Frame(Box::new(self.params.clone()))
+                    .encode(ParamsFormat::from_path(path))?,
             )
             .map_err(ErrorKind::WriteParameterFile)
         } else {
@@ -815,83 +2912,416 @@ impl Gui {
         }
     }
 
-    fn notify<S: ToString>(&mut self, msg: S) {
-        self.message = Some((msg.to_string(), Instant::now()));
+    fn export_zoom_animation(&self, path: &Path) -> Result<()> {
+        let animation_params = AnimationParams::zoom_into(&self.params).ok_or(
+            ErrorKind::ZoomAnimation("this fractal can't be animated".to_string()),
+        )?;
+
+        fs::write(
+            path,
+            ParamsKind::Animation(Box::new(animation_params))
+                .encode(ParamsFormat::from_path(path))?,
+        )
+        .map_err(ErrorKind::WriteParameterFile)
     }
 
-    // Gui display related stuff
+    /// Applies the recommended settings of the current fractal and
+    /// notifies which ones changed.
+    fn tune(&mut self) {
+        let recommended = self.params.fractal.recommended_settings();
+        let previous = self.apply_settings(recommended);
+
+        let mut changes = Vec::new();
+        if previous.max_iter != recommended.max_iter {
+            changes.push(format!(
+                "max_iter {} -> {}",
+                previous.max_iter, recommended.max_iter
+            ));
+        }
+        if (previous.center_x, previous.center_y) != (recommended.center_x, recommended.center_y) {
+            changes.push("center".to_string());
+        }
+        if previous.zoom != recommended.zoom {
+            changes.push(format!("zoom {} -> {}", previous.zoom, recommended.zoom));
+        }
 
-    fn format_label_ron(value: impl Serialize) -> String {
-        ron::to_string(&value)
-            .unwrap_or_default()
-            .replace(":", ": ")
-            .replace(",", ", ")
+        if changes.is_empty() {
+            self.notify("already tuned");
+        } else {
+            self.tune_undo = Some(previous);
+            self.notify(format!("tuned: {}", changes.join(", ")));
+        }
     }
 
-    fn show_gradient_ui(&mut self, ui: &mut egui::Ui) -> bool {
-        let mut changed = false;
+    /// Sets max_iter and the view, returns the replaced settings.
+    fn apply_settings(&mut self, settings: RecommendedSettings) -> RecommendedSettings {
+        let previous = RecommendedSettings {
+            max_iter: self.params.max_iter,
+            center_x: self.params.center_x,
+            center_y: self.params.center_y,
+            zoom: self.params.zoom,
+        };
 
-        let l = self.params.gradient.len();
-        let t_values = self
-            .params
-            .gradient
-            .iter()
-            .map(|&(t, _)| t)
+        if settings != previous {
+            self.params.max_iter = settings.max_iter;
+            self.params.center_x = settings.center_x;
+            self.params.center_y = settings.center_y;
+            self.params.zoom = settings.zoom;
+            self.params_changes.set_breaking();
+        }
+
+        previous
+    }
+
+    /// Decodes the built-in presets, reads the user presets and
+    /// starts rendering the thumbnails that aren't cached yet.
+    fn load_presets(&mut self, ctx: &egui::Context) {
+        let mut presets = Vec::new();
+        let mut errors = Vec::new();
+
+        for &(name, cfg_file) in PRESETS {
+            match decode_preset(cfg_file) {
+                Ok(params) => presets.push((name.to_string(), params, false)),
+                Err(reason) => errors.push(ErrorKind::DecodePreset(name.to_string(), reason)),
+            }
+        }
+        let user_presets = match &self.workspace_path {
+            Some(path) => {
+                read_workspace_or_default(path).map(|workspace| (workspace.presets, Vec::new()))
+            }
+            None => user_presets_dir().and_then(|dir| load_user_presets(&dir)),
+        };
+        match user_presets {
+            Ok((user_presets, user_errors)) => {
+                presets.extend(
+                    user_presets
+                        .into_iter()
+                        .map(|(name, params)| (name, params, true)),
+                );
+                errors.extend(user_errors);
+            }
+            // Without a config directory there are no user presets.
+            Err(ErrorKind::MissingConfigDir) => {}
+            Err(e) => errors.push(e),
+        }
+
+        if !errors.is_empty() {
+            self.notify(
+                errors
+                    .iter()
+                    .map(|e| format!("{:?}", e).trim().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+        }
+
+        let presets = presets
+            .into_iter()
+            .map(|(name, params, user)| Preset {
+                thumbnail_key: params_key(&params),
+                name,
+                params,
+                user,
+            })
             .collect::<Vec<_>>();
 
-        let mut reorder = (0..l).map(Some).collect::<Vec<_>>();
+        self.request_thumbnails(
+            ctx,
+            presets
+                .iter()
+                .map(|preset| (preset.thumbnail_key, preset.params.clone()))
+                .collect(),
+        );
+        self.presets = Some(presets);
+    }
 
-        for (i, (t, c)) in &mut self.params.gradient.iter_mut().enumerate() {
-            ui.horizontal(|ui| {
-                let is_start = i == 0;
-                let is_end = i + 1 == l;
-                let is_start_or_end = is_start || is_end;
+    /// Starts rendering in the background the thumbnails that aren't
+    /// cached nor being rendered yet.
+    fn request_thumbnails(&mut self, ctx: &egui::Context, thumbnails: Vec<(u64, FrameParams)>) {
+        let missing = thumbnails
+            .into_iter()
+            .filter(|(key, _)| {
+                !self.thumbnails.contains_key(key) && self.pending_thumbnails.insert(*key)
+            })
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return;
+        }
 
-                if is_start {
-                    *t = 0.;
-                }
-                if is_end {
-                    *t = 1.;
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for (key, params) in missing {
+                // Stops when the gui is closed.
+                if sender.send((key, render_thumbnail(&params))).is_err() {
+                    break;
                 }
+                ctx.request_repaint();
+            }
+        });
+        self.thumbnail_receivers.push(receiver);
+    }
 
-                let range = if !is_start_or_end {
-                    t_values.get(i - 1).copied().unwrap_or(0.)
-                        ..=t_values.get(i + 1).copied().unwrap_or(1.)
-                } else {
-                    0. ..=1.
-                };
-                changed |= ui
-                    .add_enabled(
-                        !is_start_or_end,
-                        DragValue::new(t).range(range).fixed_decimals(2).speed(0.01),
-                    )
-                    .changed();
-                changed |= color_edit_button_srgb(ui, c).changed();
-                if ui.add_enabled(!is_start, Button::new("up")).clicked() {
-                    reorder.swap(i - 1, i);
-                    changed = true;
-                }
-                if ui.add_enabled(!is_end, Button::new("down")).clicked() {
-                    reorder.swap(i, i + 1);
-                    changed = true;
+    fn receive_thumbnails(&mut self, ctx: &egui::Context) {
+        let thumbnails = &mut self.thumbnails;
+        let pending_thumbnails = &mut self.pending_thumbnails;
+        self.thumbnail_receivers.retain(|receiver| loop {
+            match receiver.try_recv() {
+                Ok((key, image)) => {
+                    let texture =
+                        ctx.load_texture(format!("thumbnail_{}", key), image, Default::default());
+                    thumbnails.insert(key, texture);
+                    pending_thumbnails.remove(&key);
                 }
-                if ui.add_enabled(l > 2, Button::new("remove")).clicked() {
-                    reorder[i] = None;
-                    changed = true;
+                Err(TryRecvError::Empty) => break true,
+                Err(TryRecvError::Disconnected) => break false,
+            }
+        });
+    }
+
+    /// Reads the bookmarks of the param file and starts rendering
+    /// their thumbnails.
+    fn load_bookmarks(&mut self, ctx: &egui::Context) {
+        let param_file_path = self.param_file_path.clone();
+        let mut path = match &self.workspace_path {
+            Some(workspace_path) => Some(workspace_path.to_owned()),
+            None => param_file_path.as_deref().map(bookmarks_path),
+        };
+        let load = |path: &Path| match &self.workspace_path {
+            Some(_) => read_workspace_or_default(path).map(|workspace| workspace.bookmarks),
+            None => load_bookmarks(path),
+        };
+        let bookmarks = match path.as_deref().map(load).transpose() {
+            Ok(bookmarks) => bookmarks.unwrap_or_default(),
+            Err(e) => {
+                self.notify(format!("{:?}", e).trim());
+                path = None;
+                Vec::new()
+            }
+        };
+
+        let mut thumbnails = Vec::new();
+        let entries = bookmarks
+            .into_iter()
+            .map(|bookmark| {
+                let (entry, params) = self.bookmark_entry(bookmark);
+                thumbnails.push((entry.thumbnail_key, params));
+                entry
+            })
+            .collect();
+        self.request_thumbnails(ctx, thumbnails);
+
+        self.bookmarks = Some(Bookmarks {
+            param_file_path,
+            path,
+            entries,
+        });
+    }
+
+    /// Entry of a bookmark and the params of its thumbnail, the
+    /// current params moved to the bookmark.
+    fn bookmark_entry(&self, bookmark: Bookmark) -> (BookmarkEntry, FrameParams) {
+        let mut params = self.params.clone();
+        bookmark.apply(&mut params);
+        let entry = BookmarkEntry {
+            label: format!(
+                "{}, zoom {:.2e}",
+                Self::format_label_ron(&bookmark.fractal),
+                bookmark.zoom
+            ),
+            thumbnail_key: params_key(&params),
+            bookmark,
+        };
+        (entry, params)
+    }
+
+    fn add_bookmark(&mut self, ctx: &egui::Context) {
+        let (entry, params) = self.bookmark_entry(Bookmark::new(&self.params));
+        self.request_thumbnails(ctx, vec![(entry.thumbnail_key, params)]);
+        if let Some(bookmarks) = &mut self.bookmarks {
+            bookmarks.entries.push(entry);
+        }
+        self.save_bookmarks();
+    }
+
+    fn save_bookmarks(&mut self) {
+        let Some(Bookmarks {
+            path: Some(path),
+            entries,
+            ..
+        }) = &self.bookmarks
+        else {
+            return;
+        };
+        let bookmarks = entries
+            .iter()
+            .map(|entry| entry.bookmark.clone())
+            .collect::<Vec<_>>();
+        let result = match &self.workspace_path {
+            Some(_) => update_workspace(path, |workspace| workspace.bookmarks = bookmarks),
+            None => save_bookmarks(path, &bookmarks),
+        };
+        if let Err(e) = result {
+            self.notify(format!("{:?}", e).trim());
+        }
+    }
+
+    fn load_preset(&mut self, name: String, params: FrameParams) {
+        self.params = params;
+        self.provenance
+            .record(&self.params, Source::Preset(name.to_owned()));
+        self.params_changes.set_breaking();
+        self.notify(format!("loaded {}", name));
+    }
+
+    fn save_preset(&mut self) -> Result<PathBuf> {
+        let name = self.preset_name.trim();
+        let path = match &self.workspace_path {
+            Some(path) => {
+                check_preset_name(name)?;
+                update_workspace(path, |workspace| workspace.set_preset(name, &self.params))?;
+                path.to_owned()
+            }
+            None => save_user_preset(&user_presets_dir()?, name, &self.params)?,
+        };
+        // Reloaded with the new preset on the next opening of the
+        // menu.
+        self.presets = None;
+        Ok(path)
+    }
+
+    /// Bookmarks of the param file (or of the workspace), the ones
+    /// shown when they were loaded by the bookmarks section.
+    fn current_bookmarks(&self) -> Result<Vec<Bookmark>> {
+        if let Some(bookmarks) = self
+            .bookmarks
+            .as_ref()
+            .filter(|bookmarks| bookmarks.param_file_path == self.param_file_path)
+        {
+            return Ok(bookmarks
+                .entries
+                .iter()
+                .map(|entry| entry.bookmark.clone())
+                .collect());
+        }
+        match (&self.workspace_path, &self.param_file_path) {
+            (Some(path), _) => read_workspace_or_default(path).map(|workspace| workspace.bookmarks),
+            (None, Some(param_file_path)) => load_bookmarks(&bookmarks_path(param_file_path)),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+
+    /// User presets, from the workspace or the config directory.
+    fn user_presets(&self) -> Result<UserPresets> {
+        match &self.workspace_path {
+            Some(path) => read_workspace_or_default(path).map(|workspace| workspace.presets),
+            None => match user_presets_dir().and_then(|dir| load_user_presets(&dir)) {
+                Ok((presets, _)) => Ok(presets),
+                Err(ErrorKind::MissingConfigDir) => Ok(Vec::new()),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    /// Writes the bookmarks, the user presets and the samples of the
+    /// session to a workspace file.
+    fn export_workspace(&self, path: &Path) -> Result<()> {
+        let workspace = Workspace {
+            bookmarks: self.current_bookmarks()?,
+            presets: self.user_presets()?,
+            autosave: self.raw_image.as_ref().map(|raw_image| Autosave {
+                params: self.params.clone(),
+                samples_per_pixel: self.samples_per_pixel,
+                raw_image: raw_image.to_owned(),
+            }),
+        };
+        write_workspace(&workspace, path)
+    }
+
+    /// Adds the bookmarks and presets of a workspace file to the
+    /// current ones (see [`ImportMode`]), and offers to restore its
+    /// samples when they match the params.
+    fn import_workspace(&mut self, ctx: &egui::Context, path: &Path) -> Result<()> {
+        let imported = read_workspace(path)?;
+        let mut workspace = Workspace {
+            bookmarks: self.current_bookmarks()?,
+            presets: self.user_presets()?,
+            autosave: None,
+        };
+        let summary = workspace.import(imported, self.import_mode);
+
+        match &self.workspace_path {
+            Some(workspace_path) => update_workspace(workspace_path, |stored| {
+                stored.bookmarks = workspace.bookmarks.clone();
+                stored.presets = workspace.presets.clone();
+            })?,
+            None if !summary.saved_presets.is_empty() => {
+                let dir = user_presets_dir()?;
+                for name in &summary.saved_presets {
+                    if let Some((_, params)) = workspace.presets.iter().find(|(n, _)| n == name) {
+                        save_user_preset(&dir, name, params)?;
+                    }
                 }
-                if ui.button("duplicate").clicked() {
-                    reorder.insert(i, Some(i));
+            }
+            None => {}
+        }
+        // Reloaded on the next opening of the menu.
+        self.presets = None;
+
+        let entries = workspace
+            .bookmarks
+            .into_iter()
+            .map(|bookmark| {
+                let (entry, params) = self.bookmark_entry(bookmark);
+                self.request_thumbnails(ctx, vec![(entry.thumbnail_key, params)]);
+                entry
+            })
+            .collect();
+        match &mut self.bookmarks {
+            Some(bookmarks) if bookmarks.param_file_path == self.param_file_path => {
+                bookmarks.entries = entries;
+            }
+            _ => {
+                self.load_bookmarks(ctx);
+                if let Some(bookmarks) = &mut self.bookmarks {
+                    bookmarks.entries = entries;
                 }
-            });
+            }
         }
+        self.save_bookmarks();
 
-        self.params.gradient = reorder
-            .iter()
-            .filter_map(|&v| v)
-            .map(|i| self.params.gradient[i])
-            .collect::<Vec<_>>();
+        let mut message = format!(
+            "imported {} bookmark(s) and {} preset(s)",
+            summary.added_bookmarks,
+            summary.saved_presets.len()
+        );
+        for (name, new_name) in &summary.renamed_presets {
+            message.push_str(&format!("\npreset {} renamed to {}", name, new_name));
+        }
+        match workspace.autosave {
+            Some(autosave) if raw_params_match(&autosave.params, &self.params) => {
+                self.autosave_offer = Some(autosave);
+            }
+            Some(_) => message
+                .push_str("\nthe samples of the workspace were rendered with other parameters"),
+            None => {}
+        }
+        self.notify(message);
 
-        changed
+        Ok(())
+    }
+
+    pub fn notify<S: ToString>(&mut self, msg: S) {
+        self.message = Some((msg.to_string(), Instant::now()));
+    }
+
+    // Gui display related stuff
+
+    fn format_label_ron(value: impl Serialize) -> String {
+        ron::to_string(&value)
+            .unwrap_or_default()
+            .replace(":", ": ")
+            .replace(",", ", ")
     }
 
     fn show_combobox_fractal(&mut self, ui: &mut egui::Ui) -> bool {
@@ -1072,97 +3502,285 @@ impl Gui {
             changed = true;
         };
 
+        let selected = matches!(self.params.fractal, Fractal::Newton { .. });
+        if ui
+            .selectable_label(selected, "Newton(coefficients)")
+            .clicked()
+            && !selected
+        {
+            // z^3 - 1
+            let mut coefficients = [(0., 0.); NEWTON_COEFFICIENT_COUNT];
+            coefficients[0] = (-1., 0.);
+            coefficients[3] = (1., 0.);
+            self.params.fractal = Fractal::Newton { coefficients };
+            self.params.coloring_mode = ColoringMode::Roots { darkening: 0.1 };
+            changed = true;
+        };
+
+        for (fractal, name, formula) in [
+            (Fractal::SinMandelbrot, "SinMandelbrot", "z = sin(z) + c"),
+            (Fractal::CosMandelbrot, "CosMandelbrot", "z = cos(z) + c"),
+            (Fractal::ExpMandelbrot, "ExpMandelbrot", "z = c * exp(z)"),
+        ] {
+            let selected = self.params.fractal == fractal;
+            if ui
+                .selectable_label(selected, name)
+                .on_hover_text(formula)
+                .clicked()
+                && !selected
+            {
+                self.params.fractal = fractal;
+                changed = true;
+            };
+        }
+
+        let selected = matches!(self.params.fractal, Fractal::Mandelbox { .. });
+        if ui
+            .selectable_label(selected, "Mandelbox(scale, fold)")
+            .clicked()
+            && !selected
+        {
+            self.params.fractal = Fractal::Mandelbox {
+                scale: 2.,
+                fold: 0.5,
+            };
+            changed = true;
+        };
+
+        let selected = matches!(self.params.fractal, Fractal::Custom { .. });
+        if ui
+            .selectable_label(selected, "Custom(formula)")
+            .on_hover_text("iteration typed as a formula, e.g. z = sin(z) + c*z")
+            .clicked()
+            && !selected
+        {
+            self.params.fractal = Fractal::Custom {
+                formula: Formula::default(),
+            };
+            changed = true;
+        };
+
         changed
     }
 
     fn show_fractal_parameters(&mut self, ui: &mut egui::Ui) -> bool {
-        const SPEED: F = 0.0001;
-        const N_DECIMALS: usize = 8;
-
         let mut changed = false;
 
-        if let Fractal::MandelbrotCustomExp { exp } = &mut self.params.fractal {
+        Grid::new("fractal parameters grid").show(ui, |ui| {
+            for parameter in self.params.fractal.parameters() {
+                match parameter {
+                    FractalParameter::Real { name, value, range } => {
+                        ui.label(format!("{}:", name));
+                        changed |= Self::add_parameter_value(ui, value, range).changed();
+                    }
+                    FractalParameter::Integer {
+                        name,
+                        value,
+                        min,
+                        max,
+                    } => {
+                        ui.label(format!("{}:", name));
+                        changed |= ui
+                            .add(Slider::new(value, min..=max).clamping(SliderClamping::Edits))
+                            .changed();
+                    }
+                    FractalParameter::Complex {
+                        name,
+                        re,
+                        im,
+                        range,
+                        plane,
+                    } => {
+                        ui.label(format!("{}:", name));
+                        ui.horizontal(|ui| {
+                            if plane {
+                                changed |= complex_plane(ui, re, im, range).changed();
+                            }
+                            ui.vertical(|ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("re:");
+                                    changed |= Self::add_parameter_value(ui, re, range).changed();
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("im:");
+                                    changed |= Self::add_parameter_value(ui, im, range).changed();
+                                });
+                            });
+                        });
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Fractal::Custom { formula } = &mut self.params.fractal {
+            // The text follows the formula unless it is being fixed.
+            if self.formula_error.is_none() && self.formula_text != formula.source() {
+                self.formula_text = formula.source().to_string();
+            }
+
             ui.horizontal(|ui| {
-                ui.label("exp:");
-                let res = ui.add(
-                    DragValue::new(exp)
-                        .speed(SPEED)
-                        .range(0.001..=20.)
-                        .fixed_decimals(N_DECIMALS),
-                );
-                changed |= res.changed();
+                ui.label("formula:");
+                let res = ui
+                    .text_edit_singleline(&mut self.formula_text)
+                    .on_hover_text(
+                        "variables z and c, constants such as 1.5 or 0.5i, + - * / ^ and the \
+                         functions sin, exp, conj and abs",
+                    );
+                if res.changed() {
+                    match Formula::parse(&self.formula_text) {
+                        Ok(parsed) => {
+                            *formula = parsed;
+                            self.formula_error = None;
+                            changed = true;
+                        }
+                        Err(e) => self.formula_error = Some(e),
+                    }
+                }
             });
+            if let Some(e) = &self.formula_error {
+                ui.colored_label(Color32::RED, e);
+            }
         }
 
-        if let Fractal::SdrgeCustomIntExp { exp } = &mut self.params.fractal {
-            ui.horizontal(|ui| {
-                ui.label("exp:");
-                let res = ui.add(DragValue::new(exp).range(1..=10));
-                changed |= res.changed();
-            });
+        changed
+    }
+
+    fn add_parameter_value(
+        ui: &mut egui::Ui,
+        value: &mut F,
+        range: ParameterRange,
+    ) -> egui::Response {
+        if range.logarithmic {
+            ui.add(
+                Slider::new(value, range.min..=range.max)
+                    .logarithmic(true)
+                    .clamping(SliderClamping::Edits)
+                    .fixed_decimals(range.decimals),
+            )
+        } else {
+            ui.add(
+                DragValue::new(value)
+                    .speed(range.speed)
+                    .range(range.min..=range.max)
+                    .clamp_existing_to_range(false)
+                    .fixed_decimals(range.decimals),
+            )
         }
-        if let Fractal::SdrgeCustomExp { exp } = &mut self.params.fractal {
-            ui.horizontal(|ui| {
-                ui.label("exp:");
-                let res = ui.add(
-                    DragValue::new(exp)
-                        .speed(SPEED)
-                        .range(1..=10)
-                        .fixed_decimals(N_DECIMALS),
-                );
-                changed |= res.changed();
-            });
+    }
+
+    fn filter_name(filter: ReconstructionFilter) -> &'static str {
+        match filter {
+            ReconstructionFilter::Box { .. } => "Box",
+            ReconstructionFilter::Tent { .. } => "Tent",
+            ReconstructionFilter::Gaussian { .. } => "Gaussian",
+            ReconstructionFilter::Mitchell { .. } => "Mitchell",
         }
+    }
 
-        if let Fractal::SdrgeParam { a_re, a_im }
-        | Fractal::ComplexLogisticMapLike { a_re, a_im }
-        | Fractal::Wmriho { a_re, a_im }
-        | Fractal::Iigdzh { a_re, a_im } = &mut self.params.fractal
-        {
-            ui.horizontal(|ui| {
-                ui.label("a_re:");
-                let res1 = ui.add(DragValue::new(a_re).speed(SPEED).fixed_decimals(N_DECIMALS));
-                ui.label("a_im:");
-                let res2 = ui.add(DragValue::new(a_im).speed(SPEED).fixed_decimals(N_DECIMALS));
+    fn show_combobox_filter(&mut self, ui: &mut egui::Ui, radius: F) {
+        let filters = [
+            ReconstructionFilter::Box { radius },
+            ReconstructionFilter::Tent { radius },
+            ReconstructionFilter::Gaussian { radius },
+            ReconstructionFilter::Mitchell { radius },
+        ];
 
-                changed |= res1.changed() || res2.changed();
-            });
+        let current = self.params.sampling.filter();
+        for filter in filters {
+            let selected = Self::filter_name(filter) == Self::filter_name(current);
+            if ui
+                .selectable_label(selected, Self::filter_name(filter))
+                .clicked()
+                && !selected
+            {
+                self.params.sampling.filter = Some(filter);
+                self.params_changes.set_breaking();
+            }
         }
+    }
 
-        if let Fractal::NthDrge(n) = &mut self.params.fractal {
-            ui.horizontal(|ui| {
-                ui.label("n:");
-                let res = ui.add(Slider::new(n, 2..=20));
-                changed |= res.changed();
-            });
+    fn value_kind_name(value: ValueKind) -> &'static str {
+        match value {
+            ValueKind::EscapeTime => "escape time",
+            ValueKind::StripeAverage { .. } => "stripe average",
+            ValueKind::DistanceEstimate => "distance estimate",
         }
+    }
 
-        if let Fractal::Sfwypc { alpha, beta, gamma } = &mut self.params.fractal {
-            Grid::new("param grid").show(ui, |ui| {
-                [(alpha, "alpha"), (beta, "beta"), (gamma, "gamma")]
-                    .iter_mut()
-                    .for_each(|(v, name)| {
-                        ui.label(name.to_string() + "_re:");
-                        changed |= ui
-                            .add(
-                                DragValue::new(&mut v.0)
-                                    .speed(SPEED)
-                                    .fixed_decimals(N_DECIMALS),
-                            )
-                            .changed();
-                        ui.label(name.to_string() + "_im:");
-                        changed |= ui
-                            .add(
-                                DragValue::new(&mut v.1)
-                                    .speed(SPEED)
-                                    .fixed_decimals(N_DECIMALS),
-                            )
-                            .changed();
-                        ui.end_row();
-                    });
-            });
+    fn show_combobox_value_kind(&mut self, ui: &mut egui::Ui) -> bool {
+        let values = [
+            ValueKind::EscapeTime,
+            ValueKind::StripeAverage { frequency: 5. },
+            ValueKind::DistanceEstimate,
+        ];
+
+        let current = Self::value_kind_name(self.params.value);
+        let mut changed = false;
+        for value in values {
+            let name = Self::value_kind_name(value);
+            let selected = name == current;
+            let supported = value != ValueKind::DistanceEstimate
+                || self.params.fractal.supports_distance_estimate();
+            let res = ui
+                .add_enabled(supported, egui::Button::selectable(selected, name))
+                .on_disabled_hover_text("not supported by this fractal");
+            if res.clicked() && !selected {
+                self.params.value = value;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn channel_name(channel: ChannelSelect) -> &'static str {
+        match channel {
+            ChannelSelect::Value => "value",
+            ChannelSelect::FinalNorm => "final norm",
+            ChannelSelect::FinalArg => "final angle",
+        }
+    }
+
+    /// Coloring changes don't invalidate the samples, unless the
+    /// coloring needs channels that were not rendered with them.
+    fn set_coloring_changed(&mut self) {
+        if self.params.coloring_mode.needs_channels()
+            && self.raw_image.is_some()
+            && self.raw_channels.is_none()
+        {
+            self.params_changes.set_breaking();
+        } else {
+            self.params_changes.set_non_breaking();
+        }
+    }
+
+    fn interior_mode_name(interior_mode: Option<&InteriorMode>) -> &'static str {
+        match interior_mode {
+            None => "gradient end",
+            Some(InteriorMode::Flat(_)) => "flat",
+            Some(InteriorMode::RadialGradient { .. }) => "radial gradient",
         }
+    }
+
+    fn show_combobox_interior_mode(&mut self, ui: &mut egui::Ui) -> bool {
+        let interior_modes = [
+            None,
+            Some(InteriorMode::Flat([0, 0, 0])),
+            Some(InteriorMode::RadialGradient {
+                gradient: MAGMA.to_vec(),
+            }),
+        ];
 
+        let current = Self::interior_mode_name(self.params.interior_mode.as_ref());
+        let mut changed = false;
+        for interior_mode in interior_modes {
+            let name = Self::interior_mode_name(interior_mode.as_ref());
+            let selected = name == current;
+            if ui.selectable_label(selected, name).clicked() && !selected {
+                self.params.interior_mode = interior_mode;
+                changed = true;
+            }
+        }
         changed
     }
 
@@ -1175,10 +3793,647 @@ impl Gui {
             (SamplingLevel::High, "High"),
             (SamplingLevel::Ultra, "Ultra"),
             (SamplingLevel::Extreme, "Extreme"),
+            (SamplingLevel::Adaptive { base: 8, max: 55 }, "Adaptive"),
         ];
 
+        let suggested = self
+            .calibration
+            .as_ref()
+            .map(|calibration| calibration.suggested_level(&self.params));
         for &(level, name) in LEVELS {
-            ui.selectable_value(&mut self.params.sampling.level, level, name);
+            if Some(level) == suggested {
+                ui.selectable_value(
+                    &mut self.params.sampling.level,
+                    level,
+                    format!("{} (suggested)", name),
+                )
+                .on_hover_text(format!(
+                    "highest level rendering in about {}s on this machine",
+                    SUGGESTED_RENDER_TIME
+                ));
+            } else {
+                ui.selectable_value(&mut self.params.sampling.level, level, name);
+            }
+        }
+    }
+
+    fn show_combobox_sampling_pattern(&mut self, ui: &mut egui::Ui) {
+        const PATTERNS: &[(SamplingPattern, &str)] = &[
+            (SamplingPattern::GoldenRatio, "GoldenRatio"),
+            (SamplingPattern::Halton, "Halton"),
+            (SamplingPattern::UniformGrid, "UniformGrid"),
+            (SamplingPattern::JitteredGrid, "JitteredGrid"),
+        ];
+
+        for &(pattern, name) in PATTERNS {
+            if ui
+                .selectable_value(&mut self.params.sampling.pattern, pattern, name)
+                .changed()
+            {
+                self.params_changes.set_breaking();
+            }
+        }
+    }
+}
+
+/// Small plane on which a complex constant can be dragged, the
+/// imaginary axis pointing up.
+fn complex_plane(
+    ui: &mut egui::Ui,
+    re: &mut F,
+    im: &mut F,
+    range: ParameterRange,
+) -> egui::Response {
+    const SIZE: f32 = 48.;
+
+    let (rect, mut response) = ui.allocate_exact_size(Vec2::splat(SIZE), Sense::drag());
+    let span = range.max - range.min;
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        let t = ((pos - rect.min) / rect.size()).clamp(Vec2::ZERO, Vec2::splat(1.));
+        *re = range.min + t.x as F * span;
+        *im = range.max - t.y as F * span;
+        response.mark_changed();
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    let to_screen = |re: F, im: F| {
+        let x = ((re - range.min) / span).clamp(0., 1.) as f32;
+        let y = ((range.max - im) / span).clamp(0., 1.) as f32;
+        rect.min + Vec2::new(x, y) * rect.size()
+    };
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+    let origin = to_screen(0., 0.);
+    let stroke = (1., ui.visuals().weak_text_color());
+    painter.hline(rect.x_range(), origin.y, stroke);
+    painter.vline(origin.x, rect.y_range(), stroke);
+    painter.circle_filled(to_screen(*re, *im), 3., Color32::WHITE);
+
+    response.on_hover_text(format!(
+        "{:.*} + {:.*}i",
+        range.decimals, re, range.decimals, im
+    ))
+}
+
+/// Bar chart of the counts of a histogram.
+fn histogram_chart(ui: &mut egui::Ui, histogram: &[u32]) -> egui::Response {
+    const SIZE: Vec2 = Vec2::new(300., 60.);
+
+    let (rect, response) = ui.allocate_exact_size(SIZE, Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+
+    let max_count = histogram.iter().copied().max().unwrap_or(0).max(1);
+    let bar_width = rect.width() / histogram.len().max(1) as f32;
+    for (k, &count) in histogram.iter().enumerate() {
+        let x = rect.left() + k as f32 * bar_width;
+        let height = rect.height() * count as f32 / max_count as f32;
+        painter.rect_filled(
+            Rect::from_x_y_ranges(x..=x + bar_width, rect.bottom() - height..=rect.bottom()),
+            0.,
+            ui.visuals().selection.bg_fill,
+        );
+    }
+
+    response
+}
+
+/// Combo box choosing the kind of an extremum, `custom` and
+/// `percentile` being the values given when switching to these kinds.
+/// Returns whether the kind changed.
+fn extremum_selector(
+    ui: &mut egui::Ui,
+    id_salt: &str,
+    extremum: &mut Extremum,
+    custom: F,
+    percentile: F,
+) -> (egui::Response, bool) {
+    let mut changed = false;
+    let res = ComboBox::from_id_salt(id_salt)
+        .width(80.)
+        .selected_text(match extremum {
+            Extremum::Auto => "auto",
+            Extremum::Custom(_) => "custom",
+            Extremum::Percentile(_) => "percentile",
+        })
+        .show_ui(ui, |ui| {
+            for (selected, label, kind) in [
+                (extremum.is_auto(), "auto", Extremum::Auto),
+                (
+                    matches!(extremum, Extremum::Custom(_)),
+                    "custom",
+                    Extremum::Custom(custom),
+                ),
+                (
+                    extremum.is_percentile(),
+                    "percentile",
+                    Extremum::Percentile(percentile),
+                ),
+            ] {
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    *extremum = kind;
+                    changed = true;
+                }
+            }
+        })
+        .response;
+
+    (res, changed)
+}
+
+/// Rows editing the position and color of each stop of the
+/// gradient, with buttons to reorder, remove and duplicate them.
+fn show_gradient_ui(ui: &mut egui::Ui, gradient: &mut Vec<(F, [u8; 3])>) -> bool {
+    let mut changed = false;
+
+    let l = gradient.len();
+    let t_values = gradient.iter().map(|&(t, _)| t).collect::<Vec<_>>();
+
+    let mut reorder = (0..l).map(Some).collect::<Vec<_>>();
+
+    for (i, (t, c)) in gradient.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let is_start = i == 0;
+            let is_end = i + 1 == l;
+            let is_start_or_end = is_start || is_end;
+
+            if is_start {
+                *t = 0.;
+            }
+            if is_end {
+                *t = 1.;
+            }
+
+            let range = if !is_start_or_end {
+                t_values.get(i - 1).copied().unwrap_or(0.)
+                    ..=t_values.get(i + 1).copied().unwrap_or(1.)
+            } else {
+                0. ..=1.
+            };
+            changed |= ui
+                .add_enabled(
+                    !is_start_or_end,
+                    DragValue::new(t).range(range).fixed_decimals(2).speed(0.01),
+                )
+                .changed();
+            changed |= color_edit_button_srgb(ui, c).changed();
+            if ui.add_enabled(!is_start, Button::new("up")).clicked() {
+                reorder.swap(i - 1, i);
+                changed = true;
+            }
+            if ui.add_enabled(!is_end, Button::new("down")).clicked() {
+                reorder.swap(i, i + 1);
+                changed = true;
+            }
+            if ui.add_enabled(l > 2, Button::new("remove")).clicked() {
+                reorder[i] = None;
+                changed = true;
+            }
+            if ui.button("duplicate").clicked() {
+                reorder.insert(i, Some(i));
+                changed = true;
+            }
+        });
+    }
+
+    *gradient = reorder
+        .iter()
+        .filter_map(|&v| v)
+        .map(|i| gradient[i])
+        .collect::<Vec<_>>();
+
+    changed
+}
+
+/// Bar showing the gradient with handles to drag its stops,
+/// clicking the bar adds a stop there. The color of the selected
+/// stop is edited below the bar. The first and last stops stay at
+/// the ends.
+fn gradient_editor(
+    ui: &mut egui::Ui,
+    gradient: &mut Vec<(F, [u8; 3])>,
+    selected: &mut usize,
+) -> bool {
+    const WIDTH: f32 = 300.;
+    const BAR_HEIGHT: f32 = 20.;
+    const HANDLE_SIZE: f32 = 10.;
+
+    let mut changed = false;
+
+    let (rect, bar_res) =
+        ui.allocate_exact_size(Vec2::new(WIDTH, BAR_HEIGHT + HANDLE_SIZE), Sense::click());
+    let bar = Rect::from_min_size(rect.min, Vec2::new(WIDTH, BAR_HEIGHT));
+    #[allow(clippy::unnecessary_cast)]
+    let to_x = |t: F| bar.left() + t as f32 * bar.width();
+    let to_t = |x: f32| ((x - bar.left()) / bar.width()).clamp(0., 1.) as F;
+
+    let painter = ui.painter_at(rect);
+    paint_gradient(&painter, bar, gradient);
+
+    let l = gradient.len();
+    for i in 0..l {
+        let is_start_or_end = i == 0 || i + 1 == l;
+
+        let (t, [r, g, b]) = gradient[i];
+        let center = egui::pos2(to_x(t), bar.bottom() + 0.5 * HANDLE_SIZE);
+        let res = ui.interact(
+            Rect::from_center_size(center, Vec2::splat(HANDLE_SIZE)),
+            ui.id().with(("gradient_stop", i)),
+            Sense::click_and_drag(),
+        );
+        if res.clicked() || res.drag_started() {
+            *selected = i;
+        }
+        if let (true, false, Some(pos)) =
+            (res.dragged(), is_start_or_end, res.interact_pointer_pos())
+        {
+            gradient[i].0 = to_t(pos.x).clamp(gradient[i - 1].0, gradient[i + 1].0);
+            changed = true;
+        }
+
+        let half = 0.5 * HANDLE_SIZE;
+        let stroke_color = if *selected == i {
+            Color32::WHITE
+        } else {
+            ui.visuals().weak_text_color()
+        };
+        painter.add(egui::Shape::convex_polygon(
+            vec![
+                center + Vec2::new(0., -half),
+                center + Vec2::new(half, half),
+                center + Vec2::new(-half, half),
+            ],
+            Color32::from_rgb(r, g, b),
+            (1., stroke_color),
+        ));
+    }
+
+    if let (true, Some(pos)) = (bar_res.clicked(), bar_res.interact_pointer_pos()) {
+        if bar.contains(pos) {
+            let t = to_t(pos.x);
+            let color = gradient_color(t, gradient).map(|c| c.round().clamp(0., 255.) as u8);
+            let i = gradient
+                .partition_point(|&(stop, _)| stop <= t)
+                .clamp(1, l - 1);
+            gradient.insert(i, (t, color));
+            *selected = i;
+            changed = true;
+        }
+    }
+
+    let l = gradient.len();
+    *selected = (*selected).min(l - 1);
+    ui.horizontal(|ui| {
+        ui.label(format!("stop {}:", *selected + 1));
+        changed |= color_edit_button_srgb(ui, &mut gradient[*selected].1).changed();
+        let removable = *selected != 0 && *selected + 1 != l;
+        if ui
+            .add_enabled(removable, Button::new("remove"))
+            .on_disabled_hover_text("the first and last stops can't be removed")
+            .clicked()
+        {
+            gradient.remove(*selected);
+            changed = true;
+        }
+    });
+
+    changed
+}
+
+const GRADIENT_PREVIEW_WIDTH: f32 = 80.;
+
+/// Paints the gradient from left to right over `rect`.
+fn paint_gradient(painter: &egui::Painter, rect: Rect, gradient: &[(F, [u8; 3])]) {
+    /// Width of the bands the gradient is painted with.
+    const BAND_WIDTH: f32 = 2.;
+
+    let band_count = (rect.width() / BAND_WIDTH).ceil() as usize;
+    for k in 0..band_count {
+        let x = rect.left() + k as f32 * BAND_WIDTH;
+        let t = ((x + 0.5 * BAND_WIDTH - rect.left()) / rect.width()).clamp(0., 1.) as F;
+        let [r, g, b] = gradient_color(t, gradient).map(|c| c.clamp(0., 255.) as u8);
+        painter.rect_filled(
+            Rect::from_x_y_ranges(x..=(x + BAND_WIDTH).min(rect.right()), rect.y_range()),
+            0.,
+            Color32::from_rgb(r, g, b),
+        );
+    }
+}
+
+/// Writes the panic message along with the parameters and build
+/// info to a file to attach to a bug report, returns its path.
+/// Reads the workspace file, changes it and writes it back.
+fn update_workspace(path: &Path, change: impl FnOnce(&mut Workspace)) -> Result<()> {
+    let mut workspace = read_workspace_or_default(path)?;
+    change(&mut workspace);
+    write_workspace(&workspace, path)
+}
+
+fn write_crash_log(message: &str, params: &FrameParams) -> Result<PathBuf> {
+    let path = PathBuf::from(format!(
+        "fractal_rndr_crash_{}.log",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    ));
+
+    let params = ron::ser::to_string_pretty(
+        &ParamsKind::Frame(Box::new(params.clone())),
+        PrettyConfig::default(),
+    )
+    .map_err(ErrorKind::EncodeParameterFile)?;
+    fs::write(
+        &path,
+        format!(
+            "render failed: {}\n\nversion: {}\ntarget: {}-{}\n\nparameters:\n{}\n",
+            message,
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::ARCH,
+            std::env::consts::OS,
+            params
+        ),
+    )
+    .map_err(ErrorKind::WriteCrashLog)?;
+
+    Ok(path)
+}
+
+/// Single line text field editing an optional path, left empty when
+/// there is none.
+fn path_text_edit(ui: &mut egui::Ui, path: &mut Option<PathBuf>) -> egui::Response {
+    let mut text = path
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+    let res = ui.add(
+        egui::TextEdit::singleline(&mut text)
+            .hint_text("no path")
+            .desired_width(160.),
+    );
+    if res.changed() {
+        *path = (!text.is_empty()).then(|| PathBuf::from(text));
+    }
+    res
+}
+
+/// Params of a preview whose largest side is `size`, with a low
+/// sampling level.
+fn preview_params(params: &FrameParams, size: u32) -> FrameParams {
+    let (width, height) = if params.img_width > params.img_height {
+        (size, ((params.img_height * size) / params.img_width).max(1))
+    } else {
+        (((params.img_width * size) / params.img_height).max(1), size)
+    };
+
+    FrameParams {
+        img_width: width,
+        img_height: height,
+        // The rounded size of the preview doesn't have exactly the
+        // ratio of the image, the view has to keep it.
+        aspect: Some(
+            params
+                .aspect
+                .unwrap_or(params.img_width as F / params.img_height as F),
+        ),
+        sampling: Sampling {
+            level: SamplingLevel::Exploration,
+            random_offsets: true,
+            filter: params.sampling.filter,
+            pattern: params.sampling.pattern,
+        },
+        // The preview is seeded with the breaking params so that
+        // it only changes when the fractal itself changes.
+        seed: Some(preview_seed(params)),
+        ..params.clone()
+    }
+}
+
+/// Params of the pilot previews: smaller, with a single sample per
+/// pixel and fewer iterations.
+fn pilot_params(params: &FrameParams) -> FrameParams {
+    const MAX_ITER_DIVISOR: u32 = 4;
+    const MIN_MAX_ITER: u32 = 64;
+
+    let params = preview_params(params, Gui::PILOT_SIZE);
+    FrameParams {
+        sampling: Sampling {
+            level: SamplingLevel::Raw,
+            random_offsets: false,
+            ..params.sampling
+        },
+        max_iter: (params.max_iter / MAX_ITER_DIVISOR)
+            .max(MIN_MAX_ITER)
+            .min(params.max_iter),
+        ..params
+    }
+}
+
+fn render_thumbnail(params: &FrameParams) -> ColorImage {
+    render_colored(&preview_params(params, Gui::THUMBNAIL_SIZE))
+}
+
+fn render_colored(params: &FrameParams) -> ColorImage {
+    let sampling_points = params.sampling.generate_sampling_points();
+    let (raw_image, raw_channels, _) =
+        render_raw_image_channels(params, &sampling_points, None, None);
+    let image = color_raw_channels(params, raw_image, raw_channels.as_ref());
+    ColorImage::from_rgb([image.width() as _, image.height() as _], image.as_raw())
+}
+
+/// Hashes all the params.
+fn params_key(params: &FrameParams) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    ron::to_string(params).unwrap_or_default().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hashes the params that change the raw image.
+fn preview_seed(params: &FrameParams) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    ron::to_string(&params.fractal)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    for v in [
+        params.zoom,
+        params.center_x,
+        params.center_y,
+        params.rotate.unwrap_or(0.),
+    ] {
+        v.to_bits().hash(&mut hasher);
+    }
+    (
+        params.img_width,
+        params.img_height,
+        params.max_iter,
+        params.seed,
+    )
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Key of the raw preview image: the params hashed by
+/// [`preview_seed`] along with the value and the sampling of the
+/// preview and whether channels are needed, which can change
+/// without a breaking change.
+fn preview_raw_key(params: &FrameParams) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    preview_seed(params).hash(&mut hasher);
+    ron::to_string(&(params.value, params.sampling))
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    params.coloring_mode.needs_channels().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Number of decimals needed to display a coordinate without
+/// losing meaningful digits at the given zoom level.
+fn coordinate_decimals(zoom: F) -> usize {
+    const MIN_DECIMALS: i32 = 8;
+    const MAX_DECIMALS: i32 = 17;
+
+    ((-zoom.log10()).ceil() as i32 + 4).clamp(MIN_DECIMALS, MAX_DECIMALS) as usize
+}
+
+/// Formats a coordinate with a precision adapted to the zoom
+/// level, switching to scientific notation when the plain
+/// representation gets too wide.
+fn format_coordinate(value: F, zoom: F) -> String {
+    const MAX_WIDTH: usize = 20;
+
+    let decimals = coordinate_decimals(zoom);
+    let plain = format!("{:.*}", decimals, value);
+    if plain.len() <= MAX_WIDTH || value == 0. {
+        return plain;
+    }
+
+    // Keep the same absolute precision: the mantissa needs as many
+    // digits as there are significant digits in `plain`.
+    let magnitude = value.abs().log10().floor() as i32;
+    let mantissa_decimals = (decimals as i32 + magnitude).max(0) as usize;
+    let scientific = format!("{:.*e}", mantissa_decimals, value);
+    if scientific.len() < plain.len() {
+        scientific
+    } else {
+        plain
+    }
+}
+
+/// Parses a coordinate typed in the gui. If the text is exactly
+/// what [`format_coordinate`] displays for the current value, the
+/// current value is returned as is so that digits hidden by the
+/// display are not lost when the field loses focus.
+fn parse_coordinate(s: &str, current: F, zoom: F) -> Option<F> {
+    let s = s.trim();
+    if s == format_coordinate(current, zoom) {
+        Some(current)
+    } else {
+        s.parse::<F>().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZOOMS: [F; 5] = [4., 1., 1e-5, 1e-10, 1e-14];
+
+    #[test]
+    fn coordinate_decimals_follow_zoom() {
+        assert_eq!(coordinate_decimals(4.), 8);
+        assert_eq!(coordinate_decimals(1e-5), 9);
+        assert_eq!(coordinate_decimals(1e-10), 14);
+        assert_eq!(coordinate_decimals(1e-14), 17);
+        assert_eq!(coordinate_decimals(1e-30), 17);
+    }
+
+    #[test]
+    fn formatted_coordinate_is_precise_enough() {
+        for zoom in ZOOMS {
+            for value in [0., -0.743643887037151, 0.131825904205330, 1.5e-3, -2.] {
+                let s = format_coordinate(value, zoom);
+                let parsed: F = s.parse().unwrap();
+                // The error must stay well below the size of a pixel.
+                assert!(
+                    (parsed - value).abs() <= zoom * 1e-3,
+                    "{value} at zoom {zoom} displayed as {s}"
+                );
+            }
         }
     }
+
+    #[test]
+    fn parse_keeps_hidden_digits() {
+        for zoom in ZOOMS {
+            let value = -0.743_643_887_037_158_7;
+            let shown = format_coordinate(value, zoom);
+            assert_eq!(parse_coordinate(&shown, value, zoom), Some(value));
+            assert_eq!(
+                parse_coordinate(&format!(" {shown} "), value, zoom),
+                Some(value)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_accepts_full_precision_and_rejects_garbage() {
+        let typed = "0.1234567890123456";
+        assert_eq!(
+            parse_coordinate(typed, 0., 1.),
+            Some(0.1234567890123456 as F)
+        );
+        assert_eq!(parse_coordinate("1.5e-3", 0., 1.), Some(1.5e-3));
+        assert_eq!(parse_coordinate("abc", 0.5, 1.), None);
+    }
+
+    #[test]
+    fn previews_of_the_same_params_are_identical() {
+        let params = FrameParams::default();
+        let a = render_colored(&preview_params(&params, 24));
+        let b = render_colored(&preview_params(&params, 24));
+        assert_eq!(a.pixels, b.pixels);
+    }
+
+    #[test]
+    fn preview_seed_only_depends_on_the_raw_image() {
+        let params = FrameParams::default();
+        let seed = preview_seed(&params);
+
+        let mut recolored = params.clone();
+        recolored.gradient_offset = Some(0.3);
+        recolored.dither = Some(true);
+        assert_eq!(preview_seed(&recolored), seed);
+
+        let mut moved = params.clone();
+        moved.center_x += 0.1;
+        assert_ne!(preview_seed(&moved), seed);
+    }
+
+    #[test]
+    fn cancelled_render_restores_the_samples() {
+        let mut raw_image = Some(Mat2D::filled_with(3., 4, 2));
+        let mut raw_channels = None;
+        let mut samples_per_pixel = 8.;
+        let snapshot = SamplesSnapshot::take(&raw_image, &raw_channels, samples_per_pixel);
+
+        // A partial pass (half the pixels) accumulated by mistake.
+        let mut partial = Mat2D::filled_with(0., 4, 2);
+        partial.vec[..4].fill(5.);
+        accumulate_samples(
+            &Fractal::Mandelbrot,
+            raw_image.as_mut().unwrap(),
+            &mut raw_channels,
+            samples_per_pixel,
+            &partial,
+            None,
+            8.,
+        );
+        samples_per_pixel += 8.;
+
+        snapshot.restore(&mut raw_image, &mut raw_channels, &mut samples_per_pixel);
+        assert_eq!(raw_image, Some(Mat2D::filled_with(3., 4, 2)));
+        assert_eq!(samples_per_pixel, 8.);
+        assert!(raw_channels.is_none());
+    }
 }
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use image::{Rgb, RgbImage};
+
+use crate::{
+    error::{ErrorKind, Result},
+    F,
+};
+
+/// Differences between two images of the same size, over the 8-bit
+/// rgb channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffStats {
+    /// Mean absolute difference of the channels, from 0 to 255.
+    pub mae: F,
+    /// Peak signal-to-noise ratio in dB, infinite for identical
+    /// images.
+    pub psnr: F,
+    /// Number of pixels with at least one differing channel.
+    pub differing_pixels: usize,
+    pub pixel_count: usize,
+}
+
+/// Reads an image to compare as 8-bit rgb.
+pub fn load_image(path: &Path) -> Result<RgbImage> {
+    image::open(path)
+        .map(|image| image.into_rgb8())
+        .map_err(|e| ErrorKind::Diff(format!("cannot read {}: {}", path.display(), e)))
+}
+
+/// Compares two images, returns the per-pixel absolute difference of
+/// each channel multiplied by `amplification` (so that small
+/// differences are visible) along with the stats of the differences.
+pub fn diff_images(a: &RgbImage, b: &RgbImage, amplification: F) -> Result<(RgbImage, DiffStats)> {
+    if a.dimensions() != b.dimensions() {
+        return Err(ErrorKind::Diff(format!(
+            "the images have different sizes ({}x{} and {}x{})",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+
+    let mut abs_sum = 0u64;
+    let mut sq_sum = 0u64;
+    let mut differing_pixels = 0;
+
+    let diff = RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (Rgb(pa), Rgb(pb)) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let d = [0, 1, 2].map(|i| pa[i].abs_diff(pb[i]));
+
+        if d != [0; 3] {
+            differing_pixels += 1;
+        }
+        for v in d {
+            abs_sum += v as u64;
+            sq_sum += (v as u64).pow(2);
+        }
+
+        Rgb(d.map(|v| (v as F * amplification).round().min(255.) as u8))
+    });
+
+    let pixel_count = (a.width() * a.height()) as usize;
+    let channel_count = (3 * pixel_count).max(1) as F;
+    let mse = sq_sum as F / channel_count;
+    let psnr = if mse == 0. {
+        F::INFINITY
+    } else {
+        10. * (255. * 255. / mse).log10()
+    };
+
+    Ok((
+        diff,
+        DiffStats {
+            mae: abs_sum as F / channel_count,
+            psnr,
+            differing_pixels,
+            pixel_count,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width: u32, height: u32, value: u8) -> RgbImage {
+        RgbImage::from_pixel(width, height, Rgb([value; 3]))
+    }
+
+    #[test]
+    fn identical_images_do_not_differ() {
+        let a = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8 * 40, y as u8 * 90, 7]));
+        let (diff, stats) = diff_images(&a, &a.clone(), 8.).unwrap();
+
+        assert_eq!(diff, gray(3, 2, 0));
+        assert_eq!(stats.mae, 0.);
+        assert_eq!(stats.psnr, F::INFINITY);
+        assert_eq!(stats.differing_pixels, 0);
+        assert_eq!(stats.pixel_count, 6);
+    }
+
+    #[test]
+    fn stats_of_one_differing_channel() {
+        let a = gray(2, 2, 0);
+        let mut b = a.clone();
+        b.put_pixel(1, 0, Rgb([10, 0, 0]));
+
+        let (diff, stats) = diff_images(&a, &b, 4.).unwrap();
+        assert_eq!(*diff.get_pixel(1, 0), Rgb([40, 0, 0]));
+        assert_eq!(*diff.get_pixel(0, 1), Rgb([0, 0, 0]));
+
+        // 12 channels, one of which differs by 10.
+        assert!((stats.mae - 10. / 12.).abs() < 1e-12);
+        let psnr = 10. * (255. * 255. * 12. / 100. as F).log10();
+        assert!((stats.psnr - psnr).abs() < 1e-9);
+        assert_eq!(stats.differing_pixels, 1);
+        assert_eq!(stats.pixel_count, 4);
+    }
+
+    #[test]
+    fn difference_is_symmetric_and_amplification_saturates() {
+        let a = gray(2, 1, 100);
+        let b = RgbImage::from_fn(2, 1, |x, _| Rgb([120, 100, 90 + x as u8]));
+
+        let (diff_ab, stats_ab) = diff_images(&a, &b, 30.).unwrap();
+        let (diff_ba, stats_ba) = diff_images(&b, &a, 30.).unwrap();
+        assert_eq!(diff_ab, diff_ba);
+        assert_eq!(stats_ab, stats_ba);
+
+        // 20 * 30 and 10 * 30 saturate, 9 * 30 too, 0 stays 0.
+        assert_eq!(*diff_ab.get_pixel(0, 0), Rgb([255, 0, 255]));
+        assert_eq!(*diff_ab.get_pixel(1, 0), Rgb([255, 0, 255]));
+        assert_eq!(stats_ab.differing_pixels, 2);
+    }
+
+    #[test]
+    fn different_sizes_are_an_error() {
+        assert!(matches!(
+            diff_images(&gray(2, 3, 0), &gray(3, 2, 0), 1.),
+            Err(ErrorKind::Diff(_))
+        ));
+    }
+}
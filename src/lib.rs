@@ -0,0 +1,117 @@
+//! Fractal renderer, usable as a library to render frames from a
+//! program of your own.
+//!
+//! The items at the root of the crate are its stable api: build
+//! [`FrameParams`] (or decode them with [`ParamsKind::from_ron`]) and
+//! render them with [`render_frame_to_image`], or with
+//! [`render_raw_image`] and [`color_raw_image`] to get at the raw
+//! values. The documented modules hold the types these need, the
+//! ones used by the `fractal_rndr` binary only are hidden and may
+//! change between versions.
+//!
+//! ```no_run
+//! use fractal_rndr::{render_frame_to_image, ParamsKind};
+//!
+//! let ParamsKind::Frame(params) = ParamsKind::from_ron(
+//!     "Frame((img_width: 640, img_height: 480, fractal: Mandelbrot, max_iter: 500, \
+//!     coloring_mode: MinMaxNorm(map: Linear), sampling: (level: Low, random_offsets: true)))",
+//! )
+//! .unwrap() else {
+//!     unreachable!()
+//! };
+//! render_frame_to_image(&params).save("mandelbrot.png").unwrap();
+//! ```
+//!
+//! The gui is behind the `gui` feature (enabled by default), the
+//! library builds without it.
+
+pub mod coloring;
+pub mod complexx;
+pub mod error;
+pub mod formula;
+pub mod fractal;
+pub mod gradient_file;
+pub mod mat;
+pub mod params;
+pub mod progress;
+pub mod rendering;
+pub mod sampling;
+
+// Only used by the binary, not part of the api.
+#[doc(hidden)]
+pub mod animated_gif;
+#[doc(hidden)]
+pub mod autosave;
+#[doc(hidden)]
+pub mod band_file;
+#[cfg(feature = "gui")]
+mod bookmarks;
+#[cfg(feature = "gui")]
+mod calibration;
+#[doc(hidden)]
+pub mod ffmpeg;
+#[doc(hidden)]
+pub mod frame_hash;
+#[cfg(feature = "gui")]
+#[doc(hidden)]
+pub mod gui;
+#[doc(hidden)]
+pub mod image_diff;
+#[doc(hidden)]
+pub mod png_metadata;
+#[doc(hidden)]
+pub mod poster;
+#[cfg(feature = "gui")]
+mod presets;
+#[doc(hidden)]
+pub mod provenance;
+#[cfg(feature = "gui")]
+mod settings;
+#[doc(hidden)]
+pub mod sweep;
+#[doc(hidden)]
+pub mod values_file;
+#[cfg(feature = "gui")]
+mod workspace;
+
+use image::RgbImage;
+
+pub use crate::{
+    coloring::{color_raw_image, ColoringMode},
+    error::{ErrorKind, Result},
+    fractal::Fractal,
+    params::{FrameParams, ParamsKind},
+    rendering::render_raw_image,
+    sampling::Sampling,
+};
+
+#[cfg(feature = "force_f32")]
+pub type F = f32;
+#[cfg(feature = "force_f32")]
+use wide::f32x8;
+#[cfg(feature = "force_f32")]
+pub type FX = f32x8;
+
+#[cfg(not(feature = "force_f32"))]
+pub type F = f64;
+#[cfg(not(feature = "force_f32"))]
+use wide::f64x4;
+#[cfg(not(feature = "force_f32"))]
+pub type FX = f64x4;
+
+/// Renders and colors a frame on the current thread pool (see
+/// [`rendering::thread_pool`]). `gradient_path` is not read,
+/// the gradient has to be loaded into `gradient` beforehand as a
+/// [`coloring::GradientSpec::Custom`] (see
+/// [`gradient_file::load_gradient`]). `max_iter` is chosen from the
+/// zoom when `auto_max_iter` is set.
+pub fn render_frame_to_image(params: &FrameParams) -> RgbImage {
+    let mut params = params.clone();
+    params.apply_auto_max_iter();
+
+    let sampling_points = params.sampling.generate_sampling_points();
+    let (raw_image, raw_channels, _) =
+        rendering::render_raw_image_channels(&params, &sampling_points, None, None);
+
+    coloring::color_raw_channels(&params, raw_image, raw_channels.as_ref())
+}
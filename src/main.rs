@@ -1,126 +1,1719 @@
-mod coloring;
-mod complexx;
-mod error;
-mod fractal;
-mod gui;
-mod mat;
-mod params;
-#[allow(dead_code)]
-mod presets;
-mod progress;
-mod rendering;
-mod sampling;
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+#[cfg(feature = "gui")]
+use eframe::egui::ViewportBuilder;
+use exr::{
+    image::{write::WritableImage, Image, SpecificChannels},
+    math::Vec2,
+};
+use image::{DynamicImage, ImageFormat};
+
+#[cfg(feature = "gui")]
+use fractal_rndr::gui::{Gui, WINDOW_SIZE};
+use fractal_rndr::{
+    animated_gif::GifWriter,
+    autosave::autosave_path,
+    band_file::{band_rows, merge_bands, read_band, write_band, Band},
+    coloring::{
+        analyze_raw_image, color_mapping, color_raw_channels, color_raw_region, parts_raw_extrema,
+        raw_image_to_rgb32f, sanitize_gradient, scale_image, scale_linear_image, ColoringMode,
+        Extremum, GradientSpec, InteriorMode, MapValue, RenderStats, STATS_HISTOGRAM_BARS,
+        STATS_PERCENTILES,
+    },
+    error::{panic_message, ErrorKind, Result},
+    ffmpeg::FfmpegWriter,
+    fractal::{Fractal, FractalParameter},
+    frame_hash::{
+        first_different_pixel, read_frame_values, read_manifest, write_frame_values,
+        write_manifest, FrameHash, FrameManifest,
+    },
+    gradient_file::load_gradient,
+    image_diff,
+    mat::Mat2D,
+    params::{AnimationParams, DevOptions, FrameParams, ParamsFormat, ParamsKind},
+    png_metadata,
+    poster::{self, PosterManifest, PosterTile},
+    progress::{format_remaining_time, Progress},
+    provenance::{apply_layer, Provenance, Source},
+    rendering::{
+        accumulate_samples, mix_seed, perturbation, progress_total, region_params,
+        region_progress_total, render_raw_image, render_raw_image_channels,
+        render_raw_image_region, render_raw_image_rows, thread_pool, time_render, RawChannels,
+        View,
+    },
+    sampling::{preview_sampling_points, Sampling, SamplingLevel},
+    sweep::{self, Sweep},
+    F,
+};
+
+const USAGE: &str = "This is a fractal renderer.
+Usage: fractal_rndr <param file path> <output image path>
+A png image rendered by fractal_rndr can be given in place of the param file.
+Use --no-gui for cli mode.
+Use --info to print the parameters (add --verbose to see where each value comes from).
+Flags go after the paths.
+
+Options:
+  --save-scale <scale>   scale the image when saving it (e.g. 0.5)
+  --threads <n>          number of threads used to render (0 means all cores), shared
+                         by the frames of an animation rendered in parallel
+  --seed <n>             seed of the random sampling offsets (renders with the same
+                         seed are identical)
+  --passes <n>           render the frame n times with other sampling offsets and
+                         average the passes, Ctrl-C saves the passes rendered so far
+                         (frames only)
+  --checkpoints          also save the image after each pass (out.png gives
+                         out_pass01.png, out_pass02.png...)
+  --stripe-height <n>    render frames in stripes of n rows to save memory (png only)
+  --export-zoom-animation <path>
+                         write animation parameters zooming into the view of the
+                         param file
+  --tile <i>/<n>         render only the i-th of n horizontal bands of the frame and
+                         write its raw values to the output path, to be merged with
+                         --merge (bands can be rendered on different machines)
+  --poster <c>x<r>       render the frame as a grid of c by r tiles saved next to the
+                         output path (out.png gives out_1_1.png, out_1_2.png...) with
+                         a manifest of their positions (out.poster.ron), for images
+                         too large to render at once
+  --sweep <field>=<from>..<to>
+                         render a contact sheet of small frames with the field (e.g.
+                         zoom, rotate, max_iter or a parameter of the fractal like exp
+                         or a.re) going from 'from' to 'to', each labeled with its value
+  --sweep-grid <c>x<r>   columns and rows of the contact sheet (defaults to 4x3)
+  --sweep-cell <w>       width of the cells of the contact sheet (defaults to 256)
+  --merge <image>        color the bands given as arguments into a single image,
+                         e.g. 'fractal_rndr --merge out.png band1 band2'
+  --diff <a> <b> [<diff image>]
+                         compare two images of the same size, print the mean absolute
+                         difference, PSNR and number of differing pixels and write the
+                         difference of each pixel, e.g. 'fractal_rndr --diff a.png
+                         b.png diff.png'
+  --diff-amplify <k>     factor applied to the differences of the diff image (defaults
+                         to 10)
+  --extract-params <image>
+                         write the parameters embedded in a png image to a .ron file
+  --stdin                read the parameters from the standard input (the only path
+                         given is then the output image path)
+  --stdout               write the image as png to the standard output (frames are
+                         concatenated for animations), logs go to stderr
+  --raw-output           save the normalized raw values instead of the colored
+                         image (32-bit float for .exr, 16-bit grayscale otherwise,
+                         frames only)
+  --resume               skip the frames of an animation whose image already exists
+                         (after an interrupted render)
+  --batch                render every .ron (or .json) file of the directory given in
+                         place of the param file to a png of the same name in the
+                         directory given in place of the output path, e.g.
+                         'fractal_rndr params/ images/ --no-gui --batch'
+  --watch                keep running after the render and render again each time the
+                         param file is modified
+  --watch-once-per <s>   render again at most once every s seconds when watching
+  --region <x>,<y>,<w>,<h>
+                         render only the w by h pixels region of the frame whose top
+                         left corner is (x, y), e.g. to render a detail again
+  --hash-frames <path>   write a hash of the raw image of each frame to a manifest,
+                         and its raw values next to it (<path>.<frame>.values) to
+                         locate differences (whole frames rendered in a single pass
+                         only)
+  --verify <manifest>    render the frames listed in a manifest again and check that
+                         they match their hashes, reporting the first pixel that
+                         differs (renders must be seeded or without random offsets
+                         to be reproducible)
+  --frames <a>-<b>       verify only the frames from a to b (or only frame a when
+                         given a single number)
+  --bench                render a small frame of each fractal (or only of the one of
+                         the param file) several times and print the timings as CSV
+  --bench-level <level>  sampling level of the benchmark (e.g. 'Low', defaults to
+                         the one of the param file)
+  --bench-size <w>x<h>   image size of the benchmark (defaults to 256x256), e.g.
+                         1920x1080 or 3840x2160 to measure full size renders
+  --ffmpeg-args <args>   encoding arguments given to ffmpeg when rendering an animation
+                         to an .mp4 file (defaults to
+                         '-c:v libx264 -pix_fmt yuv420p -vf pad=ceil(iw/2)*2:ceil(ih/2)*2')
+  --autosave <minutes>   minutes between two autosaves of the samples accumulated in
+                         the gui, restored after a crash (defaults to 5, 0 disables it)
+  --workspace <file>     keep the bookmarks and user presets of the gui in this
+                         workspace file (and the autosave next to it) instead of
+                         next to the param file and in the config directory
+  --gui-backend <glow|wgpu|auto>
+                         renderer used by the gui (wgpu requires building with the
+                         wgpu feature)
+
+More information: https://gitlab.com/valflrt/fractal_rndr";
+
+fn main() -> Result<()> {
+    let args = valargs::parse();
+
+    // Checked first so that the usage is printed whatever the other
+    // arguments are, even when they are invalid.
+    if has_flag(&args, "help") || has_flag(&args, "h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    // The arguments are band files, not a parameter file.
+    if let Some(output_image_path) = args.option("merge") {
+        let band_paths = (1..).map_while(|i| args.nth(i)).map(Path::new);
+        let output_image_path = check_output_path(PathBuf::from(output_image_path), false)?;
+        return merge(band_paths, output_image_path);
+    }
+
+    // The arguments are the images to compare.
+    if let Some(image_a_path) = args.option("diff") {
+        let (Some(image_b_path), output_path) = (args.nth(1), args.nth(2)) else {
+            return Err(ErrorKind::MissingCliArg);
+        };
+        let amplification = args
+            .option("diff-amplify")
+            .map(|amplification| {
+                amplification
+                    .parse::<F>()
+                    .ok()
+                    .filter(|&a| a > 0.)
+                    .ok_or(ErrorKind::InvalidCliArg("diff-amplify".to_string()))
+            })
+            .transpose()?
+            .unwrap_or(DEFAULT_DIFF_AMPLIFICATION);
+        let output_path = output_path
+            .map(|path| check_output_path(PathBuf::from(path), false))
+            .transpose()?;
+        return diff(
+            Path::new(image_a_path),
+            Path::new(image_b_path),
+            output_path.as_deref(),
+            amplification,
+        );
+    }
+
+    let read_stdin = has_flag(&args, "stdin");
+    let write_stdout = has_flag(&args, "stdout");
+
+    // When parameters are read from the standard input, the only
+    // path given is the output image path.
+    let (param_file_path, output_image_path) = if read_stdin {
+        (None, args.nth(1).map(PathBuf::from))
+    } else {
+        (
+            args.nth(1).map(PathBuf::from),
+            args.nth(2).map(PathBuf::from),
+        )
+    };
+
+    let save_scale = args
+        .option("save-scale")
+        .map(|save_scale| {
+            save_scale
+                .parse::<F>()
+                .ok()
+                .filter(|&s| s > 0.)
+                .ok_or(ErrorKind::InvalidCliArg("save-scale".to_string()))
+        })
+        .transpose()?;
+    let threads = args
+        .option("threads")
+        .map(|threads| {
+            threads
+                .parse::<usize>()
+                .map_err(|_| ErrorKind::InvalidCliArg("threads".to_string()))
+        })
+        .transpose()?;
+    let seed = args
+        .option("seed")
+        .map(|seed| {
+            seed.parse::<u64>()
+                .map_err(|_| ErrorKind::InvalidCliArg("seed".to_string()))
+        })
+        .transpose()?;
+    let passes = args
+        .option("passes")
+        .map(|passes| {
+            passes
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or(ErrorKind::InvalidCliArg("passes".to_string()))
+        })
+        .transpose()?;
+    let checkpoints = has_flag(&args, "checkpoints");
+    let raw_output = has_flag(&args, "raw-output").then_some(true);
+    let cli_params = CliParams {
+        save_scale,
+        raw_output,
+        threads,
+        seed,
+        passes,
+    };
+    let tile = args
+        .option("tile")
+        .map(|tile| {
+            tile.split_once('/')
+                .and_then(|(i, n)| Some((i.parse::<usize>().ok()?, n.parse::<usize>().ok()?)))
+                .filter(|&(i, n)| (1..=n).contains(&i))
+                .ok_or(ErrorKind::InvalidCliArg("tile".to_string()))
+        })
+        .transpose()?;
+    let stripe_height = args
+        .option("stripe-height")
+        .map(|stripe_height| {
+            stripe_height
+                .parse::<u32>()
+                .ok()
+                .filter(|&h| h > 0)
+                .ok_or(ErrorKind::InvalidCliArg("stripe-height".to_string()))
+        })
+        .transpose()?;
+    let region = args
+        .option("region")
+        .map(|region| {
+            let values = region
+                .split(',')
+                .map(|v| v.trim().parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>();
+            match values.as_deref() {
+                Some(&[x, y, width, height]) if width > 0 && height > 0 => x
+                    .checked_add(width)
+                    .zip(y.checked_add(height))
+                    .map(|(x_end, y_end)| (x..x_end, y..y_end)),
+                _ => None,
+            }
+            .ok_or(ErrorKind::InvalidCliArg("region".to_string()))
+        })
+        .transpose()?;
+    let poster = args
+        .option("poster")
+        .map(|poster| {
+            poster
+                .split_once('x')
+                .and_then(|(c, r)| {
+                    Some((c.trim().parse::<u32>().ok()?, r.trim().parse::<u32>().ok()?))
+                })
+                .filter(|&(c, r)| c > 0 && r > 0)
+                .ok_or(ErrorKind::InvalidCliArg("poster".to_string()))
+        })
+        .transpose()?;
+    let sweep = args.option("sweep").map(Sweep::parse).transpose()?;
+    let sweep_grid = args
+        .option("sweep-grid")
+        .map(|grid| {
+            grid.split_once('x')
+                .and_then(|(c, r)| {
+                    Some((c.trim().parse::<u32>().ok()?, r.trim().parse::<u32>().ok()?))
+                })
+                .filter(|&(c, r)| c > 0 && r > 0)
+                .ok_or(ErrorKind::InvalidCliArg("sweep-grid".to_string()))
+        })
+        .transpose()?
+        .unwrap_or((4, 3));
+    let sweep_cell_width = args
+        .option("sweep-cell")
+        .map(|width| {
+            width
+                .parse::<u32>()
+                .ok()
+                .filter(|&w| w > 0)
+                .ok_or(ErrorKind::InvalidCliArg("sweep-cell".to_string()))
+        })
+        .transpose()?
+        .unwrap_or(256);
+    let hash_frames = args.option("hash-frames").map(PathBuf::from);
+    let verified_frames = args
+        .option("frames")
+        .map(|frames| {
+            let (first, last) = frames.split_once('-').unwrap_or((frames, frames));
+            first
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .zip(last.trim().parse::<usize>().ok())
+                .filter(|&(first, last)| first > 0 && first <= last)
+                .map(|(first, last)| first - 1..last)
+                .ok_or(ErrorKind::InvalidCliArg("frames".to_string()))
+        })
+        .transpose()?;
+    let watch_interval = args
+        .option("watch-once-per")
+        .map(|seconds| {
+            seconds
+                .parse::<f64>()
+                .ok()
+                .filter(|&s| s >= 0.)
+                .map(Duration::from_secs_f64)
+                .ok_or(ErrorKind::InvalidCliArg("watch-once-per".to_string()))
+        })
+        .transpose()?;
+    let ffmpeg_args = args.option("ffmpeg-args");
+    let autosave_interval = args
+        .option("autosave")
+        .map(|minutes| {
+            minutes
+                .parse::<f64>()
+                .ok()
+                .filter(|&m| m >= 0.)
+                .ok_or(ErrorKind::InvalidCliArg("autosave".to_string()))
+        })
+        .transpose()?
+        .unwrap_or(DEFAULT_AUTOSAVE_MINUTES);
+    let bench_level = args
+        .option("bench-level")
+        .map(|level| {
+            ron::from_str::<SamplingLevel>(level)
+                .map_err(|_| ErrorKind::InvalidCliArg("bench-level".to_string()))
+        })
+        .transpose()?;
+    let bench_size = args
+        .option("bench-size")
+        .map(|size| {
+            size.split_once('x')
+                .and_then(|(w, h)| {
+                    Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?))
+                })
+                .filter(|&(w, h)| w > 0 && h > 0)
+                .ok_or(ErrorKind::InvalidCliArg("bench-size".to_string()))
+        })
+        .transpose()?;
+    let render = |params: ParamsKind, output: Output| match params {
+        // Hashes are checked by rendering the whole frame in a single
+        // pass (see `verify_frames`).
+        ParamsKind::Frame(_) if hash_frames.is_some() && region.is_some() => {
+            Err(ErrorKind::FrameHashing("regions".to_string()))
+        }
+        ParamsKind::Frame(params)
+            if hash_frames.is_some() && params.passes.is_some_and(|passes| passes > 1) =>
+        {
+            Err(ErrorKind::FrameHashing("several passes".to_string()))
+        }
+        ParamsKind::Frame(params) => match stripe_height {
+            Some(_) if hash_frames.is_some() => {
+                Err(ErrorKind::StripedRendering("frame hashing".to_string()))
+            }
+            Some(_) if region.is_some() => Err(ErrorKind::StripedRendering("regions".to_string())),
+            Some(_) if params.passes.is_some_and(|passes| passes > 1) => {
+                Err(ErrorKind::StripedRendering("several passes".to_string()))
+            }
+            Some(stripe_height) => render_frame_striped(*params, output, stripe_height),
+            None => render_frame(
+                *params,
+                output,
+                hash_frames.as_deref(),
+                region.clone(),
+                checkpoints,
+            ),
+        },
+        ParamsKind::Animation(_) if region.is_some() => Err(ErrorKind::Region(
+            "only frames can be rendered partially".to_string(),
+        )),
+        ParamsKind::Animation(animation_params) => render_animation(
+            *animation_params,
+            output,
+            hash_frames.as_deref(),
+            ffmpeg_args,
+            has_flag(&args, "resume"),
+        ),
+    };
+
+    // The paths are directories, not a parameter file and an image.
+    if has_flag(&args, "batch") {
+        let (Some(params_dir), Some(output_dir)) = (param_file_path, output_image_path) else {
+            return Err(ErrorKind::MissingCliArg);
+        };
+        if read_stdin || write_stdout || hash_frames.is_some() {
+            return Err(ErrorKind::Batch(
+                "--stdin, --stdout and --hash-frames can't be used with --batch".to_string(),
+            ));
+        }
+        return batch(&params_dir, &output_dir, &cli_params, render);
+    }
+
+    let (params, provenance, loaded_message) =
+        load_params(param_file_path.as_deref(), read_stdin, &cli_params)?;
+
+    if let Some(image_path) = args.option("extract-params") {
+        extract_params(Path::new(image_path))
+    } else if let Some(path) = args.option("export-zoom-animation") {
+        export_zoom_animation(&params, Path::new(path))
+    } else if let Some((index, count)) = tile {
+        let ParamsKind::Frame(params) = params else {
+            return Err(ErrorKind::RenderBand(
+                "frame parameters are required".to_string(),
+            ));
+        };
+        if params.coloring_mode.needs_channels() {
+            return Err(ErrorKind::RenderBand(
+                "bands don't store the channels needed by two channel coloring".to_string(),
+            ));
+        }
+        let output_path = output_image_path.ok_or(ErrorKind::MissingCliArg)?;
+        render_band(*params, &output_path, index, count)
+    } else if let Some((columns, rows)) = poster {
+        let ParamsKind::Frame(params) = params else {
+            return Err(ErrorKind::PosterRendering("animations".to_string()));
+        };
+        let output_path = output_image_path.ok_or(ErrorKind::MissingCliArg)?;
+        render_poster(
+            *params,
+            &check_output_path(output_path, false)?,
+            columns,
+            rows,
+        )
+    } else if let Some(sweep) = sweep {
+        let ParamsKind::Frame(params) = params else {
+            return Err(ErrorKind::Sweep(
+                "frame parameters are required".to_string(),
+            ));
+        };
+        let output_path = output_image_path.ok_or(ErrorKind::MissingCliArg)?;
+        let (columns, rows) = sweep_grid;
+        render_sweep(
+            *params,
+            &check_output_path(output_path, false)?,
+            &sweep,
+            columns,
+            rows,
+            sweep_cell_width,
+        )
+    } else if has_flag(&args, "info") {
+        print_info(&provenance, has_flag(&args, "verbose"));
+        let frame_params = match &params {
+            ParamsKind::Frame(params) => Some(params.as_ref().clone()),
+            ParamsKind::Animation(params) => print_animation_curves(params)?
+                .then(|| params.get_frame_params(0.))
+                .transpose()?,
+        };
+        if let Some(frame_params) = frame_params {
+            print_recommendations(&frame_params);
+        }
+        Ok(())
+    } else if has_flag(&args, "bench") {
+        let ParamsKind::Frame(params) = params else {
+            return Err(ErrorKind::Bench(
+                "frame parameters are required".to_string(),
+            ));
+        };
+        // Without a parameter file, every fractal is benchmarked.
+        let fractals = if param_file_path.is_some() || read_stdin {
+            None
+        } else {
+            Some(Fractal::all_default())
+        };
+        bench(*params, fractals, bench_level, bench_size)
+    } else if let Some(manifest_path) = args.option("verify") {
+        verify_frames(&params, Path::new(manifest_path), verified_frames)
+    } else if has_flag(&args, "no-gui") {
+        // Checked before rendering rather than failing when saving.
+        let is_animation = matches!(params, ParamsKind::Animation(_));
+        let output = if write_stdout {
+            Some(Output::Stdout)
+        } else {
+            output_image_path
+                .map(|path| check_output_path(path, is_animation))
+                .transpose()?
+                .map(Output::File)
+        };
+        if let (true, Some(output)) = (param_file_path.is_some() || read_stdin, output) {
+            if has_flag(&args, "watch") || watch_interval.is_some() {
+                let Some(param_file_path) = param_file_path.filter(|_| !read_stdin) else {
+                    return Err(ErrorKind::Watch(
+                        "the parameters must come from a file".to_string(),
+                    ));
+                };
+                let Output::File(output_image_path) = output else {
+                    return Err(ErrorKind::Watch(
+                        "the images can't be written to the standard output".to_string(),
+                    ));
+                };
+                watch(
+                    &param_file_path,
+                    params,
+                    watch_interval.unwrap_or(Duration::ZERO),
+                    || Ok(load_params(Some(&param_file_path), false, &cli_params)?.0),
+                    |params| render(params, Output::File(output_image_path.clone())),
+                )
+            } else {
+                render(params, output)
+            }
+        } else {
+            Err(ErrorKind::MissingCliArg)
+        }
+    } else {
+        let workspace = args.option("workspace").map(PathBuf::from);
+        // With a workspace, the autosave is kept next to it.
+        let autosave = (autosave_interval > 0.).then(|| {
+            (
+                autosave_path(workspace.as_deref().or(param_file_path.as_deref())),
+                Duration::from_secs_f64(autosave_interval * 60.),
+            )
+        });
+        // Saving the parameters must not overwrite the image they were
+        // read from.
+        let param_file_path = param_file_path.filter(|_| loaded_message.is_none());
+        start_gui(
+            params,
+            provenance,
+            param_file_path,
+            output_image_path,
+            loaded_message,
+            args.option("gui-backend"),
+            GuiStateFiles {
+                autosave,
+                workspace,
+            },
+        )
+    }
+}
+
+/// Files the gui keeps its state in, besides the param file.
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+struct GuiStateFiles {
+    /// Autosave file and time between two autosaves, `None` when
+    /// autosaving is disabled.
+    autosave: Option<(PathBuf, Duration)>,
+    /// Workspace file given with `--workspace`.
+    workspace: Option<PathBuf>,
+}
+
+/// Parameters given on the command line, which take precedence over
+/// the parameter file.
+struct CliParams {
+    save_scale: Option<F>,
+    raw_output: Option<bool>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    passes: Option<usize>,
+}
+
+/// Reads the parameters from the parameter file (or the standard
+/// input) and applies the gradient file and the command line
+/// parameters. Also returns the message shown in the gui when the
+/// parameters come from an image.
+fn load_params(
+    param_file_path: Option<&Path>,
+    read_stdin: bool,
+    cli_params: &CliParams,
+) -> Result<(ParamsKind, Provenance, Option<String>)> {
+    let mut params = ParamsKind::default();
+    let mut provenance = Provenance::new(&params);
+    let mut loaded_message = None;
+
+    if read_stdin {
+        let param_str = io::read_to_string(io::stdin()).map_err(ErrorKind::ReadParameterFile)?;
+        let stdin_params = ParamsKind::decode(&param_str, ParamsFormat::detect(&param_str))?;
+        apply_layer(&mut params, &mut provenance, Source::Stdin, |params| {
+            *params = stdin_params
+        });
+    } else if let Some(param_file_path) = param_file_path {
+        let param_file = fs::read(param_file_path).map_err(ErrorKind::ReadParameterFile)?;
+        // Rendered png images can be given in place of a parameter
+        // file, whatever their extension.
+        let (param_file_str, format) = if png_metadata::is_png(&param_file) {
+            let metadata = png_metadata::read_metadata(param_file.as_slice())?;
+            let message = format!("parameters read from image ({})", metadata.describe());
+            eprintln!("{}", message);
+            loaded_message = Some(message);
+            (metadata.params, ParamsFormat::Ron)
+        } else {
+            let param_file_str = String::from_utf8(param_file).map_err(|e| {
+                ErrorKind::ReadParameterFile(io::Error::new(io::ErrorKind::InvalidData, e))
+            })?;
+            (param_file_str, ParamsFormat::from_path(param_file_path))
+        };
+        let file_params = ParamsKind::decode(&param_file_str, format)?;
+        apply_layer(
+            &mut params,
+            &mut provenance,
+            Source::File(param_file_path.to_path_buf()),
+            |params| *params = file_params,
+        );
+    }
+
+    let gradient_path = match &params {
+        ParamsKind::Frame(params) => params.gradient_path.as_ref(),
+        ParamsKind::Animation(params) => params.gradient_path.as_ref(),
+    };
+    if let Some(gradient_path) = gradient_path {
+        // Relative gradient paths are relative to the parameter file.
+        let gradient_path = param_file_path
+            .and_then(|p| p.parent())
+            .map_or_else(|| gradient_path.to_owned(), |dir| dir.join(gradient_path));
+        let gradient = load_gradient(&gradient_path)?;
+        apply_layer(
+            &mut params,
+            &mut provenance,
+            Source::File(gradient_path),
+            |params| match params {
+                ParamsKind::Frame(params) => params.gradient = GradientSpec::Custom(gradient),
+                ParamsKind::Animation(params) => params.gradient = GradientSpec::Custom(gradient),
+            },
+        );
+    }
+
+    // Built-in gradients need no fixing up.
+    if let GradientSpec::Custom(gradient) = match &mut params {
+        ParamsKind::Frame(params) => &mut params.gradient,
+        ParamsKind::Animation(params) => &mut params.gradient,
+    } {
+        for warning in sanitize_gradient(gradient) {
+            eprintln!("warning: {}", warning);
+        }
+    }
+    params.validate()?;
+
+    if let ParamsKind::Animation(_) = params {
+        for (option, given) in [
+            ("raw-output", cli_params.raw_output.is_some()),
+            ("passes", cli_params.passes.is_some()),
+        ] {
+            if given {
+                return Err(ErrorKind::FrameOnlyOption(option.to_string()));
+            }
+        }
+    }
+
+    apply_layer(
+        &mut params,
+        &mut provenance,
+        Source::Cli,
+        |params| match params {
+            ParamsKind::Frame(params) => {
+                params.save_scale = cli_params.save_scale.or(params.save_scale);
+                params.raw_output = cli_params.raw_output.or(params.raw_output);
+                params.threads = cli_params.threads.or(params.threads);
+                params.seed = cli_params.seed.or(params.seed);
+                params.passes = cli_params.passes.or(params.passes);
+            }
+            ParamsKind::Animation(params) => {
+                params.save_scale = cli_params.save_scale.or(params.save_scale);
+                params.threads = cli_params.threads.or(params.threads);
+                params.seed = cli_params.seed.or(params.seed);
+            }
+        },
+    );
+
+    if let ParamsKind::Frame(params) = &params {
+        if View::new(params).exceeds_precision()
+            && !perturbation::is_supported(&params.fractal, params.value)
+        {
+            eprintln!(
+                "warning: the zoom exceeds the precision of floating point numbers, the image will be pixelated"
+            );
+        }
+    }
+
+    Ok((params, provenance, loaded_message))
+}
+
+/// Renders each parameter file of `params_dir` to an image of the
+/// same name in `output_dir`. Failures are printed and counted
+/// without stopping the other renders.
+fn batch(
+    params_dir: &Path,
+    output_dir: &Path,
+    cli_params: &CliParams,
+    render: impl Fn(ParamsKind, Output) -> Result<()>,
+) -> Result<()> {
+    let mut param_file_paths = fs::read_dir(params_dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .map_err(ErrorKind::ReadParameterFile)?;
+    // Bookmarks of the gui are saved next to the param files.
+    param_file_paths.retain(|path| {
+        path.extension()
+            .is_some_and(|ext| ext == "ron" || ext == "json")
+            && !path.to_string_lossy().ends_with(".bookmarks.json")
+    });
+    param_file_paths.sort();
+    if param_file_paths.is_empty() {
+        return Err(ErrorKind::Batch(format!(
+            "no .ron or .json file in {}",
+            params_dir.display()
+        )));
+    }
+    fs::create_dir_all(output_dir).map_err(|e| {
+        ErrorKind::Batch(format!("failed to create {}: {}", output_dir.display(), e))
+    })?;
+
+    let start = Instant::now();
+    let mut failed = Vec::new();
+    for (i, param_file_path) in param_file_paths.iter().enumerate() {
+        eprintln!(
+            "[{}/{}] {}",
+            i + 1,
+            param_file_paths.len(),
+            param_file_path.display()
+        );
+
+        let mut file_name = param_file_path.file_stem().unwrap_or_default().to_owned();
+        file_name.push(".png");
+        let output = Output::File(output_dir.join(file_name));
+        let result = load_params(Some(param_file_path), false, cli_params)
+            .and_then(|(params, _, _)| render(params, output));
+        if let Err(e) = result {
+            eprint!("Error: {:?}", e);
+            failed.push(param_file_path);
+        }
+        eprintln!();
+    }
+
+    eprintln!(
+        "{} of {} parameter files rendered - {:.1}s elapsed",
+        param_file_paths.len() - failed.len(),
+        param_file_paths.len(),
+        start.elapsed().as_secs_f32()
+    );
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        eprintln!("failed:");
+        for path in &failed {
+            eprintln!("  {}", path.display());
+        }
+        Err(ErrorKind::BatchFailures(failed.len()))
+    }
+}
+
+/// Interval between two checks of the modification time of the
+/// watched parameter file.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Time without modification after which animation parameters are
+/// considered saved, animations being long to render again.
+const WATCH_ANIMATION_SETTLE: Duration = Duration::from_millis(500);
+
+/// Renders `params`, then renders again with the parameters returned
+/// by `load` each time the parameter file is modified, at most once
+/// every `min_interval`. Loading and rendering errors are printed
+/// without stopping, the file can be invalid while it's being edited.
+fn watch(
+    param_file_path: &Path,
+    params: ParamsKind,
+    min_interval: Duration,
+    load: impl Fn() -> Result<ParamsKind>,
+    render: impl Fn(ParamsKind) -> Result<()>,
+) -> Result<()> {
+    let modified = || {
+        fs::metadata(param_file_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    let mut rendered = modified();
+    let mut is_animation = matches!(params, ParamsKind::Animation(_));
+    let mut last_render = Instant::now();
+    if let Err(e) = render(params) {
+        eprint!("Error: {:?}", e);
+    }
+    // Modification time not rendered yet and when it was first seen.
+    let mut pending: Option<(SystemTime, Instant)> = None;
+
+    eprintln!(
+        "watching {} for modifications (ctrl-c to stop)",
+        param_file_path.display()
+    );
+    loop {
+        thread::sleep(WATCH_POLL_INTERVAL);
+
+        // The file can be missing for a moment when editors replace
+        // it.
+        let Some(current) = modified().filter(|&m| Some(m) != rendered) else {
+            pending = None;
+            continue;
+        };
+        let seen = match pending {
+            Some((m, seen)) if m == current => seen,
+            _ => {
+                pending = Some((current, Instant::now()));
+                continue;
+            }
+        };
+        if (is_animation && seen.elapsed() < WATCH_ANIMATION_SETTLE)
+            || last_render.elapsed() < min_interval
+        {
+            continue;
+        }
+
+        rendered = Some(current);
+        pending = None;
+        last_render = Instant::now();
+
+        eprintln!();
+        eprintln!("{} modified, rendering again", param_file_path.display());
+        let result = load().and_then(|params| {
+            is_animation = matches!(params, ParamsKind::Animation(_));
+            render(params)
+        });
+        if let Err(e) = result {
+            eprint!("Error: {:?}", e);
+        }
+    }
+}
+
+/// Minutes between two autosaves of the samples accumulated in the
+/// gui.
+const DEFAULT_AUTOSAVE_MINUTES: f64 = 5.;
+
+/// Factor applied to the differences of the images compared with
+/// --diff, small differences being otherwise too dark to see.
+const DEFAULT_DIFF_AMPLIFICATION: F = 10.;
+
+/// Options that don't take a value.
+const FLAGS: &[&str] = &[
+    "help",
+    "h",
+    "info",
+    "verbose",
+    "no-gui",
+    "raw-output",
+    "stdin",
+    "stdout",
+    "bench",
+    "resume",
+    "watch",
+    "batch",
+    "checkpoints",
+];
+
+/// `valargs` takes the token following an option as its value, so
+/// a flag directly following another flag ends up as the value of
+/// the latter.
+fn has_flag(args: &valargs::Args, name: &str) -> bool {
+    args.has_option(name)
+        || FLAGS.iter().any(|flag| {
+            args.option(flag)
+                .is_some_and(|value| value.trim_start_matches('-') == name)
+        })
+}
+
+fn print_info(provenance: &Provenance, verbose: bool) {
+    for (name, value, source) in provenance.iter() {
+        if verbose {
+            println!("{}: {} ({})", name, value, source);
+        } else {
+            println!("{}: {}", name, value);
+        }
+    }
+}
+
+/// Plots each animated value over the frames, the zoom in log scale,
+/// so that discontinuities and wrong easings show before rendering.
+/// Returns `false` (after listing them) when some frames are not
+/// covered by the steps of a value.
+fn print_animation_curves(params: &AnimationParams) -> Result<bool> {
+    const WIDTH: usize = 60;
+
+    let frame_count = params.frame_count();
+
+    println!();
+    println!("animation curves ({} frames):", frame_count);
+
+    let uncovered_frames = params.uncovered_frames();
+    if !uncovered_frames.is_empty() {
+        for (name, frames) in uncovered_frames {
+            println!(
+                "  {}: no step covers {} frame(s), first one is frame {} (t = {}s)",
+                name,
+                frames.len(),
+                frames[0] + 1,
+                frames[0] as F / params.fps
+            );
+        }
+        return Ok(false);
+    }
+
+    // Values of each frame, in the same order for all the frames as
+    // the fractal doesn't change.
+    let frames = (0..frame_count)
+        .map(|i| {
+            let t = i as F / params.fps;
+            let mut frame = params.get_frame_params(t)?;
+
+            let mut values = vec![
+                ("zoom (log)".to_string(), frame.zoom.ln()),
+                ("center_x".to_string(), frame.center_x),
+                ("center_y".to_string(), frame.center_y),
+                ("max_iter".to_string(), frame.max_iter as F),
+            ];
+            if let Some(rotate) = frame.rotate {
+                values.push(("rotate".to_string(), rotate));
+            }
+            if let Some(gradient_offset) = frame.gradient_offset {
+                values.push(("gradient_offset".to_string(), gradient_offset));
+            }
+            if let ColoringMode::MinMaxNorm { min, max, .. } = frame.coloring_mode {
+                if let Extremum::Custom(min) = min {
+                    values.push(("min".to_string(), min));
+                }
+                if let Extremum::Custom(max) = max {
+                    values.push(("max".to_string(), max));
+                }
+            }
+            if let Some(map_parameter) = params
+                .map_parameter
+                .as_ref()
+                .map(|map_parameter| map_parameter.get_value("map_parameter", t))
+                .transpose()?
+            {
+                values.push(("map_parameter".to_string(), map_parameter));
+            }
+            for parameter in frame.fractal.parameters() {
+                match parameter {
+                    FractalParameter::Real { name, value, .. } => {
+                        values.push((name.to_string(), *value))
+                    }
+                    FractalParameter::Integer { name, value, .. } => {
+                        values.push((name.to_string(), *value as F))
+                    }
+                    FractalParameter::Complex { name, re, im, .. } => {
+                        values.push((format!("{}_re", name), *re));
+                        values.push((format!("{}_im", name), *im));
+                    }
+                }
+            }
+            Ok(values)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let Some(first_frame) = frames.first() else {
+        return Ok(true);
+    };
+    for (k, (name, _)) in first_frame.iter().enumerate() {
+        let values = frames.iter().map(|frame| frame[k].1).collect::<Vec<_>>();
+        let min = values.iter().copied().fold(F::INFINITY, F::min);
+        let max = values.iter().copied().fold(F::NEG_INFINITY, F::max);
+        // The zoom comes first, it is plotted in log scale but its
+        // actual extrema are shown.
+        let (shown_min, shown_max) = if k == 0 {
+            (min.exp(), max.exp())
+        } else {
+            (min, max)
+        };
+
+        if min == max {
+            println!("  {:<16} constant ({})", name, format_value(shown_min));
+        } else {
+            println!(
+                "  {:<16} {} [{}, {}]",
+                name,
+                sparkline(&values, min, max, WIDTH),
+                format_value(shown_min),
+                format_value(shown_max)
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+/// Formats a value with a few significant digits, in scientific
+/// notation when it is very small or very large.
+fn format_value(v: F) -> String {
+    if v != 0. && !(1e-3..1e6).contains(&v.abs()) {
+        format!("{:.4e}", v)
+    } else {
+        let s = format!("{:.6}", v);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Plots values with block characters, on at most `width` columns.
+fn sparkline(values: &[F], min: F, max: F, width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let columns = values.len().min(width);
+    (0..columns)
+        .map(|c| {
+            let v = values[c * values.len() / columns];
+            let t = (v - min) / (max - min);
+            BARS[(t * (BARS.len() - 1) as F).round() as usize]
+        })
+        .collect()
+}
+
+/// Prints the distribution of the raw values of a render.
+fn print_stats(stats: &RenderStats) {
+    let max_count = stats.histogram.iter().copied().max().unwrap_or(0);
+    let histogram = stats
+        .histogram
+        .iter()
+        .map(|&count| count as F)
+        .collect::<Vec<_>>();
+
+    eprintln!(
+        " values: min {} - median {} - mean {} - max {} - interior {:.1}%",
+        format_value(stats.min),
+        format_value(stats.median),
+        format_value(stats.mean),
+        format_value(stats.max),
+        100. * stats.interior_fraction
+    );
+    if stats.non_finite_count > 0 {
+        eprintln!(
+            " warning: {} pixel(s) had NaN or infinite values, colored as the nearest extremum",
+            stats.non_finite_count
+        );
+    }
+    eprintln!(
+        " percentiles: {}",
+        STATS_PERCENTILES
+            .iter()
+            .zip(stats.percentiles)
+            .map(|(p, v)| format!("{}%: {}", p, format_value(v)))
+            .collect::<Vec<_>>()
+            .join(" - ")
+    );
+    eprintln!(
+        " histogram: {}",
+        sparkline(&histogram, 0., max_count.max(1) as F, STATS_HISTOGRAM_BARS)
+    );
+}
+
+/// Prints the recommended settings of the fractal next to the
+/// current values, flagging the ones far from the recommendations.
+fn print_recommendations(params: &FrameParams) {
+    let recommended = params.fractal.recommended_settings();
+    let flag =
+        |deviation: Option<&str>| deviation.map(|d| format!(" <- {}", d)).unwrap_or_default();
+
+    println!();
+    println!("recommended settings:");
+    if params.auto_max_iter == Some(true) {
+        println!(
+            "  max_iter: chosen from the zoom (current: {})",
+            params.max_iter
+        );
+    } else {
+        println!(
+            "  max_iter: {} (current: {}){}",
+            recommended.max_iter,
+            params.max_iter,
+            flag(recommended.max_iter_deviation(params.max_iter))
+        );
+    }
+    println!(
+        "  center: ({}, {}) (current: ({}, {}))",
+        recommended.center_x, recommended.center_y, params.center_x, params.center_y
+    );
+    println!(
+        "  zoom: {} (current: {}){}",
+        recommended.zoom,
+        params.zoom,
+        flag(recommended.zoom_deviation(params.zoom))
+    );
+}
+
+/// Writes animation parameters zooming into the view of the given
+/// frame parameters.
+fn export_zoom_animation(params: &ParamsKind, path: &Path) -> Result<()> {
+    let ParamsKind::Frame(params) = params else {
+        return Err(ErrorKind::ZoomAnimation(
+            "frame parameters are required".to_string(),
+        ));
+    };
+    let animation_params = AnimationParams::zoom_into(params).ok_or(ErrorKind::ZoomAnimation(
+        "this fractal can't be animated".to_string(),
+    ))?;
+
+    fs::write(
+        path,
+        ParamsKind::Animation(Box::new(animation_params)).encode(ParamsFormat::from_path(path))?,
+    )
+    .map_err(ErrorKind::WriteParameterFile)?;
+    println!("zoom animation written to {}", path.display());
+
+    Ok(())
+}
+
+/// Writes the parameters embedded in a png image to a `.ron` file
+/// next to it.
+fn extract_params(image_path: &Path) -> Result<()> {
+    let file = fs::File::open(image_path).map_err(ErrorKind::ReadImage)?;
+    let params_str = png_metadata::read_metadata(BufReader::new(file))?.params;
+
+    let param_file_path = image_path.with_extension("ron");
+    fs::write(&param_file_path, params_str).map_err(ErrorKind::WriteParameterFile)?;
+    println!("parameters written to {}", param_file_path.display());
+
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+fn start_gui(
+    params: ParamsKind,
+    provenance: Provenance,
+    param_file_path: Option<PathBuf>,
+    output_image_path: Option<PathBuf>,
+    message: Option<String>,
+    gui_backend: Option<&str>,
+    state_files: GuiStateFiles,
+) -> Result<()> {
+    let renderer = match gui_backend {
+        None | Some("auto") => None,
+        Some("glow") => Some(eframe::Renderer::Glow),
+        #[cfg(feature = "wgpu")]
+        Some("wgpu") => Some(eframe::Renderer::Wgpu),
+        Some(_) => return Err(ErrorKind::InvalidCliArg("gui-backend".to_string())),
+    };
+
+    // Fails early instead of letting winit fail with a less explicit
+    // message.
+    #[cfg(target_os = "linux")]
+    {
+        let is_set = |var| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+        if !is_set("DISPLAY") && !is_set("WAYLAND_DISPLAY") {
+            return Err(ErrorKind::MissingDisplay);
+        }
+    }
+
+    if let ParamsKind::Frame(frame_params) = params {
+        eframe::run_native(
+            "fractal renderer",
+            eframe::NativeOptions {
+                viewport: ViewportBuilder::default()
+                    .with_inner_size(WINDOW_SIZE)
+                    .with_min_inner_size(WINDOW_SIZE),
+                renderer: renderer.unwrap_or_default(),
+                ..Default::default()
+            },
+            Box::new(|cc| {
+                let mut gui = Gui::new(
+                    cc,
+                    *frame_params,
+                    provenance,
+                    param_file_path,
+                    output_image_path,
+                    state_files.autosave,
+                    state_files.workspace,
+                );
+                if let Some(message) = message {
+                    gui.notify(message);
+                }
+                Ok(Box::new(gui))
+            }),
+        )
+        .map_err(|e| ErrorKind::StartGui(e.to_string()))
+    } else {
+        Err(ErrorKind::AnimationInGui)
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+fn start_gui(
+    _params: ParamsKind,
+    _provenance: Provenance,
+    _param_file_path: Option<PathBuf>,
+    _output_image_path: Option<PathBuf>,
+    _message: Option<String>,
+    _gui_backend: Option<&str>,
+    _state_files: GuiStateFiles,
+) -> Result<()> {
+    Err(ErrorKind::StartGui(
+        "fractal_rndr was built without the gui feature".to_string(),
+    ))
+}
+
+/// Extensions of the outputs written as a single file by
+/// [`render_animation`] rather than as numbered images.
+const ANIMATION_EXTENSIONS: &[&str] = &["gif", "mp4"];
+
+/// Checks that images can be encoded in the format of the extension
+/// of the output path. Paths without an extension are saved as png.
+fn check_output_path(path: PathBuf, is_animation: bool) -> Result<PathBuf> {
+    let Some(extension) = path.extension() else {
+        let path = path.with_extension("png");
+        eprintln!(
+            "note: the output path has no extension, saving as png to {}",
+            path.display()
+        );
+        return Ok(path);
+    };
+
+    let extension = extension.to_string_lossy().to_lowercase();
+    let is_supported = (is_animation && ANIMATION_EXTENSIONS.contains(&extension.as_str()))
+        || ImageFormat::from_extension(&extension).is_some_and(|format| format.writing_enabled());
+    if is_supported {
+        Ok(path)
+    } else {
+        Err(ErrorKind::UnsupportedImageFormat(extension))
+    }
+}
+
+/// Where the rendered images are written.
+enum Output {
+    File(PathBuf),
+    /// Images are written as png to the standard output, one after
+    /// the other for animations.
+    Stdout,
+}
+
+impl Output {
+    fn extension(&self) -> Option<&str> {
+        match self {
+            Output::File(path) => path.extension().and_then(|e| e.to_str()),
+            Output::Stdout => Some("png"),
+        }
+    }
+
+    /// Saves the image, png images embed the parameters.
+    fn save(
+        &self,
+        image: impl Into<DynamicImage>,
+        params: &FrameParams,
+        samples_per_pixel: F,
+    ) -> Result<()> {
+        match self {
+            Output::File(path) => {
+                png_metadata::save_image(image.into(), params, samples_per_pixel, path)
+            }
+            Output::Stdout => {
+                let mut stdout = io::stdout().lock();
+                png_metadata::write_png(&image.into(), params, samples_per_pixel, &mut stdout)?;
+                stdout.flush().map_err(|e| ErrorKind::SaveImage(e.into()))
+            }
+        }
+    }
+
+    /// Describes the written image (size and format) for the logs.
+    fn describe(&self) -> String {
+        match self {
+            Output::File(path) => {
+                let image_size = fs::metadata(path).map_or(0, |m| m.len());
+                format!(
+                    "{}{}",
+                    if image_size / 1_000_000 != 0 {
+                        format!("{:.1}mb", image_size as f32 / 1_000_000.)
+                    } else if image_size / 1_000 != 0 {
+                        format!("{:.1}kb", image_size as f32 / 1_000.)
+                    } else {
+                        format!("{}b", image_size)
+                    },
+                    if let Some(ext) = self.extension() {
+                        format!(" - {}", ext)
+                    } else {
+                        "".to_string()
+                    }
+                )
+            }
+            Output::Stdout => "stdout - png".to_string(),
+        }
+    }
+}
+
+/// Set by Ctrl-C while the passes of a frame are accumulated, the
+/// pass being rendered is then cancelled and the passes already
+/// rendered are saved.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Whether Ctrl-C stops the passes rather than exiting.
+static ACCUMULATING: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl-C set [`INTERRUPTED`] while passes are accumulated, it
+/// exits as usual otherwise and when pressed a second time.
+fn catch_interrupts() {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        let result = ctrlc::set_handler(|| {
+            if ACCUMULATING.load(Ordering::Relaxed) && !INTERRUPTED.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "\n interrupted, saving the passes rendered so far (Ctrl-C again to quit)"
+                );
+            } else {
+                process::exit(130);
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("warning: failed to catch Ctrl-C: {}", e);
+        }
+    });
+}
+
+/// Path of the image saved after the given pass (from 1), out.png
+/// gives out_pass01.png.
+fn checkpoint_path(output_image_path: &Path, pass: usize) -> PathBuf {
+    let mut file_name = output_image_path.file_stem().unwrap_or_default().to_owned();
+    file_name.push(format!("_pass{:02}", pass));
+    if let Some(extension) = output_image_path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    output_image_path.with_file_name(file_name)
+}
+
+/// Renders a frame, or only the pixels of `region` (columns and
+/// rows) which are then saved as an image of the size of the region.
+/// The samples of `params.passes` passes are accumulated, the image
+/// being saved after each pass when `checkpoints` is set.
+fn render_frame(
+    params: FrameParams,
+    output: Output,
+    manifest_path: Option<&Path>,
+    region: Option<(Range<u32>, Range<u32>)>,
+    checkpoints: bool,
+) -> Result<()> {
+    let FrameParams {
+        img_width,
+        img_height,
+
+        sampling,
+        passes,
+        ..
+    } = params;
+    let passes = passes.unwrap_or(1).max(1);
+
+    let (columns, rows) = region.unwrap_or((0..img_width, 0..img_height));
+    if columns.end > img_width || rows.end > img_height {
+        return Err(ErrorKind::Region(format!(
+            "the region goes beyond the {}x{} image",
+            img_width, img_height
+        )));
+    }
+
+    let sampling_points = sampling.generate_sampling_points();
+
+    if let Some(DevOptions {
+        save_sampling_pattern: Some(true),
+        ..
+    }) = params.dev_options
+    {
+        preview_sampling_points(&sampling, &sampling_points, params.seed)?;
+    }
+
+    // The region is colored and saved as an image of its own.
+    let output_params = if (columns.len(), rows.len()) != (img_width as usize, img_height as usize)
+    {
+        region_params(&params, &columns, &rows)
+    } else {
+        params.clone()
+    };
+
+    let is_exr = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+    let save_raw = matches!(
+        params.dev_options,
+        Some(DevOptions {
+            save_raw: Some(true),
+            ..
+        })
+    );
+    let write = |raw_image: Mat2D<F>,
+                 raw_channels: Option<&RawChannels>,
+                 samples_per_pixel: F,
+                 output: &Output| match output {
+        Output::File(output_image_path) if save_raw && is_exr => {
+            save_raw_exr(&raw_image, output_image_path)?;
+
+            Ok((output_params.img_width, output_params.img_height))
+        }
+        _ => save_frame(
+            &output_params,
+            raw_image,
+            raw_channels,
+            samples_per_pixel,
+            output,
+            is_exr,
+        ),
+    };
+
+    INTERRUPTED.store(false, Ordering::Relaxed);
+    if passes > 1 {
+        catch_interrupts();
+        ACCUMULATING.store(true, Ordering::Relaxed);
+    }
+
+    let start = Instant::now();
+
+    let mut accumulated: Option<(Mat2D<F>, Option<RawChannels>, F)> = None;
+    for pass in 0..passes {
+        let progress = Progress::new(region_progress_total(&params, &columns, &rows));
+        let pass_start = Instant::now();
+
+        // Passes after the first one derive their seed from the
+        // number of samples already taken (as in the gui), otherwise
+        // they would take the same samples again.
+        let seed = params.seed.map(|seed| match &accumulated {
+            Some((_, _, samples_per_pixel)) => mix_seed(seed, samples_per_pixel.round() as u64),
+            None => seed,
+        });
+        let params_clone = FrameParams {
+            seed,
+            ..params.clone()
+        };
+        let progress_clone = progress.clone();
+        let sampling_points_clone = sampling_points.clone();
+        let (columns_clone, rows_clone) = (columns.clone(), rows.clone());
+        let handle = thread::spawn(move || {
+            render_raw_image_region(
+                &params_clone,
+                &sampling_points_clone,
+                columns_clone,
+                rows_clone,
+                Some(progress_clone),
+                Some(&INTERRUPTED),
+            )
+        });
+
+        while !handle.is_finished() {
+            if passes > 1 {
+                // The passes left are expected to take as long as
+                // this one.
+                let remaining = progress.remaining_time(pass_start).map(|remaining| {
+                    remaining + (pass_start.elapsed() + remaining) * (passes - pass - 1) as u32
+                });
+                eprint!(
+                    "\r pass {}/{} - {:.1}% - {:.1}s elapsed - {:<20}",
+                    pass + 1,
+                    passes,
+                    100. * progress.get_progress(),
+                    start.elapsed().as_secs_f32(),
+                    format_remaining_time(remaining),
+                );
+            } else {
+                eprint!(
+                    "\r {:.1}% - {:.1}s elapsed - {:<20}",
+                    100. * progress.get_progress(),
+                    start.elapsed().as_secs_f32(),
+                    format_remaining_time(progress.remaining_time(start)),
+                );
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let (raw_image, raw_channels, samples_per_pixel) = handle
+            .join()
+            .map_err(|payload| ErrorKind::RenderPanicked(panic_message(payload.as_ref())))?;
+
+        // The pass was cancelled, its samples are incomplete.
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break;
+        }
+
+        match &mut accumulated {
+            Some((accumulated_image, accumulated_channels, accumulated_samples)) => {
+                accumulate_samples(
+                    &params.fractal,
+                    accumulated_image,
+                    accumulated_channels,
+                    *accumulated_samples,
+                    &raw_image,
+                    raw_channels,
+                    samples_per_pixel,
+                );
+                *accumulated_samples += samples_per_pixel;
+            }
+            None => accumulated = Some((raw_image, raw_channels, samples_per_pixel)),
+        }
+
+        if let (true, Output::File(output_image_path), Some((raw_image, raw_channels, spp))) =
+            (checkpoints && pass + 1 < passes, &output, &accumulated)
+        {
+            let checkpoint = Output::File(checkpoint_path(output_image_path, pass + 1));
+            write(raw_image.clone(), raw_channels.as_ref(), *spp, &checkpoint)?;
+        }
+    }
+    ACCUMULATING.store(false, Ordering::Relaxed);
+
+    eprintln!();
+
+    let (raw_image, raw_channels, samples_per_pixel) = accumulated.ok_or(ErrorKind::Interrupted)?;
+
+    if passes > 1 || matches!(sampling.level, SamplingLevel::Adaptive { .. }) {
+        eprintln!(" average spp: {:.1}", samples_per_pixel);
+    }
+    print_stats(&analyze_raw_image(&output_params, &raw_image));
+
+    if let Some(manifest_path) = manifest_path {
+        let frame_hash = FrameHash::new(0, &raw_image);
+        eprintln!(" hash: {:016x}", frame_hash.hash);
+        write_frame_values(manifest_path, 0, &raw_image)?;
+        write_manifest(
+            &FrameManifest {
+                frames: vec![frame_hash],
+            },
+            manifest_path,
+        )?;
+    }
 
-use std::{
-    fs,
-    io::Write,
-    path::PathBuf,
-    thread,
-    time::{Duration, Instant},
-};
+    let (width, height) = write(raw_image, raw_channels.as_ref(), samples_per_pixel, &output)?;
 
-use eframe::egui::ViewportBuilder;
-use gui::WINDOW_SIZE;
-
-use crate::{
-    coloring::{color_mapping, color_raw_image},
-    error::{ErrorKind, Result},
-    gui::Gui,
-    params::{AnimationParams, DevOptions, FrameParams, ParamsKind},
-    progress::Progress,
-    rendering::render_raw_image,
-    sampling::preview_sampling_points,
-};
+    eprintln!(
+        " output image: {}x{} - {}",
+        width,
+        height,
+        output.describe()
+    );
 
-#[cfg(feature = "force_f32")]
-type F = f32;
-#[cfg(feature = "force_f32")]
-use wide::f32x8;
-#[cfg(feature = "force_f32")]
-type FX = f32x8;
+    Ok(())
+}
 
-#[cfg(not(feature = "force_f32"))]
-type F = f64;
-#[cfg(not(feature = "force_f32"))]
-use wide::f64x4;
-#[cfg(not(feature = "force_f32"))]
-type FX = f64x4;
+/// Renders the band of the given index (from 1 to `count`) and
+/// writes its raw values to a band file.
+fn render_band(params: FrameParams, path: &Path, index: usize, count: usize) -> Result<()> {
+    let rows = band_rows(index, count, params.img_height);
+    eprintln!("band {}/{}: rows {}-{}", index, count, rows.start, rows.end);
 
-const USAGE: &str = "This is a fractal renderer.
-Usage: fractal_rndr <param file path> <output image path>
-Use --no-gui for cli mode.
+    let sampling_points = params.sampling.generate_sampling_points();
+    let progress = Progress::new(progress_total(&params, &rows));
 
-More information: https://gitlab.com/valflrt/fractal_rndr";
+    let start = Instant::now();
 
-fn main() -> Result<()> {
-    let args = valargs::parse();
+    let params_clone = params.clone();
+    let progress_clone = progress.clone();
+    let handle = thread::spawn(move || {
+        render_raw_image_rows(
+            &params_clone,
+            &sampling_points,
+            rows,
+            Some(progress_clone),
+            None,
+        )
+    });
+
+    while !handle.is_finished() {
+        eprint!(
+            "\r {:.1}% - {:.1}s elapsed - {:<20}",
+            100. * progress.get_progress(),
+            start.elapsed().as_secs_f32(),
+            format_remaining_time(progress.remaining_time(start)),
+        );
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let (raw_image, samples_per_pixel) = handle
+        .join()
+        .map_err(|payload| ErrorKind::RenderPanicked(panic_message(payload.as_ref())))?;
+
+    eprintln!();
+
+    write_band(
+        &Band {
+            index,
+            count,
+            params,
+            samples_per_pixel,
+            raw_image,
+        },
+        path,
+    )?;
+    eprintln!(" band written to {}", path.display());
+
+    Ok(())
+}
 
-    let (param_file_path, output_image_path) = (
-        args.nth(1).map(PathBuf::from),
-        args.nth(2).map(PathBuf::from),
+/// Merges band files rendered with `--tile` and colors the result,
+/// so that normalizations computed over the whole image (like the
+/// cumulative histogram) are the same as for a single render.
+fn merge<'a>(band_paths: impl Iterator<Item = &'a Path>, output_path: PathBuf) -> Result<()> {
+    let bands = band_paths.map(read_band).collect::<Result<Vec<_>>>()?;
+    let (params, raw_image, samples_per_pixel) = merge_bands(&bands)?;
+
+    let output = Output::File(output_path);
+    let is_exr = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+    let (width, height) = save_frame(&params, raw_image, None, samples_per_pixel, &output, is_exr)?;
+
+    eprintln!(
+        " output image: {}x{} - {}",
+        width,
+        height,
+        output.describe()
     );
 
-    let params = param_file_path
-        .as_ref()
-        .map(|param_file_path| {
-            let param_file_str =
-                fs::read_to_string(param_file_path).map_err(ErrorKind::ReadParameterFile)?;
-            let params = ron::from_str::<ParamsKind>(&param_file_str)
-                .map_err(ErrorKind::DecodeParameterFile)?;
-            Ok(params)
-        })
-        .transpose()?
-        .unwrap_or_default();
+    Ok(())
+}
 
-    if args.has_option("help") || args.has_option("h") {
-        println!("{}", USAGE);
-        Ok(())
-    } else if args.has_option("no-gui") {
-        if let (Some(_), Some(output_image_path)) = (param_file_path, output_image_path) {
-            match params {
-                ParamsKind::Frame(params) => render_frame(params, output_image_path),
-                ParamsKind::Animation(animation_params) => {
-                    render_animation(animation_params, output_image_path)
-                }
-            }
-        } else {
-            Err(ErrorKind::MissingCliArg)
-        }
-    } else {
-        start_gui(params, param_file_path, output_image_path)
+/// Compares two images and prints the stats of their differences,
+/// writing the amplified differences to `output_path` if given.
+fn diff(
+    image_a_path: &Path,
+    image_b_path: &Path,
+    output_path: Option<&Path>,
+    amplification: F,
+) -> Result<()> {
+    let image_a = image_diff::load_image(image_a_path)?;
+    let image_b = image_diff::load_image(image_b_path)?;
+    let (diff_image, stats) = image_diff::diff_images(&image_a, &image_b, amplification)?;
+
+    println!(" mae: {:.4}", stats.mae);
+    println!(" psnr: {:.2} dB", stats.psnr);
+    println!(
+        " differing pixels: {} of {} ({:.2}%)",
+        stats.differing_pixels,
+        stats.pixel_count,
+        100. * stats.differing_pixels as F / stats.pixel_count.max(1) as F
+    );
+
+    if let Some(output_path) = output_path {
+        diff_image.save(output_path).map_err(ErrorKind::SaveImage)?;
+        eprintln!(
+            " output: difference amplified {}x - {}",
+            amplification,
+            output_path.display()
+        );
     }
+
+    Ok(())
 }
 
-fn start_gui(
-    params: ParamsKind,
-    param_file_path: Option<PathBuf>,
-    output_image_path: Option<PathBuf>,
-) -> Result<()> {
-    if let ParamsKind::Frame(frame_params) = params {
-        eframe::run_native(
-            "fractal renderer",
-            eframe::NativeOptions {
-                viewport: ViewportBuilder::default()
-                    .with_inner_size(WINDOW_SIZE)
-                    .with_min_inner_size(WINDOW_SIZE),
-                ..Default::default()
-            },
-            Box::new(|cc| {
-                Ok(Box::new(Gui::new(
-                    cc,
-                    frame_params,
-                    param_file_path,
-                    output_image_path,
-                )))
-            }),
-        )
-        .map_err(|_| ErrorKind::StartGui)
+/// Colors the raw image (or normalizes it when `raw_output` is set)
+/// and writes it, returns the dimensions of the written image.
+fn save_frame(
+    params: &FrameParams,
+    raw_image: Mat2D<F>,
+    raw_channels: Option<&RawChannels>,
+    samples_per_pixel: F,
+    output: &Output,
+    is_exr: bool,
+) -> Result<(u32, u32)> {
+    Ok(if params.raw_output == Some(true) {
+        let output_image = raw_image_to_rgb32f(params, raw_image);
+        let output_image = match params.save_scale {
+            Some(scale) if scale != 1. => scale_linear_image(&output_image, scale),
+            _ => output_image,
+        };
+        let dimensions = output_image.dimensions();
+
+        let output_image = DynamicImage::ImageRgb32F(output_image);
+        if is_exr {
+            output.save(output_image, params, samples_per_pixel)?;
+        } else {
+            output.save(output_image.into_luma16(), params, samples_per_pixel)?;
+        }
+
+        dimensions
     } else {
-        Err(ErrorKind::StartGui)
-    }
+        let output_image = color_raw_channels(params, raw_image, raw_channels);
+        let output_image = match params.save_scale {
+            Some(scale) if scale != 1. => scale_image(&output_image, scale),
+            _ => output_image,
+        };
+        let dimensions = output_image.dimensions();
+
+        output.save(output_image, params, samples_per_pixel)?;
+
+        dimensions
+    })
 }
 
-fn render_frame(params: FrameParams, output_image_path: PathBuf) -> Result<()> {
+/// Renders the frame in horizontal stripes of `stripe_height` rows
+/// that are colored and written to the png one after the other, so
+/// that the whole image is never held in memory. When the min or max
+/// is automatic, the stripes are rendered twice: first to find the
+/// extrema, then to color them.
+fn render_frame_striped(params: FrameParams, output: Output, stripe_height: u32) -> Result<()> {
     let FrameParams {
         img_width,
         img_height,
@@ -129,197 +1722,755 @@ fn render_frame(params: FrameParams, output_image_path: PathBuf) -> Result<()> {
         ..
     } = params;
 
+    let is_png = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if !is_png {
+        return Err(ErrorKind::StripedRendering(
+            "outputs other than png".to_string(),
+        ));
+    }
+    let extrema =
+        coloring_in_parts(&params).map_err(|e| ErrorKind::StripedRendering(e.to_string()))?;
+
     let sampling_points = sampling.generate_sampling_points();
 
-    if let Some(DevOptions {
-        save_sampling_pattern: Some(true),
-        ..
-    }) = params.dev_options
-    {
-        preview_sampling_points(&sampling_points)?;
-    }
+    let stripes = (0..img_height)
+        .step_by(stripe_height as usize)
+        .map(|start| start..(start + stripe_height).min(img_height))
+        .collect::<Vec<_>>();
+    let find_extrema = extrema.is_some_and(|(min, max, _)| min.is_auto() || max.is_auto());
 
-    let progress = Progress::new((img_width * img_height) as usize);
+    let pass_count = if find_extrema { 2 } else { 1 };
+    let progress = Progress::new(pass_count * progress_total(&params, &(0..img_height)));
 
     let start = Instant::now();
 
-    let params_clone = params.clone();
+    let writer: Box<dyn Write + Send> = match &output {
+        Output::File(output_image_path) => Box::new(BufWriter::new(
+            fs::File::create(output_image_path).map_err(|e| ErrorKind::EncodePng(e.into()))?,
+        )),
+        Output::Stdout => Box::new(io::stdout()),
+    };
+    let mut encoder = png::Encoder::new(writer, img_width, img_height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Stripes are written as they are rendered so the nominal sample
+    // count is embedded.
+    png_metadata::add_metadata_chunks(&mut encoder, &params, sampling.sample_count() as F)?;
+
     let progress_clone = progress.clone();
-    let sampling_points_clone = sampling_points.clone();
-    let handle = thread::spawn(move || {
-        render_raw_image(&params_clone, &sampling_points_clone, Some(progress_clone))
+    let handle = thread::spawn(move || -> Result<()> {
+        let render_stripe = |rows: &Range<u32>| {
+            render_raw_image_rows(
+                &params,
+                &sampling_points,
+                rows.clone(),
+                Some(progress_clone.clone()),
+                None,
+            )
+            .0
+        };
+
+        let coloring_mode = fixed_coloring_mode(&params, extrema, || {
+            parts_raw_extrema(&params, stripes.iter().map(render_stripe))
+        });
+
+        let mut writer = encoder.write_header().map_err(ErrorKind::EncodePng)?;
+        let mut stream_writer = writer.stream_writer().map_err(ErrorKind::EncodePng)?;
+        for rows in &stripes {
+            let stripe_params = FrameParams {
+                img_height: rows.len() as u32,
+                coloring_mode,
+                ..params.clone()
+            };
+            let stripe = color_raw_region(
+                &stripe_params,
+                render_stripe(rows),
+                None,
+                (0, rows.start as usize),
+            );
+            stream_writer
+                .write_all(stripe.as_raw())
+                .map_err(|e| ErrorKind::EncodePng(e.into()))?;
+        }
+        stream_writer.finish().map_err(ErrorKind::EncodePng)
     });
 
     while !handle.is_finished() {
-        print!(
-            "\r {:.1}% - {:.1}s elapsed",
+        eprint!(
+            "\r {:.1}% - {:.1}s elapsed - {:<20}",
             100. * progress.get_progress(),
             start.elapsed().as_secs_f32(),
+            format_remaining_time(progress.remaining_time(start)),
         );
-        std::io::stdout().flush().unwrap();
 
         thread::sleep(Duration::from_millis(50));
     }
 
-    let raw_image = handle.join().unwrap(); // TODO replace unwrap
+    handle
+        .join()
+        .map_err(|payload| ErrorKind::RenderPanicked(panic_message(payload.as_ref())))??;
 
-    println!();
+    eprintln!();
 
-    let output_image = color_raw_image(&params, raw_image);
+    eprintln!(
+        " output image: {}x{} - {}",
+        img_width,
+        img_height,
+        output.describe()
+    );
 
-    output_image
-        .save(&output_image_path)
-        .map_err(ErrorKind::SaveImage)?;
+    Ok(())
+}
 
-    let image_size = fs::metadata(&output_image_path).unwrap().len();
-    println!(
-        " output image: {}x{} - {} {}",
+/// Renders the frame as a grid of `columns` by `rows` tiles saved to
+/// their own images, along with a manifest of their positions. Tiles
+/// are the same as the pixels of a single render, so that stitching
+/// them leaves no seams. When the min or max is automatic, the tiles
+/// are rendered twice: first to find the extrema, then to color them.
+fn render_poster(params: FrameParams, output_path: &Path, columns: u32, rows: u32) -> Result<()> {
+    let FrameParams {
         img_width,
         img_height,
-        if image_size / 1_000_000 != 0 {
-            format!("{:.1}mb", image_size as f32 / 1_000_000.)
-        } else if image_size / 1_000 != 0 {
-            format!("{:.1}kb", image_size as f32 / 1_000.)
-        } else {
-            format!("{}b", image_size)
-        },
-        if let Some(ext) = output_image_path.extension().and_then(|s| s.to_str()) {
-            format!("- {} ", ext)
-        } else {
-            "".to_string()
+
+        sampling,
+        ..
+    } = params;
+
+    if columns > img_width || rows > img_height {
+        return Err(ErrorKind::InvalidCliArg("poster".to_string()));
+    }
+    let extrema =
+        coloring_in_parts(&params).map_err(|e| ErrorKind::PosterRendering(e.to_string()))?;
+
+    let manifest = PosterManifest::new(img_width, img_height, columns, rows, output_path);
+    let tile_dir = output_path.parent().unwrap_or(Path::new(""));
+
+    let sampling_points = sampling.generate_sampling_points();
+
+    let find_extrema = extrema.is_some_and(|(min, max, _)| min.is_auto() || max.is_auto());
+    let pass_count = if find_extrema { 2 } else { 1 };
+    let progress = Progress::new(
+        pass_count
+            * manifest
+                .tiles
+                .iter()
+                .map(|tile| region_progress_total(&params, &tile.columns(), &tile.rows()))
+                .sum::<usize>(),
+    );
+
+    let start = Instant::now();
+
+    let progress_clone = progress.clone();
+    let tiles = manifest.tiles.clone();
+    let tile_dir_clone = tile_dir.to_owned();
+    let handle = thread::spawn(move || -> Result<()> {
+        let render_tile = |tile: &PosterTile| {
+            render_raw_image_region(
+                &params,
+                &sampling_points,
+                tile.columns(),
+                tile.rows(),
+                Some(progress_clone.clone()),
+                None,
+            )
+        };
+
+        let coloring_mode = fixed_coloring_mode(&params, extrema, || {
+            parts_raw_extrema(&params, tiles.iter().map(|tile| render_tile(tile).0))
+        });
+
+        for tile in &tiles {
+            let (raw_image, _, samples_per_pixel) = render_tile(tile);
+            // Each tile embeds the parameters of its own view.
+            let tile_params = FrameParams {
+                coloring_mode,
+                ..region_params(&params, &tile.columns(), &tile.rows())
+            };
+            Output::File(tile_dir_clone.join(&tile.path)).save(
+                color_raw_region(
+                    &tile_params,
+                    raw_image,
+                    None,
+                    (tile.x as usize, tile.y as usize),
+                ),
+                &tile_params,
+                samples_per_pixel,
+            )?;
         }
+
+        Ok(())
+    });
+
+    while !handle.is_finished() {
+        eprint!(
+            "\r {:.1}% - {:.1}s elapsed - {:<20}",
+            100. * progress.get_progress(),
+            start.elapsed().as_secs_f32(),
+            format_remaining_time(progress.remaining_time(start)),
+        );
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    handle
+        .join()
+        .map_err(|payload| ErrorKind::RenderPanicked(panic_message(payload.as_ref())))??;
+
+    eprintln!();
+
+    let manifest_path = poster::manifest_path(output_path);
+    poster::write_manifest(&manifest, &manifest_path)?;
+
+    eprintln!(
+        " output: {} tiles of about {}x{} - manifest: {}",
+        columns * rows,
+        img_width / columns,
+        img_height / rows,
+        manifest_path.display()
+    );
+
+    Ok(())
+}
+
+/// Renders a contact sheet of the frame with `sweep.field` going
+/// from `sweep.from` to `sweep.to` over the cells, the sheet doesn't
+/// embed the parameters as no single frame matches them.
+fn render_sweep(
+    params: FrameParams,
+    output_path: &Path,
+    sweep: &Sweep,
+    columns: u32,
+    rows: u32,
+    cell_width: u32,
+) -> Result<()> {
+    let start = Instant::now();
+    let cell_count = columns * rows;
+
+    let sheet = sweep::render_contact_sheet(&params, sweep, columns, rows, cell_width, |i| {
+        eprint!(
+            "\r cell {}/{} - {:.1}s elapsed",
+            i + 1,
+            cell_count,
+            start.elapsed().as_secs_f32()
+        );
+    })?;
+    eprintln!();
+
+    sheet.save(output_path).map_err(ErrorKind::SaveImage)?;
+
+    eprintln!(
+        " output: {}x{} contact sheet of {} cells - {}",
+        sheet.width(),
+        sheet.height(),
+        cell_count,
+        output_path.display()
     );
 
     Ok(())
 }
 
-fn render_animation(params: AnimationParams, output_image_path: PathBuf) -> Result<()> {
+/// Min/max coloring of a frame rendered in parts (stripes or poster
+/// tiles) which are colored one at a time, `None` for root coloring
+/// which only depends on each pixel. Errors with what can't be
+/// colored in parts.
+fn coloring_in_parts(
+    params: &FrameParams,
+) -> std::result::Result<Option<(Extremum, Extremum, MapValue)>, &'static str> {
+    let extrema = match params.coloring_mode {
+        ColoringMode::MinMaxNorm { min, max, map } => Some((min, max, map)),
+        ColoringMode::Roots { .. } => None,
+        ColoringMode::CumulativeHistogram { .. } => return Err("cumulative histogram coloring"),
+        ColoringMode::TwoChannel { .. } => return Err("two channel coloring"),
+    };
+
+    if params.boundary_band.is_some() {
+        Err("boundary_band")
+    } else if matches!(
+        params.interior_mode,
+        Some(InteriorMode::RadialGradient { .. })
+    ) {
+        Err("radial gradient interiors")
+    } else if params.save_scale.is_some_and(|scale| scale != 1.) {
+        Err("save_scale")
+    } else if params.raw_output == Some(true) {
+        Err("raw_output")
+    } else if extrema.is_some_and(|(min, max, _)| min.is_percentile() || max.is_percentile()) {
+        Err("percentile extrema")
+    } else {
+        Ok(extrema)
+    }
+}
+
+/// Coloring mode of the parts of a frame, the extrema being fixed so
+/// that all the parts are colored the same way. `raw_extrema` gives
+/// the min and max raw values of the whole frame (see
+/// [`parts_raw_extrema`]), it is only called when an extremum is
+/// automatic.
+fn fixed_coloring_mode(
+    params: &FrameParams,
+    extrema: Option<(Extremum, Extremum, MapValue)>,
+    raw_extrema: impl FnOnce() -> (F, F),
+) -> ColoringMode {
+    match extrema {
+        Some((min, max, map)) => {
+            let (min, max) = if min.is_auto() || max.is_auto() {
+                let (min_v, max_v) = raw_extrema();
+                (min.unwrap_custom_or(min_v), max.unwrap_custom_or(max_v))
+            } else {
+                (min.unwrap_custom_or(0.), max.unwrap_custom_or(0.))
+            };
+            ColoringMode::MinMaxNorm {
+                min: Extremum::Custom(min),
+                max: Extremum::Custom(max),
+                map,
+            }
+        }
+        None => params.coloring_mode,
+    }
+}
+
+/// Writes the raw values (before any normalization) to a single
+/// channel float EXR image.
+fn save_raw_exr(raw_image: &Mat2D<F>, path: &Path) -> Result<()> {
+    let channels = SpecificChannels::build()
+        .with_channel("Y")
+        .with_pixel_fn(|Vec2(x, y)| (raw_image[(x, y)] as f32,));
+
+    Image::from_channels((raw_image.width, raw_image.height), channels)
+        .write()
+        .to_file(path)
+        .map_err(ErrorKind::SaveRawImage)
+}
+
+fn render_animation(
+    params: AnimationParams,
+    output: Output,
+    manifest_path: Option<&Path>,
+    ffmpeg_args: Option<&str>,
+    resume: bool,
+) -> Result<()> {
     let AnimationParams {
+        img_width,
+        img_height,
+
         sampling,
 
-        duration,
         fps,
         ..
     } = params;
 
-    let frame_count = (duration * fps) as usize;
+    let frame_count = params.frame_count();
+    let frames_in_flight = params.frames_in_flight();
 
-    println!("frame count: {}", frame_count);
-    println!();
+    // Frames of gif and mp4 outputs all go to the same file, which
+    // is created with the first frame.
+    let single_file_path = |extension: &str| match &output {
+        Output::File(path)
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(extension)) =>
+        {
+            Some(path.to_owned())
+        }
+        _ => None,
+    };
+    let gif_path = single_file_path("gif");
+    let video_path = single_file_path("mp4");
+    let gif_local_palettes = params.gif_local_palettes == Some(true);
+    let mut gif_writer = None;
+    let mut ffmpeg_writer = None;
+
+    // Other frames are written as numbered images.
+    let frame_output = |frame_i: usize| match &output {
+        Output::File(output_image_path) => {
+            let mut file_name = output_image_path.file_stem().unwrap_or_default().to_owned();
+            file_name.push(format!("_{:06}", frame_i));
+            if let Some(extension) = output_image_path.extension() {
+                file_name.push(".");
+                file_name.push(extension);
+            }
+            Output::File(output_image_path.with_file_name(file_name))
+        }
+        Output::Stdout => Output::Stdout,
+    };
+
+    let frames = if resume {
+        if gif_path.is_some() || video_path.is_some() || matches!(output, Output::Stdout) {
+            return Err(ErrorKind::Resume(
+                "only frames written as separate images can be resumed".to_string(),
+            ));
+        }
+        (0..frame_count)
+            .filter(|&frame_i| match frame_output(frame_i) {
+                Output::File(path) => !fs::metadata(path).is_ok_and(|m| m.len() > 0),
+                Output::Stdout => true,
+            })
+            .collect::<Vec<_>>()
+    } else {
+        (0..frame_count).collect::<Vec<_>>()
+    };
+
+    eprintln!("frame count: {}", frame_count);
+    if resume {
+        eprintln!(
+            "skipped frames: {} (already rendered)",
+            frame_count - frames.len()
+        );
+    }
+    eprintln!("frames in flight: {}", frames_in_flight);
+    eprintln!();
 
     let sampling_points = sampling.generate_sampling_points();
 
+    // The frames in flight share the same pool, otherwise each of
+    // them would use `threads` threads.
+    let pool = thread_pool(params.threads);
+
     let global_start = Instant::now();
 
-    for frame_i in 0..frame_count {
-        let t = frame_i as F / fps;
+    let mut manifest = FrameManifest::default();
 
-        let params = params.get_frame_params(t);
-        let FrameParams {
-            img_width,
-            img_height,
-            ..
-        } = params;
+    for batch_frames in frames.chunks(frames_in_flight) {
+        let start = Instant::now();
 
-        let progress = Progress::new((img_width * img_height) as usize);
+        // Computed before starting any render of the batch so that a
+        // failing frame doesn't leave renders behind.
+        let batch_params = batch_frames
+            .iter()
+            .map(|&frame_i| params.get_frame_params(frame_i as F / fps))
+            .collect::<Result<Vec<_>>>()?;
 
-        let start = Instant::now();
+        // Each frame has its own progress so that a stalled one
+        // shows, the combined one sums them for the estimate.
+        let frame_progresses = batch_params
+            .iter()
+            .map(|frame_params| Progress::new(progress_total(frame_params, &(0..img_height))))
+            .collect::<Vec<_>>();
+        let progress = Progress::new(frame_progresses.iter().map(|p| p.total).sum());
 
-        let params_clone = params.clone();
-        let progress_clone = progress.clone();
-        let sampling_points_clone = sampling_points.clone();
-        let handle = thread::spawn(move || {
-            render_raw_image(&params_clone, &sampling_points_clone, Some(progress_clone))
-        });
+        let batch = batch_frames
+            .iter()
+            .zip(batch_params)
+            .zip(&frame_progresses)
+            .map(|((&frame_i, params), frame_progress)| {
+                let params_clone = FrameParams {
+                    threads: None,
+                    ..params.clone()
+                };
+                let progress_clone = frame_progress.clone();
+                let sampling_points_clone = sampling_points.clone();
+                let pool_clone = pool.clone();
+                let handle = thread::spawn(move || {
+                    let render = || {
+                        render_raw_image_channels(
+                            &params_clone,
+                            &sampling_points_clone,
+                            Some(progress_clone),
+                            None,
+                        )
+                    };
+                    match pool_clone {
+                        Some(pool) => pool.install(render),
+                        None => render(),
+                    }
+                });
 
-        while !handle.is_finished() {
-            print!(
-                "\r {:.1}% - {:.1}s elapsed",
+                (frame_i, params, handle)
+            })
+            .collect::<Vec<_>>();
+
+        while !batch.iter().all(|(_, _, handle)| handle.is_finished()) {
+            progress.set_sum(&frame_progresses);
+            let frame_percents = frame_progresses
+                .iter()
+                .map(|p| format!("{:.0}%", 100. * p.get_progress()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            eprint!(
+                "\r frames {}-{}: {:.1}% ({}) - {:.1}s elapsed - {:<20}",
+                batch_frames[0] + 1,
+                batch_frames[batch_frames.len() - 1] + 1,
                 100. * progress.get_progress(),
+                frame_percents,
                 start.elapsed().as_secs_f32(),
+                format_remaining_time(progress.remaining_time(start)),
             );
-            std::io::stdout().flush().unwrap();
 
             thread::sleep(Duration::from_millis(50));
         }
 
-        let raw_image = handle.join().unwrap(); // TODO replace unwrap
+        eprintln!();
 
-        println!();
+        // Frames are joined in order so that they are written with
+        // increasing indices.
+        for (frame_i, params, handle) in batch {
+            let (raw_image, raw_channels, samples_per_pixel) = handle
+                .join()
+                .map_err(|payload| ErrorKind::RenderPanicked(panic_message(payload.as_ref())))?;
 
-        let mut output_image = color_raw_image(&params, raw_image);
+            let frame_hash = manifest_path
+                .map(|manifest_path| {
+                    write_frame_values(manifest_path, frame_i, &raw_image)?;
+                    Ok(FrameHash::new(frame_i, &raw_image))
+                })
+                .transpose()?;
 
-        if let Some(DevOptions {
-            display_gradient: Some(true),
-            ..
-        }) = params.dev_options
-        {
-            const GRADIENT_HEIGHT: u32 = 8;
-            const GRADIENT_WIDTH: u32 = 64;
-            const OFFSET: u32 = 8;
-
-            for j in 0..GRADIENT_HEIGHT {
-                for i in 0..GRADIENT_WIDTH {
-                    output_image.put_pixel(
-                        img_width - GRADIENT_WIDTH - OFFSET + i,
-                        img_height - GRADIENT_HEIGHT - OFFSET + j,
-                        color_mapping(i as F / GRADIENT_WIDTH as F, &params.gradient),
-                    );
+            let mut output_image = color_raw_channels(&params, raw_image, raw_channels.as_ref());
+
+            if let Some(DevOptions {
+                display_gradient: Some(true),
+                ..
+            }) = params.dev_options
+            {
+                const GRADIENT_HEIGHT: u32 = 8;
+                const GRADIENT_WIDTH: u32 = 64;
+                const OFFSET: u32 = 8;
+
+                let gradient = params.gradient.stops();
+                for j in 0..GRADIENT_HEIGHT {
+                    for i in 0..GRADIENT_WIDTH {
+                        output_image.put_pixel(
+                            img_width - GRADIENT_WIDTH - OFFSET + i,
+                            img_height - GRADIENT_HEIGHT - OFFSET + j,
+                            color_mapping(i as F / GRADIENT_WIDTH as F, gradient),
+                        );
+                    }
                 }
             }
-        }
 
-        let output_image_path = PathBuf::from(
-            output_image_path
-                .parent()
-                .and_then(|p| p.to_str())
-                .unwrap()
-                .to_string()
-                + "/"
-                + output_image_path
-                    .file_stem()
-                    .and_then(|e| e.to_str())
-                    .unwrap()
-                + "_"
-                + &format!("{:06}", frame_i)
-                + "."
-                + output_image_path
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap(),
-        );
+            let output_image = match params.save_scale {
+                Some(scale) if scale != 1. => scale_image(&output_image, scale),
+                _ => output_image,
+            };
 
-        output_image
-            .save(&output_image_path)
-            .map_err(ErrorKind::SaveImage)?;
+            let (width, height) = output_image.dimensions();
+            let description = match (&gif_path, &video_path) {
+                (Some(gif_path), _) => {
+                    let gif_writer = match gif_writer.as_mut() {
+                        Some(gif_writer) => gif_writer,
+                        None => gif_writer.insert(GifWriter::create(
+                            gif_path,
+                            &output_image,
+                            fps,
+                            gif_local_palettes,
+                        )?),
+                    };
+                    gif_writer.add_frame(&output_image)?;
 
-        let image_size = fs::metadata(&output_image_path).unwrap().len();
-        println!(
-            " frame {}: {}x{} - {} {}",
-            frame_i + 1,
-            img_width,
-            img_height,
-            if image_size / 1_000_000 != 0 {
-                format!("{:.1}mb", image_size as f32 / 1_000_000.)
-            } else if image_size / 1_000 != 0 {
-                format!("{:.1}kb", image_size as f32 / 1_000.)
-            } else {
-                format!("{}b", image_size)
-            },
-            if let Some(ext) = output_image_path.extension().and_then(|s| s.to_str()) {
-                format!("- {} ", ext)
-            } else {
-                "".to_string()
+                    "gif".to_string()
+                }
+                (_, Some(video_path)) => {
+                    let ffmpeg_writer = match ffmpeg_writer.as_mut() {
+                        Some(ffmpeg_writer) => ffmpeg_writer,
+                        None => ffmpeg_writer.insert(FfmpegWriter::spawn(
+                            video_path,
+                            &output_image,
+                            fps,
+                            ffmpeg_args,
+                        )?),
+                    };
+                    ffmpeg_writer.add_frame(&output_image)?;
+
+                    "video".to_string()
+                }
+                _ => {
+                    // Frames written to the standard output are simply
+                    // concatenated (as expected by ffmpeg's image2pipe).
+                    let frame_output = frame_output(frame_i);
+
+                    frame_output.save(output_image, &params, samples_per_pixel)?;
+
+                    frame_output.describe()
+                }
+            };
+
+            eprintln!(
+                " frame {}: {}x{} - {}",
+                frame_i + 1,
+                width,
+                height,
+                description
+            );
+            if let Some(frame_hash) = frame_hash {
+                eprintln!("  hash: {:016x}", frame_hash.hash);
+                manifest.frames.push(frame_hash);
             }
-        );
-        println!();
+        }
+        eprintln!();
     }
 
-    println!(
+    eprintln!(
         "{} frames - {:.1}s elapsed",
-        frame_count,
+        frames.len(),
         global_start.elapsed().as_secs_f32()
     );
 
+    if let Some(gif_writer) = gif_writer {
+        gif_writer.finish()?;
+    }
+    if let Some(ffmpeg_writer) = ffmpeg_writer {
+        ffmpeg_writer.finish()?;
+    }
+    if let Some(manifest_path) = manifest_path {
+        write_manifest(&manifest, manifest_path)?;
+    }
+
+    Ok(())
+}
+
+/// Renders a small frame of each fractal `BENCH_RUNS` times and
+/// prints the mean and standard deviation of the render times and
+/// the number of samples per second as CSV. When `fractals` is
+/// `None`, only the fractal of `params` is rendered with its view,
+/// otherwise each fractal is rendered with its recommended
+/// settings.
+fn bench(
+    params: FrameParams,
+    fractals: Option<Vec<Fractal>>,
+    level: Option<SamplingLevel>,
+    size: Option<(u32, u32)>,
+) -> Result<()> {
+    const BENCH_SIZE: u32 = 256;
+    const BENCH_RUNS: usize = 5;
+
+    let (img_width, img_height) = size.unwrap_or((BENCH_SIZE, BENCH_SIZE));
+    let params = FrameParams {
+        img_width,
+        img_height,
+        sampling: Sampling {
+            level: level.unwrap_or(params.sampling.level),
+            ..params.sampling
+        },
+        // Renders are seeded so that every run takes the same
+        // samples.
+        seed: Some(params.seed.unwrap_or(0)),
+        ..params
+    };
+    let sampling_points = params.sampling.generate_sampling_points();
+
+    let frames = match fractals {
+        None => vec![params],
+        Some(fractals) => fractals
+            .into_iter()
+            .map(|fractal| {
+                let recommended = fractal.recommended_settings();
+                FrameParams {
+                    fractal,
+                    max_iter: recommended.max_iter,
+                    center_x: recommended.center_x,
+                    center_y: recommended.center_y,
+                    zoom: recommended.zoom,
+                    ..params.clone()
+                }
+            })
+            .collect(),
+    };
+
+    println!("fractal,max_iter,width,height,runs,mean_secs,stddev_secs,samples_per_sec");
+    for params in frames {
+        let runs = (0..BENCH_RUNS)
+            .map(|_| time_render(&params, &sampling_points))
+            .collect::<Vec<_>>();
+
+        let secs = runs
+            .iter()
+            .map(|(elapsed, _)| elapsed.as_secs_f64())
+            .collect::<Vec<_>>();
+        let mean = secs.iter().sum::<f64>() / BENCH_RUNS as f64;
+        let stddev =
+            (secs.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / BENCH_RUNS as f64).sqrt();
+        #[allow(clippy::unnecessary_cast)]
+        let samples = runs.iter().map(|&(_, samples)| samples as f64).sum::<f64>();
+
+        // The name of the variant, without its parameters.
+        let name = format!("{:?}", params.fractal);
+        let name = name.split([' ', '(', '{']).next().unwrap_or_default();
+        println!(
+            "{},{},{},{},{},{:.6},{:.6},{:.0}",
+            name,
+            params.max_iter,
+            params.img_width,
+            params.img_height,
+            BENCH_RUNS,
+            mean,
+            stddev,
+            samples / secs.iter().sum::<f64>()
+        );
+    }
+
     Ok(())
 }
+
+/// Renders the frames listed in the manifest (or the selected ones)
+/// again and compares their hashes, reporting the first pixel that
+/// differs, or only its row when the values of the frame weren't
+/// kept next to the manifest.
+fn verify_frames(
+    params: &ParamsKind,
+    manifest_path: &Path,
+    selection: Option<Range<usize>>,
+) -> Result<()> {
+    let manifest = read_manifest(manifest_path)?;
+    let frames = manifest
+        .frames
+        .iter()
+        .filter(|expected| {
+            selection
+                .as_ref()
+                .is_none_or(|selection| selection.contains(&expected.frame))
+        })
+        .collect::<Vec<_>>();
+    if frames.is_empty() {
+        return Err(ErrorKind::InvalidCliArg("frames".to_string()));
+    }
+
+    let mut mismatch_count = 0;
+    for expected in &frames {
+        let frame_params = match params {
+            ParamsKind::Frame(params) if params.passes.is_some_and(|passes| passes > 1) => {
+                return Err(ErrorKind::FrameHashing("several passes".to_string()));
+            }
+            ParamsKind::Frame(params) => params.as_ref().clone(),
+            ParamsKind::Animation(params) => {
+                params.get_frame_params(expected.frame as F / params.fps)?
+            }
+        };
+        let sampling_points = frame_params.sampling.generate_sampling_points();
+        let (raw_image, _) = render_raw_image(&frame_params, &sampling_points, None, None);
+
+        let frame_hash = FrameHash::new(expected.frame, &raw_image);
+        let Some(row) = expected.first_different_row(&frame_hash) else {
+            eprintln!(" frame {}: ok", expected.frame + 1);
+            continue;
+        };
+        mismatch_count += 1;
+        let difference = match read_frame_values(manifest_path, expected.frame) {
+            Ok(expected_values) => match first_different_pixel(&expected_values, &raw_image) {
+                Some((x, y)) => format!("first difference at pixel ({}, {})", x, y),
+                // The values don't match the hash, e.g. when the
+                // manifest was written again by another render.
+                None => format!("first difference in row {}", row),
+            },
+            Err(ErrorKind::ReadManifest(e)) if e.kind() == io::ErrorKind::NotFound => {
+                format!("first difference in row {}", row)
+            }
+            Err(e) => return Err(e),
+        };
+        eprintln!(
+            " frame {}: mismatch ({:016x} instead of {:016x}), {}",
+            expected.frame + 1,
+            frame_hash.hash,
+            expected.hash,
+            difference
+        );
+    }
+
+    if mismatch_count == 0 {
+        eprintln!("{} frame(s) verified", frames.len());
+        Ok(())
+    } else {
+        Err(ErrorKind::VerifyFrames(mismatch_count))
+    }
+}
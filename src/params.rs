@@ -1,119 +1,716 @@
-use animation::RenderStep;
+use std::{
+    path::{Path, PathBuf},
+    thread,
+};
+
+use animation::{Animated, Easing, RenderStep, ZoomSequence};
+use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    coloring::{ColoringMode, Extremum, MapValue, DEFAULT_GRADIENT},
-    fractal::Fractal,
-    sampling::{Sampling, SamplingLevel},
+    coloring::{
+        BoundaryBand, ColoringMode, Extremum, GradientSpec, InteriorMode, MapValue, ToneCurve,
+    },
+    error::{ErrorKind, Result},
+    fractal::{Fractal, ValueKind},
+    rendering::mix_seed,
+    sampling::{Sampling, SamplingLevel, SamplingPattern},
     F,
 };
 
-fn default_gradient() -> Vec<(F, [u8; 3])> {
-    DEFAULT_GRADIENT.to_vec()
+/// Placeholder of the view fields left out of a parameter file,
+/// replaced by the default view of the fractal when decoding.
+fn unset_view() -> F {
+    F::NAN
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ParamsKind {
-    Frame(FrameParams),
-    Animation(AnimationParams),
+    Frame(Box<FrameParams>),
+    Animation(Box<AnimationParams>),
 }
 
 impl Default for ParamsKind {
     fn default() -> Self {
-        ParamsKind::Frame(FrameParams {
+        ParamsKind::Frame(Box::default())
+    }
+}
+
+/// A 1920x1080 view of the Mandelbrot set.
+impl Default for FrameParams {
+    fn default() -> Self {
+        FrameParams {
             img_width: 1920,
             img_height: 1080,
             zoom: 10.,
             center_x: -0.5,
             center_y: 0.,
             rotate: None,
+            aspect: None,
             fractal: Fractal::Mandelbrot,
             max_iter: 100,
+            auto_max_iter: None,
+            value: ValueKind::EscapeTime,
             coloring_mode: ColoringMode::MinMaxNorm {
                 min: Extremum::Custom(0.),
                 max: Extremum::Custom(100.),
                 map: MapValue::Linear,
             },
-            gradient: DEFAULT_GRADIENT.to_vec(),
+            gradient: GradientSpec::default(),
+            gradient_path: None,
+            gradient_offset: None,
+            gradient_repeat: None,
+            dither: None,
+            boundary_band: None,
+            tone_curve: None,
+            interior_mode: None,
             sampling: Sampling {
                 level: SamplingLevel::Exploration,
                 random_offsets: true,
+                filter: None,
+                pattern: SamplingPattern::GoldenRatio,
             },
+            supersample: None,
+            seed: None,
+            passes: None,
+            save_scale: None,
+            raw_output: None,
+            threads: None,
             dev_options: None,
-        })
+        }
+    }
+}
+
+/// Format of a parameter file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamsFormat {
+    Ron,
+    Json,
+}
+
+impl ParamsFormat {
+    /// JSON for `.json` files, RON for any other file.
+    pub fn from_path(path: &Path) -> Self {
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            ParamsFormat::Json
+        } else {
+            ParamsFormat::Ron
+        }
+    }
+
+    /// Tells JSON from RON by the first character, RON parameters
+    /// starting with `Frame` or `Animation`.
+    pub fn detect(s: &str) -> Self {
+        if s.trim_start().starts_with('{') {
+            ParamsFormat::Json
+        } else {
+            ParamsFormat::Ron
+        }
+    }
+}
+
+impl ParamsKind {
+    /// Decodes parameters, the view fields left out of frame
+    /// parameters getting the default view of the fractal, and
+    /// `max_iter` being set from the zoom when `auto_max_iter` is.
+    pub fn from_ron(s: &str) -> std::result::Result<Self, ron::de::SpannedError> {
+        Ok(ron::from_str::<ParamsKind>(s)?.with_default_view())
+    }
+
+    /// Same as [`ParamsKind::from_ron`] for JSON.
+    pub fn from_json(s: &str) -> std::result::Result<Self, serde_json::Error> {
+        Ok(serde_json::from_str::<ParamsKind>(s)?.with_default_view())
+    }
+
+    pub fn decode(s: &str, format: ParamsFormat) -> Result<Self> {
+        match format {
+            ParamsFormat::Ron => Self::from_ron(s).map_err(ErrorKind::DecodeParameterFile),
+            ParamsFormat::Json => Self::from_json(s).map_err(ErrorKind::DecodeJsonParameterFile),
+        }
+    }
+
+    pub fn encode(&self, format: ParamsFormat) -> Result<String> {
+        match format {
+            ParamsFormat::Ron => ron::ser::to_string_pretty(self, PrettyConfig::default())
+                .map_err(ErrorKind::EncodeParameterFile),
+            ParamsFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(ErrorKind::EncodeJsonParameterFile)
+            }
+        }
+    }
+
+    fn with_default_view(mut self) -> Self {
+        if let ParamsKind::Frame(params) = &mut self {
+            let (center_x, center_y, zoom, _) = params.fractal.default_view();
+            for (value, default) in [
+                (&mut params.center_x, center_x),
+                (&mut params.center_y, center_y),
+                (&mut params.zoom, zoom),
+            ] {
+                if value.is_nan() {
+                    *value = default;
+                }
+            }
+            params.apply_auto_max_iter();
+        }
+        self
+    }
+
+    /// Checks the combinations of parameters which can't be
+    /// rendered.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ParamsKind::Frame(params) => params.validate(),
+            ParamsKind::Animation(params) => {
+                validate_aspect(params.aspect)?;
+                validate_supersample(params.supersample)?;
+                params.gradient.validate()?;
+                params.validate_animated_coloring()?;
+                params.validate_steps()?;
+                validate_value(&params.get_frame_params(0.)?.fractal, params.value)
+            }
+        }
     }
 }
 
+fn validate_aspect(aspect: Option<F>) -> Result<()> {
+    match aspect {
+        Some(aspect) if !(aspect.is_finite() && aspect > 0.) => Err(ErrorKind::InvalidParams(
+            format!("aspect must be positive, got {}", aspect),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_supersample(supersample: Option<u32>) -> Result<()> {
+    match supersample {
+        Some(0) => Err(ErrorKind::InvalidParams(
+            "supersample must be at least 1".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_value(fractal: &Fractal, value: ValueKind) -> Result<()> {
+    if value == ValueKind::DistanceEstimate && !fractal.supports_distance_estimate() {
+        return Err(ErrorKind::InvalidParams(
+            "distance estimates are only supported by Mandelbrot and MandelbrotCustomExp"
+                .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FrameParams {
     pub img_width: u32,
     pub img_height: u32,
 
+    /// The view fields can be left out of parameter files to use
+    /// the default view of the fractal (see
+    /// [`Fractal::default_view`]).
+    ///
+    /// `zoom` is twice the width of the view in the complex plane:
+    /// the image spans `center_x - zoom / 4` to `center_x + zoom / 4`
+    /// horizontally whatever its size, smaller values zoom in. The
+    /// height of the view is the width divided by the aspect ratio
+    /// (see `aspect`), so that pixels are square by default.
+    #[serde(default = "unset_view")]
     pub zoom: F,
+    #[serde(default = "unset_view")]
     pub center_x: F,
+    #[serde(default = "unset_view")]
     pub center_y: F,
     pub rotate: Option<F>,
+    /// Width to height ratio of the view in the complex plane, the
+    /// ratio of the image (square pixels) by default. Other values
+    /// stretch the pixels, and the view no longer changes with the
+    /// image size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect: Option<F>,
     pub fractal: Fractal,
 
     pub max_iter: u32,
+    /// Replaces `max_iter` by a value chosen from the zoom (see
+    /// [`Fractal::auto_max_iter`]) when the parameters are loaded
+    /// and whenever the view changes in the gui.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_max_iter: Option<bool>,
+    /// Value computed for each sample.
+    #[serde(default)]
+    pub value: ValueKind,
 
     pub coloring_mode: ColoringMode,
-    #[serde(default = "default_gradient")]
-    pub gradient: Vec<(F, [u8; 3])>,
+    /// Name of a built-in gradient or list of stops, also read from
+    /// `custom_gradient` in older files.
+    #[serde(default, alias = "custom_gradient")]
+    pub gradient: GradientSpec,
+    /// Gradient file loaded in place of `gradient` (see
+    /// [`crate::gradient_file::load_gradient`] for supported
+    /// formats).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient_path: Option<PathBuf>,
+    /// Shifts where the gradient starts, wrapping around (in range
+    /// [0,1)).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient_offset: Option<F>,
+    /// Number of times the gradient is cycled through over the range
+    /// of values, shifted by `gradient_offset`. The last color of
+    /// the gradient then blends into the first one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient_repeat: Option<F>,
+    /// Adds a sub-LSB noise to colors before quantizing them to
+    /// avoid banding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dither: Option<bool>,
+    /// Blends a color over the pixels close to the maximum value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boundary_band: Option<BoundaryBand>,
+    /// See [`ToneCurve`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tone_curve: Option<ToneCurve>,
+    /// Colors the pixels which never escaped, by default they get
+    /// the color of the maximum value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interior_mode: Option<InteriorMode>,
 
     pub sampling: Sampling,
+    /// Renders the image this many times larger in each dimension
+    /// and averages the raw values of each block of pixels down to
+    /// the image size before coloring. Defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supersample: Option<u32>,
+    /// Seed of the random sampling offsets, renders with the same
+    /// seed are identical.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Number of passes of `sampling` accumulated by the cli, each
+    /// one with other random offsets. Defaults to a single pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passes: Option<usize>,
+
+    /// Scale applied to the colored image when it is saved (for
+    /// instance 0.5 to save a half-size image).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_scale: Option<F>,
+    /// Saves the normalized raw values instead of the colored
+    /// image: 32-bit float for `.exr` outputs, 16-bit grayscale
+    /// otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<bool>,
+
+    /// Number of threads used to render, 0 or `None` means all
+    /// cores.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dev_options: Option<DevOptions>,
 }
 
+impl FrameParams {
+    /// Sets `max_iter` from the zoom when `auto_max_iter` is set,
+    /// otherwise leaves it as it is.
+    pub fn apply_auto_max_iter(&mut self) {
+        if self.auto_max_iter == Some(true) {
+            self.max_iter = self.fractal.auto_max_iter(self.zoom);
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_aspect(self.aspect)?;
+        validate_supersample(self.supersample)?;
+        self.gradient.validate()?;
+        validate_value(&self.fractal, self.value)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnimationParams {
     pub img_width: u32,
     pub img_height: u32,
 
+    /// Ignored (and can be left empty) when `zoom_sequence` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub zoom: Vec<RenderStep>,
+    /// Ignored (and can be left empty) when `zoom_sequence` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub center_x: Vec<RenderStep>,
+    /// Ignored (and can be left empty) when `zoom_sequence` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub center_y: Vec<RenderStep>,
+    /// Zooms into a fixed point over the whole animation, in place
+    /// of `zoom`, `center_x` and `center_y`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom_sequence: Option<ZoomSequence>,
     pub rotate: Option<Vec<RenderStep>>,
+    /// See [`FrameParams::aspect`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect: Option<F>,
     pub fractal: animation::Fractal,
 
-    pub max_iter: u32,
+    /// Rounded to the nearest integer on each frame.
+    pub max_iter: Animated,
+    /// Replaces `max_iter` by a value chosen from the zoom of each
+    /// frame (see [`Fractal::auto_max_iter`]), so that the wide
+    /// frames render fast and the deep ones get enough iterations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_max_iter: Option<bool>,
+    #[serde(default)]
+    pub value: ValueKind,
 
     pub duration: F,
     pub fps: F,
+    /// Number of frames rendered at the same time. Defaults to the
+    /// number of available cores, capped so that the raw images of
+    /// the frames in flight fit in [`ANIMATION_MEMORY_BUDGET`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frames_in_flight: Option<usize>,
 
     pub coloring_mode: ColoringMode,
-    #[serde(default = "default_gradient")]
-    pub gradient: Vec<(F, [u8; 3])>,
+    /// Replace the `min` and `max` of a `MinMaxNorm` coloring mode
+    /// with custom values, so that long zooms neither blow out early
+    /// nor look flat late.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coloring_min: Option<Animated>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coloring_max: Option<Animated>,
+    /// Name of a built-in gradient or list of stops, also read from
+    /// `custom_gradient` in older files.
+    #[serde(default, alias = "custom_gradient")]
+    pub gradient: GradientSpec,
+    /// Gradient file loaded in place of `gradient` (see
+    /// [`crate::gradient_file::load_gradient`] for supported
+    /// formats).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient_offset: Option<Animated>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradient_repeat: Option<F>,
+    /// Replaces the parameter of the `map` of `coloring_mode` (the
+    /// exponent of `Powf`, `k` of `Log` and the frequency of
+    /// `Sine`), also read from `map_exp`.
+    #[serde(skip_serializing_if = "Option::is_none", alias = "map_exp")]
+    pub map_parameter: Option<Animated>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dither: Option<bool>,
+    /// Blends a color over the pixels close to the maximum value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boundary_band: Option<BoundaryBand>,
+    /// See [`ToneCurve`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tone_curve: Option<ToneCurve>,
+    /// Colors the pixels which never escaped, by default they get
+    /// the color of the maximum value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interior_mode: Option<InteriorMode>,
 
     pub sampling: Sampling,
+    /// Renders the image this many times larger in each dimension
+    /// and averages the raw values of each block of pixels down to
+    /// the image size before coloring. Defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supersample: Option<u32>,
+    /// Seed of the random sampling offsets, renders with the same
+    /// seed are identical.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    /// Scale applied to the colored image when it is saved (for
+    /// instance 0.5 to save a half-size image).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_scale: Option<F>,
+    /// Gives each frame of `.gif` outputs its own palette instead of
+    /// the palette of the first frame, which is more accurate when
+    /// the colors change over the animation but larger.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gif_local_palettes: Option<bool>,
+
+    /// Number of threads used to render, 0 or `None` means all
+    /// cores. The frames rendered in parallel share these threads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dev_options: Option<DevOptions>,
 }
 
+/// Memory (in bytes) the raw images of the frames rendered in
+/// parallel are allowed to take during an animation render.
+pub const ANIMATION_MEMORY_BUDGET: usize = 2_000_000_000;
+
 impl AnimationParams {
-    pub fn get_frame_params(&self, t: F) -> FrameParams {
-        FrameParams {
+    pub fn frames_in_flight(&self) -> usize {
+        // Frames are rendered at the supersampled size, along with
+        // their channels when the coloring mode needs them.
+        let factor = self.supersample.unwrap_or(1).max(1) as usize;
+        let values_per_pixel = if self.coloring_mode.needs_channels() {
+            4
+        } else {
+            1
+        };
+        let frame_size = self.img_width as usize
+            * self.img_height as usize
+            * factor
+            * factor
+            * values_per_pixel
+            * size_of::<F>();
+        let max_frames = (ANIMATION_MEMORY_BUDGET / frame_size.max(1)).max(1);
+
+        self.frames_in_flight
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+            .clamp(1, max_frames)
+    }
+
+    /// Animation zooming from the recommended view of the fractal
+    /// to the view of the given frame, lasting 10 seconds at 30 fps
+    /// (to be adjusted in the written file). `None` when the
+    /// fractal can't be animated.
+    pub fn zoom_into(params: &FrameParams) -> Option<AnimationParams> {
+        const DURATION: F = 10.;
+        const FPS: F = 30.;
+
+        let (duration, fps) = (DURATION, FPS);
+        let start_zoom = params.fractal.recommended_settings().zoom;
+        let constant = |value: F| vec![RenderStep::Const(0., duration, value)];
+
+        Some(AnimationParams {
+            img_width: params.img_width,
+            img_height: params.img_height,
+            zoom: Vec::new(),
+            center_x: Vec::new(),
+            center_y: Vec::new(),
+            zoom_sequence: Some(ZoomSequence {
+                center_x: params.center_x,
+                center_y: params.center_y,
+                start_zoom,
+                end_zoom: params.zoom,
+                easing: Easing::Linear,
+            }),
+            rotate: params.rotate.map(constant),
+            aspect: params.aspect,
+            fractal: animation::Fractal::from_fractal(params.fractal.clone(), duration)?,
+            max_iter: Animated::Const(params.max_iter as F),
+            auto_max_iter: params.auto_max_iter,
+            value: params.value,
+            duration,
+            fps,
+            frames_in_flight: None,
+            coloring_mode: params.coloring_mode,
+            coloring_min: None,
+            coloring_max: None,
+            gradient: params.gradient.to_owned(),
+            gradient_path: params.gradient_path.to_owned(),
+            gradient_offset: params.gradient_offset.map(Animated::Const),
+            gradient_repeat: params.gradient_repeat,
+            map_parameter: None,
+            dither: params.dither,
+            boundary_band: params.boundary_band,
+            tone_curve: params.tone_curve,
+            interior_mode: params.interior_mode.to_owned(),
+            sampling: params.sampling,
+            supersample: params.supersample,
+            seed: params.seed,
+            save_scale: params.save_scale,
+            gif_local_palettes: None,
+            threads: params.threads,
+            dev_options: params.dev_options,
+        })
+    }
+
+    /// Checks that the animated coloring values are used by the
+    /// coloring mode rather than silently ignored.
+    fn validate_animated_coloring(&self) -> Result<()> {
+        let is_min_max_norm = matches!(self.coloring_mode, ColoringMode::MinMaxNorm { .. });
+        for (name, value) in [
+            ("coloring_min", &self.coloring_min),
+            ("coloring_max", &self.coloring_max),
+        ] {
+            if value.is_some() && !is_min_max_norm {
+                return Err(ErrorKind::InvalidParams(format!(
+                    "{} is only used by the MinMaxNorm coloring mode",
+                    name
+                )));
+            }
+        }
+
+        let map = match self.coloring_mode {
+            ColoringMode::MinMaxNorm { map, .. }
+            | ColoringMode::CumulativeHistogram { map, .. }
+            | ColoringMode::TwoChannel { map, .. } => Some(map),
+            ColoringMode::Roots { .. } => None,
+        };
+        if self.map_parameter.is_some()
+            && !matches!(
+                map,
+                Some(MapValue::Powf(_) | MapValue::Log(_) | MapValue::Sine(_))
+            )
+        {
+            return Err(ErrorKind::InvalidParams(
+                "map_parameter is only used by the Powf, Log and Sine maps".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the steps of each animated value cover the whole
+    /// animation without overlapping, reporting all the problems at
+    /// once.
+    fn validate_steps(&self) -> Result<()> {
+        let problems = self
+            .step_sequences()
+            .into_iter()
+            .flat_map(|(name, steps)| {
+                RenderStep::coverage_problems(steps, self.duration)
+                    .into_iter()
+                    .map(move |problem| format!("{}: {}", name, problem))
+            })
+            .collect::<Vec<_>>();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ErrorKind::AnimationSteps(problems))
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        (self.duration * self.fps) as usize
+    }
+
+    /// Animated values along with their names.
+    pub fn step_sequences(&self) -> Vec<(&'static str, &[RenderStep])> {
+        let mut sequences: Vec<(&str, &[RenderStep])> = Vec::new();
+        if self.zoom_sequence.is_none() {
+            sequences.push(("zoom", &self.zoom));
+            sequences.push(("center_x", &self.center_x));
+            sequences.push(("center_y", &self.center_y));
+        }
+        if let Some(rotate) = &self.rotate {
+            sequences.push(("rotate", rotate));
+        }
+        if let Some(steps) = self.max_iter.steps() {
+            sequences.push(("max_iter", steps));
+        }
+        if let Some(steps) = self.gradient_offset.as_ref().and_then(Animated::steps) {
+            sequences.push(("gradient_offset", steps));
+        }
+        if let Some(steps) = self.coloring_min.as_ref().and_then(Animated::steps) {
+            sequences.push(("coloring_min", steps));
+        }
+        if let Some(steps) = self.coloring_max.as_ref().and_then(Animated::steps) {
+            sequences.push(("coloring_max", steps));
+        }
+        if let Some(steps) = self.map_parameter.as_ref().and_then(Animated::steps) {
+            sequences.push(("map_parameter", steps));
+        }
+        sequences.extend(self.fractal.step_sequences());
+        sequences
+    }
+
+    /// Frames for which an animated value has no step covering the
+    /// frame time, grouped by value. [`AnimationParams::get_frame_params`]
+    /// can only be called for the frames that are not listed.
+    pub fn uncovered_frames(&self) -> Vec<(&'static str, Vec<usize>)> {
+        self.step_sequences()
+            .into_iter()
+            .map(|(name, steps)| {
+                let frames = (0..self.frame_count().max(1))
+                    .filter(|&i| {
+                        RenderStep::find_current_step_index(steps, i as F / self.fps).is_none()
+                    })
+                    .collect::<Vec<_>>();
+                (name, frames)
+            })
+            .filter(|(_, frames)| !frames.is_empty())
+            .collect()
+    }
+
+    /// Fails when an animated value has no step covering `t`, which
+    /// can't happen once the params are validated.
+    pub fn get_frame_params(&self, t: F) -> Result<FrameParams> {
+        let (zoom, center_x, center_y) = match &self.zoom_sequence {
+            Some(zoom_sequence) => (
+                zoom_sequence.get_zoom(t / self.duration),
+                zoom_sequence.center_x,
+                zoom_sequence.center_y,
+            ),
+            None => (
+                RenderStep::value_at(&self.zoom, "zoom", t)?,
+                RenderStep::value_at(&self.center_x, "center_x", t)?,
+                RenderStep::value_at(&self.center_y, "center_y", t)?,
+            ),
+        };
+
+        let mut coloring_mode = self.coloring_mode;
+        if let Some(map_parameter) = &self.map_parameter {
+            if let ColoringMode::MinMaxNorm { map, .. }
+            | ColoringMode::CumulativeHistogram { map, .. }
+            | ColoringMode::TwoChannel { map, .. } = &mut coloring_mode
+            {
+                *map = map.with_parameter(map_parameter.get_value("map_parameter", t)?);
+            }
+        }
+        if let ColoringMode::MinMaxNorm { min, max, .. } = &mut coloring_mode {
+            if let Some(coloring_min) = &self.coloring_min {
+                *min = Extremum::Custom(coloring_min.get_value("coloring_min", t)?);
+            }
+            if let Some(coloring_max) = &self.coloring_max {
+                *max = Extremum::Custom(coloring_max.get_value("coloring_max", t)?);
+            }
+        }
+
+        let mut params = FrameParams {
             img_width: self.img_width,
             img_height: self.img_height,
-            zoom: self.zoom[RenderStep::get_current_step_index(&self.zoom, t)].get_value(t),
-            center_x: self.center_x[RenderStep::get_current_step_index(&self.center_x, t)]
-                .get_value(t),
-            center_y: self.center_y[RenderStep::get_current_step_index(&self.center_y, t)]
-                .get_value(t),
+            zoom,
+            center_x,
+            center_y,
             rotate: self
                 .rotate
-                .clone()
-                .map(|v| v[RenderStep::get_current_step_index(&v, t)].get_value(t)),
-            fractal: self.fractal.get_fractal(t),
-            max_iter: self.max_iter,
-            coloring_mode: self.coloring_mode,
+                .as_ref()
+                .map(|steps| RenderStep::value_at(steps, "rotate", t))
+                .transpose()?,
+            aspect: self.aspect,
+            fractal: self.fractal.get_fractal(t)?,
+            max_iter: self.max_iter.get_value("max_iter", t)?.round().max(1.) as u32,
+            auto_max_iter: self.auto_max_iter,
+            value: self.value,
+            coloring_mode,
             sampling: self.sampling,
+            supersample: self.supersample,
+            // Each frame gets its own seed so that the noise is not
+            // the same on every frame, mixed so that neighbouring
+            // seeds don't give the same frames one frame apart.
+            seed: self
+                .seed
+                .map(|seed| mix_seed(seed, (t * self.fps).round() as u64)),
+            passes: None,
+            save_scale: self.save_scale,
+            raw_output: None,
+            threads: self.threads,
             gradient: self.gradient.to_owned(),
+            gradient_path: self.gradient_path.to_owned(),
+            gradient_offset: self
+                .gradient_offset
+                .as_ref()
+                .map(|v| v.get_value("gradient_offset", t))
+                .transpose()?,
+            gradient_repeat: self.gradient_repeat,
+            dither: self.dither,
+            boundary_band: self.boundary_band,
+            tone_curve: self.tone_curve,
+            interior_mode: self.interior_mode.to_owned(),
             dev_options: self.dev_options,
-        }
+        };
+        params.apply_auto_max_iter();
+        Ok(params)
     }
 }
 
@@ -123,12 +720,42 @@ pub struct DevOptions {
     pub save_sampling_pattern: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub display_gradient: Option<bool>,
+    /// Saves the raw values (before any normalization) as a single
+    /// channel float image when the output is an `.exr` file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_raw: Option<bool>,
+    /// Lets a panic of the render thread take the gui down instead
+    /// of reporting it, to debug it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reraise_panics: Option<bool>,
+    /// Shows the hits and misses of the preview cache of the gui
+    /// when hovering the preview.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_cache_stats: Option<bool>,
+    /// Shows the duration of each pass of the gui under the preview
+    /// heading, in red when it goes over the budget.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ui_time: Option<bool>,
 }
 
 pub mod animation {
-    use serde::{Deserialize, Serialize};
+    use std::fmt;
+
+    use serde::{
+        de::{self, value::SeqAccessDeserializer, SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize,
+    };
+
+    use crate::{
+        error::{self, ErrorKind},
+        formula::Formula,
+        F,
+    };
 
-    use crate::F;
+    /// How far (in seconds) `t` can be from the steps and still be
+    /// covered by the closest one, so that rounding errors (e.g. the
+    /// last step ending slightly before the duration) are tolerated.
+    const TIME_EPSILON: F = 1e-3;
 
     #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
     pub enum RenderStep {
@@ -138,21 +765,154 @@ pub mod animation {
         Linear(F, F, F, F),
         /// (start_time, end_time, start_value, end_value)
         Smooth(F, F, F, F),
+        /// (start_time, end_time, start_value, end_value, c1, c2)
+        ///
+        /// Cubic bezier timing curve, `c1` and `c2` are the weights
+        /// of its two control points (`Smooth` is `c1 = 0, c2 = 1`).
+        Bezier(F, F, F, F, F, F),
+        /// (start_time, end_time, start_value, end_value)
+        ///
+        /// Starts slowly and ends at full speed.
+        EaseIn(F, F, F, F),
+        /// (start_time, end_time, start_value, end_value)
+        ///
+        /// Starts at full speed and ends slowly.
+        EaseOut(F, F, F, F),
     }
 
     impl RenderStep {
-        pub fn get_current_step_index(steps: &[RenderStep], t: F) -> usize {
+        /// (start_time, end_time)
+        pub fn time_range(&self) -> (F, F) {
+            match *self {
+                RenderStep::Const(start_time, end_time, _)
+                | RenderStep::Linear(start_time, end_time, _, _)
+                | RenderStep::Smooth(start_time, end_time, _, _)
+                | RenderStep::Bezier(start_time, end_time, _, _, _, _)
+                | RenderStep::EaseIn(start_time, end_time, _, _)
+                | RenderStep::EaseOut(start_time, end_time, _, _) => (start_time, end_time),
+            }
+        }
+
+        /// Same as [`RenderStep::find_current_step_index`], the error
+        /// names the animated value `name` and lists what its steps
+        /// cover.
+        pub fn get_current_step_index(
+            steps: &[RenderStep],
+            name: &str,
+            t: F,
+        ) -> error::Result<usize> {
+            RenderStep::find_current_step_index(steps, t).ok_or_else(|| {
+                let covered = steps
+                    .iter()
+                    .map(|step| {
+                        let (start_time, end_time) = step.time_range();
+                        format!("{}s..{}s", start_time, end_time)
+                    })
+                    .collect::<Vec<_>>();
+                ErrorKind::AnimationSteps(vec![format!(
+                    "{}: no step covers t = {}s (the steps cover {})",
+                    name,
+                    t,
+                    if covered.is_empty() {
+                        "nothing".to_string()
+                    } else {
+                        covered.join(", ")
+                    }
+                )])
+            })
+        }
+
+        /// Index of the step covering `t`, or of the closest one when
+        /// it is less than [`TIME_EPSILON`] away. `None` when no step
+        /// does.
+        pub fn find_current_step_index(steps: &[RenderStep], t: F) -> Option<usize> {
+            let distance = |step: &RenderStep| {
+                let (start_time, end_time) = step.time_range();
+                (start_time - t).max(t - end_time).max(0.)
+            };
+
             steps
                 .iter()
-                .enumerate()
-                .find_map(|(i, &step)| match step {
-                    RenderStep::Const(start_time, end_time, _)
-                    | RenderStep::Linear(start_time, end_time, _, _)
-                    | RenderStep::Smooth(start_time, end_time, _, _) => {
-                        (start_time <= t && t <= end_time).then_some(i)
-                    }
+                .position(|step| distance(step) == 0.)
+                .or_else(|| {
+                    steps
+                        .iter()
+                        .enumerate()
+                        .map(|(i, step)| (i, distance(step)))
+                        .filter(|&(_, distance)| distance <= TIME_EPSILON)
+                        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                        .map(|(i, _)| i)
                 })
-                .unwrap()
+        }
+
+        /// Value of the animated value `name` at `t`, which is clamped
+        /// to the step found so that the easings stay in range.
+        pub fn value_at(steps: &[RenderStep], name: &str, t: F) -> error::Result<F> {
+            let step = steps[RenderStep::get_current_step_index(steps, name, t)?];
+            let (start_time, end_time) = step.time_range();
+            Ok(step.get_value(t.max(start_time).min(end_time)))
+        }
+
+        /// Gaps, overlaps and reversed steps, and whether the steps
+        /// fall short of covering the animation from 0 to `duration`.
+        pub fn coverage_problems(steps: &[RenderStep], duration: F) -> Vec<String> {
+            let mut problems = Vec::new();
+
+            // Steps are numbered from 1 as in the parameter file.
+            let mut ranges = steps
+                .iter()
+                .map(RenderStep::time_range)
+                .enumerate()
+                .map(|(i, range)| (i + 1, range))
+                .collect::<Vec<_>>();
+            // Also false for NaN times.
+            let is_ordered =
+                |&(_, (start_time, end_time)): &(usize, (F, F))| start_time <= end_time;
+            for range @ &(k, (start_time, end_time)) in &ranges {
+                if !is_ordered(range) {
+                    problems.push(format!(
+                        "step {} ends ({}s) before it starts ({}s)",
+                        k, end_time, start_time
+                    ));
+                }
+            }
+            ranges.retain(is_ordered);
+            ranges.sort_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b));
+
+            let Some(&(_, (first_start, _))) = ranges.first() else {
+                problems.push("there are no steps".to_string());
+                return problems;
+            };
+            if first_start > TIME_EPSILON {
+                problems.push(format!("no step covers 0s..{}s", first_start));
+            }
+
+            let (mut last_k, mut covered_end) = (ranges[0].0, first_start);
+            for &(k, (start_time, end_time)) in &ranges {
+                if start_time > covered_end + TIME_EPSILON {
+                    problems.push(format!("gap between {}s and {}s", covered_end, start_time));
+                } else if start_time < covered_end - TIME_EPSILON {
+                    problems.push(format!(
+                        "steps {} and {} overlap between {}s and {}s",
+                        last_k,
+                        k,
+                        start_time,
+                        end_time.min(covered_end)
+                    ));
+                }
+                if end_time > covered_end {
+                    (last_k, covered_end) = (k, end_time);
+                }
+            }
+
+            if covered_end < duration - TIME_EPSILON {
+                problems.push(format!(
+                    "the steps end at {}s, before the end of the animation ({}s)",
+                    covered_end, duration
+                ));
+            }
+
+            problems
         }
 
         pub fn get_value(&self, t: F) -> F {
@@ -160,14 +920,141 @@ pub mod animation {
             match *self {
                 RenderStep::Const(_, _, value) => value,
                 RenderStep::Linear(start_time, end_time, start_value, end_value) => {
-                    let w = (t - start_time) / (end_time - start_time);
+                    let w = step_progress(t, start_time, end_time);
                     start_value * (1. - w) + end_value * w
                 }
                 RenderStep::Smooth(start_time, end_time, start_value, end_value) => {
-                    let w = (t - start_time) / (end_time - start_time);
+                    let w = step_progress(t, start_time, end_time);
                     let smooth_w = w * w * (3. - 2. * w);
                     start_value * (1. - smooth_w) + end_value * smooth_w
                 }
+                RenderStep::Bezier(start_time, end_time, start_value, end_value, c1, c2) => {
+                    let w = step_progress(t, start_time, end_time);
+                    let bezier_w = cubic_bezier(w, c1, c2);
+                    start_value * (1. - bezier_w) + end_value * bezier_w
+                }
+                // Quadratic curves written as cubic bezier ones.
+                RenderStep::EaseIn(start_time, end_time, start_value, end_value) => {
+                    RenderStep::Bezier(start_time, end_time, start_value, end_value, 0., 1. / 3.)
+                        .get_value(t)
+                }
+                RenderStep::EaseOut(start_time, end_time, start_value, end_value) => {
+                    RenderStep::Bezier(start_time, end_time, start_value, end_value, 2. / 3., 1.)
+                        .get_value(t)
+                }
+            }
+        }
+    }
+
+    /// Value which is either the same for the whole animation or
+    /// animated with steps, written as a number or as a list of
+    /// steps.
+    #[derive(Debug, Clone, Serialize)]
+    #[serde(untagged)]
+    pub enum Animated {
+        Const(F),
+        Steps(Vec<RenderStep>),
+    }
+
+    // Untagged enums can't be deserialized from RON when their
+    // variants contain enums, hence the visitor.
+    impl<'de> Deserialize<'de> for Animated {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct AnimatedVisitor;
+
+            impl<'de> Visitor<'de> for AnimatedVisitor {
+                type Value = Animated;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "a number or a list of steps")
+                }
+
+                fn visit_f64<E: de::Error>(self, v: f64) -> Result<Animated, E> {
+                    Ok(Animated::Const(v as F))
+                }
+                fn visit_i64<E: de::Error>(self, v: i64) -> Result<Animated, E> {
+                    Ok(Animated::Const(v as F))
+                }
+                fn visit_u64<E: de::Error>(self, v: u64) -> Result<Animated, E> {
+                    Ok(Animated::Const(v as F))
+                }
+                fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Animated, A::Error> {
+                    Vec::deserialize(SeqAccessDeserializer::new(seq)).map(Animated::Steps)
+                }
+            }
+
+            deserializer.deserialize_any(AnimatedVisitor)
+        }
+    }
+
+    impl Animated {
+        /// See [`RenderStep::value_at`].
+        pub fn get_value(&self, name: &str, t: F) -> error::Result<F> {
+            match self {
+                Animated::Const(value) => Ok(*value),
+                Animated::Steps(steps) => RenderStep::value_at(steps, name, t),
+            }
+        }
+
+        pub fn steps(&self) -> Option<&[RenderStep]> {
+            match self {
+                Animated::Const(_) => None,
+                Animated::Steps(steps) => Some(steps),
+            }
+        }
+    }
+
+    /// Fraction of a step at `t`. Zero-length steps are jumps, they
+    /// are done as soon as they start instead of dividing by zero.
+    fn step_progress(t: F, start_time: F, end_time: F) -> F {
+        if end_time > start_time {
+            (t - start_time) / (end_time - start_time)
+        } else {
+            1.
+        }
+    }
+
+    /// Cubic bezier going from 0 to 1 with control points `c1` and
+    /// `c2` at `w` (in range [0,1]).
+    fn cubic_bezier(w: F, c1: F, c2: F) -> F {
+        let v = 1. - w;
+        3. * v * v * w * c1 + 3. * v * w * w * c2 + w * w * w
+    }
+
+    /// Zoom into a fixed point. The zoom is interpolated in log
+    /// space so that the apparent zoom speed stays constant:
+    /// `zoom = start_zoom * (end_zoom / start_zoom)^w(t)`.
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+    pub struct ZoomSequence {
+        pub center_x: F,
+        pub center_y: F,
+        pub start_zoom: F,
+        pub end_zoom: F,
+        pub easing: Easing,
+    }
+
+    impl ZoomSequence {
+        /// Zoom at the given fraction of the animation (in range
+        /// [0,1]).
+        pub fn get_zoom(&self, w: F) -> F {
+            let w = self.easing.apply(w.clamp(0., 1.));
+            self.start_zoom * (self.end_zoom / self.start_zoom).powf(w)
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+    pub enum Easing {
+        Linear,
+        /// Starts and ends slowly (same curve as
+        /// [`RenderStep::Smooth`]).
+        Smooth,
+    }
+
+    impl Easing {
+        pub fn apply(&self, w: F) -> F {
+            match self {
+                Easing::Linear => w,
+                Easing::Smooth => w * w * (3. - 2. * w),
             }
         }
     }
@@ -204,19 +1091,114 @@ pub mod animation {
             a_re: Vec<RenderStep>,
             a_im: Vec<RenderStep>,
         },
+
+        SinMandelbrot,
+        CosMandelbrot,
+        ExpMandelbrot,
+        Mandelbox {
+            scale: Vec<RenderStep>,
+            fold: Vec<RenderStep>,
+        },
+
+        /// The formula itself isn't animated.
+        Custom {
+            formula: Formula,
+        },
     }
 
     impl Fractal {
-        pub fn get_fractal(&self, t: F) -> crate::fractal::Fractal {
+        /// Animated fractal with constant parameters, `None` for
+        /// fractals that can't be animated.
+        pub fn from_fractal(fractal: crate::fractal::Fractal, duration: F) -> Option<Self> {
+            use crate::fractal::Fractal as FrameFractal;
+
+            let constant = |value| vec![RenderStep::Const(0., duration, value)];
+
+            Some(match fractal {
+                FrameFractal::Mandelbrot => Self::Mandelbrot,
+                FrameFractal::MandelbrotCustomExp { exp } => {
+                    Self::MandelbrotCustomExp { exp: constant(exp) }
+                }
+                FrameFractal::Sdrge => Self::Sdrge,
+                FrameFractal::SdrgeParam { a_re, a_im } => Self::SdrgeParam {
+                    a_re: constant(a_re),
+                    a_im: constant(a_im),
+                },
+                FrameFractal::Sdrage => Self::Sdrage,
+                FrameFractal::Tdrge => Self::Tdrge,
+                FrameFractal::NthDrge(n) => Self::NthDrge(n),
+                FrameFractal::ThirdDegreeRecPairs => Self::ThirdDegreeRecPairs,
+                FrameFractal::SecondDegreeThirtySevenBlend => Self::SecondDegreeThirtySevenBlend,
+                FrameFractal::Vshqwj => Self::Vshqwj,
+                FrameFractal::Wmriho { a_re, a_im } => Self::Wmriho {
+                    a_re: constant(a_re),
+                    a_im: constant(a_im),
+                },
+                FrameFractal::Iigdzh { a_re, a_im } => Self::Iigdzh {
+                    a_re: constant(a_re),
+                    a_im: constant(a_im),
+                },
+                FrameFractal::Mjygzr => Self::Mjygzr,
+                FrameFractal::ComplexLogisticMapLike { a_re, a_im } => {
+                    Self::ComplexLogisticMapLike {
+                        a_re: constant(a_re),
+                        a_im: constant(a_im),
+                    }
+                }
+                FrameFractal::SinMandelbrot => Self::SinMandelbrot,
+                FrameFractal::CosMandelbrot => Self::CosMandelbrot,
+                FrameFractal::ExpMandelbrot => Self::ExpMandelbrot,
+                FrameFractal::Mandelbox { scale, fold } => Self::Mandelbox {
+                    scale: constant(scale),
+                    fold: constant(fold),
+                },
+                FrameFractal::Custom { formula } => Self::Custom { formula },
+                FrameFractal::SdrgeCustomIntExp { .. }
+                | FrameFractal::SdrgeCustomExp { .. }
+                | FrameFractal::Fxdicq
+                | FrameFractal::Sfwypc { .. }
+                | FrameFractal::Newton { .. }
+                | FrameFractal::MoireTest => return None,
+            })
+        }
+
+        /// Animated parameters of the fractal along with their names.
+        pub fn step_sequences(&self) -> Vec<(&'static str, &[RenderStep])> {
             match self {
+                Self::MandelbrotCustomExp { exp } => vec![("exp", exp)],
+                Self::Mandelbox { scale, fold } => vec![("scale", scale), ("fold", fold)],
+                Self::SdrgeParam { a_re, a_im }
+                | Self::Wmriho { a_re, a_im }
+                | Self::Iigdzh { a_re, a_im }
+                | Self::ComplexLogisticMapLike { a_re, a_im } => {
+                    vec![("a_re", a_re), ("a_im", a_im)]
+                }
+                Self::Mandelbrot
+                | Self::Sdrge
+                | Self::Sdrage
+                | Self::Tdrge
+                | Self::NthDrge(_)
+                | Self::ThirdDegreeRecPairs
+                | Self::SecondDegreeThirtySevenBlend
+                | Self::Vshqwj
+                | Self::Mjygzr
+                | Self::SinMandelbrot
+                | Self::CosMandelbrot
+                | Self::ExpMandelbrot
+                | Self::Custom { .. } => Vec::new(),
+            }
+        }
+
+        pub fn get_fractal(&self, t: F) -> error::Result<crate::fractal::Fractal> {
+            Ok(match self {
                 Self::Mandelbrot => crate::fractal::Fractal::Mandelbrot,
                 Self::MandelbrotCustomExp { exp } => crate::fractal::Fractal::MandelbrotCustomExp {
-                    exp: exp[RenderStep::get_current_step_index(exp, t)].get_value(t),
+                    exp: RenderStep::value_at(exp, "exp", t)?,
                 },
                 Self::Sdrge => crate::fractal::Fractal::Sdrge,
                 Self::SdrgeParam { a_re, a_im } => crate::fractal::Fractal::SdrgeParam {
-                    a_re: a_re[RenderStep::get_current_step_index(a_re, t)].get_value(t),
-                    a_im: a_im[RenderStep::get_current_step_index(a_im, t)].get_value(t),
+                    a_re: RenderStep::value_at(a_re, "a_re", t)?,
+                    a_im: RenderStep::value_at(a_im, "a_im", t)?,
                 },
                 Self::Sdrage => crate::fractal::Fractal::Sdrage,
                 Self::Tdrge => crate::fractal::Fractal::Tdrge,
@@ -228,22 +1210,199 @@ pub mod animation {
 
                 Self::Vshqwj => crate::fractal::Fractal::Vshqwj,
                 Self::Wmriho { a_re, a_im } => crate::fractal::Fractal::Wmriho {
-                    a_re: a_re[RenderStep::get_current_step_index(a_re, t)].get_value(t),
-                    a_im: a_im[RenderStep::get_current_step_index(a_im, t)].get_value(t),
+                    a_re: RenderStep::value_at(a_re, "a_re", t)?,
+                    a_im: RenderStep::value_at(a_im, "a_im", t)?,
                 },
                 Self::Iigdzh { a_re, a_im } => crate::fractal::Fractal::Iigdzh {
-                    a_re: a_re[RenderStep::get_current_step_index(a_re, t)].get_value(t),
-                    a_im: a_im[RenderStep::get_current_step_index(a_im, t)].get_value(t),
+                    a_re: RenderStep::value_at(a_re, "a_re", t)?,
+                    a_im: RenderStep::value_at(a_im, "a_im", t)?,
                 },
                 Self::Mjygzr => crate::fractal::Fractal::Mjygzr,
 
                 Self::ComplexLogisticMapLike { a_re, a_im } => {
                     crate::fractal::Fractal::ComplexLogisticMapLike {
-                        a_re: a_re[RenderStep::get_current_step_index(a_re, t)].get_value(t),
-                        a_im: a_im[RenderStep::get_current_step_index(a_im, t)].get_value(t),
+                        a_re: RenderStep::value_at(a_re, "a_re", t)?,
+                        a_im: RenderStep::value_at(a_im, "a_im", t)?,
                     }
                 }
-            }
+
+                Self::SinMandelbrot => crate::fractal::Fractal::SinMandelbrot,
+                Self::CosMandelbrot => crate::fractal::Fractal::CosMandelbrot,
+                Self::ExpMandelbrot => crate::fractal::Fractal::ExpMandelbrot,
+                Self::Mandelbox { scale, fold } => crate::fractal::Fractal::Mandelbox {
+                    scale: RenderStep::value_at(scale, "scale", t)?,
+                    fold: RenderStep::value_at(fold, "fold", t)?,
+                },
+                Self::Custom { formula } => crate::fractal::Fractal::Custom {
+                    formula: formula.clone(),
+                },
+            })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::coloring::ChannelSelect;
+
+    #[test]
+    fn frames_in_flight_fit_in_the_memory_budget() {
+        let frame = FrameParams {
+            img_width: 4000,
+            img_height: 4000,
+            ..Default::default()
+        };
+        let params = AnimationParams {
+            frames_in_flight: Some(1000),
+            ..AnimationParams::zoom_into(&frame).unwrap()
+        };
+        let frame_size = 4000 * 4000 * size_of::<F>();
+        assert_eq!(
+            params.frames_in_flight(),
+            ANIMATION_MEMORY_BUDGET / frame_size
+        );
+
+        let supersampled = AnimationParams {
+            supersample: Some(2),
+            ..params.clone()
+        };
+        assert_eq!(
+            supersampled.frames_in_flight(),
+            ANIMATION_MEMORY_BUDGET / (4 * frame_size)
+        );
+
+        // The channels take three more values per pixel.
+        let channels = AnimationParams {
+            coloring_mode: ColoringMode::TwoChannel {
+                value: ChannelSelect::Value,
+                hue: ChannelSelect::FinalArg,
+                saturation: 1.,
+                map: MapValue::Linear,
+            },
+            ..params.clone()
+        };
+        assert_eq!(
+            channels.frames_in_flight(),
+            ANIMATION_MEMORY_BUDGET / (4 * frame_size)
+        );
+
+        // At least one frame is rendered whatever its size.
+        let huge = AnimationParams {
+            supersample: Some(8),
+            ..channels
+        };
+        assert_eq!(huge.frames_in_flight(), 1);
+    }
+
+    #[test]
+    fn frame_seeds_of_neighbouring_seeds_differ() {
+        let params = |seed| AnimationParams {
+            seed: Some(seed),
+            ..AnimationParams::zoom_into(&FrameParams::default()).unwrap()
+        };
+        let frame_seed = |seed, frame: usize| {
+            let params = params(seed);
+            params
+                .get_frame_params(frame as F / params.fps)
+                .unwrap()
+                .seed
+                .unwrap()
+        };
+
+        assert_eq!(frame_seed(7, 3), frame_seed(7, 3));
+        assert_ne!(frame_seed(7, 3), frame_seed(7, 4));
+        assert_ne!(frame_seed(7, 4), frame_seed(8, 3));
+    }
+
+    #[test]
+    fn steps_are_found_within_the_time_epsilon() {
+        use animation::RenderStep;
+
+        let steps = [
+            RenderStep::Linear(0., 1., 0., 10.),
+            RenderStep::Const(1., 2., 10.),
+        ];
+        assert_eq!(RenderStep::find_current_step_index(&steps, 0.5), Some(0));
+        assert_eq!(RenderStep::find_current_step_index(&steps, 1.5), Some(1));
+        assert_eq!(RenderStep::find_current_step_index(&steps, 2.0005), Some(1));
+        assert_eq!(
+            RenderStep::find_current_step_index(&steps, -0.0005),
+            Some(0)
+        );
+        assert_eq!(RenderStep::find_current_step_index(&steps, 2.1), None);
+        assert_eq!(RenderStep::find_current_step_index(&[], 0.), None);
+
+        // Values are clamped to the step found.
+        assert_eq!(RenderStep::value_at(&steps, "zoom", 0.25).unwrap(), 2.5);
+        assert_eq!(RenderStep::value_at(&steps, "zoom", -0.0005).unwrap(), 0.);
+
+        let Err(ErrorKind::AnimationSteps(problems)) = RenderStep::value_at(&steps, "zoom", 3.)
+        else {
+            panic!("t = 3s is not covered");
+        };
+        assert_eq!(
+            problems,
+            ["zoom: no step covers t = 3s (the steps cover 0s..1s, 1s..2s)"]
+        );
+    }
+
+    #[test]
+    fn zero_length_steps_jump_to_their_end_value() {
+        use animation::RenderStep;
+
+        for step in [
+            RenderStep::Linear(5., 5., 1., 2.),
+            RenderStep::Smooth(5., 5., 1., 2.),
+            RenderStep::Bezier(5., 5., 1., 2., 0.2, 0.8),
+            RenderStep::EaseIn(5., 5., 1., 2.),
+            RenderStep::EaseOut(5., 5., 1., 2.),
+        ] {
+            assert_eq!(step.get_value(5.), 2., "{step:?}");
+        }
+
+        let steps = [
+            RenderStep::Linear(0., 5., 0., 1.),
+            RenderStep::Linear(5., 5., 1., 3.),
+            RenderStep::Const(5., 10., 3.),
+        ];
+        assert!(RenderStep::coverage_problems(&steps, 10.).is_empty());
+        for t in [0., 2.5, 5., 7.5, 10.] {
+            assert!(RenderStep::value_at(&steps, "zoom", t).unwrap().is_finite());
+        }
+    }
+
+    #[test]
+    fn coverage_problems_are_all_reported() {
+        use animation::RenderStep;
+
+        let covering = [
+            RenderStep::Const(1., 2., 0.),
+            RenderStep::Linear(0., 1., 0., 1.),
+            RenderStep::Const(2., 2.9995, 0.),
+        ];
+        assert!(RenderStep::coverage_problems(&covering, 3.).is_empty());
+
+        let steps = [
+            RenderStep::Const(0.5, 1., 0.),
+            RenderStep::Const(2., 1.5, 0.),
+            RenderStep::Const(0.8, 1.2, 0.),
+            RenderStep::Const(1.5, 2., 0.),
+        ];
+        assert_eq!(
+            RenderStep::coverage_problems(&steps, 3.),
+            [
+                "step 2 ends (1.5s) before it starts (2s)",
+                "no step covers 0s..0.5s",
+                "steps 1 and 3 overlap between 0.8s and 1s",
+                "gap between 1.2s and 1.5s",
+                "the steps end at 2s, before the end of the animation (3s)",
+            ]
+        );
+        assert_eq!(
+            RenderStep::coverage_problems(&[], 3.),
+            ["there are no steps"]
+        );
+    }
+}
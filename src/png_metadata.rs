@@ -0,0 +1,262 @@
+use std::{
+    fs,
+    io::{BufWriter, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::DynamicImage;
+use ron::ser::PrettyConfig;
+
+use crate::{
+    error::{ErrorKind, Result},
+    params::{FrameParams, ParamsKind},
+    F,
+};
+
+/// Keyword of the png text chunk holding the parameters.
+const PARAMS_KEYWORD: &str = "fractal_rndr params";
+/// Keyword of the png text chunk holding the average number of
+/// samples per pixel of the render.
+const SPP_KEYWORD: &str = "fractal_rndr spp";
+/// Keyword of the png text chunk holding the date of the render.
+const DATE_KEYWORD: &str = "fractal_rndr date";
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Checks whether the given file content is a png image.
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&PNG_SIGNATURE)
+}
+
+/// Saves an image, png images get the parameters embedded in a text
+/// chunk so that they can be extracted later.
+pub fn save_image(
+    image: DynamicImage,
+    params: &FrameParams,
+    samples_per_pixel: F,
+    path: &Path,
+) -> Result<()> {
+    let is_png = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+
+    if is_png {
+        let file = fs::File::create(path).map_err(|e| ErrorKind::EncodePng(e.into()))?;
+        write_png(&image, params, samples_per_pixel, BufWriter::new(file))
+    } else {
+        image.save(path).map_err(ErrorKind::SaveImage)
+    }
+}
+
+/// Encodes an image as png with the parameters embedded. 16-bit
+/// grayscale images are kept as is, others are saved as 8-bit RGB.
+pub fn write_png(
+    image: &DynamicImage,
+    params: &FrameParams,
+    samples_per_pixel: F,
+    writer: impl Write,
+) -> Result<()> {
+    let (color, depth, data) = match image {
+        DynamicImage::ImageLuma16(image) => (
+            png::ColorType::Grayscale,
+            png::BitDepth::Sixteen,
+            image.iter().flat_map(|v| v.to_be_bytes()).collect(),
+        ),
+        image => (
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            image.to_rgb8().into_raw(),
+        ),
+    };
+
+    let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+    add_metadata_chunks(&mut encoder, params, samples_per_pixel)?;
+
+    let mut writer = encoder.write_header().map_err(ErrorKind::EncodePng)?;
+    writer
+        .write_image_data(&data)
+        .map_err(ErrorKind::EncodePng)?;
+    writer.finish().map_err(ErrorKind::EncodePng)
+}
+
+/// Adds the parameters, the number of samples per pixel and the
+/// current date to the png.
+pub fn add_metadata_chunks<W: Write>(
+    encoder: &mut png::Encoder<W>,
+    params: &FrameParams,
+    samples_per_pixel: F,
+) -> Result<()> {
+    let params_str = ron::ser::to_string_pretty(
+        &ParamsKind::Frame(Box::new(params.clone())),
+        PrettyConfig::default(),
+    )
+    .map_err(ErrorKind::EncodeParameterFile)?;
+
+    for (keyword, text) in [
+        (PARAMS_KEYWORD, params_str),
+        (SPP_KEYWORD, samples_per_pixel.to_string()),
+        (DATE_KEYWORD, format_utc_date(SystemTime::now())),
+    ] {
+        encoder
+            .add_itxt_chunk(keyword.to_string(), text)
+            .map_err(ErrorKind::EncodePng)?;
+    }
+
+    Ok(())
+}
+
+/// Metadata embedded in a png image by [`write_png`].
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    /// Parameters of the render, as RON.
+    pub params: String,
+    /// Missing in images saved before it was embedded.
+    pub samples_per_pixel: Option<F>,
+    pub date: Option<String>,
+}
+
+impl Metadata {
+    /// Describes the render for notifications, for instance
+    /// "rendered with 34 spp on 2024-05-01 12:30 UTC".
+    pub fn describe(&self) -> String {
+        let spp = self
+            .samples_per_pixel
+            .map(|spp| format!(" with {} spp", (10. * spp).round() / 10.));
+        let date = self.date.as_ref().map(|date| format!(" on {}", date));
+
+        match (spp, date) {
+            (None, None) => "no render information".to_string(),
+            (spp, date) => format!(
+                "rendered{}{}",
+                spp.unwrap_or_default(),
+                date.unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Reads the metadata embedded in a png image by [`write_png`].
+pub fn read_metadata(reader: impl Read) -> Result<Metadata> {
+    let reader = png::Decoder::new(reader)
+        .read_info()
+        .map_err(ErrorKind::DecodePng)?;
+
+    let chunks = &reader.info().utf8_text;
+    let text = |keyword: &str| {
+        chunks
+            .iter()
+            .find(|chunk| chunk.keyword == keyword)
+            .and_then(|chunk| chunk.get_text().ok())
+    };
+
+    Ok(Metadata {
+        params: text(PARAMS_KEYWORD).ok_or(ErrorKind::MissingEmbeddedParams)?,
+        samples_per_pixel: text(SPP_KEYWORD).and_then(|spp| spp.parse().ok()),
+        date: text(DATE_KEYWORD),
+    })
+}
+
+/// Formats a date as "YYYY-MM-DD HH:MM UTC".
+fn format_utc_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Converts days since 1970-01-01 to a civil date (see Howard
+    // Hinnant's `civil_from_days`).
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use image::{ImageBuffer, Luma, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn written_png_decodes_with_the_image_crate() {
+        let image = RgbImage::from_fn(7, 5, |i, j| Rgb([i as u8 * 30, j as u8 * 50, 200]));
+        let mut bytes = Vec::new();
+        write_png(
+            &image.clone().into(),
+            &FrameParams::default(),
+            21.,
+            &mut bytes,
+        )
+        .unwrap();
+
+        assert!(is_png(&bytes));
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.to_rgb8(), image);
+    }
+
+    #[test]
+    fn written_luma16_png_keeps_its_values() {
+        let image: ImageBuffer<Luma<u16>, _> =
+            ImageBuffer::from_fn(4, 3, |i, j| Luma([(i * 1000 + j * 20000 + 1) as u16]));
+        let mut bytes = Vec::new();
+        write_png(
+            &image.clone().into(),
+            &FrameParams::default(),
+            1.,
+            &mut bytes,
+        )
+        .unwrap();
+
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(decoded.to_luma16(), image);
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let params = FrameParams {
+            zoom: 1e-3,
+            max_iter: 1234,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        write_png(&RgbImage::new(2, 2).into(), &params, 34., &mut bytes).unwrap();
+
+        let metadata = read_metadata(bytes.as_slice()).unwrap();
+        assert_eq!(metadata.samples_per_pixel, Some(34.));
+        assert!(metadata.date.is_some());
+        let ParamsKind::Frame(decoded) = ron::from_str(&metadata.params).unwrap() else {
+            panic!("expected frame parameters");
+        };
+        assert_eq!((decoded.zoom, decoded.max_iter), (1e-3, 1234));
+    }
+
+    #[test]
+    fn utc_dates() {
+        assert_eq!(format_utc_date(UNIX_EPOCH), "1970-01-01 00:00 UTC");
+        assert_eq!(
+            format_utc_date(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            "2023-11-14 22:13 UTC"
+        );
+        assert_eq!(
+            format_utc_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29 00:00 UTC"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorKind, Result};
+
+/// Layout of the tiles of a poster, written next to them so that
+/// they can be stitched together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PosterManifest {
+    /// Size of the whole poster.
+    pub img_width: u32,
+    pub img_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub tiles: Vec<PosterTile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PosterTile {
+    /// Row and column of the tile, from 1.
+    pub row: u32,
+    pub column: u32,
+    /// File name of the tile, in the directory of the manifest.
+    pub path: PathBuf,
+    /// Position of the top left pixel of the tile in the poster.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PosterTile {
+    pub fn columns(&self) -> Range<u32> {
+        self.x..self.x + self.width
+    }
+
+    pub fn rows(&self) -> Range<u32> {
+        self.y..self.y + self.height
+    }
+}
+
+impl PosterManifest {
+    /// Splits an image into `columns` by `rows` tiles whose sizes
+    /// differ by at most one pixel, the tiles being named after
+    /// `output_path` (`poster.png` gives `poster_1_1.png`,
+    /// `poster_1_2.png`...).
+    pub fn new(
+        img_width: u32,
+        img_height: u32,
+        columns: u32,
+        rows: u32,
+        output_path: &Path,
+    ) -> Self {
+        let split = |len: u32, count: u32, i: u32| {
+            let bound = |i: u32| (i as u64 * len as u64 / count as u64) as u32;
+            bound(i)..bound(i + 1)
+        };
+        let stem = output_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let extension = output_path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        let tiles = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let x = split(img_width, columns, column);
+                let y = split(img_height, rows, row);
+                PosterTile {
+                    row: row + 1,
+                    column: column + 1,
+                    path: PathBuf::from(format!(
+                        "{}_{}_{}.{}",
+                        stem,
+                        row + 1,
+                        column + 1,
+                        extension
+                    )),
+                    x: x.start,
+                    y: y.start,
+                    width: x.len() as u32,
+                    height: y.len() as u32,
+                }
+            })
+            .collect();
+
+        PosterManifest {
+            img_width,
+            img_height,
+            columns,
+            rows,
+            tiles,
+        }
+    }
+}
+
+/// Manifest of the poster rendered to `output_path`, next to its
+/// tiles.
+pub fn manifest_path(output_path: &Path) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    output_path.with_file_name(format!("{}.poster.ron", stem))
+}
+
+pub fn write_manifest(manifest: &PosterManifest, path: &Path) -> Result<()> {
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(manifest, PrettyConfig::default())
+            .map_err(ErrorKind::EncodeParameterFile)?,
+    )
+    .map_err(ErrorKind::WritePosterManifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::RgbImage;
+
+    use crate::{
+        coloring::{color_raw_image, color_raw_region, ColoringMode, Extremum, MapValue},
+        mat::Mat2D,
+        params::FrameParams,
+        rendering::{region_params, render_raw_image, render_raw_image_region},
+        F,
+    };
+
+    #[test]
+    fn tiles_cover_the_poster() {
+        let manifest = PosterManifest::new(33, 21, 3, 2, Path::new("out/poster.png"));
+        assert_eq!(manifest.tiles.len(), 6);
+        assert_eq!(manifest.tiles[4].path, PathBuf::from("poster_2_2.png"));
+
+        let mut covered = Mat2D::filled_with(0, 33, 21);
+        for tile in &manifest.tiles {
+            assert_eq!(tile.width, 11);
+            assert!((10..=11).contains(&tile.height));
+            for j in tile.rows() {
+                for i in tile.columns() {
+                    covered[(i as usize, j as usize)] += 1;
+                }
+            }
+        }
+        assert!(covered.vec.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn tiles_stitch_into_the_single_render() {
+        for supersample in [None, Some(2)] {
+            let params = FrameParams {
+                img_width: 37,
+                img_height: 23,
+                max_iter: 100,
+                rotate: Some(0.4),
+                seed: Some(5),
+                supersample,
+                // Tiles are colored with the extrema of the frame.
+                coloring_mode: ColoringMode::MinMaxNorm {
+                    min: Extremum::Custom(0.),
+                    max: Extremum::Custom(100.),
+                    map: MapValue::Linear,
+                },
+                dither: Some(true),
+                ..Default::default()
+            };
+            let sampling_points = params.sampling.generate_sampling_points();
+            let (single, _) = render_raw_image(&params, &sampling_points, None, None);
+
+            let manifest = PosterManifest::new(37, 23, 2, 2, Path::new("poster.png"));
+            let mut stitched = Mat2D::filled_with(F::NAN, 37, 23);
+            let mut stitched_colors = RgbImage::new(37, 23);
+            for tile in &manifest.tiles {
+                let (raw_image, _, _) = render_raw_image_region(
+                    &params,
+                    &sampling_points,
+                    tile.columns(),
+                    tile.rows(),
+                    None,
+                    None,
+                );
+                assert_eq!(
+                    (raw_image.width, raw_image.height),
+                    (tile.width as usize, tile.height as usize)
+                );
+                for j in 0..raw_image.height {
+                    for i in 0..raw_image.width {
+                        stitched[(tile.x as usize + i, tile.y as usize + j)] = raw_image[(i, j)];
+                    }
+                }
+
+                let colors = color_raw_region(
+                    &region_params(&params, &tile.columns(), &tile.rows()),
+                    raw_image,
+                    None,
+                    (tile.x as usize, tile.y as usize),
+                );
+                for (i, j, &color) in colors.enumerate_pixels() {
+                    stitched_colors.put_pixel(tile.x + i, tile.y + j, color);
+                }
+            }
+
+            assert_eq!(stitched, single, "supersample {supersample:?}");
+            assert_eq!(
+                stitched_colors,
+                color_raw_image(&params, single),
+                "supersample {supersample:?}"
+            );
+        }
+    }
+}
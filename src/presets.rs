@@ -1,3 +1,15 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ron::ser::PrettyConfig;
+
+use crate::{
+    error::{ErrorKind, Result},
+    params::{FrameParams, ParamsKind},
+};
+
 pub const PRESETS: &[(&str, &str)] = &[
     ("cyggmf", include_str!("../presets/cyggmf.ron")),
     ("kajan-unmyai", include_str!("../presets/kajan-unmyai.ron")),
@@ -6,3 +18,109 @@ pub const PRESETS: &[(&str, &str)] = &[
     ("utxwso", include_str!("../presets/utxwso.ron")),
     ("yvajbc", include_str!("../presets/yvajbc.ron")),
 ];
+
+/// Presets saved from the gui, by name.
+pub type UserPresets = Vec<(String, FrameParams)>;
+
+/// Config directory of the gui: `$XDG_CONFIG_HOME/fractal_rndr`,
+/// falling back to `~/.config` and then to `%APPDATA%` for
+/// `$XDG_CONFIG_HOME`.
+pub fn config_dir() -> Result<PathBuf> {
+    let var = |name| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let config_dir = var("XDG_CONFIG_HOME")
+        .or_else(|| var("HOME").map(|home| home.join(".config")))
+        .or_else(|| var("APPDATA"))
+        .ok_or(ErrorKind::MissingConfigDir)?;
+
+    Ok(config_dir.join("fractal_rndr"))
+}
+
+/// Directory of the presets saved from the gui, in the
+/// [`config_dir`].
+pub fn user_presets_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("presets"))
+}
+
+/// Decodes a preset, only frame parameters can be used as presets.
+pub fn decode_preset(s: &str) -> std::result::Result<FrameParams, String> {
+    match ParamsKind::from_ron(s) {
+        Ok(ParamsKind::Frame(params)) => match params.validate() {
+            Ok(()) => Ok(*params),
+            Err(e) => Err(format!("{:?}", e).trim().to_string()),
+        },
+        Ok(ParamsKind::Animation(_)) => Err("animation parameters can't be a preset".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Reads the presets of `dir`, sorted by name. Malformed preset
+/// files are skipped and their errors returned alongside the
+/// presets.
+pub fn load_user_presets(dir: &Path) -> Result<(UserPresets, Vec<ErrorKind>)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+        Err(e) => return Err(ErrorKind::ReadPreset(e)),
+    };
+
+    let mut presets = Vec::new();
+    let mut errors = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(ErrorKind::ReadPreset)?.path();
+        if path.extension().is_none_or(|ext| ext != "ron") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| decode_preset(&s))
+        {
+            Ok(params) => presets.push((name.to_string(), params)),
+            Err(reason) => errors.push(ErrorKind::DecodePreset(path.display().to_string(), reason)),
+        }
+    }
+    presets.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok((presets, errors))
+}
+
+/// Preset names are used as file names.
+pub fn check_preset_name(name: &str) -> Result<()> {
+    let valid_name = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '));
+    if valid_name {
+        Ok(())
+    } else {
+        Err(ErrorKind::InvalidPresetName(name.to_string()))
+    }
+}
+
+/// Writes a preset to `dir`, replacing the preset with the same
+/// name if any.
+pub fn save_user_preset(dir: &Path, name: &str, params: &FrameParams) -> Result<PathBuf> {
+    check_preset_name(name)?;
+
+    fs::create_dir_all(dir).map_err(ErrorKind::WritePreset)?;
+    let path = dir.join(format!("{}.ron", name));
+    fs::write(
+        &path,
+        ron::ser::to_string_pretty(
+            &ParamsKind::Frame(Box::new(params.clone())),
+            PrettyConfig::default(),
+        )
+        .map_err(ErrorKind::EncodeParameterFile)?,
+    )
+    .map_err(ErrorKind::WritePreset)?;
+
+    Ok(path)
+}
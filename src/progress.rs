@@ -1,22 +1,59 @@
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 #[derive(Debug, Clone)]
 pub struct Progress {
     progress: Arc<AtomicUsize>,
     pub total: usize,
+    estimate: Arc<Mutex<Option<Estimate>>>,
+    /// Duration the work is expected to take before any progress,
+    /// see [`Progress::with_expected_duration`].
+    expected: Option<Duration>,
+}
+
+/// Last estimate of the remaining time and when it was made.
+#[derive(Debug, Clone, Copy)]
+struct Estimate {
+    remaining_secs: f64,
+    elapsed: Duration,
 }
 
 impl Progress {
+    /// Time constant of the moving average of the estimate, the
+    /// larger the smoother (and the slower to react) it is.
+    const SMOOTHING: Duration = Duration::from_secs(5);
+    /// No estimate is given before this progress nor before this
+    /// much time has elapsed, as the rate is then meaningless.
+    const MIN_PROGRESS: f32 = 0.01;
+    const MIN_ELAPSED: Duration = Duration::from_millis(500);
+
     pub fn new(total: usize) -> Self {
         Progress {
             progress: Arc::new(AtomicUsize::new(0)),
             total,
+            estimate: Arc::new(Mutex::new(None)),
+            expected: None,
         }
     }
 
+    /// Sets the duration the work is expected to take, given by a
+    /// model of the speed of the machine. It is counted down while
+    /// there is not enough progress to tell, then it is the first
+    /// estimate the measured ones are averaged with.
+    pub fn with_expected_duration(mut self, expected: Duration) -> Self {
+        self.expected = Some(expected);
+        *self.estimate.lock().unwrap() = Some(Estimate {
+            remaining_secs: expected.as_secs_f64(),
+            elapsed: Duration::ZERO,
+        });
+        self
+    }
+
     pub fn incr(&self) {
         // Using atomic::Ordering::Relaxed because we don't really
         // care about the order `progress` is updated. As long as it
@@ -24,6 +61,17 @@ impl Progress {
         self.progress.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn add(&self, n: usize) {
+        self.progress.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Sets the progress to the sum of the progresses of `parts`,
+    /// for a combined readout of renders that have their own.
+    pub fn set_sum(&self, parts: &[Progress]) {
+        self.progress
+            .store(parts.iter().map(Progress::get).sum(), Ordering::Relaxed);
+    }
+
     pub fn get(&self) -> usize {
         self.progress.load(Ordering::Relaxed)
     }
@@ -31,4 +79,70 @@ impl Progress {
     pub fn get_progress(&self) -> f32 {
         self.get() as f32 / self.total as f32
     }
+
+    /// Estimates the time left given the time the work started at.
+    /// Meant to be called periodically, `None` is returned while
+    /// there is not enough progress to tell, unless the expected
+    /// duration is known.
+    ///
+    /// The average rate since `start` is used rather than the recent
+    /// one because some parts of an image are much slower to render
+    /// than others. The estimate is then smoothed with an
+    /// exponential moving average, the previous estimate counting
+    /// down in the meantime.
+    pub fn remaining_time(&self, start: Instant) -> Option<Duration> {
+        let elapsed = start.elapsed();
+        let done = self.get();
+        if self.get_progress() < Self::MIN_PROGRESS || elapsed < Self::MIN_ELAPSED {
+            return self
+                .expected
+                .map(|expected| expected.saturating_sub(elapsed));
+        }
+
+        let current = elapsed.as_secs_f64() * self.total.saturating_sub(done) as f64 / done as f64;
+
+        let mut estimate = self.estimate.lock().unwrap();
+        let remaining_secs = match *estimate {
+            None => current,
+            Some(previous) => {
+                let dt = elapsed.saturating_sub(previous.elapsed).as_secs_f64();
+                let predicted = (previous.remaining_secs - dt).max(0.);
+                let alpha = 1. - (-dt / Self::SMOOTHING.as_secs_f64()).exp();
+                predicted + alpha * (current - predicted)
+            }
+        };
+        *estimate = Some(Estimate {
+            remaining_secs,
+            elapsed,
+        });
+
+        Some(Duration::from_secs_f64(remaining_secs))
+    }
+}
+
+/// Formats an estimate of [`Progress::remaining_time`], for instance
+/// "~12s remaining".
+pub fn format_remaining_time(remaining: Option<Duration>) -> String {
+    match remaining {
+        Some(remaining) => format!("~{:.0}s remaining", remaining.as_secs_f32()),
+        None => "estimating...".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expected_duration_counts_down_before_any_progress() {
+        let start = Instant::now();
+        assert!(Progress::new(100).remaining_time(start).is_none());
+
+        let remaining = Progress::new(100)
+            .with_expected_duration(Duration::from_secs(10))
+            .remaining_time(start)
+            .unwrap();
+        assert!(remaining <= Duration::from_secs(10));
+        assert!(remaining > Duration::from_secs(9));
+    }
 }
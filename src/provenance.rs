@@ -0,0 +1,315 @@
+use std::{fmt::Display, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::params::{AnimationParams, FrameParams, ParamsKind};
+
+/// Where the value of a parameter comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    Preset(String),
+    File(PathBuf),
+    Stdin,
+    Cli,
+    GuiEdit,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Preset(name) => write!(f, "preset {}", name),
+            Source::File(path) => write!(f, "file {}", path.display()),
+            Source::Stdin => write!(f, "standard input"),
+            Source::Cli => write!(f, "command line"),
+            Source::GuiEdit => write!(f, "gui edit"),
+        }
+    }
+}
+
+/// Keeps track of the source of the value of each parameter
+/// field.
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    /// (field name, RON value when last recorded, source) in
+    /// declaration order.
+    fields: Vec<(&'static str, String, Source)>,
+}
+
+impl Provenance {
+    pub fn new(params: &impl ParamFields) -> Self {
+        let mut provenance = Provenance::default();
+        provenance.record(params, Source::Default);
+        provenance
+    }
+
+    /// Credits `source` for every field whose value changed since
+    /// the last time the parameters were recorded.
+    pub fn record(&mut self, params: &impl ParamFields, source: Source) {
+        self.fields = params
+            .fields()
+            .into_iter()
+            .map(|(name, value)| {
+                let last = self
+                    .fields
+                    .iter()
+                    .find(|(last_name, last_value, _)| *last_name == name && *last_value == value);
+                let source =
+                    last.map_or_else(|| source.clone(), |(_, _, last_source)| last_source.clone());
+                (name, value, source)
+            })
+            .collect();
+    }
+
+    /// Iterates over (field name, value, source).
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str, &Source)> {
+        self.fields
+            .iter()
+            .map(|(name, value, source)| (*name, value.as_str(), source))
+    }
+}
+
+/// Applies a layer of parameters (a file, command line options,
+/// etc.) and records the fields it changed in `provenance`.
+pub fn apply_layer<T: ParamFields>(
+    params: &mut T,
+    provenance: &mut Provenance,
+    source: Source,
+    layer: impl FnOnce(&mut T),
+) {
+    layer(params);
+    provenance.record(params, source);
+}
+
+pub trait ParamFields {
+    /// Lists the fields along with their RON representation.
+    fn fields(&self) -> Vec<(&'static str, String)>;
+}
+
+fn to_ron(value: &impl Serialize) -> String {
+    ron::to_string(value).unwrap_or_default()
+}
+
+impl ParamFields for ParamsKind {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            ParamsKind::Frame(params) => params.fields(),
+            ParamsKind::Animation(params) => params.fields(),
+        }
+    }
+}
+
+impl ParamFields for FrameParams {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let FrameParams {
+            img_width,
+            img_height,
+            zoom,
+            center_x,
+            center_y,
+            rotate,
+            aspect,
+            fractal,
+            max_iter,
+            auto_max_iter,
+            value,
+            coloring_mode,
+            gradient,
+            gradient_path,
+            gradient_offset,
+            gradient_repeat,
+            dither,
+            boundary_band,
+            tone_curve,
+            interior_mode,
+            sampling,
+            supersample,
+            seed,
+            passes,
+            save_scale,
+            raw_output,
+            threads,
+            dev_options,
+        } = self;
+
+        vec![
+            ("img_width", to_ron(img_width)),
+            ("img_height", to_ron(img_height)),
+            ("zoom", to_ron(zoom)),
+            ("center_x", to_ron(center_x)),
+            ("center_y", to_ron(center_y)),
+            ("rotate", to_ron(rotate)),
+            ("aspect", to_ron(aspect)),
+            ("fractal", to_ron(fractal)),
+            ("max_iter", to_ron(max_iter)),
+            ("auto_max_iter", to_ron(auto_max_iter)),
+            ("value", to_ron(value)),
+            ("coloring_mode", to_ron(coloring_mode)),
+            ("gradient", to_ron(gradient)),
+            ("gradient_path", to_ron(gradient_path)),
+            ("gradient_offset", to_ron(gradient_offset)),
+            ("gradient_repeat", to_ron(gradient_repeat)),
+            ("dither", to_ron(dither)),
+            ("boundary_band", to_ron(boundary_band)),
+            ("tone_curve", to_ron(tone_curve)),
+            ("interior_mode", to_ron(interior_mode)),
+            ("sampling", to_ron(sampling)),
+            ("supersample", to_ron(supersample)),
+            ("seed", to_ron(seed)),
+            ("passes", to_ron(passes)),
+            ("save_scale", to_ron(save_scale)),
+            ("raw_output", to_ron(raw_output)),
+            ("threads", to_ron(threads)),
+            ("dev_options", to_ron(dev_options)),
+        ]
+    }
+}
+
+impl ParamFields for AnimationParams {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let AnimationParams {
+            img_width,
+            img_height,
+            zoom,
+            center_x,
+            center_y,
+            zoom_sequence,
+            rotate,
+            aspect,
+            fractal,
+            max_iter,
+            auto_max_iter,
+            value,
+            duration,
+            fps,
+            frames_in_flight,
+            coloring_mode,
+            coloring_min,
+            coloring_max,
+            gradient,
+            gradient_path,
+            gradient_offset,
+            gradient_repeat,
+            map_parameter,
+            dither,
+            boundary_band,
+            tone_curve,
+            interior_mode,
+            sampling,
+            supersample,
+            seed,
+            save_scale,
+            gif_local_palettes,
+            threads,
+            dev_options,
+        } = self;
+
+        vec![
+            ("img_width", to_ron(img_width)),
+            ("img_height", to_ron(img_height)),
+            ("zoom", to_ron(zoom)),
+            ("center_x", to_ron(center_x)),
+            ("center_y", to_ron(center_y)),
+            ("zoom_sequence", to_ron(zoom_sequence)),
+            ("rotate", to_ron(rotate)),
+            ("aspect", to_ron(aspect)),
+            ("fractal", to_ron(fractal)),
+            ("max_iter", to_ron(max_iter)),
+            ("auto_max_iter", to_ron(auto_max_iter)),
+            ("value", to_ron(value)),
+            ("duration", to_ron(duration)),
+            ("fps", to_ron(fps)),
+            ("frames_in_flight", to_ron(frames_in_flight)),
+            ("coloring_mode", to_ron(coloring_mode)),
+            ("coloring_min", to_ron(coloring_min)),
+            ("coloring_max", to_ron(coloring_max)),
+            ("gradient", to_ron(gradient)),
+            ("gradient_path", to_ron(gradient_path)),
+            ("gradient_offset", to_ron(gradient_offset)),
+            ("gradient_repeat", to_ron(gradient_repeat)),
+            ("map_parameter", to_ron(map_parameter)),
+            ("dither", to_ron(dither)),
+            ("boundary_band", to_ron(boundary_band)),
+            ("tone_curve", to_ron(tone_curve)),
+            ("interior_mode", to_ron(interior_mode)),
+            ("sampling", to_ron(sampling)),
+            ("supersample", to_ron(supersample)),
+            ("seed", to_ron(seed)),
+            ("save_scale", to_ron(save_scale)),
+            ("gif_local_palettes", to_ron(gif_local_palettes)),
+            ("threads", to_ron(threads)),
+            ("dev_options", to_ron(dev_options)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_of<'a>(provenance: &'a Provenance, field: &str) -> &'a Source {
+        provenance
+            .iter()
+            .find(|&(name, _, _)| name == field)
+            .map(|(_, _, source)| source)
+            .unwrap()
+    }
+
+    #[test]
+    fn later_layers_take_precedence() {
+        let mut params = ParamsKind::default();
+        let mut provenance = Provenance::new(&params);
+        let file = Source::File(PathBuf::from("frame.ron"));
+
+        apply_layer(&mut params, &mut provenance, file.clone(), |params| {
+            if let ParamsKind::Frame(params) = params {
+                params.zoom = 5.;
+                params.max_iter = 500;
+            }
+        });
+        apply_layer(&mut params, &mut provenance, Source::Cli, |params| {
+            if let ParamsKind::Frame(params) = params {
+                params.max_iter = 1000;
+            }
+        });
+
+        assert_eq!(source_of(&provenance, "zoom"), &file);
+        assert_eq!(source_of(&provenance, "max_iter"), &Source::Cli);
+        assert_eq!(source_of(&provenance, "center_x"), &Source::Default);
+    }
+
+    #[test]
+    fn unchanged_values_keep_their_source() {
+        let mut params = ParamsKind::default();
+        let mut provenance = Provenance::new(&params);
+
+        apply_layer(&mut params, &mut provenance, Source::Stdin, |params| {
+            if let ParamsKind::Frame(params) = params {
+                params.zoom = 5.;
+            }
+        });
+        // Setting the same value again does not change its source.
+        apply_layer(&mut params, &mut provenance, Source::Cli, |params| {
+            if let ParamsKind::Frame(params) = params {
+                params.zoom = 5.;
+            }
+        });
+        assert_eq!(source_of(&provenance, "zoom"), &Source::Stdin);
+
+        // Setting it back to the default value is still a change.
+        apply_layer(&mut params, &mut provenance, Source::GuiEdit, |params| {
+            if let ParamsKind::Frame(params) = params {
+                params.zoom = 10.;
+            }
+        });
+        assert_eq!(source_of(&provenance, "zoom"), &Source::GuiEdit);
+        assert_eq!(
+            provenance
+                .iter()
+                .find(|&(name, _, _)| name == "zoom")
+                .map(|(_, value, _)| value),
+            Some("10.0")
+        );
+    }
+}
@@ -1,116 +1,1454 @@
-use std::{array, sync::mpsc};
+use std::{
+    array,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Once, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use rayon::prelude::*;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 
 use crate::{
     complexx::Complexx, fractal::Fractal, mat::Mat2D, params::FrameParams, progress::Progress,
-    sampling::map_points_with_offsets, F, FX,
+    rendering::perturbation::ReferenceOrbit, F, FX,
 };
 
+/// Renders the raw image, also returning the average number of
+/// samples taken per pixel.
+///
+/// When `params.seed` is set, the random offsets only depend on it
+/// and on the pixel position so that the render is reproducible.
+///
+/// When `cancel` is set during the render, the remaining tiles are
+/// skipped and the returned image is incomplete.
 pub fn render_raw_image(
     params: &FrameParams,
     sampling_points: &[(F, F)],
     progress: Option<Progress>,
-) -> Mat2D<F> {
-    let &FrameParams {
-        img_width,
-        img_height,
+    cancel: Option<&AtomicBool>,
+) -> (Mat2D<F>, F) {
+    render_raw_image_rows(
+        params,
+        sampling_points,
+        0..params.img_height,
+        progress,
+        cancel,
+    )
+}
+
+/// Same as [`render_raw_image`], also rendering the channels of
+/// the last points of the orbits when the coloring mode needs them.
+pub fn render_raw_image_channels(
+    params: &FrameParams,
+    sampling_points: &[(F, F)],
+    progress: Option<Progress>,
+    cancel: Option<&AtomicBool>,
+) -> (Mat2D<F>, Option<RawChannels>, F) {
+    render_raw_image_region(
+        params,
+        sampling_points,
+        0..params.img_width,
+        0..params.img_height,
+        progress,
+        cancel,
+    )
+}
+
+/// Same as [`render_raw_image_channels`] for the pixels of the given
+/// columns and rows only. The view is the one of the whole image so
+/// that they get the same samples as in a full render, except with
+/// adaptive sampling whose additional samples depend on the
+/// neighbouring pixels.
+pub fn render_raw_image_region(
+    params: &FrameParams,
+    sampling_points: &[(F, F)],
+    columns: Range<u32>,
+    rows: Range<u32>,
+    progress: Option<Progress>,
+    cancel: Option<&AtomicBool>,
+) -> (Mat2D<F>, Option<RawChannels>, F) {
+    render_region(
+        params,
+        sampling_points,
+        columns,
+        rows,
+        progress,
+        cancel,
+        params.coloring_mode.needs_channels(),
+    )
+}
+
+/// Params whose view covers the given region of the image with the
+/// same pixel size, to color and save a region rendered with
+/// [`render_raw_image_region`].
+pub fn region_params(params: &FrameParams, columns: &Range<u32>, rows: &Range<u32>) -> FrameParams {
+    let view = View::new(params);
+    let (center_x, center_y) = view.pixel_to_complex(
+        (columns.start + columns.end) as F / 2.,
+        (rows.start + rows.end) as F / 2.,
+    );
 
-        zoom,
+    FrameParams {
+        img_width: columns.len() as u32,
+        img_height: rows.len() as u32,
+        zoom: params.zoom * columns.len() as F / params.img_width as F,
         center_x,
-        center_y,
-        rotate,
+        center_y: -center_y,
+        aspect: params.aspect.map(|aspect| {
+            aspect * (columns.len() as F / params.img_width as F)
+                / (rows.len() as F / params.img_height as F)
+        }),
+        ..params.clone()
+    }
+}
+
+/// Channels of the raw image used by two-channel coloring (see
+/// [`crate::coloring::ColoringMode::TwoChannel`]): the mean norm of
+/// the last points of the orbits and the mean cosine and sine of
+/// their argument, as angles can't be averaged directly.
+pub type RawChannels = Mat2D<[F; 3]>;
+
+/// Mixes the samples of another render of the same view into
+/// `raw_image` and `raw_channels`, weighting both by their number of
+/// samples per pixel. Channels only rendered by one of the two are
+/// kept as they are.
+///
+/// Values of `fractal` that can't be averaged (see
+/// [`Fractal::packs_values`]) keep the first samples taken.
+pub fn accumulate_samples(
+    fractal: &Fractal,
+    raw_image: &mut Mat2D<F>,
+    raw_channels: &mut Option<RawChannels>,
+    samples_per_pixel: F,
+    new_raw_image: &Mat2D<F>,
+    new_raw_channels: Option<RawChannels>,
+    added_sample_count: F,
+) {
+    if fractal.packs_values() {
+        if raw_channels.is_none() {
+            *raw_channels = new_raw_channels;
+        }
+        return;
+    }
+
+    let w1 = samples_per_pixel;
+    let w2 = added_sample_count;
+    for (x, y) in raw_image.enumerate() {
+        raw_image[(x, y)] = (w1 * raw_image[(x, y)] + w2 * new_raw_image[(x, y)]) / (w1 + w2);
+    }
+    match (raw_channels.as_mut(), new_raw_channels) {
+        (Some(raw_channels), Some(new_raw_channels)) => {
+            for (x, y) in raw_channels.enumerate() {
+                raw_channels[(x, y)] = array::from_fn(|k| {
+                    (w1 * raw_channels[(x, y)][k] + w2 * new_raw_channels[(x, y)][k]) / (w1 + w2)
+                });
+            }
+        }
+        (None, new_raw_channels @ Some(_)) => *raw_channels = new_raw_channels,
+        _ => {}
+    }
+}
+
+/// Total of the progress passed to [`render_raw_image_rows`] once
+/// the given rows are rendered: each pass counts every pixel once.
+pub fn progress_total(params: &FrameParams, rows: &Range<u32>) -> usize {
+    region_progress_total(params, &(0..params.img_width), rows)
+}
+
+/// Same as [`progress_total`] for the given columns of the rows.
+pub fn region_progress_total(
+    params: &FrameParams,
+    columns: &Range<u32>,
+    rows: &Range<u32>,
+) -> usize {
+    let factor = params.supersample.unwrap_or(1).max(1) as usize;
+    params.sampling.pass_count() * columns.len() * rows.len() * factor * factor
+}
+
+/// Renders the given range of rows of the image, row `rows.start`
+/// being the first row of the returned matrix.
+///
+/// With adaptive sampling, a base pass is rendered first, then
+/// additional samples are spread over the tiles depending on the
+/// variance of their values.
+pub fn render_raw_image_rows(
+    params: &FrameParams,
+    sampling_points: &[(F, F)],
+    rows: Range<u32>,
+    progress: Option<Progress>,
+    cancel: Option<&AtomicBool>,
+) -> (Mat2D<F>, F) {
+    let (raw_image, _, samples_per_pixel) = render_region(
+        params,
+        sampling_points,
+        0..params.img_width,
+        rows,
+        progress,
+        cancel,
+        false,
+    );
+    (raw_image, samples_per_pixel)
+}
+
+fn render_region(
+    params: &FrameParams,
+    sampling_points: &[(F, F)],
+    columns: Range<u32>,
+    rows: Range<u32>,
+    progress: Option<Progress>,
+    cancel: Option<&AtomicBool>,
+    channels: bool,
+) -> (Mat2D<F>, Option<RawChannels>, F) {
+    // Supersampled images are rendered larger and averaged down to
+    // the image size, they are colored afterwards like any other.
+    if let Some(factor @ 2..) = params.supersample {
+        let scaled_params = FrameParams {
+            img_width: params.img_width * factor,
+            img_height: params.img_height * factor,
+            supersample: None,
+            ..params.clone()
+        };
+        let scale = |range: Range<u32>| range.start * factor..range.end * factor;
+        let (raw_image, raw_channels, samples_per_pixel) = render_region(
+            &scaled_params,
+            sampling_points,
+            scale(columns),
+            scale(rows),
+            progress,
+            cancel,
+            channels,
+        );
+        let factor = factor as usize;
+        if params.fractal.packs_values() {
+            return (
+                center_downscale(&raw_image, factor),
+                raw_channels.map(|raw_channels| center_downscale(&raw_channels, factor)),
+                samples_per_pixel,
+            );
+        }
+        return (
+            box_downscale(&raw_image, factor, |a, b| a + b, |v, s| v * s),
+            raw_channels.map(|raw_channels| {
+                box_downscale(
+                    &raw_channels,
+                    factor,
+                    |a, b| array::from_fn(|k| a[k] + b[k]),
+                    |v, s| v.map(|c| c * s),
+                )
+            }),
+            samples_per_pixel * (factor * factor) as F,
+        );
+    }
+
+    let seed = params.seed;
+    let base_count = sampling_points.len();
+    let (base_sums, base_channel_sums) = sample_region(
+        params,
+        (columns.clone(), rows.clone()),
+        seed,
+        progress.as_ref(),
+        cancel,
+        channels,
+        |_, _| sampling_points,
+    );
+    let mut raw_image = Mat2D {
+        width: base_sums.width,
+        height: base_sums.height,
+        vec: base_sums
+            .vec
+            .iter()
+            .map(|&sums| weighted_mean(sums))
+            .collect(),
+    };
+    let mut raw_channels = base_channel_sums.as_ref().map(|channel_sums| Mat2D {
+        width: channel_sums.width,
+        height: channel_sums.height,
+        vec: channel_sums
+            .vec
+            .iter()
+            .zip(&base_sums.vec)
+            .map(|(c, &(_, weight))| c.map(|c| weighted_mean((c, weight))))
+            .collect(),
+    });
+
+    let additional_points = params.sampling.generate_additional_sampling_points();
+    if additional_points.is_empty() || params.fractal.packs_values() || is_cancelled(cancel) {
+        if let (Some(progress), false) = (progress.as_ref(), is_cancelled(cancel)) {
+            // The additional pass is counted in the total even when
+            // there is nothing to sample.
+            progress.add((params.sampling.pass_count() - 1) * raw_image.width * raw_image.height);
+        }
+        return (raw_image, raw_channels, base_count as F);
+    }
+
+    let (width, height) = (raw_image.width, raw_image.height);
+    let additional_counts = additional_sample_counts(&raw_image, additional_points.len());
+
+    // The additional pass uses its own seed, otherwise its offsets
+    // would be the same as the ones of the base pass.
+    let additional_seed = seed.map(|seed| mix_seed(seed, 1));
+    let (additional_sums, additional_channel_sums) = sample_region(
+        params,
+        (columns, rows),
+        additional_seed,
+        progress.as_ref(),
+        cancel,
+        channels,
+        |i, j| {
+            let count = additional_counts[(
+                i as usize / ADAPTIVE_TILE_SIZE,
+                j as usize / ADAPTIVE_TILE_SIZE,
+            )];
+            &additional_points[..count]
+        },
+    );
+
+    let mut sample_count = 0;
+    for (x, y) in raw_image.enumerate() {
+        let (base_sum, base_weight) = base_sums[(x, y)];
+        let (additional_sum, additional_weight) = additional_sums[(x, y)];
+        raw_image[(x, y)] =
+            weighted_mean((base_sum + additional_sum, base_weight + additional_weight));
+        if let (Some(raw_channels), Some(base_channel_sums), Some(additional_channel_sums)) = (
+            raw_channels.as_mut(),
+            &base_channel_sums,
+            &additional_channel_sums,
+        ) {
+            raw_channels[(x, y)] = array::from_fn(|k| {
+                weighted_mean((
+                    base_channel_sums[(x, y)][k] + additional_channel_sums[(x, y)][k],
+                    base_weight + additional_weight,
+                ))
+            });
+        }
+        sample_count +=
+            base_count + additional_counts[(x / ADAPTIVE_TILE_SIZE, y / ADAPTIVE_TILE_SIZE)];
+    }
+
+    (
+        raw_image,
+        raw_channels,
+        sample_count as F / (width * height) as F,
+    )
+}
+
+/// Size of the square tiles over which adaptive sampling spreads the
+/// additional samples.
+const ADAPTIVE_TILE_SIZE: usize = 8;
+
+/// Number of additional samples of each tile of the image, out of
+/// `additional_count`, depending on the standard deviation of the
+/// values of the tile.
+fn additional_sample_counts(raw_image: &Mat2D<F>, additional_count: usize) -> Mat2D<usize> {
+    let (width, height) = (raw_image.width, raw_image.height);
+    let tiles_x = width.div_ceil(ADAPTIVE_TILE_SIZE);
+    let tiles_y = height.div_ceil(ADAPTIVE_TILE_SIZE);
+
+    // Standard deviation of the values of each tile.
+    let mut deviations = Mat2D::filled_with(0., tiles_x, tiles_y);
+    for (tx, ty) in deviations.enumerate() {
+        let values = (ty * ADAPTIVE_TILE_SIZE..((ty + 1) * ADAPTIVE_TILE_SIZE).min(height))
+            .flat_map(|y| {
+                (tx * ADAPTIVE_TILE_SIZE..((tx + 1) * ADAPTIVE_TILE_SIZE).min(width))
+                    .map(move |x| (x, y))
+            })
+            .map(|(x, y)| raw_image[(x, y)])
+            .collect::<Vec<_>>();
+        let n = values.len() as F;
+        let mean = values.iter().sum::<F>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<F>() / n;
+        deviations[(tx, ty)] = variance.sqrt();
+    }
+
+    // The tile that varies the most gets all the additional
+    // samples, the others get a share proportional to their
+    // deviation.
+    let max_deviation = deviations.vec.iter().copied().fold(0., F::max);
+    Mat2D {
+        width: tiles_x,
+        height: tiles_y,
+        vec: deviations
+            .vec
+            .iter()
+            .map(|&d| {
+                if max_deviation > 0. {
+                    (additional_count as F * d / max_deviation).round() as usize
+                } else {
+                    0
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Averages each `factor` by `factor` block of pixels of `image`
+/// (whose size is a multiple of `factor`) into a single pixel.
+fn box_downscale<T: Copy + Send + Sync>(
+    image: &Mat2D<T>,
+    factor: usize,
+    add: impl Fn(T, T) -> T + Sync,
+    scale: impl Fn(T, F) -> T + Sync,
+) -> Mat2D<T> {
+    let (width, height) = (image.width / factor, image.height / factor);
+    let weight = 1. / (factor * factor) as F;
 
-        fractal,
+    Mat2D {
+        width,
+        height,
+        vec: (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let (x, y) = (index % width * factor, index / width * factor);
+                let sum = (y..y + factor)
+                    .flat_map(|j| (x..x + factor).map(move |i| image[(i, j)]))
+                    .reduce(&add)
+                    .unwrap();
+                scale(sum, weight)
+            })
+            .collect(),
+    }
+}
+
+/// Keeps the pixel at the center of each `factor` by `factor` block
+/// of pixels of `image`, for values that can't be averaged.
+fn center_downscale<T: Copy>(image: &Mat2D<T>, factor: usize) -> Mat2D<T> {
+    let (width, height) = (image.width / factor, image.height / factor);
+
+    Mat2D {
+        width,
+        height,
+        vec: (0..width * height)
+            .map(|index| {
+                let (x, y) = (index % width * factor, index / width * factor);
+                image[(x + factor / 2, y + factor / 2)]
+            })
+            .collect(),
+    }
+}
+
+#[inline]
+fn is_cancelled(cancel: Option<&AtomicBool>) -> bool {
+    cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+#[inline]
+fn weighted_mean((sum, weight): (F, F)) -> F {
+    if weight != 0. {
+        sum / weight
+    } else {
+        0.
+    }
+}
+
+/// Samples every pixel of the given columns and rows with the points
+/// returned by `pixel_points` and returns the weighted sum of the
+/// samples of each pixel along with the sum of their weights (see
+/// [`crate::sampling::ReconstructionFilter`]). Indices passed to
+/// `pixel_points` are relative to `columns.start` and `rows.start`.
+/// Tiles starting after `cancel` is set are left empty.
+///
+/// When `channels` is set, the weighted sums of the channels of
+/// [`RawChannels`] are returned as well.
+///
+/// Values that can't be averaged (see [`Fractal::packs_values`])
+/// are not summed, each pixel gets a single sample with a weight of
+/// 1 instead.
+#[allow(clippy::type_complexity)]
+fn sample_region<'a>(
+    params: &FrameParams,
+    (columns, rows): (Range<u32>, Range<u32>),
+    seed: Option<u64>,
+    progress: Option<&Progress>,
+    cancel: Option<&AtomicBool>,
+    channels: bool,
+    pixel_points: impl Fn(u32, u32) -> &'a [(F, F)] + Send + Sync,
+) -> (Mat2D<(F, F)>, Option<Mat2D<[F; 3]>>) {
+    let FrameParams {
+        ref fractal,
 
         max_iter,
+        value,
 
         sampling,
+
+        threads,
         ..
-    } = params;
+    } = *params;
+
+    let view = View::new(params);
+    let reference_orbit = ReferenceOrbit::for_view(fractal, value, &view, max_iter);
+    let filter = sampling.filter();
+    let packs_values = fractal.packs_values();
+
+    let (first_column, first_row) = (columns.start, rows.start);
+
+    let sample_pixel = |rng: &mut fastrand::Rng, i: u32, j: u32| {
+        let x = i as F;
+        let y = j as F;
+
+        let (offset_x, offset_y) = if sampling.random_offsets {
+            // Tiles are sampled in no particular order, so seeded
+            // renders derive an rng for each pixel.
+            if let Some(seed) = seed {
+                *rng = fastrand::Rng::with_seed(mix_seed(seed, ((j as u64) << 32) | i as u64));
+            }
+
+            #[cfg(feature = "force_f32")]
+            let v = (rng.f32(), rng.f32());
+            #[cfg(not(feature = "force_f32"))]
+            let v = (rng.f64(), rng.f64());
+
+            v
+        } else {
+            (0., 0.)
+        };
+        let sampling_points = pixel_points(i - first_column, j - first_row)
+            .iter()
+            .map(|&(dx, dy)| filter.map_point(dx, dy, offset_x, offset_y))
+            .collect::<Vec<_>>();
+
+        #[cfg(feature = "force_f32")]
+        const CHUNK_SIZE: usize = 8;
+        #[cfg(not(feature = "force_f32"))]
+        const CHUNK_SIZE: usize = 4;
+        let samples = sampling_points.chunks(CHUNK_SIZE).flat_map(|d| {
+            let l = d.len();
+            // Here we use `i % l` to avoid out of bounds error (when i < 4).
+            // When `i < 4`, the modulo operation will repeat the sample
+            // but as we use simd this is acceptable (the cost is the
+            // same whether it is computed along with the others or not).
+            let points: [(F, F); CHUNK_SIZE] = array::from_fn(|i| {
+                let (dx, dy, _) = d[i % l];
+                let (x, y) = (x + 0.5 + dx, y + 0.5 + dy);
+                if reference_orbit.is_some() {
+                    view.pixel_to_delta(x, y)
+                } else {
+                    view.pixel_to_complex(x, y)
+                }
+            });
+            let re = FX::from(points.map(|(re, _)| re));
+            let im = FX::from(points.map(|(_, im)| im));
+
+            let samples = match &reference_orbit {
+                Some(reference_orbit) => {
+                    reference_orbit.sample(Complexx { re, im }, max_iter, value, channels)
+                }
+                None => fractal.sample(Complexx { re, im }, max_iter, value, channels),
+            };
+
+            d.iter().enumerate().map(move |(i, &(_, _, weight))| {
+                let channel_values = if channels {
+                    let (sin, cos) = samples.final_arg[i].sin_cos();
+                    [samples.final_norm[i], cos, sin]
+                } else {
+                    [0.; 3]
+                };
+                (samples.value[i], weight, channel_values)
+            })
+        });
+
+        if packs_values {
+            // The pixel keeps the sample weighted the most by the
+            // filter (the first one when they are equal).
+            samples
+                .reduce(|best, sample| if sample.1 > best.1 { sample } else { best })
+                .map_or(((0., 0.), [0.; 3]), |(v, _, c)| ((v, 1.), c))
+        } else {
+            samples.fold(
+                ((0., 0.), [0.; 3]),
+                |((sum, weights), channel_sums), (v, w, c)| {
+                    (
+                        (sum + w * v, weights + w),
+                        array::from_fn(|k| channel_sums[k] + w * c[k]),
+                    )
+                },
+            )
+        }
+    };
+
+    // Neighbouring pixels take similar time and touch the same
+    // sampling points, so the image is split in square tiles which
+    // are each rendered by a single thread.
+    const TILE_SIZE: u32 = 32;
+    let tiles = rows
+        .clone()
+        .step_by(TILE_SIZE as usize)
+        .flat_map(|j| {
+            columns
+                .clone()
+                .step_by(TILE_SIZE as usize)
+                .map(move |i| (i, j))
+        })
+        .collect::<Vec<_>>();
+
+    let sample_tiles = || {
+        tiles
+            .par_iter()
+            .map_init(fastrand::Rng::new, |rng, &(i0, j0)| {
+                let width = TILE_SIZE.min(columns.end - i0);
+                let height = TILE_SIZE.min(rows.end - j0);
+
+                let mut tile = Mat2D::filled_with((0., 0.), width as usize, height as usize);
+                let mut channel_tile =
+                    channels.then(|| Mat2D::filled_with([0.; 3], width as usize, height as usize));
+                if is_cancelled(cancel) {
+                    return ((i0, j0), tile, channel_tile);
+                }
+
+                for j in 0..height {
+                    for i in 0..width {
+                        let (sums, channel_sums) = sample_pixel(rng, i0 + i, j0 + j);
+                        tile[(i as usize, j as usize)] = sums;
+                        if let Some(channel_tile) = channel_tile.as_mut() {
+                            channel_tile[(i as usize, j as usize)] = channel_sums;
+                        }
+
+                        if let Some(progress) = progress {
+                            progress.incr();
+                        }
+                    }
+                }
+
+                ((i0, j0), tile, channel_tile)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let tiles = match thread_pool(threads) {
+        Some(pool) => pool.install(sample_tiles),
+        None => sample_tiles(),
+    };
+
+    let mut sums = Mat2D::filled_with((0., 0.), columns.len(), rows.len());
+    let mut channel_sums = channels.then(|| Mat2D::filled_with([0.; 3], columns.len(), rows.len()));
+    for ((i0, j0), tile, channel_tile) in tiles {
+        for j in 0..tile.height {
+            for i in 0..tile.width {
+                let index = (
+                    (i0 - first_column) as usize + i,
+                    (j0 - first_row) as usize + j,
+                );
+                sums[index] = tile[(i, j)];
+                if let (Some(channel_sums), Some(channel_tile)) =
+                    (channel_sums.as_mut(), &channel_tile)
+                {
+                    channel_sums[index] = channel_tile[(i, j)];
+                }
+            }
+        }
+    }
+
+    (sums, channel_sums)
+}
+
+/// Renders the raw image and drops it, returning how long the
+/// render took and the total number of samples taken. Used to
+/// benchmark fractals.
+pub fn time_render(params: &FrameParams, sampling_points: &[(F, F)]) -> (Duration, F) {
+    let start = Instant::now();
+    let (raw_image, samples_per_pixel) = render_raw_image(params, sampling_points, None, None);
+    let elapsed = start.elapsed();
+
+    (
+        elapsed,
+        samples_per_pixel * (raw_image.width * raw_image.height) as F,
+    )
+}
+
+/// Derives a seed from another one and a value (splitmix64
+/// finalizer), so that close values give unrelated seeds.
+pub fn mix_seed(seed: u64, value: u64) -> u64 {
+    let mut z = seed ^ value.wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
 
-    let aspect_ratio = img_width as F / img_height as F;
+/// Thread pool used to render with the given number of threads. It
+/// is built the first time and reused by the next renders, which
+/// run once per adaptive pass and per preview of the gui. `None` is
+/// returned when all cores should be used, in which case the global
+/// rayon pool is used.
+pub fn thread_pool(threads: Option<usize>) -> Option<Arc<ThreadPool>> {
+    static CLAMP_WARNING: Once = Once::new();
+    static POOLS: Mutex<Vec<(usize, Arc<ThreadPool>)>> = Mutex::new(Vec::new());
 
-    let width = zoom;
-    let height = zoom / aspect_ratio;
-    let rotate = rotate.unwrap_or(0.);
+    let threads = threads.filter(|&n| n != 0)?;
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
 
-    let (cx, cy) = if matches!(fractal, Fractal::MoireTest) {
-        (0., 0.)
+    let threads = if threads > available {
+        CLAMP_WARNING.call_once(|| {
+            eprintln!(
+                "warning: {} threads requested but only {} cores are available, using {}",
+                threads, available, available
+            );
+        });
+        available
     } else {
-        (center_x, -center_y)
+        threads
     };
 
-    let mut raw_image = Mat2D::filled_with(0., img_width as usize, img_height as usize);
+    let mut pools = POOLS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, pool)) = pools.iter().find(|&&(n, _)| n == threads) {
+        return Some(pool.clone());
+    }
+    let pool = Arc::new(ThreadPoolBuilder::new().num_threads(threads).build().ok()?);
+    pools.push((threads, pool.clone()));
+    Some(pool)
+}
 
-    let rng = fastrand::Rng::new();
-    let (tx, rx) = mpsc::channel();
-    (0..img_height)
-        .flat_map(|j| (0..img_width).map(move |i| (i, j)))
-        .par_bridge()
-        .for_each_with((tx, rng), |(s, rng), (i, j)| {
-            let x = i as F;
-            let y = j as F;
+/// Maps pixel coordinates to points of the complex plane.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub img_width: u32,
+    pub img_height: u32,
 
-            let (offset_x, offset_y) = if sampling.random_offsets {
-                #[cfg(feature = "force_f32")]
-                let v = (rng.f32(), rng.f32());
-                #[cfg(not(feature = "force_f32"))]
-                let v = (rng.f64(), rng.f64());
+    /// Center of the view in the complex plane.
+    pub center: (F, F),
+    /// Size of the view in the complex plane: half the zoom wide,
+    /// and the width divided by the aspect ratio high.
+    pub size: (F, F),
+    /// Rotation (in radians) of the pixel grid about the view
+    /// center.
+    pub rotate: F,
+}
 
-                v
-            } else {
-                (0., 0.)
+impl View {
+    pub fn new(params: &FrameParams) -> Self {
+        let &FrameParams {
+            img_width,
+            img_height,
+
+            zoom,
+            center_x,
+            center_y,
+            rotate,
+            aspect,
+
+            ref fractal,
+            ..
+        } = params;
+
+        let aspect_ratio = aspect.unwrap_or(img_width as F / img_height as F);
+
+        let center = if matches!(fractal, Fractal::MoireTest) {
+            (0., 0.)
+        } else {
+            (center_x, -center_y)
+        };
+
+        View {
+            img_width,
+            img_height,
+            center,
+            size: (0.5 * zoom, 0.5 * zoom / aspect_ratio),
+            rotate: rotate.unwrap_or(0.),
+        }
+    }
+
+    /// Whether neighbouring pixels are too close for floating point
+    /// numbers to tell them apart around the view center, in which
+    /// case the image looks pixelated.
+    pub fn exceeds_precision(&self) -> bool {
+        let (cx, cy) = self.center;
+        let pixel_size = self.size.0 / self.img_width as F;
+        // Pixels need to be a few ulps apart to be resolved.
+        pixel_size < 4. * F::EPSILON * cx.abs().max(cy.abs()).max(1.)
+    }
+
+    /// Maps a (possibly fractional) pixel position to the complex
+    /// plane. The pixel grid is rotated about the image center so
+    /// that the view center stays in place whatever the rotation.
+    #[inline]
+    pub fn pixel_to_complex(&self, x: F, y: F) -> (F, F) {
+        let (cx, cy) = self.center;
+        let (dx, dy) = self.pixel_to_delta(x, y);
+        (cx + dx, cy + dy)
+    }
+
+    /// Offset from the view center of the point of a pixel position,
+    /// which keeps its precision however deep the zoom is.
+    #[inline]
+    pub fn pixel_to_delta(&self, x: F, y: F) -> (F, F) {
+        let (w, h) = self.size;
+
+        let dx = w * (x / self.img_width as F - 0.5);
+        let dy = h * (y / self.img_height as F - 0.5);
+
+        let (sin, cos) = self.rotate.sin_cos();
+        (dx * cos - dy * sin, dx * sin + dy * cos)
+    }
+}
+
+/// Perturbation for deep zooms into the Mandelbrot set: the orbit
+/// of the view center is computed once and pixels are iterated as
+/// deltas from it, which stay accurate long after the coordinates
+/// of neighbouring pixels can't be told apart.
+///
+/// The reference orbit is computed with the same floating point
+/// numbers as the rest, so the view center itself is limited to
+/// their precision.
+///
+/// Glitches, where a delta loses its precision or outlives the
+/// reference, are fixed by rebasing the lane on the spot rather than
+/// flagging its pixel to be recomputed with another reference: the
+/// other reference would have the same precision as the first one,
+/// and the lanes carry on in the same pass instead of needing a
+/// second one.
+pub mod perturbation {
+    use std::{array, mem::size_of};
+
+    use wide::{CmpEq, CmpLe, CmpLt};
+
+    use super::View;
+    use crate::{
+        complexx::Complexx,
+        fractal::{
+            EscapeTimeOrbit, FinalZOrbit, Fractal, Orbit, Samples, StripeAverageOrbit, ValueKind,
+        },
+        F, FX,
+    };
+
+    const BAILOUT: F = 4.;
+    const LANES: usize = size_of::<FX>() / size_of::<F>();
+
+    /// Whether deep zooms into the fractal are rendered with
+    /// perturbation, which doesn't iterate the derivatives needed by
+    /// distance estimates.
+    pub fn is_supported(fractal: &Fractal, value: ValueKind) -> bool {
+        matches!(fractal, Fractal::Mandelbrot) && value != ValueKind::DistanceEstimate
+    }
+
+    pub struct ReferenceOrbit {
+        points: Vec<(F, F)>,
+    }
+
+    impl ReferenceOrbit {
+        /// Computes the reference orbit when perturbation is needed,
+        /// that is when zooming into the Mandelbrot set beyond
+        /// floating point precision.
+        pub fn for_view(
+            fractal: &Fractal,
+            value: ValueKind,
+            view: &View,
+            max_iter: u32,
+        ) -> Option<Self> {
+            if !is_supported(fractal, value) || !view.exceeds_precision() {
+                return None;
+            }
+
+            Some(ReferenceOrbit::new(view.center, max_iter))
+        }
+
+        /// Computes the orbit of `(cx, cy)` until it escapes or
+        /// reaches `max_iter`.
+        fn new((cx, cy): (F, F), max_iter: u32) -> Self {
+            let mut points = Vec::with_capacity(max_iter as usize + 1);
+            let (mut x, mut y): (F, F) = (0., 0.);
+            for _ in 0..=max_iter {
+                points.push((x, y));
+                if x * x + y * y > BAILOUT {
+                    break;
+                }
+                (x, y) = (x * x - y * y + cx, 2. * x * y + cy);
+            }
+
+            ReferenceOrbit { points }
+        }
+
+        /// Samples the points at the given offsets from the view
+        /// center (see [`View::pixel_to_delta`]), same as
+        /// [`Fractal::sample`].
+        pub fn sample(
+            &self,
+            delta_c: Complexx,
+            max_iter: u32,
+            value: ValueKind,
+            final_z: bool,
+        ) -> Samples {
+            match (value, final_z) {
+                (ValueKind::EscapeTime, false) => {
+                    self.sample_orbits::<EscapeTimeOrbit>(delta_c, max_iter, value)
+                }
+                (ValueKind::EscapeTime, true) => {
+                    self.sample_orbits::<FinalZOrbit<EscapeTimeOrbit>>(delta_c, max_iter, value)
+                }
+                (ValueKind::StripeAverage { .. }, false) => {
+                    self.sample_orbits::<StripeAverageOrbit>(delta_c, max_iter, value)
+                }
+                (ValueKind::StripeAverage { .. }, true) => {
+                    self.sample_orbits::<FinalZOrbit<StripeAverageOrbit>>(delta_c, max_iter, value)
+                }
+                (ValueKind::DistanceEstimate, _) => {
+                    unreachable!("distance estimates are not rendered with perturbation")
+                }
+            }
+        }
+
+        /// Iterates `delta = 2 * z * delta + delta^2 + delta_c` where
+        /// `z` is the reference point each lane is at.
+        ///
+        /// A lane is glitched when its point gets closer to zero
+        /// than its delta is (the delta has then lost its precision)
+        /// or when the reference escaped before it. It is then
+        /// rebased: its point becomes its delta and it starts over
+        /// from the beginning of the reference orbit, which is zero.
+        #[inline]
+        fn sample_orbits<O: Orbit>(
+            &self,
+            delta_c: Complexx,
+            max_iter: u32,
+            value: ValueKind,
+        ) -> Samples {
+            let bailout_mask = FX::splat(BAILOUT);
+            let reference_at = |indices: &[usize; LANES]| {
+                let points: [(F, F); LANES] = array::from_fn(|k| self.points[indices[k]]);
+                Complexx {
+                    re: FX::from(points.map(|(re, _)| re)),
+                    im: FX::from(points.map(|(_, im)| im)),
+                }
             };
-            let sampling_points = sampling_points
-                .iter()
-                .map(|&(dx, dy)| map_points_with_offsets(dx, dy, offset_x, offset_y))
-                .collect::<Vec<_>>();
 
-            #[cfg(feature = "force_f32")]
-            const CHUNK_SIZE: usize = 8;
-            #[cfg(not(feature = "force_f32"))]
-            const CHUNK_SIZE: usize = 4;
-            let value = sampling_points
-                .chunks(CHUNK_SIZE)
-                .flat_map(|d| {
-                    let l = d.len();
-                    let re = FX::from(array::from_fn(|i| {
-                        // Here we use `i % l` to avoid out of bounds error (when i < 4).
-                        // When `i < 4`, the modulo operation will repeat the sample
-                        // but as we use simd this is acceptable (the cost is the
-                        // same whether it is computed along with the others or not).
-                        let (dx, _) = d[i % l];
-                        cx + 0.5 * width * ((x + 0.5 + dx) / img_width as F - 0.5)
-                    }));
-                    let im = FX::from(array::from_fn(|i| {
-                        let (_, dy) = d[i % l];
-                        cy + 0.5 * height * ((y + 0.5 + dy) / img_height as F - 0.5)
-                    }));
-
-                    let iter = {
-                        let c = Complexx::splat(cx, cy);
-                        fractal.sample(
-                            (Complexx { re, im } - c) * Complexx::from_polar_splat(1., rotate) + c,
-                            max_iter,
-                        )
+            let mut delta = Complexx::zeros();
+            let mut indices = [0; LANES];
+
+            let mut orbit = O::new(value, BAILOUT);
+            for _ in 0..max_iter {
+                let mut reference = reference_at(&indices);
+                let z = reference + delta;
+
+                let undiverged_mask = z.norm_sqr().cmp_le(bailout_mask);
+                if !undiverged_mask.any() {
+                    break;
+                }
+
+                let glitched = z.norm_sqr().cmp_lt(delta.norm_sqr()).move_mask();
+                for (k, index) in indices.iter_mut().enumerate() {
+                    if glitched & (1 << k) != 0 || *index + 1 == self.points.len() {
+                        *index = 0;
+                    }
+                }
+                // Rebasing lanes which are at the start already
+                // changes nothing.
+                let rebased = FX::from(indices.map(|index| index as F)).cmp_eq(FX::splat(0.));
+                delta = Complexx {
+                    re: rebased.blend(z.re, delta.re),
+                    im: rebased.blend(z.im, delta.im),
+                };
+                reference = Complexx {
+                    re: rebased.blend(FX::splat(0.), reference.re),
+                    im: rebased.blend(FX::splat(0.), reference.im),
+                };
+
+                delta = reference * delta * 2. + delta * delta + delta_c;
+                indices.iter_mut().for_each(|index| *index += 1);
+
+                orbit.add(undiverged_mask, reference_at(&indices) + delta);
+            }
+
+            let (value, final_z) = orbit.finish_with(reference_at(&indices) + delta);
+            Samples::new(value, O::TRACKS_FINAL_Z.then_some(final_z))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Escape times of the points at `deltas` from `center`,
+        /// sampled with perturbation, directly, and directly with the
+        /// real part of the points moved by one ulp.
+        fn escape_times(
+            center: (F, F),
+            deltas: &[(F, F)],
+            max_iter: u32,
+        ) -> (ReferenceOrbit, Vec<[F; 3]>) {
+            // Flipping the last bit moves by one ulp.
+            let moved = |x: F| F::from_bits(x.to_bits() ^ 1);
+            let reference = ReferenceOrbit::new(center, max_iter);
+            let times = deltas
+                .chunks(LANES)
+                .flat_map(|chunk| {
+                    let lanes = |f: &dyn Fn(F, F) -> (F, F)| {
+                        let values: [(F, F); LANES] = array::from_fn(|k| {
+                            let (dx, dy) = chunk[k % chunk.len()];
+                            f(dx, dy)
+                        });
+                        Complexx {
+                            re: FX::from(values.map(|(re, _)| re)),
+                            im: FX::from(values.map(|(_, im)| im)),
+                        }
                     };
+                    let delta_c = lanes(&|dx, dy| (dx, dy));
+                    let c = lanes(&|dx, dy| (center.0 + dx, center.1 + dy));
+                    let moved_c = lanes(&|dx, dy| (moved(center.0 + dx), center.1 + dy));
 
-                    (0..l).map(move |i| iter[i])
+                    let value = ValueKind::EscapeTime;
+                    let perturbed = reference.sample(delta_c, max_iter, value, false);
+                    let direct = Fractal::Mandelbrot.sample(c, max_iter, value, false);
+                    let moved = Fractal::Mandelbrot.sample(moved_c, max_iter, value, false);
+                    (0..chunk.len())
+                        .map(|k| [perturbed.value[k], direct.value[k], moved.value[k]])
+                        .collect::<Vec<_>>()
                 })
-                .sum::<F>()
-                / sampling_points.len() as F;
+                .collect();
+            (reference, times)
+        }
+
+        #[test]
+        fn perturbation_agrees_with_direct_sampling() {
+            let deltas = (0..1600)
+                .map(|k| {
+                    let (i, j) = ((k % 40) as F, (k / 40) as F);
+                    (1e-3 * (i / 20. - 1.), 1e-3 * (j / 20. - 1.))
+                })
+                .collect::<Vec<_>>();
+            let (_, times) = escape_times((-0.743643887037151, 0.131825904205330), &deltas, 1000);
+
+            // Close to the boundary, orbits are chaotic enough that
+            // rounding alone changes a few escape times, as much as
+            // moving the points by one ulp does.
+            let differing = |a: usize, b: usize| {
+                times
+                    .iter()
+                    .filter(|times| (times[a] - times[b]).abs() > 1e-3)
+                    .count()
+            };
+            let (perturbed, moved) = (differing(0, 1), differing(1, 2));
+            assert!(
+                perturbed <= 2 * moved + 4,
+                "{perturbed} points differ, {moved} when moved by one ulp"
+            );
+            assert!(times.iter().any(|times| times[0] < 1000.));
+            assert!(times.iter().any(|times| times[0] >= 1000.));
+        }
 
-            s.send(((i, j), value)).unwrap();
+        #[test]
+        fn glitched_lanes_are_rebased() {
+            // The reference escapes after a few iterations while the
+            // points stay bounded, so they outlive it.
+            let (reference, times) = escape_times((0.3, 0.), &[(-0.3, 0.), (-1.3, 0.)], 200);
+            assert!(reference.points.len() < 200);
+            for [perturbed, direct, _] in times {
+                assert_eq!(perturbed, direct);
+                assert_eq!(perturbed, 200.);
+            }
 
-            if let Some(progress) = &progress {
-                progress.incr();
+            // The reference goes back and forth between 0 and -1
+            // while the points stay close to zero, so they are closer
+            // to zero than their delta (about 1) is every other
+            // iteration.
+            let (reference, times) = escape_times((-1., 0.), &[(1., 0.), (0.9, 0.1)], 200);
+            assert_eq!(reference.points.len(), 201);
+            for [perturbed, direct, _] in times {
+                assert_eq!(perturbed, direct);
+                assert_eq!(perturbed, 200.);
             }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    use crate::sampling::{ReconstructionFilter, Sampling, SamplingLevel, SamplingPattern};
+
+    /// Small Mandelbrot render, quick enough for debug builds.
+    fn frame_params() -> FrameParams {
+        FrameParams {
+            img_width: 64,
+            img_height: 48,
+            center_x: -0.75,
+            center_y: 0.1,
+            zoom: 2.,
+            max_iter: 100,
+            ..Default::default()
+        }
+    }
+
+    fn render(params: &FrameParams) -> Mat2D<F> {
+        let sampling_points = params.sampling.generate_sampling_points();
+        render_raw_image(params, &sampling_points, None, None).0
+    }
+
+    fn view(rotate: Option<F>) -> View {
+        View::new(&FrameParams {
+            rotate,
+            ..frame_params()
+        })
+    }
+
+    /// Rounding error allowed on the coordinates of the views, which
+    /// are of the order of 1.
+    const TOLERANCE: F = 64. * F::EPSILON;
+
+    fn assert_close((a, b): (F, F), (c, d): (F, F)) {
+        assert!(
+            (a - c).abs() < TOLERANCE && (b - d).abs() < TOLERANCE,
+            "{:?} != {:?}",
+            (a, b),
+            (c, d)
+        );
+    }
+
+    #[test]
+    fn rotation_keeps_the_center_in_place() {
+        for rotate in [0.3, 1., PI as F, 5.] {
+            let view = view(Some(rotate));
+            assert_close(view.pixel_to_complex(32., 24.), view.center);
+        }
+    }
+
+    #[test]
+    fn opposite_rotations_cancel_out() {
+        let rotated = view(Some(0.7));
+        let unrotated = view(None);
+        for (x, y) in [(0., 0.), (10.5, 3.5), (63., 47.), (32., 0.)] {
+            let (dx, dy) = rotated.pixel_to_delta(x, y);
+            let (sin, cos) = (-rotated.rotate).sin_cos();
+            assert_close(
+                (dx * cos - dy * sin, dx * sin + dy * cos),
+                unrotated.pixel_to_delta(x, y),
+            );
+        }
+    }
+
+    #[test]
+    fn rotation_keeps_distances_to_the_center() {
+        let rotated = view(Some(2.));
+        let unrotated = view(None);
+        let norm = |(dx, dy): (F, F)| (dx * dx + dy * dy).sqrt();
+        for (x, y) in [(0., 0.), (10.5, 3.5), (63., 47.)] {
+            let (a, b) = (
+                norm(rotated.pixel_to_delta(x, y)),
+                norm(unrotated.pixel_to_delta(x, y)),
+            );
+            assert!((a - b).abs() < TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn renders_with_the_same_seed_are_equal() {
+        let params = FrameParams {
+            seed: Some(42),
+            ..frame_params()
+        };
+        assert_eq!(render(&params).vec, render(&params).vec);
+
+        let adaptive = FrameParams {
+            sampling: Sampling {
+                level: SamplingLevel::Adaptive { base: 4, max: 16 },
+                ..params.sampling
+            },
+            ..params.clone()
+        };
+        assert_eq!(render(&adaptive).vec, render(&adaptive).vec);
+
+        let other_seed = FrameParams {
+            seed: Some(43),
+            ..params.clone()
+        };
+        assert_ne!(render(&params).vec, render(&other_seed).vec);
+    }
+
+    /// Zone plate, whose values vary in every tile.
+    fn zone_plate_params(level: SamplingLevel) -> FrameParams {
+        let params = FrameParams {
+            img_width: 32,
+            img_height: 16,
+            zoom: 0.2,
+            fractal: Fractal::MoireTest,
+            max_iter: 10,
+            seed: Some(7),
+            ..Default::default()
+        };
+        FrameParams {
+            sampling: Sampling {
+                level,
+                ..params.sampling
+            },
+            ..params
+        }
+    }
+
+    #[test]
+    fn additional_samples_go_to_varying_tiles() {
+        // The left half of the zone plate is made flat.
+        let mut raw_image = render(&zone_plate_params(SamplingLevel::Exploration));
+        for (x, y) in raw_image.enumerate() {
+            if x < 16 {
+                raw_image[(x, y)] = 0.5;
+            }
+        }
+
+        let counts = additional_sample_counts(&raw_image, 12);
+        assert_eq!((counts.width, counts.height), (4, 2));
+        for (tx, ty) in counts.enumerate() {
+            if tx < 2 {
+                assert_eq!(counts[(tx, ty)], 0, "flat tile ({tx}, {ty})");
+            } else {
+                assert!(counts[(tx, ty)] > 0, "varying tile ({tx}, {ty})");
+            }
+        }
+        // The tile that varies the most gets all of them.
+        assert_eq!(counts.vec.iter().max(), Some(&12));
+
+        // A flat image gets none.
+        let flat = Mat2D::filled_with(0.5, 32, 16);
+        assert!(additional_sample_counts(&flat, 12)
+            .vec
+            .iter()
+            .all(|&c| c == 0));
+    }
+
+    #[test]
+    fn adaptive_samples_per_pixel_are_between_base_and_max() {
+        let params = zone_plate_params(SamplingLevel::Adaptive { base: 4, max: 16 });
+        let sampling_points = params.sampling.generate_sampling_points();
+        let (_, samples_per_pixel) = render_raw_image(&params, &sampling_points, None, None);
+        assert!(
+            4. < samples_per_pixel && samples_per_pixel < 16.,
+            "{samples_per_pixel}"
+        );
+    }
+
+    #[test]
+    fn newton_pixels_straddling_basins_keep_one_of_their_samples() {
+        use crate::fractal::{decode_newton_value, NEWTON_COEFFICIENT_COUNT};
+
+        // z^3 - 1, the pixel covers the three basins around 0.
+        let mut coefficients = [(0., 0.); NEWTON_COEFFICIENT_COUNT];
+        coefficients[0] = (-1., 0.);
+        coefficients[3] = (1., 0.);
+        let params = FrameParams {
+            img_width: 1,
+            img_height: 1,
+            center_x: 0.,
+            center_y: 0.,
+            zoom: 2.,
+            max_iter: 50,
+            fractal: Fractal::Newton { coefficients },
+            sampling: Sampling {
+                level: SamplingLevel::High,
+                random_offsets: false,
+                ..FrameParams::default().sampling
+            },
+            ..Default::default()
+        };
+        let sampling_points = params.sampling.generate_sampling_points();
+
+        let sample_values = sampling_points
+            .iter()
+            .map(|&point| render_raw_image(&params, &[point], None, None).0.vec[0])
+            .collect::<Vec<_>>();
+        let mut roots = sample_values
+            .iter()
+            .map(|&v| decode_newton_value(v, params.max_iter).0)
+            .collect::<Vec<_>>();
+        roots.sort();
+        roots.dedup();
+        assert!(roots.len() > 1, "the samples reach a single root");
+
+        let (raw_image, _) = render_raw_image(&params, &sampling_points, None, None);
+        assert!(sample_values.contains(&raw_image.vec[0]));
+
+        // Neither another pass nor supersampling mixes them.
+        let mut accumulated = raw_image.clone();
+        let other = Mat2D::filled_with(sample_values[0] + 2. * 51., 1, 1);
+        accumulate_samples(
+            &params.fractal,
+            &mut accumulated,
+            &mut None,
+            1.,
+            &other,
+            None,
+            1.,
+        );
+        assert_eq!(accumulated.vec, raw_image.vec);
+
+        let supersampled = FrameParams {
+            supersample: Some(2),
+            ..params.clone()
+        };
+        let scaled = FrameParams {
+            img_width: 2,
+            img_height: 2,
+            ..params.clone()
+        };
+        assert_eq!(
+            render_raw_image(&supersampled, &sampling_points, None, None)
+                .0
+                .vec[0],
+            render_raw_image(&scaled, &sampling_points, None, None).0[(1, 1)],
+        );
+    }
+
+    #[test]
+    fn thread_pools_are_reused() {
+        assert!(thread_pool(None).is_none());
+        assert!(thread_pool(Some(0)).is_none());
+        let pool = thread_pool(Some(1)).unwrap();
+        assert!(Arc::ptr_eq(&pool, &thread_pool(Some(1)).unwrap()));
+    }
+
+    #[test]
+    fn cancelled_renders_skip_the_remaining_tiles() {
+        let params = frame_params();
+        let sampling_points = params.sampling.generate_sampling_points();
+
+        let cancel = AtomicBool::new(true);
+        let (raw_image, _) = render_raw_image(&params, &sampling_points, None, Some(&cancel));
+        assert!(raw_image.vec.iter().all(|&v| v == 0.));
+
+        // Cancelled while rendering, as with Ctrl-C or the cancel
+        // button of the gui.
+        let params = FrameParams {
+            img_width: 256,
+            img_height: 256,
+            max_iter: 1000,
+            ..params
+        };
+        let progress = Progress::new(progress_total(&params, &(0..params.img_height)));
+        let cancel = AtomicBool::new(false);
+        let raw_image = thread::scope(|scope| {
+            scope.spawn(|| {
+                while progress.get() == 0 {
+                    thread::yield_now();
+                }
+                cancel.store(true, Ordering::Relaxed);
+            });
+            render_raw_image(
+                &params,
+                &sampling_points,
+                Some(progress.clone()),
+                Some(&cancel),
+            )
+            .0
         });
+        assert!(progress.get_progress() < 1.);
+        assert!(raw_image.vec.contains(&0.));
+        assert!(raw_image.vec.iter().any(|&v| v != 0.));
+    }
+
+    #[test]
+    fn progress_completes_at_the_end_of_the_render() {
+        let params = FrameParams {
+            img_width: 32,
+            img_height: 16,
+            max_iter: 50,
+            ..frame_params()
+        };
+        let levels = [
+            SamplingLevel::Raw,
+            SamplingLevel::Exploration,
+            SamplingLevel::Custom(13),
+            SamplingLevel::Adaptive { base: 4, max: 16 },
+        ];
+        for level in levels {
+            for supersample in [None, Some(2)] {
+                let params = FrameParams {
+                    sampling: Sampling {
+                        level,
+                        ..params.sampling
+                    },
+                    supersample,
+                    ..params.clone()
+                };
+                let sampling_points = params.sampling.generate_sampling_points();
 
-    for ((i, j), sample) in rx {
-        raw_image[(i as usize, j as usize)] = sample;
+                let progress = Progress::new(progress_total(&params, &(0..params.img_height)));
+                render_raw_image(&params, &sampling_points, Some(progress.clone()), None);
+                assert!(
+                    (progress.get_progress() - 1.).abs() < 0.02,
+                    "{level:?} {supersample:?}: {}",
+                    progress.get_progress()
+                );
+
+                let (columns, rows) = (8..24, 4..12);
+                let progress = Progress::new(region_progress_total(&params, &columns, &rows));
+                render_raw_image_region(
+                    &params,
+                    &sampling_points,
+                    columns,
+                    rows,
+                    Some(progress.clone()),
+                    None,
+                );
+                assert!((progress.get_progress() - 1.).abs() < 0.02);
+            }
+        }
     }
 
-    raw_image
+    /// Mean absolute difference between `a` and `b` rotated by 180°.
+    fn flipped_difference(a: &Mat2D<F>, b: &Mat2D<F>) -> F {
+        a.vec
+            .iter()
+            .zip(b.vec.iter().rev())
+            .map(|(a, b)| (a - b).abs())
+            .sum::<F>()
+            / a.vec.len() as F
+    }
+
+    #[test]
+    fn rotating_by_pi_flips_the_image_about_the_view_center() {
+        // Samples placed symmetrically about the pixel centers, so
+        // that flipping the image gives the same sampling points.
+        let params = FrameParams {
+            center_x: -0.7,
+            center_y: 0.25,
+            zoom: 1.5,
+            sampling: Sampling {
+                level: SamplingLevel::Custom(4),
+                random_offsets: false,
+                filter: Some(ReconstructionFilter::Box { radius: 0.5 }),
+                pattern: SamplingPattern::UniformGrid,
+            },
+            ..frame_params()
+        };
+        let unrotated = render(&params);
+        let rotated = render(&FrameParams {
+            rotate: Some(PI as F),
+            ..params.clone()
+        });
+
+        // Only pixels sitting exactly on the boundary may differ,
+        // by rounding. There are more of them with a lower precision.
+        let differing = unrotated
+            .vec
+            .iter()
+            .zip(rotated.vec.iter().rev())
+            .filter(|(a, b)| (*a - *b).abs() > TOLERANCE * params.max_iter as F)
+            .count();
+        let allowed = 2 + (unrotated.vec.len() as F * 10. * F::EPSILON.sqrt()) as usize;
+        assert!(differing <= allowed, "{differing} pixels differ");
+        // The image isn't symmetric about its center, so the
+        // comparison above tells a flip from an unrotated image.
+        assert!(flipped_difference(&unrotated, &unrotated) > 1.);
+    }
+
+    #[test]
+    fn rotating_by_pi_with_random_offsets() {
+        let params = FrameParams {
+            center_x: -0.7,
+            center_y: 0.25,
+            zoom: 1.5,
+            seed: Some(7),
+            ..frame_params()
+        };
+        let unrotated = render(&params);
+        let rotated = render(&FrameParams {
+            rotate: Some(PI as F),
+            ..params.clone()
+        });
+
+        let noise = flipped_difference(&unrotated, &rotated);
+        let misaligned = flipped_difference(&unrotated, &unrotated);
+        assert!(noise < misaligned / 5., "{noise} against {misaligned}");
+    }
 }
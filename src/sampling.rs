@@ -6,29 +6,41 @@ use crate::{
     F,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Sampling {
     pub level: SamplingLevel,
     pub random_offsets: bool,
+    /// Filter used to combine the samples of a pixel, defaults to
+    /// [`ReconstructionFilter::DEFAULT`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ReconstructionFilter>,
+    #[serde(default)]
+    pub pattern: SamplingPattern,
 }
 
 impl Sampling {
-    pub fn generate_sampling_points(&self) -> Vec<(F, F)> {
-        const PHI: F = 1.618033988749895;
-        const EPS: F = 0.5;
+    pub fn filter(&self) -> ReconstructionFilter {
+        self.filter.unwrap_or(ReconstructionFilter::DEFAULT)
+    }
 
-        let n = self.sample_count();
+    pub fn generate_sampling_points(&self) -> Vec<(F, F)> {
+        self.pattern.points(self.sample_count(), 0.)
+    }
 
-        (0..n)
-            .map(|i| {
-                (
-                    (i as F / PHI) % 1.,
-                    (i as F + EPS) / ((n - 1) as F + 2. * EPS),
-                )
-            })
-            .collect::<Vec<_>>()
+    /// Generates the points used to refine high variance regions
+    /// with adaptive sampling. They are shifted horizontally so that
+    /// they don't land on the base points.
+    pub fn generate_additional_sampling_points(&self) -> Vec<(F, F)> {
+        match self.level {
+            SamplingLevel::Adaptive { base, max } => {
+                self.pattern.points(max.saturating_sub(base), 0.5)
+            }
+            _ => Vec::new(),
+        }
     }
 
+    /// Number of samples taken per pixel, with adaptive sampling
+    /// this is the number of samples of the base pass.
     pub fn sample_count(&self) -> usize {
         match self.level {
             SamplingLevel::Raw => 1,
@@ -39,8 +51,100 @@ impl Sampling {
             SamplingLevel::Ultra => 89,
             SamplingLevel::Extreme => 144,
             SamplingLevel::Custom(n) => n,
+            SamplingLevel::Adaptive { base, .. } => base,
         }
     }
+
+    /// Number of passes over the image, used to size progress bars.
+    pub fn pass_count(&self) -> usize {
+        match self.level {
+            SamplingLevel::Adaptive { .. } => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Distribution of the sampling points in the unit square.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SamplingPattern {
+    /// Points spread along x by the golden ratio and evenly along y.
+    #[default]
+    GoldenRatio,
+    /// Halton sequence in bases 2 and 3.
+    Halton,
+    /// Points at the center of the cells of a grid.
+    UniformGrid,
+    /// Points placed randomly in the cells of a grid. The jitter is
+    /// the same for a given number of points so that seeded renders
+    /// stay reproducible.
+    JitteredGrid,
+}
+
+impl SamplingPattern {
+    /// Generates `n` points in range [0,1), shifted horizontally by
+    /// `shift`.
+    pub fn points(&self, n: usize, shift: F) -> Vec<(F, F)> {
+        match self {
+            SamplingPattern::GoldenRatio => golden_points(n, shift),
+            SamplingPattern::Halton => (1..=n)
+                .map(|i| ((radical_inverse(i, 2) + shift) % 1., radical_inverse(i, 3)))
+                .collect(),
+            SamplingPattern::UniformGrid => grid_points(n, shift, |_| (0.5, 0.5)),
+            SamplingPattern::JitteredGrid => {
+                let mut rng = fastrand::Rng::with_seed(n as u64);
+                #[cfg(feature = "force_f32")]
+                let jitter = |_| (rng.f32(), rng.f32());
+                #[cfg(not(feature = "force_f32"))]
+                let jitter = |_| (rng.f64(), rng.f64());
+                grid_points(n, shift, jitter)
+            }
+        }
+    }
+}
+
+fn golden_points(n: usize, shift: F) -> Vec<(F, F)> {
+    const PHI: F = 1.618033988749895;
+    const EPS: F = 0.5;
+
+    (0..n)
+        .map(|i| {
+            (
+                (i as F / PHI + shift) % 1.,
+                (i as F + EPS) / ((n.max(1) - 1) as F + 2. * EPS),
+            )
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Van der Corput sequence in the given base.
+fn radical_inverse(mut i: usize, base: usize) -> F {
+    let inv_base = 1. / base as F;
+    let mut factor = inv_base;
+    let mut v = 0.;
+    while i > 0 {
+        v += (i % base) as F * factor;
+        i /= base;
+        factor *= inv_base;
+    }
+    v
+}
+
+/// Places one point in each of the first `n` cells of the smallest
+/// grid with at least `n` cells, `position` giving the position of
+/// the point inside the cell of the given index.
+fn grid_points(n: usize, shift: F, mut position: impl FnMut(usize) -> (F, F)) -> Vec<(F, F)> {
+    let columns = (n as F).sqrt().ceil().max(1.) as usize;
+    let rows = n.div_ceil(columns).max(1);
+
+    (0..n)
+        .map(|i| {
+            let (x, y) = position(i);
+            (
+                (((i % columns) as F + x) / columns as F + shift) % 1.,
+                ((i / columns) as F + y) / rows as F,
+            )
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -53,24 +157,138 @@ pub enum SamplingLevel {
     Ultra,
     Extreme,
     Custom(usize),
+    /// Takes `base` samples per pixel, then up to `max` samples in
+    /// regions where the values vary the most.
+    Adaptive {
+        base: usize,
+        max: usize,
+    },
+}
+
+/// Filter weighting the samples of a pixel depending on their
+/// distance (in pixels) to the pixel center.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReconstructionFilter {
+    Box {
+        radius: F,
+    },
+    /// Samples are distributed along a tent instead of being
+    /// weighted.
+    Tent {
+        radius: F,
+    },
+    Gaussian {
+        radius: F,
+    },
+    /// Mitchell-Netravali filter with B = C = 1/3.
+    Mitchell {
+        radius: F,
+    },
+}
+
+impl ReconstructionFilter {
+    /// Filter of the params that don't give one. Before the tent was
+    /// sampled along a real tent, the default `Tent { radius: 1.5 }`
+    /// spread the samples evenly over 1.5 pixels, which is this box,
+    /// so that existing params keep their look.
+    pub const DEFAULT: ReconstructionFilter = ReconstructionFilter::Box { radius: 0.75 };
+
+    pub fn radius(&self) -> F {
+        match *self {
+            ReconstructionFilter::Box { radius }
+            | ReconstructionFilter::Tent { radius }
+            | ReconstructionFilter::Gaussian { radius }
+            | ReconstructionFilter::Mitchell { radius } => radius,
+        }
+    }
+
+    pub fn radius_mut(&mut self) -> &mut F {
+        match self {
+            ReconstructionFilter::Box { radius }
+            | ReconstructionFilter::Tent { radius }
+            | ReconstructionFilter::Gaussian { radius }
+            | ReconstructionFilter::Mitchell { radius } => radius,
+        }
+    }
+
+    /// Maps a sampling point (in range [0,1)) shifted by the given
+    /// offsets to a position relative to the pixel, returns it along
+    /// with the weight of the sample.
+    #[inline]
+    pub fn map_point(&self, x: F, y: F, offset_x: F, offset_y: F) -> (F, F, F) {
+        if let &ReconstructionFilter::Tent { radius } = self {
+            let (x, y) = map_points_with_offsets(x, y, offset_x, offset_y, radius);
+            return (x, y, 1.);
+        }
+
+        let (x, y) = ((x + offset_x) % 1., (y + offset_y) % 1.);
+        let radius = self.radius();
+        let (x, y) = (radius * (2. * x - 1.), radius * (2. * y - 1.));
+
+        (x, y, self.weight(x) * self.weight(y))
+    }
+
+    /// Weight of a sample at distance `d` from the pixel center
+    /// along one axis.
+    #[inline]
+    pub fn weight(&self, d: F) -> F {
+        let radius = self.radius();
+        match self {
+            ReconstructionFilter::Box { .. } => 1.,
+            ReconstructionFilter::Tent { .. } => (1. - d.abs() / radius).max(0.),
+            ReconstructionFilter::Gaussian { .. } => (-2. * (d / radius).powi(2)).exp(),
+            ReconstructionFilter::Mitchell { .. } => {
+                const B: F = 1. / 3.;
+                const C: F = 1. / 3.;
+
+                let x = (2. * d / radius).abs();
+                if x < 1. {
+                    ((12. - 9. * B - 6. * C) * x.powi(3)
+                        + (-18. + 12. * B + 6. * C) * x.powi(2)
+                        + (6. - 2. * B))
+                        / 6.
+                } else if x < 2. {
+                    ((-B - 6. * C) * x.powi(3)
+                        + (6. * B + 30. * C) * x.powi(2)
+                        + (-12. * B - 48. * C) * x
+                        + (8. * B + 24. * C))
+                        / 6.
+                } else {
+                    0.
+                }
+            }
+        }
+    }
 }
 
-pub fn map_points_with_offsets(x: F, y: F, offset_x: F, offset_y: F) -> (F, F) {
+/// Maps a sampling point shifted by the given offsets to a position
+/// relative to the pixel center, distributed along a tent of the
+/// given radius. The footprint is centered on the pixel so that it
+/// stays in place when the view is rotated.
+pub fn map_points_with_offsets(x: F, y: F, offset_x: F, offset_y: F, radius: F) -> (F, F) {
+    /// Inverse of the cumulative distribution of the tent over
+    /// [-1,1].
     #[inline]
     fn tent(t: F) -> F {
-        let t = 2. * t - 1.;
-        if t != 0. { t - t.signum() } else { 1. }.abs()
+        if t < 0.5 {
+            (2. * t).sqrt() - 1.
+        } else {
+            1. - (2. - 2. * t).sqrt()
+        }
     }
 
     let (x, y) = ((x + offset_x) % 1., (y + offset_y) % 1.);
 
-    const R: F = 1.5;
-    let (x, y) = (R * tent(x), R * tent(y));
-
-    (x, y)
+    (radius * tent(x), radius * tent(y))
 }
 
-pub fn preview_sampling_points(sampling_points: &Vec<(F, F)>) -> Result<()> {
+pub fn preview_sampling_points(
+    sampling: &Sampling,
+    sampling_points: &Vec<(F, F)>,
+    seed: Option<u64>,
+) -> Result<()> {
+    let mut rng = seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed);
+
     let size = 350;
     let center = size / 2;
     let px = 50;
@@ -86,16 +304,20 @@ pub fn preview_sampling_points(sampling_points: &Vec<(F, F)>) -> Result<()> {
 
             if i == 0 && j == 0 {
                 #[cfg(feature = "force_f32")]
-                let (offset_x, offset_y) = (fastrand::f32(), fastrand::f32());
+                let (offset_x, offset_y) = (rng.f32(), rng.f32());
                 #[cfg(not(feature = "force_f32"))]
-                let (offset_x, offset_y) = (fastrand::f64(), fastrand::f64());
+                let (offset_x, offset_y) = (rng.f64(), rng.f64());
                 for &(x, y) in sampling_points {
-                    let (x, y) = map_points_with_offsets(x, y, offset_x, offset_y);
-                    preview.put_pixel(
-                        (center as F + 2. * px as F * (x + i as F)) as u32,
-                        (center as F + 2. * px as F * (y + j as F)) as u32,
-                        color,
+                    let (x, y, _) = sampling.filter().map_point(x, y, offset_x, offset_y);
+                    let (x, y) = (
+                        center as F + 2. * px as F * (x + i as F),
+                        center as F + 2. * px as F * (y + j as F),
                     );
+                    // Wide filters can spread samples out of the
+                    // preview.
+                    if (0. ..size as F).contains(&x) && (0. ..size as F).contains(&y) {
+                        preview.put_pixel(x as u32, y as u32, color);
+                    }
                 }
             }
         }
@@ -125,3 +347,161 @@ pub fn preview_sampling_points(sampling_points: &Vec<(F, F)>) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{fractal::Fractal, params::FrameParams, rendering::render_raw_image};
+
+    const FILTERS: [ReconstructionFilter; 4] = [
+        ReconstructionFilter::Box { radius: 0.5 },
+        ReconstructionFilter::Tent { radius: 1.5 },
+        ReconstructionFilter::Gaussian { radius: 1.5 },
+        ReconstructionFilter::Mitchell { radius: 2. },
+    ];
+
+    /// Integrates `f` over [a,b] with the midpoint rule.
+    fn integrate(f: impl Fn(F) -> F, a: F, b: F) -> F {
+        const N: usize = 10_000;
+        let dx = (b - a) / N as F;
+        (0..N).map(|i| f(a + (i as F + 0.5) * dx) * dx).sum()
+    }
+
+    #[test]
+    fn sample_weights_follow_the_filter_kernels() {
+        const POINTS: usize = 100_000;
+        const BINS: usize = 12;
+
+        for filter in FILTERS {
+            let radius = filter.radius();
+            let bin_width = 2. * radius / BINS as F;
+
+            // Weights of the samples falling in each bin, along x.
+            let mut bins = [0.; BINS];
+            for i in 0..POINTS {
+                let (x, _, w) = filter.map_point((i as F + 0.5) / POINTS as F, 0.5, 0., 0.);
+                let bin = ((x + radius) / bin_width) as usize;
+                bins[bin.min(BINS - 1)] += w;
+            }
+            let total: F = bins.iter().sum();
+
+            let area = integrate(|d| filter.weight(d), -radius, radius);
+            for (k, bin) in bins.iter().enumerate() {
+                let start = -radius + k as F * bin_width;
+                let expected = integrate(|d| filter.weight(d), start, start + bin_width) / area;
+                assert!(
+                    (bin / total - expected).abs() < 1e-3,
+                    "{filter:?}: bin {k} has {} instead of {expected}",
+                    bin / total
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn default_filter_keeps_the_previous_footprint() {
+        const POINTS: usize = 10_000;
+        const BINS: usize = 10;
+
+        assert_eq!(
+            ReconstructionFilter::DEFAULT,
+            ReconstructionFilter::Box { radius: 0.75 }
+        );
+
+        // Positions given by the default `Tent { radius: 1.5 }` before
+        // the tent was sampled correctly.
+        let previous = |x: F| {
+            let t = 2. * x - 1.;
+            1.5 * (if t != 0. { t - t.signum() } else { 1. }.abs() - 0.5)
+        };
+        let bin = |x: F| (((x + 0.75) / 1.5 * BINS as F) as usize).min(BINS - 1);
+
+        let (mut bins, mut previous_bins) = ([0.; BINS], [0.; BINS]);
+        for i in 0..POINTS {
+            let x = (i as F + 0.5) / POINTS as F;
+            let (mapped, _, w) = ReconstructionFilter::DEFAULT.map_point(x, 0.5, 0., 0.);
+            assert_eq!(w, 1.);
+            bins[bin(mapped)] += 1.;
+            previous_bins[bin(previous(x))] += 1.;
+        }
+        assert_eq!(bins, previous_bins);
+    }
+
+    #[test]
+    fn samples_are_centered_on_the_pixel() {
+        let points = SamplingPattern::UniformGrid.points(256, 0.);
+        for filter in FILTERS {
+            let radius = filter.radius();
+            for (offset_x, offset_y) in [(0., 0.), (0.3, 0.7)] {
+                let samples = points
+                    .iter()
+                    .map(|&(x, y)| filter.map_point(x, y, offset_x, offset_y))
+                    .collect::<Vec<_>>();
+
+                let weights: F = samples.iter().map(|&(_, _, w)| w).sum();
+                assert!(weights > 0.);
+                let (mean_x, mean_y) = samples.iter().fold((0., 0.), |(mx, my), &(x, y, w)| {
+                    (mx + x * w / weights, my + y * w / weights)
+                });
+                assert!(mean_x.abs() < 0.05 && mean_y.abs() < 0.05, "{filter:?}");
+                assert!(samples
+                    .iter()
+                    .all(|&(x, y, _)| x.abs() <= radius && y.abs() <= radius));
+            }
+        }
+    }
+
+    /// Mean over the pixels of the variance of the values across
+    /// renders of the zone plate with different seeds.
+    fn zone_plate_noise(rotate: F) -> F {
+        const SEEDS: u64 = 16;
+
+        let params = FrameParams {
+            img_width: 32,
+            img_height: 32,
+            zoom: 0.2,
+            rotate: Some(rotate),
+            fractal: Fractal::MoireTest,
+            max_iter: 10,
+            ..Default::default()
+        };
+        let sampling_points = params.sampling.generate_sampling_points();
+
+        let renders = (0..SEEDS)
+            .map(|seed| {
+                let params = FrameParams {
+                    seed: Some(seed),
+                    ..params.clone()
+                };
+                render_raw_image(&params, &sampling_points, None, None).0
+            })
+            .collect::<Vec<_>>();
+
+        let pixel_count = renders[0].vec.len();
+        (0..pixel_count)
+            .map(|k| {
+                let mean = renders.iter().map(|r| r.vec[k]).sum::<F>() / SEEDS as F;
+                renders.iter().map(|r| (r.vec[k] - mean).powi(2)).sum::<F>() / SEEDS as F
+            })
+            .sum::<F>()
+            / pixel_count as F
+    }
+
+    #[test]
+    fn noise_is_rotation_invariant() {
+        // The zone plate is symmetric about the origin, so the
+        // rotated renders only differ by their noise. Jittering the
+        // samples after the rotation gives a ratio of about 0.7 at
+        // 45 degrees.
+        let noise = zone_plate_noise(0.);
+        assert!(noise > 0.);
+        for rotate in [std::f64::consts::FRAC_PI_4 as F, 1.] {
+            let ratio = zone_plate_noise(rotate) / noise;
+            assert!(
+                (0.9..1.1).contains(&ratio),
+                "noise ratio {ratio} at {rotate}"
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calibration::Calibration,
+    error::{ErrorKind, Result},
+    presets::config_dir,
+};
+
+/// Settings of the gui which are kept between sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+}
+
+/// Settings file, in the [`config_dir`].
+pub fn settings_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("settings.ron"))
+}
+
+/// Reads a settings file, a missing file having the default
+/// settings.
+pub fn load_settings(path: &Path) -> Result<Settings> {
+    match fs::read_to_string(path) {
+        Ok(s) => ron::from_str(&s).map_err(ErrorKind::DecodeSettings),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+        Err(e) => Err(ErrorKind::ReadSettings(e)),
+    }
+}
+
+pub fn save_settings(path: &Path, settings: &Settings) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(ErrorKind::WriteSettings)?;
+    }
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(settings, PrettyConfig::default())
+            .map_err(ErrorKind::EncodeParameterFile)?,
+    )
+    .map_err(ErrorKind::WriteSettings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn settings_round_trip() {
+        let path = env::temp_dir().join(format!(
+            "fractal_rndr_test_{}_settings.ron",
+            std::process::id()
+        ));
+        let missing = load_settings(&path).unwrap();
+
+        let calibration = Calibration {
+            version: "1.2.3".to_string(),
+            cpu: "Some CPU".to_string(),
+            samples_per_sec: 1.5e7,
+        };
+        save_settings(
+            &path,
+            &Settings {
+                calibration: Some(calibration.clone()),
+            },
+        )
+        .unwrap();
+        let read_back = load_settings(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(missing.calibration.is_none());
+        assert_eq!(read_back.unwrap().calibration, Some(calibration));
+    }
+}
@@ -0,0 +1,409 @@
+use image::{GenericImage, Rgb, RgbImage};
+
+use crate::{
+    error::{ErrorKind, Result},
+    fractal::FractalParameter,
+    params::FrameParams,
+    render_frame_to_image, F,
+};
+
+/// Parameter swept over the cells of a contact sheet, from `from`
+/// in the first cell to `to` in the last one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub field: String,
+    pub from: F,
+    pub to: F,
+}
+
+/// Fields of the frame parameters that can be swept, besides the
+/// parameters of the fractal.
+const FRAME_FIELDS: &[&str] = &[
+    "zoom",
+    "center_x",
+    "center_y",
+    "rotate",
+    "max_iter",
+    "gradient_offset",
+    "gradient_repeat",
+];
+
+impl Sweep {
+    /// Parses `<field>=<from>..<to>`, e.g. `exp=1.5..3`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let invalid = || ErrorKind::InvalidCliArg("sweep".to_string());
+        let (field, range) = s.split_once('=').ok_or_else(invalid)?;
+        let (from, to) = range.split_once("..").ok_or_else(invalid)?;
+        let parse = |v: &str| v.trim().parse::<F>().ok().filter(|v| v.is_finite());
+
+        Ok(Sweep {
+            field: field.trim().to_string(),
+            from: parse(from).ok_or_else(invalid)?,
+            to: parse(to).ok_or_else(invalid)?,
+        })
+    }
+
+    /// Value of the parameter in each of the `count` cells, evenly
+    /// spaced.
+    pub fn values(&self, count: usize) -> Vec<F> {
+        (0..count)
+            .map(|i| {
+                let t = if count > 1 {
+                    i as F / (count - 1) as F
+                } else {
+                    0.
+                };
+                self.from + t * (self.to - self.from)
+            })
+            .collect()
+    }
+}
+
+/// Sets the field of the frame parameters or the parameter of the
+/// fractal called `field`. Parts of complex parameters are named
+/// `<name>.re` and `<name>.im`, integer parameters are rounded.
+pub fn set_field(params: &mut FrameParams, field: &str, value: F) -> Result<()> {
+    match field {
+        "zoom" => params.zoom = value,
+        "center_x" => params.center_x = value,
+        "center_y" => params.center_y = value,
+        "rotate" => params.rotate = Some(value),
+        "max_iter" => params.max_iter = value.round().max(1.) as u32,
+        "gradient_offset" => params.gradient_offset = Some(value),
+        "gradient_repeat" => params.gradient_repeat = Some(value),
+        _ => {
+            let mut names = FRAME_FIELDS
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+            for parameter in params.fractal.parameters() {
+                match parameter {
+                    FractalParameter::Real { name, value: v, .. } if name == field => {
+                        *v = value;
+                        return Ok(());
+                    }
+                    FractalParameter::Integer {
+                        name,
+                        value: v,
+                        min,
+                        max,
+                    } if name == field => {
+                        *v = (value.round().max(0.) as usize).clamp(min, max);
+                        return Ok(());
+                    }
+                    FractalParameter::Complex { name, re, im, .. } => {
+                        match field.strip_prefix(name).and_then(|f| f.strip_prefix('.')) {
+                            Some("re") => *re = value,
+                            Some("im") => *im = value,
+                            _ => {
+                                names.push(format!("{}.re", name));
+                                names.push(format!("{}.im", name));
+                                continue;
+                            }
+                        }
+                        return Ok(());
+                    }
+                    FractalParameter::Real { name, .. }
+                    | FractalParameter::Integer { name, .. } => names.push(name.to_string()),
+                }
+            }
+
+            return Err(ErrorKind::Sweep(format!(
+                "unknown field \"{}\", the fields of {:?} are: {}",
+                field,
+                params.fractal,
+                names.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Space between the cells, in pixels.
+const GAP: u32 = 4;
+/// Scale of the 3x5 pixels glyphs of the labels.
+const TEXT_SCALE: u32 = 2;
+const BACKGROUND: Rgb<u8> = Rgb([16, 16, 16]);
+
+/// Renders the frame once per cell of a `columns` by `rows` grid,
+/// `cell_width` pixels wide, with the swept parameter going from
+/// `sweep.from` to `sweep.to` row after row. Each cell is labeled
+/// with its value. `on_cell` is called before rendering each cell
+/// with its index.
+pub fn render_contact_sheet(
+    params: &FrameParams,
+    sweep: &Sweep,
+    columns: u32,
+    rows: u32,
+    cell_width: u32,
+    mut on_cell: impl FnMut(usize),
+) -> Result<RgbImage> {
+    let ratio = params.img_width as F / params.img_height as F;
+    let cell_height = ((cell_width as F / ratio).round() as u32).max(1);
+
+    let mut cell_params = FrameParams {
+        img_width: cell_width,
+        img_height: cell_height,
+        // The cells show the view of the image even though their
+        // rounded size may not have its exact ratio.
+        aspect: Some(params.aspect.unwrap_or(ratio)),
+        ..params.clone()
+    };
+    // Checked before rendering anything.
+    set_field(&mut cell_params, &sweep.field, sweep.from)?;
+
+    let mut sheet = RgbImage::from_pixel(
+        columns * (cell_width + GAP) + GAP,
+        rows * (cell_height + GAP) + GAP,
+        BACKGROUND,
+    );
+
+    let values = sweep.values((columns * rows) as usize);
+    for (i, &value) in values.iter().enumerate() {
+        on_cell(i);
+
+        set_field(&mut cell_params, &sweep.field, value)?;
+        let cell = render_frame_to_image(&cell_params);
+
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        let (x, y) = (
+            GAP + column * (cell_width + GAP),
+            GAP + row * (cell_height + GAP),
+        );
+        sheet
+            .copy_from(&cell, x, y)
+            .map_err(|e| ErrorKind::Sweep(e.to_string()))?;
+
+        let label = format!("{}={}", sweep.field, format_label_value(value));
+        draw_label(&mut sheet, &label, x, y + cell_height, cell_width);
+    }
+
+    Ok(sheet)
+}
+
+fn format_label_value(v: F) -> String {
+    if v != 0. && !(1e-3..1e6).contains(&v.abs()) {
+        format!("{:.3e}", v)
+    } else {
+        let s = format!("{:.4}", v);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Draws `text` in white over a dark box whose bottom left corner is
+/// at (x, bottom), clipped to `max_width` pixels.
+fn draw_label(image: &mut RgbImage, text: &str, x: u32, bottom: u32, max_width: u32) {
+    const PADDING: u32 = 2;
+
+    let glyph_width = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+    let width = text.chars().count() as u32 * glyph_width + 2 * PADDING;
+    let height = GLYPH_HEIGHT * TEXT_SCALE + 2 * PADDING;
+    let top = bottom.saturating_sub(height);
+
+    let mut put = |px: u32, py: u32, color: Rgb<u8>| {
+        if px < (x + max_width).min(image.width()) && py < image.height() {
+            image.put_pixel(px, py, color);
+        }
+    };
+
+    for py in top..bottom {
+        for px in x..x + width {
+            put(px, py, BACKGROUND);
+        }
+    }
+
+    for (k, c) in text.chars().enumerate() {
+        let glyph = glyph(c);
+        let glyph_x = x + PADDING + k as u32 * glyph_width;
+        for (j, row) in glyph.iter().enumerate() {
+            for (i, pixel) in row.bytes().enumerate() {
+                if pixel != b'#' {
+                    continue;
+                }
+                for (sx, sy) in
+                    (0..TEXT_SCALE).flat_map(|sx| (0..TEXT_SCALE).map(move |sy| (sx, sy)))
+                {
+                    put(
+                        glyph_x + i as u32 * TEXT_SCALE + sx,
+                        top + PADDING + j as u32 * TEXT_SCALE + sy,
+                        Rgb([240, 240, 240]),
+                    );
+                }
+            }
+        }
+    }
+}
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Rows of the 3x5 glyph of `c`, letters are drawn as capitals and
+/// missing characters as a box.
+fn glyph(c: char) -> [&'static str; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_lowercase() {
+        '0' | 'o' => ["###", "#.#", "#.#", "#.#", "###"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["###", "..#", "###", "#..", "###"],
+        '3' => ["###", "..#", "###", "..#", "###"],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' | 's' => ["###", "#..", "###", "..#", "###"],
+        '6' => ["###", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", "..#", "..#", "..#"],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "###"],
+        '.' => ["...", "...", "...", "...", ".#."],
+        '-' => ["...", "...", "###", "...", "..."],
+        '+' => ["...", ".#.", "###", ".#.", "..."],
+        '=' => ["...", "###", "...", "###", "..."],
+        '_' => ["...", "...", "...", "...", "###"],
+        '^' => [".#.", "#.#", "...", "...", "..."],
+        ' ' => ["...", "...", "...", "...", "..."],
+        'a' => ["###", "#.#", "###", "#.#", "#.#"],
+        'b' => ["##.", "#.#", "##.", "#.#", "##."],
+        'c' => ["###", "#..", "#..", "#..", "###"],
+        'd' => ["##.", "#.#", "#.#", "#.#", "##."],
+        'e' => ["###", "#..", "###", "#..", "###"],
+        'f' => ["###", "#..", "###", "#..", "#.."],
+        'g' => ["###", "#..", "#.#", "#.#", "###"],
+        'h' => ["#.#", "#.#", "###", "#.#", "#.#"],
+        'i' => ["###", ".#.", ".#.", ".#.", "###"],
+        'j' => ["..#", "..#", "..#", "#.#", "###"],
+        'k' => ["#.#", "#.#", "##.", "#.#", "#.#"],
+        'l' => ["#..", "#..", "#..", "#..", "###"],
+        'm' => ["#.#", "###", "###", "#.#", "#.#"],
+        'n' => ["##.", "#.#", "#.#", "#.#", "#.#"],
+        'p' => ["###", "#.#", "###", "#..", "#.."],
+        'q' => ["###", "#.#", "#.#", "###", "..#"],
+        'r' => ["##.", "#.#", "##.", "#.#", "#.#"],
+        't' => ["###", ".#.", ".#.", ".#.", ".#."],
+        'u' => ["#.#", "#.#", "#.#", "#.#", "###"],
+        'v' => ["#.#", "#.#", "#.#", "#.#", ".#."],
+        'w' => ["#.#", "#.#", "###", "###", "#.#"],
+        'x' => ["#.#", "#.#", ".#.", "#.#", "#.#"],
+        'y' => ["#.#", "#.#", ".#.", ".#.", ".#."],
+        'z' => ["###", "..#", ".#.", "#..", "###"],
+        _ => ["###", "#.#", "#.#", "#.#", "###"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fractal::Fractal;
+
+    fn params_of(fractal: Fractal) -> FrameParams {
+        FrameParams {
+            fractal,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_sweeps() {
+        assert_eq!(
+            Sweep::parse(" exp = 1.5..3 ").unwrap(),
+            Sweep {
+                field: "exp".to_string(),
+                from: 1.5,
+                to: 3.,
+            }
+        );
+        assert_eq!(Sweep::parse("a.re=-1..-2").unwrap().to, -2.);
+
+        for invalid in [
+            "exp",
+            "exp=1.5",
+            "exp=1.5-3",
+            "exp=..3",
+            "exp=1.5..x",
+            "exp=NaN..3",
+            "exp=1..inf",
+            "exp=-inf..0",
+        ] {
+            assert!(
+                matches!(Sweep::parse(invalid), Err(ErrorKind::InvalidCliArg(_))),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn values_are_evenly_spaced() {
+        let sweep = Sweep {
+            field: "zoom".to_string(),
+            from: 2.,
+            to: -1.,
+        };
+        assert_eq!(sweep.values(1), vec![2.]);
+        assert_eq!(sweep.values(4), vec![2., 1., 0., -1.]);
+        assert!(sweep.values(0).is_empty());
+    }
+
+    #[test]
+    fn set_frame_fields() {
+        let mut params = FrameParams::default();
+        set_field(&mut params, "zoom", 0.25).unwrap();
+        set_field(&mut params, "rotate", 90.).unwrap();
+        set_field(&mut params, "max_iter", 99.6).unwrap();
+        assert_eq!(params.zoom, 0.25);
+        assert_eq!(params.rotate, Some(90.));
+        assert_eq!(params.max_iter, 100);
+
+        // At least one iteration.
+        set_field(&mut params, "max_iter", -5.).unwrap();
+        assert_eq!(params.max_iter, 1);
+    }
+
+    #[test]
+    fn set_parts_of_complex_parameters() {
+        let mut params = params_of(Fractal::SdrgeParam { a_re: 0., a_im: 0. });
+        set_field(&mut params, "a.re", 0.5).unwrap();
+        set_field(&mut params, "a.im", -1.5).unwrap();
+        assert_eq!(
+            params.fractal,
+            Fractal::SdrgeParam {
+                a_re: 0.5,
+                a_im: -1.5
+            }
+        );
+
+        // The complex parameter itself is not a field.
+        assert!(set_field(&mut params, "a", 1.).is_err());
+    }
+
+    #[test]
+    fn integer_parameters_are_rounded_and_clamped() {
+        let mut params = params_of(Fractal::SdrgeCustomIntExp { exp: 2 });
+        set_field(&mut params, "exp", 3.4).unwrap();
+        assert_eq!(params.fractal, Fractal::SdrgeCustomIntExp { exp: 3 });
+        set_field(&mut params, "exp", 3.5).unwrap();
+        assert_eq!(params.fractal, Fractal::SdrgeCustomIntExp { exp: 4 });
+
+        // Clamped to 1-10.
+        set_field(&mut params, "exp", 25.).unwrap();
+        assert_eq!(params.fractal, Fractal::SdrgeCustomIntExp { exp: 10 });
+        set_field(&mut params, "exp", -3.).unwrap();
+        assert_eq!(params.fractal, Fractal::SdrgeCustomIntExp { exp: 1 });
+    }
+
+    #[test]
+    fn unknown_fields_list_the_valid_ones() {
+        let mut params = params_of(Fractal::SdrgeParam { a_re: 0., a_im: 0. });
+        match set_field(&mut params, "b.re", 1.) {
+            Err(ErrorKind::Sweep(message)) => {
+                assert!(message.starts_with("unknown field \"b.re\""), "{message}");
+                assert!(
+                    message.ends_with(
+                        "zoom, center_x, center_y, rotate, max_iter, gradient_offset, \
+                         gradient_repeat, a.re, a.im"
+                    ),
+                    "{message}"
+                );
+            }
+            _ => panic!("the field was set"),
+        }
+        // Nothing was changed.
+        assert_eq!(params.fractal, Fractal::SdrgeParam { a_re: 0., a_im: 0. });
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    fs,
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::{ErrorKind, Result},
+    F,
+};
+
+/// FNV-1a of the bytes.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Writes a file of raw values: the magic first line, the header as
+/// RON on a single line, then the values as deflated little-endian
+/// f64, whatever the width of `F`.
+///
+/// Io errors are turned into errors with `io_error`.
+pub fn write_values_file<H: Serialize>(
+    path: &Path,
+    magic: &[u8],
+    header: &H,
+    values: &[F],
+    io_error: fn(io::Error) -> ErrorKind,
+) -> Result<()> {
+    let header = ron::to_string(header).map_err(ErrorKind::EncodeParameterFile)?;
+
+    let file = fs::File::create(path).map_err(io_error)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(magic).map_err(io_error)?;
+    writer.write_all(header.as_bytes()).map_err(io_error)?;
+    writer.write_all(b"\n").map_err(io_error)?;
+
+    let mut encoder = DeflateEncoder::new(writer, Compression::fast());
+    for &v in values {
+        #[allow(clippy::unnecessary_cast)]
+        encoder
+            .write_all(&(v as f64).to_le_bytes())
+            .map_err(io_error)?;
+    }
+    encoder
+        .finish()
+        .and_then(|mut writer| writer.flush())
+        .map_err(io_error)
+}
+
+/// Reads a file written by [`write_values_file`], checking that it
+/// has `len(&header)` values.
+///
+/// Io errors are turned into errors with `io_error` and invalid
+/// files into errors with `invalid`, given the reason.
+pub fn read_values_file<H: DeserializeOwned>(
+    path: &Path,
+    magic: &[u8],
+    len: impl FnOnce(&H) -> usize,
+    io_error: fn(io::Error) -> ErrorKind,
+    invalid: impl Fn(&str) -> ErrorKind,
+) -> Result<(H, Vec<F>)> {
+    let bytes = fs::read(path).map_err(io_error)?;
+
+    let bytes = bytes
+        .strip_prefix(magic)
+        .ok_or_else(|| invalid("wrong kind of file"))?;
+    let header_end = bytes
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(|| invalid("truncated header"))?;
+    let header = std::str::from_utf8(&bytes[..header_end])
+        .ok()
+        .and_then(|header| ron::from_str::<H>(header).ok())
+        .ok_or_else(|| invalid("invalid header"))?;
+
+    let mut values = Vec::new();
+    DeflateDecoder::new(&bytes[header_end + 1..])
+        .read_to_end(&mut values)
+        .map_err(|_| invalid("invalid values"))?;
+    if values.len() != len(&header) * size_of::<f64>() {
+        return Err(invalid("the number of values doesn't match the header"));
+    }
+
+    Ok((
+        header,
+        values
+            .chunks_exact(size_of::<f64>())
+            .map(
+                #[allow(clippy::unnecessary_cast)]
+                |v| f64::from_le_bytes(v.try_into().unwrap()) as F,
+            )
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Header {
+        len: usize,
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("fractal_rndr_test_{}_{}", std::process::id(), name))
+    }
+
+    fn read(path: &Path, magic: &[u8]) -> Result<(Header, Vec<F>)> {
+        read_values_file(
+            path,
+            magic,
+            |header: &Header| header.len,
+            ErrorKind::ReadBand,
+            |reason| ErrorKind::DecodeBand(path.display().to_string(), reason.into()),
+        )
+    }
+
+    #[test]
+    fn fnv1a_of_known_bytes() {
+        assert_eq!(fnv1a([]), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(*b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn values_file_round_trips() {
+        let values = (0..10).map(|k| k as F * -0.75).collect::<Vec<_>>();
+        let path = temp_path("round_trip.values");
+        write_values_file(
+            &path,
+            b"test\n",
+            &Header { len: 10 },
+            &values,
+            ErrorKind::WriteBand,
+        )
+        .unwrap();
+        let read_back = read(&path, b"test\n");
+        let wrong_magic = read(&path, b"other\n");
+        fs::remove_file(&path).unwrap();
+
+        let (header, read_values) = read_back.unwrap();
+        assert_eq!(header.len, 10);
+        assert_eq!(read_values, values);
+        assert!(
+            matches!(wrong_magic, Err(ErrorKind::DecodeBand(_, reason)) if reason == "wrong kind of file")
+        );
+    }
+
+    #[test]
+    fn values_must_match_the_header() {
+        let path = temp_path("short.values");
+        write_values_file(
+            &path,
+            b"test\n",
+            &Header { len: 3 },
+            &[1., 2.],
+            ErrorKind::WriteBand,
+        )
+        .unwrap();
+        let read_back = read(&path, b"test\n");
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            read_back,
+            Err(ErrorKind::DecodeBand(_, reason))
+                if reason == "the number of values doesn't match the header"
+        ));
+    }
+}
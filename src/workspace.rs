@@ -0,0 +1,354 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    autosave::Autosave,
+    bookmarks::Bookmark,
+    error::{ErrorKind, Result},
+    mat::Mat2D,
+    params::FrameParams,
+    presets::UserPresets,
+    values_file::{read_values_file, write_values_file},
+    F,
+};
+
+/// First line of workspace files.
+const MAGIC: &[u8] = b"fractal_rndr workspace\n";
+
+/// Version of the workspace files written, files of later versions
+/// can't be read.
+pub const WORKSPACE_VERSION: u32 = 1;
+
+/// What the gui keeps across sessions, bundled in a single file to
+/// move it to another machine or to keep it in a synced folder.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    pub bookmarks: Vec<Bookmark>,
+    pub presets: UserPresets,
+    /// Samples of the session the workspace was exported from.
+    pub autosave: Option<Autosave>,
+}
+
+/// Header of workspace files (see [`write_values_file`]). The values
+/// are the ones of the autosave, if any.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    bookmarks: Vec<Bookmark>,
+    presets: UserPresets,
+    autosave: Option<AutosaveHeader>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AutosaveHeader {
+    samples_per_pixel: F,
+    width: usize,
+    height: usize,
+    params: FrameParams,
+}
+
+/// How the workspace read by an import is added to the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Bookmarks are added after the current ones (skipping the ones
+    /// already there) and presets whose name is taken are renamed.
+    Merge,
+    /// Bookmarks are replaced and presets whose name is taken are
+    /// overwritten.
+    Replace,
+}
+
+/// What an import changed, for the gui to save it and report it.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub added_bookmarks: usize,
+    /// Names of the presets added or overwritten.
+    pub saved_presets: Vec<String>,
+    /// Presets renamed because their name was taken, as (name in the
+    /// imported workspace, new name).
+    pub renamed_presets: Vec<(String, String)>,
+}
+
+impl Workspace {
+    /// Adds the bookmarks and presets of `imported`. Its autosave is
+    /// only taken when the workspace has none.
+    pub fn import(&mut self, imported: Workspace, mode: ImportMode) -> ImportSummary {
+        let mut summary = ImportSummary::default();
+
+        if mode == ImportMode::Replace {
+            self.bookmarks.clear();
+        }
+        for bookmark in imported.bookmarks {
+            if !self.bookmarks.contains(&bookmark) {
+                self.bookmarks.push(bookmark);
+                summary.added_bookmarks += 1;
+            }
+        }
+
+        for (name, params) in imported.presets {
+            let existing = self.presets.iter().position(|(n, _)| *n == name);
+            match existing {
+                None => {
+                    summary.saved_presets.push(name.to_owned());
+                    self.presets.push((name, params));
+                }
+                Some(i) if same_params(&self.presets[i].1, &params) => {}
+                Some(i) if mode == ImportMode::Replace => {
+                    self.presets[i].1 = params;
+                    summary.saved_presets.push(name);
+                }
+                Some(_) => {
+                    let new_name = (2..)
+                        .map(|k| format!("{}-{}", name, k))
+                        .find(|new_name| self.presets.iter().all(|(n, _)| n != new_name))
+                        .unwrap();
+                    summary.saved_presets.push(new_name.to_owned());
+                    summary.renamed_presets.push((name, new_name.to_owned()));
+                    self.presets.push((new_name, params));
+                }
+            }
+        }
+        self.presets.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        if self.autosave.is_none() {
+            self.autosave = imported.autosave;
+        }
+
+        summary
+    }
+
+    /// Sets the preset of the given name, replacing the preset with
+    /// the same name if any.
+    pub fn set_preset(&mut self, name: &str, params: &FrameParams) {
+        match self.presets.iter_mut().find(|(n, _)| n == name) {
+            Some((_, p)) => *p = params.clone(),
+            None => {
+                self.presets.push((name.to_string(), params.clone()));
+                self.presets.sort_by(|(a, _), (b, _)| a.cmp(b));
+            }
+        }
+    }
+}
+
+/// Whether two presets have the same params, compared through their
+/// RON representation as `FrameParams` isn't `PartialEq`.
+fn same_params(a: &FrameParams, b: &FrameParams) -> bool {
+    matches!((ron::to_string(a), ron::to_string(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+pub fn write_workspace(workspace: &Workspace, path: &Path) -> Result<()> {
+    let header = Header {
+        version: WORKSPACE_VERSION,
+        bookmarks: workspace.bookmarks.clone(),
+        presets: workspace.presets.clone(),
+        autosave: workspace.autosave.as_ref().map(|autosave| AutosaveHeader {
+            samples_per_pixel: autosave.samples_per_pixel,
+            width: autosave.raw_image.width,
+            height: autosave.raw_image.height,
+            params: autosave.params.clone(),
+        }),
+    };
+    let values = workspace
+        .autosave
+        .as_ref()
+        .map_or(&[][..], |autosave| &autosave.raw_image.vec);
+    write_values_file(path, MAGIC, &header, values, ErrorKind::WriteWorkspace)
+}
+
+pub fn read_workspace(path: &Path) -> Result<Workspace> {
+    let invalid =
+        |reason: &str| ErrorKind::DecodeWorkspace(path.display().to_string(), reason.into());
+
+    let (header, values) = read_values_file(
+        path,
+        MAGIC,
+        |header: &Header| {
+            header
+                .autosave
+                .as_ref()
+                .map_or(0, |autosave| autosave.width * autosave.height)
+        },
+        ErrorKind::ReadWorkspace,
+        invalid,
+    )?;
+    if header.version > WORKSPACE_VERSION {
+        return Err(invalid(&format!(
+            "version {} is newer than the supported version {}",
+            header.version, WORKSPACE_VERSION
+        )));
+    }
+
+    Ok(Workspace {
+        bookmarks: header.bookmarks,
+        presets: header.presets,
+        autosave: header.autosave.map(|autosave| Autosave {
+            params: autosave.params,
+            samples_per_pixel: autosave.samples_per_pixel,
+            raw_image: Mat2D {
+                width: autosave.width,
+                height: autosave.height,
+                vec: values,
+            },
+        }),
+    })
+}
+
+/// Reads the workspace, a missing file being an empty workspace.
+pub fn read_workspace_or_default(path: &Path) -> Result<Workspace> {
+    if path.exists() {
+        read_workspace(path)
+    } else {
+        Ok(Workspace::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::*;
+
+    use crate::fractal::Fractal;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("fractal_rndr_test_{}_{}", std::process::id(), name))
+    }
+
+    fn bookmark(zoom: F) -> Bookmark {
+        Bookmark::new(&FrameParams {
+            zoom,
+            ..Default::default()
+        })
+    }
+
+    fn preset(max_iter: u32) -> FrameParams {
+        FrameParams {
+            max_iter,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn workspace_round_trips() {
+        let workspace = Workspace {
+            bookmarks: vec![bookmark(1.), bookmark(0.5)],
+            presets: vec![("deep".to_string(), preset(5000))],
+            autosave: Some(Autosave {
+                params: FrameParams {
+                    fractal: Fractal::Sdrge,
+                    ..Default::default()
+                },
+                samples_per_pixel: 34.,
+                raw_image: Mat2D {
+                    vec: (0..12).map(|k| k as F * 0.5).collect(),
+                    width: 4,
+                    height: 3,
+                },
+            }),
+        };
+        let path = temp_path("round_trip.workspace");
+        write_workspace(&workspace, &path).unwrap();
+        let read = read_workspace(&path);
+        fs::remove_file(&path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(read.bookmarks, workspace.bookmarks);
+        assert_eq!(read.presets.len(), 1);
+        assert_eq!(read.presets[0].0, "deep");
+        assert!(same_params(&read.presets[0].1, &preset(5000)));
+        let autosave = read.autosave.unwrap();
+        assert_eq!(autosave.samples_per_pixel, 34.);
+        assert_eq!(autosave.params.fractal, Fractal::Sdrge);
+        assert_eq!(autosave.raw_image, workspace.autosave.unwrap().raw_image);
+
+        // Without an autosave, there are no values.
+        write_workspace(&Workspace::default(), &path).unwrap();
+        let read = read_workspace(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(read.unwrap().autosave.is_none());
+        assert!(read_workspace_or_default(&path)
+            .unwrap()
+            .bookmarks
+            .is_empty());
+    }
+
+    #[test]
+    fn later_versions_are_rejected() {
+        let path = temp_path("later.workspace");
+        let header = Header {
+            version: WORKSPACE_VERSION + 1,
+            bookmarks: Vec::new(),
+            presets: Vec::new(),
+            autosave: None,
+        };
+        write_values_file(&path, MAGIC, &header, &[], ErrorKind::WriteWorkspace).unwrap();
+        let read = read_workspace(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(read, Err(ErrorKind::DecodeWorkspace(_, _))));
+    }
+
+    #[test]
+    fn import_merges() {
+        let mut workspace = Workspace {
+            bookmarks: vec![bookmark(1.)],
+            presets: vec![
+                ("a".to_string(), preset(100)),
+                ("b".to_string(), preset(200)),
+                ("b-2".to_string(), preset(300)),
+            ],
+            autosave: None,
+        };
+        let imported = Workspace {
+            bookmarks: vec![bookmark(1.), bookmark(0.25)],
+            presets: vec![
+                ("a".to_string(), preset(100)),
+                ("b".to_string(), preset(400)),
+                ("c".to_string(), preset(500)),
+            ],
+            autosave: None,
+        };
+
+        let summary = workspace.import(imported, ImportMode::Merge);
+        assert_eq!(summary.added_bookmarks, 1);
+        assert_eq!(workspace.bookmarks, vec![bookmark(1.), bookmark(0.25)]);
+
+        // The same preset is not added again and a different one
+        // with a taken name is renamed.
+        assert_eq!(summary.saved_presets, vec!["b-3", "c"]);
+        assert_eq!(
+            summary.renamed_presets,
+            vec![("b".to_string(), "b-3".to_string())]
+        );
+        let names = workspace
+            .presets
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", "b-2", "b-3", "c"]);
+        assert!(same_params(&workspace.presets[1].1, &preset(200)));
+        assert!(same_params(&workspace.presets[3].1, &preset(400)));
+    }
+
+    #[test]
+    fn import_replaces() {
+        let mut workspace = Workspace {
+            bookmarks: vec![bookmark(1.)],
+            presets: vec![("b".to_string(), preset(200))],
+            autosave: None,
+        };
+        let imported = Workspace {
+            bookmarks: vec![bookmark(0.25)],
+            presets: vec![("b".to_string(), preset(400))],
+            autosave: None,
+        };
+
+        let summary = workspace.import(imported, ImportMode::Replace);
+        assert_eq!(workspace.bookmarks, vec![bookmark(0.25)]);
+        assert_eq!(summary.saved_presets, vec!["b"]);
+        assert!(summary.renamed_presets.is_empty());
+        assert_eq!(workspace.presets.len(), 1);
+        assert!(same_params(&workspace.presets[0].1, &preset(400)));
+    }
+}